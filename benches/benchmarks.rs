@@ -5,8 +5,8 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion, Benchmark
 use prompt_tracking::{
     analysis::{EfficiencyAnalyzer, QualityAnalyzer},
     capture::CaptureService,
-    database::Database,
-    models::Prompt,
+    database::{Database, PromptFilter},
+    models::{EfficiencyMetrics, Prompt},
     utils::{calculate_hash, normalize_whitespace},
 };
 
@@ -175,6 +175,73 @@ fn bench_database(c: &mut Criterion) {
     group.finish();
 }
 
+/// Prompts stored for the bulk loading benchmarks
+const BULK_PROMPTS: usize = 100_000;
+
+/// Benchmark loading relations and metrics for many prompts: one query per
+/// prompt against the batched queries used by reports and exports
+fn bench_bulk_loading(c: &mut Criterion) {
+    let db = Database::in_memory().expect("in-memory database");
+    db.transaction(|tx| {
+        for i in 0..BULK_PROMPTS {
+            let mut prompt = create_prompt(&format!("Bulk prompt {i}"));
+            prompt.tags = vec![format!("tag{}", i % 50), "bulk".to_string()];
+            tx.create_prompt(&prompt)?;
+            tx.save_efficiency_metrics(&EfficiencyMetrics {
+                prompt_id: prompt.id.clone(),
+                efficiency_score: 75.0,
+                token_efficiency: 70.0,
+                time_efficiency: 80.0,
+                cost_efficiency: 75.0,
+                calculated_at: chrono::Utc::now(),
+            })?;
+        }
+        Ok(())
+    })
+    .expect("bulk prompts inserted");
+
+    let prompts = db
+        .list_prompts(&PromptFilter::default())
+        .expect("bulk prompts listed");
+    let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+
+    let mut group = c.benchmark_group("bulk_loading_100k");
+    group.sample_size(10);
+
+    group.bench_function("prompts_per_row", |b| {
+        b.iter(|| {
+            ids.iter()
+                .filter_map(|id| db.get_prompt(black_box(id)).expect("prompt loaded"))
+                .count()
+        });
+    });
+    group.bench_function("prompts_batched", |b| {
+        b.iter(|| {
+            db.list_prompts(black_box(&PromptFilter::default()))
+                .expect("prompts listed")
+        });
+    });
+
+    group.bench_function("metrics_per_row", |b| {
+        b.iter(|| {
+            ids.iter()
+                .filter_map(|id| {
+                    db.get_efficiency_metrics(black_box(id))
+                        .expect("metrics loaded")
+                })
+                .count()
+        });
+    });
+    group.bench_function("metrics_batched", |b| {
+        b.iter(|| {
+            db.get_latest_efficiency_metrics(black_box(&ids))
+                .expect("metrics listed")
+        });
+    });
+
+    group.finish();
+}
+
 /// Benchmark similarity calculation
 fn bench_similarity(c: &mut Criterion) {
     let service = CaptureService::default();
@@ -215,6 +282,7 @@ criterion_group!(
     bench_quality_analysis,
     bench_efficiency_analysis,
     bench_database,
+    bench_bulk_loading,
     bench_similarity,
 );
 
@@ -27,18 +27,34 @@
 
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use prompt_tracking::{
     analysis::{EfficiencyAnalyzer, QualityAnalyzer},
+    backup::{self, BackupManager},
     capture::CaptureService,
-    config::Config,
-    database::{Database, PromptFilter},
+    config::{Config, EncryptionConfig, WatchRootConfig},
+    database::{CaptureFailure, Database, DeliveryStatus, PromptFilter, WebhookDelivery},
+    doctor,
+    encryption::{self, KeyMaterial},
+    events::Event,
     filter::parse_filter_query,
+    hooks::HookRunner,
+    migration::{CURRENT_VERSION, MIGRATIONS},
+    models::{PostCaptureAction, Prompt, PromptTemplate, RedactionFinding},
+    redaction::{RedactionOutcome, Redactor},
     reporting::{build_report_data, ReportGenerator, ReportType},
+    retention::RetentionPolicy,
+    store::PromptStore,
+    template,
     utils::truncate_string,
-    watcher::{FileWatcher, WatcherConfig},
+    watcher::{self, FileWatcher, WatchEvent, WatchRoot, WatcherConfig},
+    webhooks::{self, WebhookDispatcher},
 };
 
+#[cfg(unix)]
+use prompt_tracking::daemon::{self, DaemonOptions, DaemonState};
+
 #[derive(Parser)]
 #[command(name = "prompt-tracking")]
 #[command(author, version, about = "Enterprise-grade prompt tracking system for Claude Code")]
@@ -70,6 +86,10 @@ enum Commands {
         #[arg(short, long)]
         tags: Option<String>,
 
+        /// Custom attribute (repeatable, e.g. --attr ticket=PROJ-123)
+        #[arg(long = "attr", value_name = "KEY=VALUE")]
+        attrs: Vec<String>,
+
         /// Watch directory for new prompt files
         #[arg(short, long)]
         watch: bool,
@@ -134,10 +154,20 @@ enum Commands {
     /// Show system status and statistics
     Status,
 
-    /// Delete a prompt
+    /// Move a prompt to the trash
     Delete {
         /// Prompt ID to delete
         id: String,
+
+        /// Delete the prompt and its history right away instead
+        #[arg(long)]
+        permanent: bool,
+    },
+
+    /// List, restore or empty deleted prompts
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
     },
 
     /// Update a prompt
@@ -160,6 +190,10 @@ enum Commands {
         /// New context
         #[arg(long)]
         context: Option<String>,
+
+        /// Set custom attribute (repeatable, e.g. --attr ticket=PROJ-123)
+        #[arg(long = "attr", value_name = "KEY=VALUE")]
+        attrs: Vec<String>,
     },
 
     /// Show version history of a prompt
@@ -187,6 +221,10 @@ enum Commands {
         /// Show category distribution
         #[arg(long)]
         categories: bool,
+
+        /// Group statistics by git project
+        #[arg(long, conflicts_with = "categories")]
+        projects: bool,
     },
 
     /// Export data to file
@@ -197,6 +235,10 @@ enum Commands {
         /// Export format (json)
         #[arg(short, long, default_value = "json")]
         format: String,
+
+        /// Write an encrypted bundle using the configured key
+        #[arg(long)]
+        encrypt: bool,
     },
 
     /// Import data from file
@@ -230,17 +272,372 @@ enum Commands {
 
     /// Query prompts with advanced filter syntax
     ///
-    /// Supports filters like: category:code tag:rust quality:>80 date:>2024-01-01
+    /// Supports filters like: category:code tag:rust quality:>80 date:>2024-01-01 attr:ticket=PROJ-1 template:unit-tests
     Query {
         /// Filter query string (e.g., "category:code tag:rust quality:>80")
         query: String,
     },
+
+    /// Scan stored prompts and version history for secrets
+    Scan {
+        /// Scrub matches in place instead of only reporting them
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Manage and render prompt templates
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+
+    /// Watch the configured roots and capture new or edited prompt files
+    ///
+    /// Files already in the roots are backfilled first; checkpoints make
+    /// restarts skip files that have not changed since they were processed.
+    Watch {
+        /// Directory to watch instead of the configured roots
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+
+        /// Category for captured prompts
+        #[arg(short = 'c', long)]
+        category: Option<String>,
+
+        /// Tags for captured prompts (comma-separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+
+        /// Scan the roots once and exit without watching
+        #[arg(long, conflicts_with = "no_backfill")]
+        backfill_only: bool,
+
+        /// Skip the startup scan and only capture files changed from now on
+        #[arg(long)]
+        no_backfill: bool,
+
+        /// What to do with captured files: leave, move, rename or delete
+        #[arg(long)]
+        after_capture: Option<String>,
+    },
+
+    /// Review or undo files moved, renamed or deleted after capture
+    FileActions {
+        #[command(subcommand)]
+        action: FileActionsAction,
+    },
+
+    /// List, retry or dismiss files the watcher failed to capture
+    Failures {
+        #[command(subcommand)]
+        action: FailuresAction,
+    },
+
+    /// Test webhook endpoints and inspect or retry queued deliveries
+    Webhooks {
+        #[command(subcommand)]
+        action: WebhooksAction,
+    },
+
+    /// Encrypt, decrypt or rekey the database and generate key files
+    Encryption {
+        #[command(subcommand)]
+        action: EncryptionAction,
+    },
+
+    /// Create, list, verify and restore database backups
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+
+    /// Show the schema version and apply or revert migrations
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+
+    /// Apply the retention rules from the `retention` config section
+    Maintenance {
+        #[command(subcommand)]
+        action: MaintenanceAction,
+    },
+
+    /// Check the database for corruption and inconsistencies
+    Doctor {
+        /// Repair the problems that can be repaired
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Run the watcher as a background service
+    #[cfg(unix)]
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
 }
 
-fn main() {
-    // Initialize logger
-    env_logger::init();
+#[derive(Subcommand)]
+enum FileActionsAction {
+    /// List recent post-capture actions
+    List {
+        /// Maximum number of actions to show
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Put a moved, renamed or deleted file back where it was captured
+    Undo {
+        /// File action ID
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum FailuresAction {
+    /// List failed captures
+    List {
+        /// Include dismissed failures
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Retry a failed capture now
+    ///
+    /// Failures for files that were removed or are outside the watch roots
+    /// are cleared.
+    Retry {
+        /// Failure ID (or 'all' for every listed failure)
+        id: String,
+
+        /// Directory the files were watched from (defaults to configured roots)
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+    },
+
+    /// Dismiss a failed capture so it is no longer listed or retried
+    Dismiss {
+        /// Failure ID (or 'all' for every listed failure)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum WebhooksAction {
+    /// Send a test event to an endpoint and show the response
+    Test {
+        /// Endpoint name (defaults to every configured endpoint)
+        name: Option<String>,
+    },
+
+    /// List queued, delivered and failed deliveries
+    List {
+        /// Only show deliveries with this status: pending, delivered or failed
+        #[arg(short, long)]
+        status: Option<String>,
+
+        /// Maximum number of deliveries to show
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Attempt every pending delivery that is due
+    Flush,
+
+    /// Queue failed deliveries again and attempt them now
+    Retry {
+        /// Delivery ID (or 'all' for every failed delivery)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum EncryptionAction {
+    /// Show whether the database is encrypted and where the key comes from
+    Status,
+
+    /// Write a new random key file, readable only by you
+    Keygen {
+        /// Path of the key file to create
+        path: PathBuf,
+    },
+
+    /// Encrypt the stored prompts with the configured key
+    ///
+    /// Content hashes stay in plaintext, so identical prompts remain
+    /// recognizable, and the full-text index is emptied until the database
+    /// is decrypted again.
+    Encrypt,
+
+    /// Decrypt the stored prompts and store new ones in plaintext
+    Decrypt,
+
+    /// Re-encrypt the stored prompts under a new key
+    Rekey {
+        /// Key file holding the new key
+        #[arg(long, conflicts_with = "new_passphrase_env", required_unless_present = "new_passphrase_env")]
+        new_key_file: Option<PathBuf>,
+
+        /// Environment variable holding the new passphrase
+        #[arg(long)]
+        new_passphrase_env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupAction {
+    /// Take a verified backup now and prune old ones
+    Create,
+
+    /// List backups, newest first
+    List,
+
+    /// Check the integrity of a backup
+    Verify {
+        /// Backup name (defaults to every backup)
+        name: Option<String>,
+    },
+
+    /// Replace the database with a backup
+    ///
+    /// The current contents are backed up first so the restore can be undone.
+    Restore {
+        /// Backup name as shown by `backup list`
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Show the schema version and the migrations applied
+    Status,
+
+    /// Apply pending migrations, or revert to an earlier schema with --to
+    ///
+    /// The database is backed up first, and a failing migration leaves the
+    /// schema unchanged. A schema reverted with --to stays pinned there:
+    /// other commands refuse to open it until `db migrate` brings it back
+    /// to the latest version.
+    Migrate {
+        /// Schema version to migrate to (defaults to the latest)
+        #[arg(long)]
+        to: Option<i32>,
+
+        /// Print the SQL that would run without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List prompts in the trash
+    List,
+
+    /// Bring a prompt back from the trash
+    Restore {
+        /// Prompt ID to restore
+        id: String,
+    },
+
+    /// Permanently delete every prompt in the trash
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum MaintenanceAction {
+    /// Archive, purge and trim old data according to the retention rules
+    Run {
+        /// Report what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Run the daemon in the foreground until SIGTERM or SIGINT (SIGHUP reloads the config)
+    Start {
+        /// Skip the startup scan of the watch roots
+        #[arg(long)]
+        no_backfill: bool,
+    },
+
+    /// Stop the running daemon
+    Stop,
+
+    /// Reload the running daemon's configuration
+    Reload,
+
+    /// Show the daemon's counts, last error and uptime
+    Status,
+}
+
+#[derive(Subcommand)]
+enum TemplateAction {
+    /// Add a template (placeholders use `{{name}}` syntax)
+    Add {
+        /// Template name
+        name: String,
+
+        /// Template content
+        #[arg(short, long, conflicts_with = "file")]
+        content: Option<String>,
+
+        /// Read template content from file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Short description
+        #[arg(short, long)]
+        description: Option<String>,
+
+        /// Replace an existing template with the same name
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// List templates
+    List,
+
+    /// Show a template and its variables
+    Show {
+        /// Template name or ID
+        name: String,
+    },
+
+    /// Delete a template (rendered prompts keep their lineage)
+    Delete {
+        /// Template name or ID
+        name: String,
+    },
+
+    /// Render a template with variable values
+    Render {
+        /// Template name or ID
+        name: String,
+
+        /// Variable value as KEY=VALUE (repeatable)
+        #[arg(long = "var")]
+        vars: Vec<String>,
 
+        /// Capture the rendered prompt
+        #[arg(long)]
+        capture: bool,
+
+        /// Category for the captured prompt
+        #[arg(long, requires = "capture")]
+        category: Option<String>,
+
+        /// Comma-separated tags for the captured prompt
+        #[arg(long, requires = "capture")]
+        tags: Option<String>,
+    },
+}
+
+fn main() {
     let cli = Cli::parse();
 
     // Load configuration
@@ -253,8 +650,37 @@ fn main() {
         Config::default()
     };
 
-    // Initialize database
-    let db = match Database::new(&config.database.path) {
+    // Initialize logger: the daemon logs to its own rotating file
+    #[cfg(unix)]
+    if let Commands::Daemon {
+        action: DaemonAction::Start { .. },
+    } = cli.command
+    {
+        if let Err(e) = daemon::RotatingLog::from_config(&config.daemon).and_then(|l| l.install())
+        {
+            eprintln!("Error: Failed to open daemon log: {}", e);
+            std::process::exit(1);
+        }
+    } else {
+        env_logger::init();
+    }
+    #[cfg(not(unix))]
+    env_logger::init();
+
+    // Initialize database; encryption commands open it whatever the
+    // `enabled` requirement says, so a plaintext database can be encrypted,
+    // and `db` commands open it as it is so they can report and migrate it
+    let mut db_config = config.database.clone();
+    if let Commands::Encryption { .. } = cli.command {
+        db_config.encryption.enabled = false;
+    }
+    let admin = matches!(cli.command, Commands::Db { .. });
+    let opened = if admin {
+        Database::open_unmigrated(&db_config)
+    } else {
+        Database::from_config(&db_config)
+    };
+    let mut db = match opened {
         Ok(db) => db,
         Err(e) => {
             eprintln!("Error: Failed to initialize database: {}", e);
@@ -262,6 +688,39 @@ fn main() {
         }
     };
 
+    let hooks = HookRunner::new(&config.hooks);
+    if !hooks.is_empty() {
+        db.subscribe(Arc::new(hooks));
+    }
+
+    // Webhooks queue through their own connection so delivery never holds `db`
+    let webhooks = if admin || config.webhooks.endpoints.is_empty() {
+        None
+    } else {
+        match Database::from_config(&config.database)
+            .and_then(|outbox| WebhookDispatcher::new(&config.webhooks, outbox))
+        {
+            Ok(dispatcher) => Some(Arc::new(dispatcher)),
+            Err(e) => {
+                eprintln!("Warning: Webhooks disabled: {}", e);
+                None
+            }
+        }
+    };
+    if let Some(ref dispatcher) = webhooks {
+        db.subscribe(dispatcher.clone());
+    }
+    // Watches run until interrupted, so they deliver as they go; other
+    // commands send their webhooks once they are done
+    let watching = matches!(
+        cli.command,
+        Commands::Watch { .. } | Commands::Capture { watch: true, .. }
+    );
+    let webhook_worker = webhooks
+        .as_ref()
+        .filter(|_| watching)
+        .map(|dispatcher| dispatcher.spawn_worker(webhooks::RETRY_INTERVAL));
+
     // Execute command
     let result = match cli.command {
         Commands::Capture {
@@ -269,13 +728,14 @@ fn main() {
             file,
             category,
             tags,
+            attrs,
             watch,
             watch_dir,
         } => {
             if watch {
-                cmd_watch(&db, &config, watch_dir, category, tags)
+                cmd_watch(&db, &config, watch_dir, category.as_deref(), tags, None, true, false)
             } else {
-                cmd_capture(&db, &config, content, file, category, tags)
+                cmd_capture(&db, &config, content, file, category, tags, &attrs)
             }
         }
 
@@ -299,7 +759,9 @@ fn main() {
 
         Commands::Status => cmd_status(&db),
 
-        Commands::Delete { id } => cmd_delete(&db, &id),
+        Commands::Delete { id, permanent } => cmd_delete(&db, &id, permanent),
+
+        Commands::Trash { action } => cmd_trash(&db, action),
 
         Commands::Update {
             id,
@@ -307,20 +769,29 @@ fn main() {
             content,
             tags,
             context,
-        } => cmd_update(&db, &id, category, content, tags, context),
+            attrs,
+        } => cmd_update(&db, &config, &id, category, content, tags, context, &attrs),
 
         Commands::History { id } => cmd_history(&db, &id),
 
         Commands::Revert { id, to } => cmd_revert(&db, &id, to),
 
-        Commands::Trends { days, categories } => cmd_trends(&db, days, categories),
+        Commands::Trends {
+            days,
+            categories,
+            projects,
+        } => cmd_trends(&db, days, categories, projects),
 
-        Commands::Export { output, format } => cmd_export(&db, &output, &format),
+        Commands::Export {
+            output,
+            format,
+            encrypt,
+        } => cmd_export(&db, &output, &format, encrypt.then_some(&config.database.encryption)),
 
         Commands::Import {
             input,
             skip_duplicates,
-        } => cmd_import(&db, &input, skip_duplicates),
+        } => cmd_import(&db, &input, skip_duplicates, &config.database.encryption),
 
         Commands::Init { force } => cmd_init(&config, force),
 
@@ -329,8 +800,56 @@ fn main() {
         Commands::Unarchive { id } => cmd_unarchive(&db, &id),
 
         Commands::Query { query } => cmd_query(&db, &query),
+
+        Commands::Scan { fix } => cmd_scan(&db, &config, fix),
+
+        Commands::Template { action } => cmd_template(&db, &config, action),
+
+        Commands::Watch {
+            dir,
+            category,
+            tags,
+            backfill_only,
+            no_backfill,
+            after_capture,
+        } => cmd_watch(
+            &db,
+            &config,
+            dir,
+            category.as_deref(),
+            tags,
+            after_capture,
+            !no_backfill,
+            backfill_only,
+        ),
+
+        Commands::FileActions { action } => cmd_file_actions(&db, &action),
+
+        Commands::Failures { action } => cmd_failures(&db, &config, action),
+
+        Commands::Webhooks { action } => cmd_webhooks(&db, webhooks.as_deref(), action),
+
+        Commands::Encryption { action } => cmd_encryption(&db, &config, action),
+
+        Commands::Backup { action } => cmd_backup(&db, &config, action),
+
+        Commands::Db { action } => cmd_db(&db, &config, &action),
+
+        Commands::Maintenance { action } => cmd_maintenance(&db, &config, &action),
+
+        Commands::Doctor { fix } => cmd_doctor(&db, fix),
+
+        #[cfg(unix)]
+        Commands::Daemon { action } => cmd_daemon(&config, cli.config.clone(), &action),
     };
 
+    drop(webhook_worker);
+    if let Some(ref dispatcher) = webhooks {
+        if let Err(e) = dispatcher.flush() {
+            eprintln!("Warning: Failed to deliver webhooks: {}", e);
+        }
+    }
+
     if let Err(e) = result {
         eprintln!("Error: {}", e);
         std::process::exit(1);
@@ -346,32 +865,41 @@ fn main() {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `config` - Application configuration
 /// * `content` - Optional prompt content as a string
 /// * `file` - Optional path to a file containing the prompt
 /// * `category` - Optional category to assign to the prompt
 /// * `tags` - Optional comma-separated tags to assign
+/// * `attrs` - Custom attributes as `key=value` strings
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message describing what went wrong.
 fn cmd_capture(
-    db: &Database,
+    db: &dyn PromptStore,
     config: &Config,
     content: Option<String>,
     file: Option<PathBuf>,
     category: Option<String>,
     tags: Option<String>,
+    attrs: &[String],
 ) -> Result<(), String> {
-    let capture_service = CaptureService::new(config.capture.similarity_threshold);
+    let attributes = parse_attr_args(attrs)?;
+
+    let capture_service = CaptureService::new(config.capture.similarity_threshold)
+        .with_git_context(config.capture.git_context)
+        .with_redactor(build_redactor(config)?)
+        .with_safeguards(config.capture.safeguards.clone());
 
     // Get content from argument or file
-    let prompt_content = if let Some(c) = content {
-        c
+    let (prompt_content, source_path) = if let Some(c) = content {
+        (c, std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
     } else if let Some(f) = file {
-        std::fs::read_to_string(&f)
-            .map_err(|e| format!("Failed to read file: {}", e))?
+        let c = capture_service
+            .read_file(&f)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        (c, f)
     } else {
         return Err("Either content or --file must be provided".to_string());
     };
@@ -381,6 +909,9 @@ fn cmd_capture(
         .process_content(&prompt_content)
         .map_err(|e| format!("Failed to process content: {}", e))?;
 
+    // Record repository context of the working directory or source file
+    capture_service.attach_git_context(&mut prompt, &source_path);
+
     // Override category if provided
     if let Some(cat) = category {
         prompt.category = Some(cat);
@@ -396,6 +927,27 @@ fn cmd_capture(
         prompt.tags.extend(additional_tags);
     }
 
+    // Add custom attributes
+    prompt.metadata.attributes.extend(attributes);
+
+    save_captured_prompt(db, config, &prompt)
+}
+
+/// Saves a newly captured prompt and reports the result.
+///
+/// Skips the prompt if deduplication is enabled and an identical prompt exists,
+/// and runs auto-analysis when enabled in the configuration.
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `config` - Application configuration
+/// * `prompt` - The processed prompt to save
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error message if the prompt cannot be saved.
+fn save_captured_prompt(db: &dyn PromptStore, config: &Config, prompt: &Prompt) -> Result<(), String> {
     // Check for duplicates
     if config.capture.deduplicate {
         if let Ok(Some(existing)) = db.find_by_hash(&prompt.content_hash) {
@@ -405,7 +957,7 @@ fn cmd_capture(
     }
 
     // Save to database
-    db.create_prompt(&prompt)
+    db.create_prompt(prompt)
         .map_err(|e| format!("Failed to save prompt: {}", e))?;
 
     println!("Prompt captured successfully!");
@@ -416,18 +968,33 @@ fn cmd_capture(
     if !prompt.tags.is_empty() {
         println!("Tags: {}", prompt.tags.join(", "));
     }
+    for (key, value) in &prompt.metadata.attributes {
+        println!("Attribute: {}={}", key, value);
+    }
+    if let Some(template) = &prompt.metadata.template {
+        println!("Template: {}", template.template_name);
+    }
+    for finding in &prompt.redactions {
+        println!(
+            "Redacted: {} ({} match{}, {})",
+            finding.rule,
+            finding.count,
+            if finding.count == 1 { "" } else { "es" },
+            finding.action
+        );
+    }
 
     // Auto-analyze if enabled
     if config.analysis.auto_analyze {
         let quality_analyzer = QualityAnalyzer::new(config.analysis.quality_weights.clone());
         let efficiency_analyzer = EfficiencyAnalyzer::default();
 
-        if let Ok(quality_score) = quality_analyzer.analyze(&prompt) {
+        if let Ok(quality_score) = quality_analyzer.analyze(prompt) {
             let _ = db.save_quality_score(&quality_score);
             println!("Quality Score: {:.1}", quality_score.total_score);
         }
 
-        if let Ok(efficiency) = efficiency_analyzer.analyze(&prompt) {
+        if let Ok(efficiency) = efficiency_analyzer.analyze(prompt) {
             let _ = db.save_efficiency_metrics(&efficiency);
             println!("Efficiency Score: {:.1}", efficiency.efficiency_score);
         }
@@ -443,7 +1010,7 @@ fn cmd_capture(
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `limit` - Maximum number of prompts to display
 /// * `category` - Optional category filter
 /// * `tags` - Optional comma-separated tags filter
@@ -452,7 +1019,7 @@ fn cmd_capture(
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
 fn cmd_list(
-    db: &Database,
+    db: &dyn PromptStore,
     limit: usize,
     category: Option<String>,
     tags: Option<String>,
@@ -508,13 +1075,13 @@ fn cmd_list(
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `id` - The unique identifier of the prompt to retrieve
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if the prompt is not found.
-fn cmd_get(db: &Database, id: &str) -> Result<(), String> {
+fn cmd_get(db: &dyn PromptStore, id: &str) -> Result<(), String> {
     let prompt = db
         .get_prompt(id)
         .map_err(|e| format!("Failed to get prompt: {}", e))?
@@ -551,6 +1118,33 @@ fn cmd_get(db: &Database, id: &str) -> Result<(), String> {
         println!("  Context: {}", context);
     }
 
+    if let Some(git) = &prompt.metadata.git {
+        println!("\nGit:");
+        println!("  Project: {}", git.project);
+        println!("  Repository: {}", git.repo_root);
+        if let Some(remote) = &git.remote_url {
+            println!("  Remote: {}", remote);
+        }
+        println!("  Branch: {}", git.branch.as_deref().unwrap_or("(detached)"));
+        if let Some(commit) = &git.commit {
+            println!("  Commit: {}{}", commit, if git.dirty { " (dirty)" } else { "" });
+        }
+    }
+
+    if let Some(usage) = &prompt.metadata.template {
+        println!("\nTemplate: {}", usage.template_name);
+        for (key, value) in &usage.variables {
+            println!("  {}: {}", key, value);
+        }
+    }
+
+    if !prompt.metadata.attributes.is_empty() {
+        println!("\nAttributes:");
+        for (key, value) in &prompt.metadata.attributes {
+            println!("  {}: {}", key, value);
+        }
+    }
+
     println!("\nContent:\n{}", prompt.content);
 
     // Show quality score if available
@@ -583,14 +1177,14 @@ fn cmd_get(db: &Database, id: &str) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `config` - Application configuration containing analysis weights
 /// * `id` - Prompt ID to analyze, or "all" to analyze all prompts
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_analyze(db: &Database, config: &Config, id: &str) -> Result<(), String> {
+fn cmd_analyze(db: &dyn PromptStore, config: &Config, id: &str) -> Result<(), String> {
     let quality_analyzer = QualityAnalyzer::new(config.analysis.quality_weights.clone());
     let efficiency_analyzer = EfficiencyAnalyzer::default();
 
@@ -675,7 +1269,7 @@ fn cmd_analyze(db: &Database, config: &Config, id: &str) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `report_type` - Type of report: "weekly" or "monthly"
 /// * `format` - Output format: "markdown", "html", "json", or "csv"
 /// * `output` - Optional file path to save the report; prints to stdout if not provided
@@ -684,7 +1278,7 @@ fn cmd_analyze(db: &Database, config: &Config, id: &str) -> Result<(), String> {
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
 fn cmd_report(
-    db: &Database,
+    db: &dyn PromptStore,
     report_type: &str,
     format: &str,
     output: Option<PathBuf>,
@@ -710,13 +1304,15 @@ fn cmd_report(
         .get_all_quality_scores()
         .map_err(|e| format!("Failed to get quality scores: {}", e))?;
 
-    // Get efficiency metrics (we need to collect them)
-    let mut efficiency_metrics = Vec::new();
-    for prompt in &prompts {
-        if let Ok(Some(metrics)) = db.get_efficiency_metrics(&prompt.id) {
-            efficiency_metrics.push(metrics);
-        }
-    }
+    // Latest efficiency metrics of each prompt, loaded in bulk
+    let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+    let mut latest_metrics = db
+        .get_latest_efficiency_metrics(&ids)
+        .map_err(|e| format!("Failed to get efficiency metrics: {}", e))?;
+    let efficiency_metrics: Vec<_> = prompts
+        .iter()
+        .filter_map(|p| latest_metrics.remove(&p.id))
+        .collect();
 
     // Build report data
     let report_data = build_report_data(rtype, &prompts, &quality_scores, &efficiency_metrics);
@@ -728,15 +1324,21 @@ fn cmd_report(
         .map_err(|e| format!("Failed to generate report: {}", e))?;
 
     // Output
-    if let Some(path) = output {
+    if let Some(ref path) = output {
         generator
-            .save_to_file(&report_data, &path)
+            .save_to_file(&report_data, path)
             .map_err(|e| format!("Failed to save report: {}", e))?;
         println!("Report saved to: {}", path.display());
     } else {
         println!("{}", report);
     }
 
+    db.emit(&Event::ReportGenerated {
+        report_type: report_type.to_lowercase(),
+        format: format.to_lowercase(),
+        path: output.map(|p| p.display().to_string()),
+    });
+
     Ok(())
 }
 
@@ -747,14 +1349,14 @@ fn cmd_report(
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `query` - Search query string
 /// * `limit` - Maximum number of results to return
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_search(db: &Database, query: &str, limit: usize) -> Result<(), String> {
+fn cmd_search(db: &dyn PromptStore, query: &str, limit: usize) -> Result<(), String> {
     let filter = PromptFilter {
         search_query: Some(query.to_string()),
         limit: Some(limit),
@@ -793,12 +1395,12 @@ fn cmd_search(db: &Database, query: &str, limit: usize) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_status(db: &Database) -> Result<(), String> {
+fn cmd_status(db: &dyn PromptStore) -> Result<(), String> {
     let total_prompts = db
         .count_prompts()
         .map_err(|e| format!("Failed to count prompts: {}", e))?;
@@ -813,6 +1415,13 @@ fn cmd_status(db: &Database) -> Result<(), String> {
     println!("  Total Prompts: {}", total_prompts);
     println!("  Analyzed Prompts: {}", quality_scores.len());
 
+    let failures = db
+        .list_capture_failures(false)
+        .map_err(|e| format!("Failed to list failures: {}", e))?;
+    if !failures.is_empty() {
+        println!("  Failed Captures: {} (see `failures list`)", failures.len());
+    }
+
     if !quality_scores.is_empty() {
         let avg_quality: f64 =
             quality_scores.iter().map(|s| s.total_score).sum::<f64>() / quality_scores.len() as f64;
@@ -847,28 +1456,86 @@ fn cmd_status(db: &Database) -> Result<(), String> {
 
 /// Deletes a prompt from the database.
 ///
-/// Permanently removes a prompt and all associated data (quality scores,
-/// efficiency metrics, version history) from the database.
+/// Moves the prompt to the trash, where it keeps its associated data
+/// (quality scores, efficiency metrics, version history) until the trash is
+/// emptied. With `permanent`, the prompt and its data are removed at once.
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `id` - The unique identifier of the prompt to delete
+/// * `permanent` - Whether to skip the trash
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if the prompt is not found.
-fn cmd_delete(db: &Database, id: &str) -> Result<(), String> {
+fn cmd_delete(db: &dyn PromptStore, id: &str, permanent: bool) -> Result<(), String> {
     // Check if prompt exists
     let _ = db
         .get_prompt(id)
         .map_err(|e| format!("Failed to get prompt: {}", e))?
         .ok_or_else(|| format!("Prompt not found: {}", id))?;
 
-    db.delete_prompt(id)
-        .map_err(|e| format!("Failed to delete prompt: {}", e))?;
-
-    println!("Prompt {} deleted successfully.", id);
+    if permanent {
+        db.delete_prompt_permanently(id)
+            .map_err(|e| format!("Failed to delete prompt: {}", e))?;
+        println!("Prompt {} deleted permanently.", id);
+    } else {
+        db.delete_prompt(id)
+            .map_err(|e| format!("Failed to delete prompt: {}", e))?;
+        println!("Prompt {} moved to the trash.", id);
+        println!("Restore it with `trash restore {}`.", id);
+    }
+
+    Ok(())
+}
+
+/// Lists, restores or empties deleted prompts.
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `action` - Trash action to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the prompt to restore is not
+/// in the trash.
+fn cmd_trash(db: &dyn PromptStore, action: TrashAction) -> Result<(), String> {
+    match action {
+        TrashAction::List => {
+            let trash = db
+                .list_trash()
+                .map_err(|e| format!("Failed to list trash: {}", e))?;
+            if trash.is_empty() {
+                println!("The trash is empty.");
+                return Ok(());
+            }
+
+            println!("{} prompts in the trash:", trash.len());
+            for (prompt, deleted_at) in &trash {
+                println!(
+                    "  {}  deleted {}  {}",
+                    prompt.id,
+                    deleted_at.format("%Y-%m-%d %H:%M"),
+                    truncate_string(&prompt.content, 50)
+                );
+            }
+        }
+
+        TrashAction::Restore { id } => {
+            db.restore_prompt(&id)
+                .map_err(|e| format!("Failed to restore prompt: {}", e))?;
+            println!("Prompt {} restored.", id);
+        }
+
+        TrashAction::Empty => {
+            let removed = db
+                .empty_trash(None)
+                .map_err(|e| format!("Failed to empty trash: {}", e))?;
+            println!("Permanently deleted {} prompts.", removed);
+        }
+    }
 
     Ok(())
 }
@@ -880,33 +1547,39 @@ fn cmd_delete(db: &Database, id: &str) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
+/// * `config` - Application configuration
 /// * `id` - The unique identifier of the prompt to update
 /// * `category` - Optional new category
 /// * `content` - Optional new content
 /// * `tags` - Optional comma-separated tags to add
 /// * `context` - Optional new context
+/// * `attrs` - Custom attributes to set as `key=value` strings
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if no updates are specified or the prompt is not found.
+#[allow(clippy::too_many_arguments)]
 fn cmd_update(
-    db: &Database,
+    db: &dyn PromptStore,
+    config: &Config,
     id: &str,
     category: Option<String>,
     content: Option<String>,
     tags: Option<String>,
     context: Option<String>,
+    attrs: &[String],
 ) -> Result<(), String> {
+    let attributes = parse_attr_args(attrs)?;
+
     // Get existing prompt
     let mut prompt = db
         .get_prompt(id)
         .map_err(|e| format!("Failed to get prompt: {}", e))?
         .ok_or_else(|| format!("Prompt not found: {}", id))?;
 
-    // Save current version before updating
-    db.save_version(&prompt)
-        .map_err(|e| format!("Failed to save version: {}", e))?;
+    // Kept as a version when the update is saved
+    let previous = prompt.clone();
 
     let mut updated = false;
 
@@ -917,8 +1590,18 @@ fn cmd_update(
     }
 
     if let Some(new_content) = content {
-        prompt.content = new_content;
+        let outcome = match build_redactor(config)? {
+            Some(redactor) => redactor
+                .redact(&new_content)
+                .map_err(|e| format!("Failed to process content: {}", e))?,
+            None => RedactionOutcome {
+                content: new_content,
+                findings: Vec::new(),
+            },
+        };
+        prompt.content = outcome.content;
         prompt.content_hash = prompt_tracking::utils::calculate_hash(&prompt.content);
+        prompt.redactions = outcome.findings;
         updated = true;
     }
 
@@ -937,13 +1620,24 @@ fn cmd_update(
         updated = true;
     }
 
+    if !attributes.is_empty() {
+        prompt.metadata.attributes.extend(attributes);
+        updated = true;
+    }
+
     if !updated {
-        return Err("No updates specified. Use --category, --content, --tags, or --context.".to_string());
+        return Err(
+            "No updates specified. Use --category, --content, --tags, --context, or --attr."
+                .to_string(),
+        );
     }
 
-    // Update in database
-    db.update_prompt(&prompt)
-        .map_err(|e| format!("Failed to update prompt: {}", e))?;
+    // Save the previous version and the update together
+    db.atomic(&mut |tx| {
+        tx.save_version(&previous)?;
+        tx.update_prompt(&prompt)
+    })
+    .map_err(|e| format!("Failed to update prompt: {}", e))?;
 
     println!("Prompt {} updated successfully.", id);
 
@@ -957,13 +1651,13 @@ fn cmd_update(
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `id` - The unique identifier of the prompt
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if the prompt is not found.
-fn cmd_history(db: &Database, id: &str) -> Result<(), String> {
+fn cmd_history(db: &dyn PromptStore, id: &str) -> Result<(), String> {
     // Check if prompt exists
     let _ = db
         .get_prompt(id)
@@ -998,14 +1692,14 @@ fn cmd_history(db: &Database, id: &str) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `id` - The unique identifier of the prompt
 /// * `version` - The version number to revert to
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if the prompt or version is not found.
-fn cmd_revert(db: &Database, id: &str, version: i32) -> Result<(), String> {
+fn cmd_revert(db: &dyn PromptStore, id: &str, version: i32) -> Result<(), String> {
     // Check if prompt exists
     let _ = db
         .get_prompt(id)
@@ -1022,20 +1716,49 @@ fn cmd_revert(db: &Database, id: &str, version: i32) -> Result<(), String> {
 
 /// Shows trends and statistics over time.
 ///
-/// Displays either daily trends (prompt count, average quality, average efficiency)
-/// or category distribution depending on the options provided.
+/// Displays either daily trends (prompt count, average quality, average efficiency),
+/// category distribution, or per-project statistics depending on the options provided.
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `days` - Number of days to analyze for trends
 /// * `show_categories` - If true, shows category distribution instead of daily trends
+/// * `show_projects` - If true, groups statistics by git project
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_trends(db: &Database, days: i32, show_categories: bool) -> Result<(), String> {
-    if show_categories {
+fn cmd_trends(
+    db: &dyn PromptStore,
+    days: i32,
+    show_categories: bool,
+    show_projects: bool,
+) -> Result<(), String> {
+    if show_projects {
+        let trends = db
+            .get_project_trends(days)
+            .map_err(|e| format!("Failed to get project trends: {}", e))?;
+
+        if trends.is_empty() {
+            println!("No data available for the last {} days.", days);
+            return Ok(());
+        }
+
+        println!("Project Trends (last {} days):\n", days);
+        println!("{:<25} {:<8} {:<15} {:<15}", "Project", "Count", "Avg Quality", "Avg Efficiency");
+        println!("{}", "-".repeat(65));
+
+        for trend in trends {
+            println!(
+                "{:<25} {:<8} {:<15.1} {:<15.1}",
+                truncate_string(&trend.project, 24),
+                trend.count,
+                trend.avg_quality,
+                trend.avg_efficiency
+            );
+        }
+    } else if show_categories {
         // Show category distribution
         let distribution = db
             .get_category_distribution()
@@ -1090,24 +1813,38 @@ fn cmd_trends(db: &Database, days: i32, show_categories: bool) -> Result<(), Str
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `output` - Path to the output file
 /// * `format` - Export format (currently only "json" is supported)
+/// * `encrypt` - Key source to seal the export into a bundle with, if any
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_export(db: &Database, output: &PathBuf, format: &str) -> Result<(), String> {
+fn cmd_export(
+    db: &dyn PromptStore,
+    output: &PathBuf,
+    format: &str,
+    encrypt: Option<&EncryptionConfig>,
+) -> Result<(), String> {
     match format.to_lowercase().as_str() {
         "json" => {
-            let json_str = db
+            let mut json_str = db
                 .export_to_json()
                 .map_err(|e| format!("Failed to export: {}", e))?;
+            if let Some(encryption) = encrypt {
+                json_str = encryption::seal_bundle(&json_str, &configured_key(encryption)?)
+                    .map_err(|e| format!("Failed to encrypt export: {}", e))?;
+            }
 
             std::fs::write(output, json_str)
                 .map_err(|e| format!("Failed to write file: {}", e))?;
 
-            println!("Data exported to: {}", output.display());
+            if encrypt.is_some() {
+                println!("Encrypted bundle exported to: {}", output.display());
+            } else {
+                println!("Data exported to: {}", output.display());
+            }
         }
         _ => {
             return Err(format!("Unsupported export format: {}. Use 'json'.", format));
@@ -1121,19 +1858,30 @@ fn cmd_export(db: &Database, output: &PathBuf, format: &str) -> Result<(), Strin
 ///
 /// Deserializes prompt data from a file and adds it to the database.
 /// Useful for restoring backups or transferring data between systems.
+/// Encrypted bundles are opened with the configured key.
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `input` - Path to the input file
 /// * `_skip_duplicates` - Reserved for future use to skip duplicate prompts
+/// * `encryption` - Where the key for encrypted bundles comes from
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_import(db: &Database, input: &PathBuf, _skip_duplicates: bool) -> Result<(), String> {
-    let json_str = std::fs::read_to_string(input)
+fn cmd_import(
+    db: &dyn PromptStore,
+    input: &PathBuf,
+    _skip_duplicates: bool,
+    encryption: &EncryptionConfig,
+) -> Result<(), String> {
+    let mut json_str = std::fs::read_to_string(input)
         .map_err(|e| format!("Failed to read file: {}", e))?;
+    if encryption::is_bundle(&json_str) {
+        json_str = encryption::open_bundle(&json_str, &configured_key(encryption)?)
+            .map_err(|e| format!("Failed to decrypt bundle: {}", e))?;
+    }
 
     let imported = db
         .import_from_json(&json_str)
@@ -1180,7 +1928,7 @@ fn cmd_init(config: &Config, force: bool) -> Result<(), String> {
     }
 
     // Initialize new database
-    let _db = Database::new(&config.database.path)
+    let _db = Database::from_config(&config.database)
         .map_err(|e| format!("Failed to initialize database: {}", e))?;
 
     println!("Database initialized at: {}", config.database.path);
@@ -1200,13 +1948,13 @@ fn cmd_init(config: &Config, force: bool) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `id` - The unique identifier of the prompt to archive
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if the prompt is not found.
-fn cmd_archive(db: &Database, id: &str) -> Result<(), String> {
+fn cmd_archive(db: &dyn PromptStore, id: &str) -> Result<(), String> {
     db.archive_prompt(id)
         .map_err(|e| format!("Failed to archive prompt: {}", e))?;
 
@@ -1222,13 +1970,13 @@ fn cmd_archive(db: &Database, id: &str) -> Result<(), String> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `id` - The unique identifier of the prompt to unarchive
 ///
 /// # Returns
 ///
 /// Returns `Ok(())` on success, or an error if the prompt is not found.
-fn cmd_unarchive(db: &Database, id: &str) -> Result<(), String> {
+fn cmd_unarchive(db: &dyn PromptStore, id: &str) -> Result<(), String> {
     db.unarchive_prompt(id)
         .map_err(|e| format!("Failed to unarchive prompt: {}", e))?;
 
@@ -1237,142 +1985,1473 @@ fn cmd_unarchive(db: &Database, id: &str) -> Result<(), String> {
     Ok(())
 }
 
-/// Watches a directory for new prompt files.
+/// Watches the configured directories for new prompt files.
 ///
-/// Starts a file watcher that monitors a directory for new or modified prompt files
-/// and automatically captures them. Runs continuously until interrupted with Ctrl+C.
+/// Starts a file watcher that monitors the roots from the `watch` config section
+/// for new or modified prompt files and automatically captures them, applying each
+/// root's default category, tags and model. Files already in the roots are
+/// backfilled first, skipping those unchanged since their last checkpoint.
+/// Runs continuously until interrupted with Ctrl+C.
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Prompt store
 /// * `config` - Application configuration
-/// * `watch_dir` - Optional directory path to watch; uses config default if not provided
-/// * `_category` - Reserved for future use
-/// * `_tags` - Reserved for future use
+/// * `watch_dir` - Optional directory to watch instead of the configured roots
+/// * `category` - Optional category applied to every captured prompt
+/// * `tags` - Optional comma-separated tags added to every captured prompt
+/// * `after_capture` - Optional post-capture action applied to every root
+/// * `backfill` - Whether to capture files already present in the roots
+/// * `backfill_only` - Exit after the backfill instead of watching
 ///
 /// # Returns
 ///
-/// This function runs indefinitely and only returns on error.
+/// With `backfill_only` returns after one pass; otherwise runs indefinitely
+/// and only returns on error.
+#[allow(clippy::too_many_arguments)]
 fn cmd_watch(
-    db: &Database,
+    db: &dyn PromptStore,
     config: &Config,
     watch_dir: Option<PathBuf>,
-    _category: Option<String>,
-    _tags: Option<String>,
+    category: Option<&str>,
+    tags: Option<String>,
+    after_capture: Option<String>,
+    backfill: bool,
+    backfill_only: bool,
 ) -> Result<(), String> {
-    let watch_path = watch_dir
-        .unwrap_or_else(|| PathBuf::from(&config.capture.watch_directory));
-
-    let watcher_config = WatcherConfig {
-        watch_path: watch_path.clone(),
-        recursive: true,
-        file_extensions: vec!["txt".to_string(), "md".to_string(), "prompt".to_string()],
-        similarity_threshold: config.capture.similarity_threshold,
-    };
+    let after_capture: Option<PostCaptureAction> = after_capture
+        .map(|a| a.parse())
+        .transpose()
+        .map_err(|e: String| format!("{}. Use 'leave', 'move', 'rename' or 'delete'.", e))?;
+
+    let mut watcher_config = WatcherConfig::from_config(config)
+        .map_err(|e| format!("Failed to configure watcher: {}", e))?;
+
+    // A directory on the command line replaces the configured roots
+    if let Some(dir) = watch_dir {
+        let root = WatchRootConfig::new(dir.to_string_lossy());
+        watcher_config.roots = vec![WatchRoot::from_config(&root, &config.watch)];
+    }
+
+    // Command-line category, tags and post-capture action apply to every root
+    let extra_tags: Vec<String> = tags
+        .map(|t| {
+            t.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    for root in &mut watcher_config.roots {
+        if let Some(cat) = category {
+            root.category = Some(cat.to_string());
+        }
+        for tag in &extra_tags {
+            if !root.tags.contains(tag) {
+                root.tags.push(tag.clone());
+            }
+        }
+        if let Some(action) = after_capture {
+            root.after_capture = action;
+        }
+    }
 
     println!("Starting file watcher...");
-    println!("Watching directory: {}", watch_path.display());
-    println!("File extensions: .txt, .md, .prompt");
-    println!("Press Ctrl+C to stop.\n");
+    for root in &watcher_config.roots {
+        println!(
+            "Watching directory: {}{}",
+            root.path.display(),
+            if root.recursive { "" } else { " (top level only)" }
+        );
+        println!(
+            "  File extensions: {}",
+            root.file_extensions
+                .iter()
+                .map(|e| format!(".{}", e.trim_start_matches('.')))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if let Some(ref cat) = root.category {
+            println!("  Category: {}", cat);
+        }
+        if !root.tags.is_empty() {
+            println!("  Tags: {}", root.tags.join(", "));
+        }
+        match root.after_capture {
+            PostCaptureAction::Leave => {}
+            PostCaptureAction::Move => {
+                println!("  After capture: move to {}", root.processed_dir.display())
+            }
+            action => println!("  After capture: {}", action),
+        }
+    }
 
     let mut watcher = FileWatcher::new(watcher_config)
         .map_err(|e| format!("Failed to create watcher: {}", e))?;
 
-    watcher.start().map_err(|e| format!("Failed to start watcher: {}", e))?;
+    // Start watching before the scan so files written during it are not missed
+    if !backfill_only {
+        watcher.start().map_err(|e| format!("Failed to start watcher: {}", e))?;
+    }
 
     let quality_analyzer = QualityAnalyzer::default();
     let efficiency_analyzer = EfficiencyAnalyzer::default();
 
+    if backfill || backfill_only {
+        println!("Scanning for existing files...");
+        let report = watcher
+            .backfill(db)
+            .map_err(|e| format!("Failed to backfill: {}", e))?;
+
+        for event in &report.events {
+            print_watch_event(event);
+            auto_analyze_event(db, config, event, &quality_analyzer, &efficiency_analyzer);
+        }
+
+        println!(
+            "Backfill complete: {} files scanned, {} captured or updated, {} unchanged, {} failed",
+            report.scanned,
+            report.events.len(),
+            report.unchanged,
+            report.failed
+        );
+    }
+
+    if backfill_only {
+        return Ok(());
+    }
+
+    println!("Press Ctrl+C to stop.\n");
+
     loop {
-        let captured_ids = watcher
-            .process_events(db)
+        let events = watcher
+            .wait_events(db)
             .map_err(|e| format!("Failed to process events: {}", e))?;
 
-        for id in captured_ids {
-            println!("Captured prompt: {}", id);
+        for event in &events {
+            print_watch_event(event);
+            auto_analyze_event(db, config, event, &quality_analyzer, &efficiency_analyzer);
+        }
+    }
+}
 
-            // Auto-analyze if enabled
-            if config.analysis.auto_analyze {
-                if let Ok(Some(prompt)) = db.get_prompt(&id) {
-                    if let Ok(quality) = quality_analyzer.analyze(&prompt) {
-                        if let Err(e) = db.save_quality_score(&quality) {
-                            eprintln!("Failed to save quality score: {}", e);
-                        }
+/// Print a line describing a captured or revised watched file
+fn print_watch_event(event: &WatchEvent) {
+    match event {
+        WatchEvent::Captured { prompt_id, path } => {
+            println!("Captured prompt: {} ({})", prompt_id, path.display());
+        }
+        WatchEvent::Revised {
+            prompt_id,
+            path,
+            version,
+        } => {
+            println!(
+                "Updated prompt: {} ({}), previous content saved as version {}",
+                prompt_id,
+                path.display(),
+                version
+            );
+        }
+    }
+}
+
+/// Analyze the prompt behind a watch event if auto-analysis is enabled
+fn auto_analyze_event(
+    db: &dyn PromptStore,
+    config: &Config,
+    event: &WatchEvent,
+    quality_analyzer: &QualityAnalyzer,
+    efficiency_analyzer: &EfficiencyAnalyzer,
+) {
+    if !config.analysis.auto_analyze {
+        return;
+    }
+    if let Ok(Some(prompt)) = db.get_prompt(event.prompt_id()) {
+        if let Ok(quality) = quality_analyzer.analyze(&prompt) {
+            if let Err(e) = db.save_quality_score(&quality) {
+                eprintln!("Failed to save quality score: {}", e);
+            }
+        }
+        if let Ok(efficiency) = efficiency_analyzer.analyze(&prompt) {
+            if let Err(e) = db.save_efficiency_metrics(&efficiency) {
+                eprintln!("Failed to save efficiency metrics: {}", e);
+            }
+        }
+    }
+}
+
+/// Review or undo post-capture file actions
+///
+/// Lists the files the watcher moved, renamed or deleted after capturing
+/// them, or puts one back where it was captured.
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `action` - File actions subcommand to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the action is not found or
+/// cannot be undone.
+fn cmd_file_actions(db: &dyn PromptStore, action: &FileActionsAction) -> Result<(), String> {
+    match *action {
+        FileActionsAction::List { limit } => {
+            let actions = db
+                .list_file_actions(limit)
+                .map_err(|e| format!("Failed to list file actions: {}", e))?;
+
+            if actions.is_empty() {
+                println!("No file actions recorded.");
+                return Ok(());
+            }
+
+            println!(
+                "{:<6} {:<8} {:<17} {:<10} Path",
+                "ID", "Action", "Performed", "Prompt"
+            );
+            println!("{}", "-".repeat(90));
+            for action in &actions {
+                println!(
+                    "{:<6} {:<8} {:<17} {:<10} {}{}",
+                    action.id,
+                    action.action,
+                    action.performed_at.format("%Y-%m-%d %H:%M"),
+                    &action.prompt_id[..8.min(action.prompt_id.len())],
+                    action.source,
+                    if action.undone_at.is_some() { " (undone)" } else { "" }
+                );
+                if let Some(ref destination) = action.destination {
+                    println!("       -> {}", destination);
+                }
+            }
+        }
+
+        FileActionsAction::Undo { id } => {
+            let action = db
+                .get_file_action(id)
+                .map_err(|e| format!("Failed to get file action: {}", e))?
+                .ok_or_else(|| format!("File action not found: {}", id))?;
+
+            watcher::undo_file_action(&action, db)
+                .map_err(|e| format!("Failed to undo file action {}: {}", id, e))?;
+            println!("Restored {}", action.source);
+        }
+    }
+
+    Ok(())
+}
+
+/// Manage captures the watcher could not complete
+///
+/// Lists failed captures with their error and attempt count, retries them
+/// with the current configuration, or dismisses them so they are no longer
+/// shown or retried automatically.
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `config` - Application configuration
+/// * `action` - Failures action to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if a failure ID is not found.
+fn cmd_failures(db: &dyn PromptStore, config: &Config, action: FailuresAction) -> Result<(), String> {
+    match action {
+        FailuresAction::List { all } => {
+            let failures = db
+                .list_capture_failures(all)
+                .map_err(|e| format!("Failed to list failures: {}", e))?;
+
+            if failures.is_empty() {
+                println!("No failed captures.");
+                return Ok(());
+            }
+
+            println!(
+                "{:<6} {:<20} {:<9} {:<17} Path",
+                "ID", "Error", "Attempts", "Last seen"
+            );
+            println!("{}", "-".repeat(90));
+            for f in &failures {
+                println!(
+                    "{:<6} {:<20} {:<9} {:<17} {}{}",
+                    f.id,
+                    f.error_kind,
+                    f.attempts,
+                    f.last_seen.format("%Y-%m-%d %H:%M"),
+                    f.path,
+                    if f.dismissed { " (dismissed)" } else { "" }
+                );
+                println!("       {}", f.message);
+                if let Some(at) = f.next_retry_at {
+                    println!("       Next retry: {}", at.format("%Y-%m-%d %H:%M:%S UTC"));
+                }
+            }
+            println!("\nTotal: {} failed captures", failures.len());
+        }
+
+        FailuresAction::Retry { id, dir } => {
+            let failures = select_failures(db, &id)?;
+            let mut watcher_config = WatcherConfig::from_config(config)
+                .map_err(|e| format!("Failed to configure watcher: {}", e))?;
+            if let Some(dir) = dir {
+                let root = WatchRootConfig::new(dir.to_string_lossy());
+                watcher_config.roots = vec![WatchRoot::from_config(&root, &config.watch)];
+            }
+            let watcher = FileWatcher::new(watcher_config)
+                .map_err(|e| format!("Failed to create watcher: {}", e))?;
+
+            let quality_analyzer = QualityAnalyzer::default();
+            let efficiency_analyzer = EfficiencyAnalyzer::default();
+            let mut resolved = 0;
+            for failure in &failures {
+                let events = watcher
+                    .retry(failure, db)
+                    .map_err(|e| format!("Failed to retry {}: {}", failure.path, e))?;
+                for event in &events {
+                    print_watch_event(event);
+                    auto_analyze_event(db, config, event, &quality_analyzer, &efficiency_analyzer);
+                }
+
+                match db
+                    .find_capture_failure(&failure.path)
+                    .map_err(|e| format!("Failed to get failure: {}", e))?
+                {
+                    Some(still) if still.last_seen > failure.last_seen => {
+                        println!("Still failing: {} ({})", failure.path, still.message);
                     }
-                    if let Ok(efficiency) = efficiency_analyzer.analyze(&prompt) {
-                        if let Err(e) = db.save_efficiency_metrics(&efficiency) {
-                            eprintln!("Failed to save efficiency metrics: {}", e);
+                    _ => {
+                        resolved += 1;
+                        if events.is_empty() {
+                            println!("Resolved: {}", failure.path);
                         }
                     }
                 }
             }
+            println!("{} of {} failures resolved", resolved, failures.len());
         }
 
-        // Sleep to avoid busy-waiting
-        std::thread::sleep(std::time::Duration::from_millis(100));
+        FailuresAction::Dismiss { id } => {
+            let failures = select_failures(db, &id)?;
+            for failure in &failures {
+                db.dismiss_capture_failure(failure.id)
+                    .map_err(|e| format!("Failed to dismiss failure: {}", e))?;
+            }
+            println!("Dismissed {} failed captures", failures.len());
+        }
     }
+
+    Ok(())
 }
 
-/// Queries prompts using advanced filter syntax.
+/// Look up failures by ID, or all undismissed failures for `all`
+fn select_failures(db: &dyn PromptStore, id: &str) -> Result<Vec<CaptureFailure>, String> {
+    if id == "all" {
+        return db
+            .list_capture_failures(false)
+            .map_err(|e| format!("Failed to list failures: {}", e));
+    }
+
+    let id: i64 = id
+        .parse()
+        .map_err(|_| format!("Invalid failure ID: {}", id))?;
+    let failure = db
+        .get_capture_failure(id)
+        .map_err(|e| format!("Failed to get failure: {}", e))?
+        .ok_or_else(|| format!("Failure not found: {}", id))?;
+    Ok(vec![failure])
+}
+
+/// Test webhook endpoints and manage the delivery outbox
 ///
-/// Supports powerful filtering with syntax like:
-/// - `category:code` - Filter by category
-/// - `tag:rust` - Filter by tag
-/// - `quality:>80` - Filter by quality score
-/// - `date:>2024-01-01` - Filter by creation date
+/// Sends a signed test event to check an endpoint, lists deliveries with
+/// their status and last error, attempts due deliveries now, or queues
+/// failed deliveries again.
 ///
 /// # Arguments
 ///
 /// * `db` - Database connection
-/// * `query` - Advanced filter query string
+/// * `dispatcher` - Dispatcher for the configured endpoints, if any
+/// * `action` - Webhooks action to perform
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` on success, or an error message on failure.
-fn cmd_query(db: &Database, query: &str) -> Result<(), String> {
-    // Parse the advanced filter query
-    let filter = parse_filter_query(query)
-        .map_err(|e| format!("Failed to parse query: {}", e))?;
+/// Returns `Ok(())` on success, or an error if a test request fails or a
+/// delivery ID is not found.
+fn cmd_webhooks(
+    db: &Database,
+    dispatcher: Option<&WebhookDispatcher>,
+    action: WebhooksAction,
+) -> Result<(), String> {
+    let require_dispatcher =
+        || dispatcher.ok_or_else(|| "No webhook endpoints configured".to_string());
+
+    match action {
+        WebhooksAction::Test { name } => {
+            let dispatcher = require_dispatcher()?;
+            let names = match name {
+                Some(name) => vec![name],
+                None => dispatcher
+                    .endpoint_names()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            };
+
+            let mut failed = 0;
+            for name in &names {
+                match dispatcher.send_test(name) {
+                    Ok(status) => println!("✓ {}: HTTP {}", name, status),
+                    Err(e) => {
+                        println!("✗ {}: {}", name, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} of {} endpoints failed", failed, names.len()));
+            }
+        }
 
-    // Query prompts
-    let prompts = db
-        .list_prompts(&filter)
-        .map_err(|e| format!("Failed to query prompts: {}", e))?;
+        WebhooksAction::List { status, limit } => {
+            let status = status
+                .map(|s| s.parse::<DeliveryStatus>())
+                .transpose()?;
+            let deliveries = db
+                .list_webhook_deliveries(status, limit)
+                .map_err(|e| format!("Failed to list deliveries: {}", e))?;
+
+            if deliveries.is_empty() {
+                println!("No webhook deliveries.");
+                return Ok(());
+            }
 
-    if prompts.is_empty() {
-        println!("No prompts found matching query.");
-        return Ok(());
+            println!(
+                "{:<6} {:<16} {:<17} {:<10} {:<9} Created",
+                "ID", "Endpoint", "Event", "Status", "Attempts"
+            );
+            println!("{}", "-".repeat(90));
+            for delivery in &deliveries {
+                println!(
+                    "{:<6} {:<16} {:<17} {:<10} {:<9} {}",
+                    delivery.id,
+                    truncate_string(&delivery.endpoint, 16),
+                    delivery.event,
+                    delivery.status,
+                    delivery.attempts,
+                    delivery.created_at.format("%Y-%m-%d %H:%M")
+                );
+                if let Some(ref error) = delivery.last_error {
+                    println!("       {}", error);
+                }
+                if let (DeliveryStatus::Pending, Some(next)) =
+                    (delivery.status, delivery.next_attempt_at)
+                {
+                    if delivery.attempts > 0 {
+                        println!("       next attempt {}", next.format("%Y-%m-%d %H:%M:%S"));
+                    }
+                }
+            }
+        }
+
+        WebhooksAction::Flush => {
+            let report = require_dispatcher()?
+                .deliver_due()
+                .map_err(|e| format!("Failed to deliver webhooks: {}", e))?;
+            println!(
+                "Delivered: {}, retrying: {}, failed: {}",
+                report.delivered, report.retrying, report.failed
+            );
+        }
+
+        WebhooksAction::Retry { id } => {
+            let deliveries = select_failed_deliveries(db, &id)?;
+            for delivery in &deliveries {
+                db.requeue_webhook(delivery.id)
+                    .map_err(|e| format!("Failed to requeue delivery {}: {}", delivery.id, e))?;
+            }
+            println!("Queued {} deliveries", deliveries.len());
+
+            if let Some(dispatcher) = dispatcher {
+                let report = dispatcher
+                    .deliver_due()
+                    .map_err(|e| format!("Failed to deliver webhooks: {}", e))?;
+                println!(
+                    "Delivered: {}, retrying: {}, failed: {}",
+                    report.delivered, report.retrying, report.failed
+                );
+            }
+        }
     }
 
-    println!("Found {} prompt(s):\n", prompts.len());
+    Ok(())
+}
 
-    for prompt in prompts {
-        // Get quality score if available
-        let quality = db.get_quality_score(&prompt.id).ok().flatten();
+/// Look up a failed delivery by ID, or all failed deliveries for `all`
+fn select_failed_deliveries(db: &Database, id: &str) -> Result<Vec<WebhookDelivery>, String> {
+    if id == "all" {
+        return db
+            .list_webhook_deliveries(Some(DeliveryStatus::Failed), i64::MAX as usize)
+            .map_err(|e| format!("Failed to list deliveries: {}", e));
+    }
 
-        println!("ID: {}", prompt.id);
-        println!("Created: {}", prompt.created_at.format("%Y-%m-%d %H:%M"));
-        println!(
-            "Content: {}",
-            truncate_string(&prompt.content.replace('\n', " "), 100)
-        );
+    let id: i64 = id
+        .parse()
+        .map_err(|_| format!("Invalid delivery ID: {}", id))?;
+    let delivery = db
+        .get_webhook_delivery(id)
+        .map_err(|e| format!("Failed to get delivery: {}", e))?
+        .ok_or_else(|| format!("Delivery not found: {}", id))?;
+    if delivery.status != DeliveryStatus::Failed {
+        return Err(format!("Delivery {} has not failed ({})", id, delivery.status));
+    }
+    Ok(vec![delivery])
+}
 
-        if let Some(category) = &prompt.category {
-            println!("Category: {}", category);
+/// Key material from the configured key file or passphrase variable
+fn configured_key(encryption: &EncryptionConfig) -> Result<KeyMaterial, String> {
+    KeyMaterial::from_config(encryption)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            format!(
+                "No encryption key found; provide one through {}",
+                encryption::describe_source(encryption)
+            )
+        })
+}
+
+/// Manage encryption of the prompt database
+///
+/// `encrypt`, `decrypt` and `rekey` rewrite every stored prompt in one
+/// transaction and then compact the file so no plaintext is left behind.
+/// The daemon must be stopped first, as it holds the key it started with.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `config` - Application configuration
+/// * `action` - Encryption action to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if no key is available, the
+/// database is in the wrong state, or the daemon is running.
+fn cmd_encryption(db: &Database, config: &Config, action: EncryptionAction) -> Result<(), String> {
+    let settings = &config.database.encryption;
+
+    if !matches!(action, EncryptionAction::Status | EncryptionAction::Keygen { .. }) {
+        #[cfg(unix)]
+        if let Some(pid) = daemon::pid(&config.daemon) {
+            return Err(format!(
+                "The daemon (pid {}) is running; stop it before changing encryption",
+                pid
+            ));
         }
+    }
 
-        if !prompt.tags.is_empty() {
-            println!("Tags: {}", prompt.tags.join(", "));
+    match action {
+        EncryptionAction::Status => {
+            match db.encryption_params() {
+                Some(params) => println!("Encrypted: yes ({}, AES-256-GCM)", params.kdf),
+                None => println!("Encrypted: no"),
+            }
+            println!("Key source: {}", encryption::describe_source(settings));
+            println!(
+                "Required: {}",
+                if settings.enabled { "yes" } else { "no" }
+            );
         }
 
-        println!("Status: {}", prompt.status);
+        EncryptionAction::Keygen { path } => {
+            encryption::generate_key_file(&path)
+                .map_err(|e| format!("Failed to generate key: {}", e))?;
+            println!("✓ Wrote a new key to {}", path.display());
+            println!("  Set database.encryption.key_file to use it, and keep a copy:");
+            println!("  without it the encrypted prompts cannot be recovered.");
+        }
 
-        if let Some(q) = quality {
-            println!("Quality: {:.1}", q.total_score);
+        EncryptionAction::Encrypt => {
+            let count = db
+                .encrypt(&configured_key(settings)?)
+                .map_err(|e| format!("Failed to encrypt database: {}", e))?;
+            println!("✓ Encrypted {} values", count);
+            if !settings.enabled {
+                println!("  Set database.encryption.enabled to require encryption from now on.");
+            }
+            println!("  Backups and exports taken earlier are still in plaintext.");
         }
 
-        println!("{}", "-".repeat(50));
+        EncryptionAction::Decrypt => {
+            let count = db
+                .decrypt()
+                .map_err(|e| format!("Failed to decrypt database: {}", e))?;
+            println!("✓ Decrypted {} values", count);
+            if settings.enabled {
+                println!("  Unset database.encryption.enabled, or the database will not open.");
+            }
+        }
+
+        EncryptionAction::Rekey {
+            new_key_file,
+            new_passphrase_env,
+        } => {
+            let material = match (new_key_file, new_passphrase_env) {
+                (Some(path), _) => KeyMaterial::from_key_file(&path)
+                    .map_err(|e| format!("Failed to read new key: {}", e))?,
+                (None, Some(var)) => KeyMaterial::from_env(&var)
+                    .map_err(|e| format!("Failed to read new key: {}", e))?
+                    .ok_or_else(|| format!("{} is not set", var))?,
+                (None, None) => return Err("Give --new-key-file or --new-passphrase-env".to_string()),
+            };
+
+            let count = db
+                .rekey(&material)
+                .map_err(|e| format!("Failed to rekey database: {}", e))?;
+            println!("✓ Re-encrypted {} values under the new key", count);
+            println!("  Point database.encryption at the new key before the next command.");
+            println!("  Backups taken earlier still need the old key.");
+        }
     }
 
     Ok(())
 }
+
+/// Create, list, verify and restore database backups
+///
+/// Backups are written to `database.backup_dir` (by default a `backups`
+/// directory next to the database) and pruned according to
+/// `backup_keep_daily` and `backup_keep_weekly` after each new one.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `config` - Application configuration
+/// * `action` - Backup action to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if a backup cannot be taken,
+/// is not found or fails verification.
+fn cmd_backup(db: &Database, config: &Config, action: BackupAction) -> Result<(), String> {
+    let manager = BackupManager::from_config(&config.database);
+
+    match action {
+        BackupAction::Create => {
+            let created = manager
+                .create(db)
+                .map_err(|e| format!("Failed to create backup: {}", e))?;
+            println!("✓ Created backup {} ({} bytes)", created.name, created.size);
+
+            let removed = manager
+                .prune()
+                .map_err(|e| format!("Failed to prune backups: {}", e))?;
+            for backup in &removed {
+                println!("  Removed {}", backup.name);
+            }
+        }
+
+        BackupAction::List => {
+            let backups = manager
+                .list()
+                .map_err(|e| format!("Failed to list backups: {}", e))?;
+            if backups.is_empty() {
+                println!("No backups in {}.", manager.dir().display());
+                return Ok(());
+            }
+
+            println!("{:<34} {:<20} {:>12}", "Name", "Created", "Size");
+            println!("{}", "-".repeat(68));
+            for backup in &backups {
+                println!(
+                    "{:<34} {:<20} {:>12}",
+                    backup.name,
+                    backup.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    backup.size
+                );
+            }
+        }
+
+        BackupAction::Verify { name } => {
+            let backups = match name {
+                Some(name) => vec![manager.find(&name).map_err(|e| e.to_string())?],
+                None => manager
+                    .list()
+                    .map_err(|e| format!("Failed to list backups: {}", e))?,
+            };
+
+            let mut failed = 0;
+            for backup in &backups {
+                match backup::verify_backup(&backup.path) {
+                    Ok(()) => println!("✓ {}", backup.name),
+                    Err(e) => {
+                        println!("✗ {}: {}", backup.name, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} of {} backups failed", failed, backups.len()));
+            }
+        }
+
+        BackupAction::Restore { name } => {
+            let previous = manager
+                .restore(db, &name)
+                .map_err(|e| format!("Failed to restore backup: {}", e))?;
+            println!("✓ Restored {}", name);
+            println!("  Previous contents saved as {}", previous.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Report and migrate the database schema
+///
+/// The database is opened without applying pending migrations. `migrate`
+/// takes a backup before changing the schema; with `--dry-run` it only
+/// prints the SQL each step would run.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `config` - Application configuration
+/// * `action` - Schema action to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the target version is
+/// unknown, the backup fails or a migration fails.
+fn cmd_db(db: &Database, config: &Config, action: &DbAction) -> Result<(), String> {
+    let version = db
+        .schema_version()
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+    match *action {
+        DbAction::Status => {
+            println!("Schema version: v{} (latest v{})", version, CURRENT_VERSION);
+            let pending = CURRENT_VERSION - version;
+            if pending > 0 {
+                println!("Pending migrations: {}", pending);
+            }
+            if let Some(pinned) = db
+                .pinned_version()
+                .map_err(|e| format!("Failed to read schema pin: {}", e))?
+            {
+                println!(
+                    "Pinned at v{} by `db migrate --to`; other commands refuse to run until `db migrate`",
+                    pinned
+                );
+            }
+
+            let history = db
+                .get_migration_history()
+                .map_err(|e| format!("Failed to read migration history: {}", e))?;
+            if history.is_empty() {
+                return Ok(());
+            }
+
+            println!();
+            println!("{:<8} {:<20} Description", "Version", "Applied");
+            println!("{}", "-".repeat(72));
+            for (applied, at) in &history {
+                let description = MIGRATIONS
+                    .iter()
+                    .find(|migration| migration.version == *applied)
+                    .map_or("(unknown)", |migration| migration.description);
+                println!("{:<8} {:<20} {}", format!("v{}", applied), at, description);
+            }
+        }
+
+        DbAction::Migrate { to, dry_run } => {
+            let target = to.unwrap_or(CURRENT_VERSION);
+            let plan = db
+                .migration_plan(target)
+                .map_err(|e| format!("Failed to plan migration: {}", e))?;
+            if plan.is_empty() {
+                println!("Schema is already at v{}", version);
+                return Ok(());
+            }
+
+            if dry_run {
+                for step in &plan {
+                    println!(
+                        "-- v{} {}: {}",
+                        step.migration.version,
+                        step.direction.as_str(),
+                        step.migration.description
+                    );
+                    println!("{}", step.script());
+                    println!();
+                }
+                println!(
+                    "-- {} steps from v{} to v{}; nothing was changed",
+                    plan.len(),
+                    version,
+                    target
+                );
+                return Ok(());
+            }
+
+            #[cfg(unix)]
+            if let Some(pid) = daemon::pid(&config.daemon) {
+                return Err(format!(
+                    "The daemon (pid {}) is running; stop it before migrating",
+                    pid
+                ));
+            }
+
+            if version > 0 && db.path().is_some() {
+                let backup = BackupManager::from_config(&config.database)
+                    .create(db)
+                    .map_err(|e| format!("Failed to back up before migrating: {}", e))?;
+                println!("✓ Backed up v{} as {}", version, backup.name);
+            }
+
+            let steps = db
+                .migrate_to(target)
+                .map_err(|e| format!("Migration failed, schema left at v{}: {}", version, e))?;
+            for step in &steps {
+                println!(
+                    "✓ v{} {}: {}",
+                    step.migration.version,
+                    step.direction.as_str(),
+                    step.migration.description
+                );
+            }
+            println!("Schema is now at v{}", target);
+            if target < CURRENT_VERSION {
+                println!(
+                    "  Pinned at v{}: other commands refuse to run until `db migrate` brings it back to v{}.",
+                    target, CURRENT_VERSION
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the retention rules from the `retention` config section
+///
+/// Archived prompts past `purge_archived_after_days` are deleted, prompts
+/// not updated for `archive_after_days` are archived, and scores and
+/// version history are trimmed to `keep_scores` and `keep_versions` per
+/// prompt. The daemon does the same every `interval_hours` when
+/// `auto_apply` is on.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `config` - Application configuration
+/// * `action` - Maintenance action to perform
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the rules could not be
+/// applied, in which case nothing was changed.
+fn cmd_maintenance(
+    db: &Database,
+    config: &Config,
+    action: &MaintenanceAction,
+) -> Result<(), String> {
+    match *action {
+        MaintenanceAction::Run { dry_run } => {
+            let policy = RetentionPolicy::from_config(&config.retention);
+            if policy.is_empty() {
+                println!("No retention rules are configured; see the `retention` config section.");
+                return Ok(());
+            }
+
+            let report = policy
+                .run(db, dry_run)
+                .map_err(|e| format!("Failed to apply retention rules: {}", e))?;
+            if report.is_empty() {
+                println!("Nothing to do.");
+                return Ok(());
+            }
+
+            let (purge, archive, remove) = if dry_run {
+                ("Would purge", "Would archive", "Would remove")
+            } else {
+                ("Purged", "Archived", "Removed")
+            };
+            if report.trash_emptied > 0 {
+                println!("{} {} prompts from the trash", purge, report.trash_emptied);
+            }
+            if !report.purged.is_empty() {
+                println!("{} {} archived prompts", purge, report.purged.len());
+                for id in &report.purged {
+                    println!("  {}", id);
+                }
+            }
+            if !report.archived.is_empty() {
+                println!("{} {} inactive prompts", archive, report.archived.len());
+                for id in &report.archived {
+                    println!("  {}", id);
+                }
+            }
+            if report.scores_removed > 0 {
+                println!("{} {} old score rows", remove, report.scores_removed);
+            }
+            if report.versions_removed > 0 {
+                println!(
+                    "{} {} version history entries",
+                    remove, report.versions_removed
+                );
+            }
+            if dry_run {
+                println!("Dry run; nothing was changed.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the database for corruption and inconsistencies
+///
+/// Runs SQLite's integrity check and looks for stale content hashes,
+/// orphaned tag and score rows, duplicate prompts and an out-of-sync
+/// search index. With `fix`, everything but duplicates is repaired.
+///
+/// # Arguments
+///
+/// * `db` - Database connection
+/// * `fix` - Whether to repair what can be repaired
+///
+/// # Returns
+///
+/// Returns `Ok(())` when no problem is left, or an error counting the
+/// problems that remain.
+fn cmd_doctor(db: &Database, fix: bool) -> Result<(), String> {
+    let report =
+        doctor::examine(db, fix).map_err(|e| format!("Failed to check database: {}", e))?;
+
+    for check in doctor::Check::ALL {
+        let issues: Vec<_> = report.issues_for(check).collect();
+        if issues.is_empty() {
+            println!("✓ {}", check.label());
+            continue;
+        }
+
+        println!("✗ {}", check.label());
+        for issue in issues {
+            println!(
+                "    {}{}",
+                issue.description,
+                if issue.fixed { " (fixed)" } else { "" }
+            );
+        }
+    }
+
+    if report.issues_for(doctor::Check::Integrity).next().is_some() {
+        println!();
+        println!("The database file is damaged; restore a backup with `backup restore`.");
+    }
+
+    let remaining = report.unresolved();
+    if remaining == 0 {
+        println!();
+        match report.issues.len() {
+            0 => println!("No problems found."),
+            n => println!("Fixed {} problems.", n),
+        }
+        return Ok(());
+    }
+
+    Err(if fix {
+        format!("{} problems need manual attention", remaining)
+    } else {
+        format!("{} problems found; run `doctor --fix` to repair them", remaining)
+    })
+}
+
+/// Control the background capture daemon
+///
+/// `start` runs the daemon in the foreground (use a service manager or the
+/// shell to background it) with a single-instance pidfile lock; `stop` and
+/// `reload` signal the running instance; `status` reads its status file.
+///
+/// # Arguments
+///
+/// * `config` - Application configuration
+/// * `config_path` - Configuration file re-read on reload
+/// * `action` - Daemon action to perform
+///
+/// # Returns
+///
+/// `start` returns once the daemon has shut down.
+#[cfg(unix)]
+fn cmd_daemon(
+    config: &Config,
+    config_path: Option<PathBuf>,
+    action: &DaemonAction,
+) -> Result<(), String> {
+    match *action {
+        DaemonAction::Start { no_backfill } => {
+            println!(
+                "Daemon starting (pid {}), logging to {}",
+                std::process::id(),
+                config.daemon.log_file
+            );
+            let options = DaemonOptions {
+                config_path,
+                backfill: !no_backfill,
+            };
+            daemon::run(config.clone(), &options).map_err(|e| e.to_string())?;
+            println!("Daemon stopped");
+            Ok(())
+        }
+
+        DaemonAction::Stop => {
+            let pid = daemon::stop(&config.daemon).map_err(|e| e.to_string())?;
+            if daemon::wait_for_exit(&config.daemon, std::time::Duration::from_secs(10)) {
+                println!("Daemon stopped (pid {})", pid);
+                Ok(())
+            } else {
+                Err(format!("Daemon (pid {}) did not stop within 10 seconds", pid))
+            }
+        }
+
+        DaemonAction::Reload => {
+            let pid = daemon::reload(&config.daemon).map_err(|e| e.to_string())?;
+            println!("Reload requested (pid {})", pid);
+            Ok(())
+        }
+
+        DaemonAction::Status => {
+            let running = daemon::pid(&config.daemon);
+            let status = daemon::status(&config.daemon).map_err(|e| e.to_string())?;
+
+            let Some(status) = status else {
+                println!("Daemon: not running (no status recorded)");
+                return Ok(());
+            };
+
+            match running {
+                Some(pid) => println!("Daemon: running (pid {})", pid),
+                None if status.state == DaemonState::Running => {
+                    println!("Daemon: not running (exited without shutting down)");
+                }
+                None => println!("Daemon: stopped"),
+            }
+
+            let uptime = status.uptime().num_seconds().max(0);
+            println!(
+                "Uptime: {}h {}m {}s",
+                uptime / 3600,
+                uptime % 3600 / 60,
+                uptime % 60
+            );
+            println!(
+                "Started: {}",
+                status.started_at.format("%Y-%m-%d %H:%M:%S UTC")
+            );
+            if !status.roots.is_empty() {
+                println!("Watching: {}", status.roots.join(", "));
+            }
+            println!("Captured: {}", status.captured);
+            println!("Updated: {}", status.revised);
+            println!("Reloads: {}", status.reloads);
+            println!("Errors: {}", status.errors);
+            if let Some(at) = status.last_event_at {
+                println!("Last event: {}", at.format("%Y-%m-%d %H:%M:%S UTC"));
+            }
+            if let Some(ref error) = status.last_error {
+                println!("Last error: {}", error);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Queries prompts using advanced filter syntax.
+///
+/// Supports powerful filtering with syntax like:
+/// - `category:code` - Filter by category
+/// - `tag:rust` - Filter by tag
+/// - `quality:>80` - Filter by quality score
+/// - `date:>2024-01-01` - Filter by creation date
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `query` - Advanced filter query string
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error message on failure.
+fn cmd_query(db: &dyn PromptStore, query: &str) -> Result<(), String> {
+    // Parse the advanced filter query
+    let filter = parse_filter_query(query)
+        .map_err(|e| format!("Failed to parse query: {}", e))?;
+
+    // Query prompts
+    let prompts = db
+        .list_prompts(&filter)
+        .map_err(|e| format!("Failed to query prompts: {}", e))?;
+
+    if prompts.is_empty() {
+        println!("No prompts found matching query.");
+        return Ok(());
+    }
+
+    println!("Found {} prompt(s):\n", prompts.len());
+
+    let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+    let mut scores = db
+        .get_latest_quality_scores(&ids)
+        .map_err(|e| format!("Failed to get quality scores: {}", e))?;
+
+    for prompt in &prompts {
+        let quality = scores.remove(&prompt.id);
+
+        println!("ID: {}", prompt.id);
+        println!("Created: {}", prompt.created_at.format("%Y-%m-%d %H:%M"));
+        println!(
+            "Content: {}",
+            truncate_string(&prompt.content.replace('\n', " "), 100)
+        );
+
+        if let Some(category) = &prompt.category {
+            println!("Category: {}", category);
+        }
+
+        if !prompt.tags.is_empty() {
+            println!("Tags: {}", prompt.tags.join(", "));
+        }
+
+        println!("Status: {}", prompt.status);
+
+        if let Some(q) = quality {
+            println!("Quality: {:.1}", q.total_score);
+        }
+
+        println!("{}", "-".repeat(50));
+    }
+
+    Ok(())
+}
+
+/// Manages prompt templates.
+///
+/// Adds, lists, shows, deletes and renders templates. Rendering with `capture`
+/// saves the result as a prompt linked to its template and variable values.
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `config` - Application configuration
+/// * `action` - The template subcommand to run
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if the template is missing or cannot be rendered.
+fn cmd_template(db: &dyn PromptStore, config: &Config, action: TemplateAction) -> Result<(), String> {
+    match action {
+        TemplateAction::Add {
+            name,
+            content,
+            file,
+            description,
+            force,
+        } => {
+            let content = match (content, file) {
+                (Some(c), _) => c,
+                (None, Some(f)) => std::fs::read_to_string(&f)
+                    .map_err(|e| format!("Failed to read file: {}", e))?,
+                (None, None) => return Err("Either --content or --file must be provided".to_string()),
+            };
+            if content.trim().is_empty() {
+                return Err("Template content is empty".to_string());
+            }
+
+            let existing = db
+                .get_template(&name)
+                .map_err(|e| format!("Failed to get template: {}", e))?;
+
+            let template = match existing {
+                Some(_) if !force => {
+                    return Err(format!(
+                        "Template '{}' already exists. Use --force to replace it.",
+                        name
+                    ))
+                }
+                Some(mut t) => {
+                    t.content = content;
+                    if description.is_some() {
+                        t.description = description;
+                    }
+                    db.update_template(&t)
+                        .map_err(|e| format!("Failed to update template: {}", e))?;
+                    t
+                }
+                None => {
+                    let mut t = PromptTemplate::new(name, content);
+                    t.description = description;
+                    db.create_template(&t)
+                        .map_err(|e| format!("Failed to create template: {}", e))?;
+                    t
+                }
+            };
+
+            println!("Template '{}' saved.", template.name);
+            let variables = template::variables(&template.content);
+            if !variables.is_empty() {
+                println!("Variables: {}", variables.join(", "));
+            }
+        }
+
+        TemplateAction::List => {
+            let templates = db
+                .list_templates()
+                .map_err(|e| format!("Failed to list templates: {}", e))?;
+
+            if templates.is_empty() {
+                println!("No templates found.");
+                return Ok(());
+            }
+
+            println!("{:<24} {:<8} {:<30} Description", "Name", "Prompts", "Variables");
+            println!("{}", "-".repeat(90));
+            for t in templates {
+                let count = db
+                    .count_template_prompts(&t.id)
+                    .map_err(|e| format!("Failed to count prompts: {}", e))?;
+                println!(
+                    "{:<24} {:<8} {:<30} {}",
+                    truncate_string(&t.name, 24),
+                    count,
+                    truncate_string(&template::variables(&t.content).join(", "), 30),
+                    t.description.as_deref().unwrap_or("")
+                );
+            }
+        }
+
+        TemplateAction::Show { name } => {
+            let t = find_template(db, &name)?;
+            let count = db
+                .count_template_prompts(&t.id)
+                .map_err(|e| format!("Failed to count prompts: {}", e))?;
+
+            println!("Template: {}", t.name);
+            println!("ID: {}", t.id);
+            if let Some(description) = &t.description {
+                println!("Description: {}", description);
+            }
+            println!("Variables: {}", template::variables(&t.content).join(", "));
+            println!("Rendered prompts: {}", count);
+            println!("Updated: {}", t.updated_at.format("%Y-%m-%d %H:%M:%S"));
+            println!("\nContent:\n{}", t.content);
+        }
+
+        TemplateAction::Delete { name } => {
+            let deleted = db
+                .delete_template(&name)
+                .map_err(|e| format!("Failed to delete template: {}", e))?;
+            if !deleted {
+                return Err(format!("Template not found: {}", name));
+            }
+            println!("Template '{}' deleted.", name);
+        }
+
+        TemplateAction::Render {
+            name,
+            vars,
+            capture,
+            category,
+            tags,
+        } => {
+            let t = find_template(db, &name)?;
+            let values = parse_attr_args(&vars)?.into_iter().collect();
+            let rendered = template::render(&t, &values).map_err(|e| e.to_string())?;
+
+            if !capture {
+                println!("{}", rendered);
+                return Ok(());
+            }
+
+            let capture_service = CaptureService::new(config.capture.similarity_threshold)
+                .with_git_context(config.capture.git_context)
+                .with_redactor(build_redactor(config)?);
+
+            let mut prompt = capture_service
+                .process_content(&rendered)
+                .map_err(|e| format!("Failed to process content: {}", e))?;
+            capture_service.attach_git_context(
+                &mut prompt,
+                &std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            );
+
+            if let Some(cat) = category {
+                prompt.category = Some(cat);
+            }
+            if let Some(tag_str) = tags {
+                prompt.tags.extend(
+                    tag_str
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty()),
+                );
+            }
+            prompt.metadata.template = Some(template::usage(&t, &values));
+
+            save_captured_prompt(db, config, &prompt)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks up a template by name or ID.
+fn find_template(db: &dyn PromptStore, name: &str) -> Result<PromptTemplate, String> {
+    db.get_template(name)
+        .map_err(|e| format!("Failed to get template: {}", e))?
+        .ok_or_else(|| format!("Template not found: {}", name))
+}
+
+/// Scans stored prompts and version history for secrets.
+///
+/// Runs the configured redaction rules over prompt content, context and every
+/// stored version. With `fix`, matches are scrubbed in place and logged as
+/// redaction events; rules set to `reject` are applied as `mask`.
+///
+/// # Arguments
+///
+/// * `db` - Prompt store
+/// * `config` - Application configuration
+/// * `fix` - Whether to scrub matches instead of only reporting them
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an error if redaction is disabled or the database fails.
+fn cmd_scan(db: &dyn PromptStore, config: &Config, fix: bool) -> Result<(), String> {
+    let redactor = build_redactor(config)?
+        .ok_or_else(|| "Redaction is disabled in the configuration".to_string())?;
+
+    let prompts = db
+        .list_prompts(&PromptFilter::default())
+        .map_err(|e| format!("Failed to list prompts: {}", e))?;
+
+    let mut prompt_hits = 0;
+    for mut prompt in prompts {
+        let content = redactor.scrub(&prompt.content);
+        let context = prompt.metadata.context.as_deref().map(|c| redactor.scrub(c));
+
+        let mut findings = content.findings.clone();
+        if let Some(ref ctx) = context {
+            findings.extend(ctx.findings.iter().cloned());
+        }
+        if findings.is_empty() {
+            continue;
+        }
+
+        prompt_hits += 1;
+        print_scan_findings(&format!("Prompt {}", prompt.id), &findings);
+
+        if fix {
+            prompt.content = content.content;
+            prompt.content_hash = prompt_tracking::utils::calculate_hash(&prompt.content);
+            if let Some(ctx) = context {
+                prompt.metadata.context = Some(ctx.content);
+            }
+            db.atomic(&mut |tx| {
+                tx.update_prompt(&prompt)?;
+                tx.log_redaction_events(&prompt.id, &findings, "scan")
+            })
+            .map_err(|e| format!("Failed to update prompt: {}", e))?;
+        }
+    }
+
+    let versions = db
+        .get_all_versions()
+        .map_err(|e| format!("Failed to get version history: {}", e))?;
+
+    let mut version_hits = 0;
+    for version in versions {
+        let outcome = redactor.scrub(&version.content);
+        if outcome.findings.is_empty() {
+            continue;
+        }
+
+        version_hits += 1;
+        print_scan_findings(
+            &format!("Prompt {} version {}", version.prompt_id, version.version),
+            &outcome.findings,
+        );
+
+        if fix {
+            db.atomic(&mut |tx| {
+                tx.update_version_content(version.id, &outcome.content)?;
+                tx.log_redaction_events(&version.prompt_id, &outcome.findings, "scan")
+            })
+            .map_err(|e| format!("Failed to update version: {}", e))?;
+        }
+    }
+
+    if prompt_hits == 0 && version_hits == 0 {
+        println!("No secrets found.");
+    } else if fix {
+        println!(
+            "\nScrubbed {} prompt(s) and {} version(s).",
+            prompt_hits, version_hits
+        );
+    } else {
+        println!(
+            "\nFound secrets in {} prompt(s) and {} version(s). Run with --fix to scrub them.",
+            prompt_hits, version_hits
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints redaction findings for one scanned item.
+fn print_scan_findings(label: &str, findings: &[RedactionFinding]) {
+    println!("{}", label);
+    for finding in findings {
+        println!("  {}: {} match(es)", finding.rule, finding.count);
+    }
+}
+
+/// Builds the redaction pipeline from configuration.
+///
+/// # Arguments
+///
+/// * `config` - Application configuration
+///
+/// # Returns
+///
+/// Returns `None` when redaction is disabled, or an error for an invalid rule.
+fn build_redactor(config: &Config) -> Result<Option<Redactor>, String> {
+    if !config.redaction.enabled {
+        return Ok(None);
+    }
+
+    Redactor::new(&config.redaction)
+        .map(Some)
+        .map_err(|e| format!("Failed to configure redaction: {}", e))
+}
+
+/// Parses repeated `--attr key=value` arguments.
+///
+/// # Arguments
+///
+/// * `attrs` - Raw `key=value` strings from the command line
+///
+/// # Returns
+///
+/// Returns the parsed pairs, or an error naming the first malformed argument.
+fn parse_attr_args(attrs: &[String]) -> Result<Vec<(String, String)>, String> {
+    attrs
+        .iter()
+        .map(|a| {
+            prompt_tracking::utils::parse_key_value(a)
+                .ok_or_else(|| format!("Invalid attribute '{}': expected key=value", a))
+        })
+        .collect()
+}
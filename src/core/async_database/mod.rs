@@ -0,0 +1,423 @@
+//! Async database access for tokio applications
+//!
+//! [`AsyncDatabase`] wraps a shared [`Database`] and runs every call on
+//! tokio's blocking thread pool, so `SQLite` I/O never stalls the async
+//! runtime. Queries still use the database's reader pool, so several
+//! awaited reads run side by side.
+//!
+//! ```rust,no_run
+//! # use prompt_tracking::{async_database::AsyncDatabase, models::Prompt};
+//! # async fn example() -> prompt_tracking::Result<()> {
+//! let db = AsyncDatabase::new("~/.prompt-tracking/prompts.db").await?;
+//! let prompt = Prompt::new("Summarize this changelog".to_string());
+//! db.create_prompt(&prompt).await?;
+//! let found = db.search_prompts("changelog").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::analysis::{generate_summary, AnalysisSummary, EfficiencyAnalyzer, QualityAnalyzer};
+use crate::config::{DatabaseConfig, QualityWeights};
+use crate::database::{Database, ProjectTrend, PromptFilter, TrendDataPoint, VersionHistory};
+use crate::models::{EfficiencyMetrics, Prompt, QualityScore};
+use crate::{PromptTrackingError, Result};
+
+/// Async facade over a shared [`Database`]
+///
+/// Cloning is cheap; clones share the same connections.
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    db: Arc<Database>,
+}
+
+impl From<Database> for AsyncDatabase {
+    fn from(db: Database) -> Self {
+        Self { db: Arc::new(db) }
+    }
+}
+
+impl From<Arc<Database>> for AsyncDatabase {
+    fn from(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+}
+
+impl AsyncDatabase {
+    /// Open a database file with the default settings
+    pub async fn new(path: &str) -> Result<Self> {
+        let path = path.to_string();
+        spawn(move || Database::new(&path)).await.map(Self::from)
+    }
+
+    /// Open the database described by the `database` config section
+    pub async fn from_config(config: &DatabaseConfig) -> Result<Self> {
+        let config = config.clone();
+        spawn(move || Database::from_config(&config))
+            .await
+            .map(Self::from)
+    }
+
+    /// Create an in-memory database for testing
+    pub async fn in_memory() -> Result<Self> {
+        spawn(Database::in_memory).await.map(Self::from)
+    }
+
+    /// The underlying blocking database
+    ///
+    /// Its methods block the calling thread, so call them from
+    /// [`run`](Self::run) or outside the async runtime.
+    pub const fn blocking(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    /// Run `f` against the database on the blocking thread pool
+    ///
+    /// For combining several calls, or for methods without an async
+    /// counterpart here.
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    {
+        let db = Arc::clone(&self.db);
+        spawn(move || f(&db)).await
+    }
+
+    /// Run `f` in a transaction, committing if it returns `Ok`
+    ///
+    /// See [`Database::transaction`].
+    pub async fn transaction<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    {
+        self.run(move |db| db.transaction(f)).await
+    }
+
+    // Prompt CRUD
+
+    /// Insert a new prompt
+    pub async fn create_prompt(&self, prompt: &Prompt) -> Result<()> {
+        let prompt = prompt.clone();
+        self.run(move |db| db.create_prompt(&prompt)).await
+    }
+
+    /// Get a prompt by ID
+    pub async fn get_prompt(&self, id: &str) -> Result<Option<Prompt>> {
+        let id = id.to_string();
+        self.run(move |db| db.get_prompt(&id)).await
+    }
+
+    /// Update an existing prompt
+    pub async fn update_prompt(&self, prompt: &Prompt) -> Result<()> {
+        let prompt = prompt.clone();
+        self.run(move |db| db.update_prompt(&prompt)).await
+    }
+
+    /// Move a prompt to the trash
+    pub async fn delete_prompt(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |db| db.delete_prompt(&id)).await
+    }
+
+    /// Delete a prompt and everything recorded about it
+    pub async fn delete_prompt_permanently(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |db| db.delete_prompt_permanently(&id)).await
+    }
+
+    /// Bring a prompt back from the trash
+    pub async fn restore_prompt(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |db| db.restore_prompt(&id)).await
+    }
+
+    /// Archive a prompt
+    pub async fn archive_prompt(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |db| db.archive_prompt(&id)).await
+    }
+
+    /// Restore an archived prompt
+    pub async fn unarchive_prompt(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.run(move |db| db.unarchive_prompt(&id)).await
+    }
+
+    /// Set a prompt attribute
+    pub async fn set_attribute(&self, prompt_id: &str, key: &str, value: &str) -> Result<()> {
+        let (prompt_id, key, value) = (prompt_id.to_string(), key.to_string(), value.to_string());
+        self.run(move |db| db.set_attribute(&prompt_id, &key, &value))
+            .await
+    }
+
+    /// Get all attributes of a prompt
+    pub async fn get_attributes(&self, prompt_id: &str) -> Result<BTreeMap<String, String>> {
+        let prompt_id = prompt_id.to_string();
+        self.run(move |db| db.get_attributes(&prompt_id)).await
+    }
+
+    /// List version history for a prompt
+    pub async fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>> {
+        let prompt_id = prompt_id.to_string();
+        self.run(move |db| db.get_version_history(&prompt_id)).await
+    }
+
+    // Search
+
+    /// List prompts matching a filter
+    pub async fn list_prompts(&self, filter: &PromptFilter) -> Result<Vec<Prompt>> {
+        let filter = filter.clone();
+        self.run(move |db| db.list_prompts(&filter)).await
+    }
+
+    /// Full-text search over prompt content
+    pub async fn search_prompts(&self, query: &str) -> Result<Vec<Prompt>> {
+        let query = query.to_string();
+        self.run(move |db| db.search_prompts(&query)).await
+    }
+
+    /// Find a prompt by content hash
+    pub async fn find_by_hash(&self, hash: &str) -> Result<Option<Prompt>> {
+        let hash = hash.to_string();
+        self.run(move |db| db.find_by_hash(&hash)).await
+    }
+
+    /// Count all prompts
+    pub async fn count_prompts(&self) -> Result<usize> {
+        self.run(Database::count_prompts).await
+    }
+
+    // Analysis
+
+    /// Score a stored prompt and save its quality score and efficiency
+    /// metrics, or return `None` if the prompt does not exist
+    pub async fn analyze_prompt(
+        &self,
+        id: &str,
+        weights: &QualityWeights,
+    ) -> Result<Option<(QualityScore, EfficiencyMetrics)>> {
+        let id = id.to_string();
+        let weights = weights.clone();
+        self.run(move |db| {
+            let Some(prompt) = db.get_prompt(&id)? else {
+                return Ok(None);
+            };
+            let quality = QualityAnalyzer::new(weights).analyze(&prompt)?;
+            let efficiency = EfficiencyAnalyzer::default().analyze(&prompt)?;
+            db.transaction(|tx| {
+                tx.save_quality_score(&quality)?;
+                tx.save_efficiency_metrics(&efficiency)
+            })?;
+            Ok(Some((quality, efficiency)))
+        })
+        .await
+    }
+
+    /// Save a quality score
+    pub async fn save_quality_score(&self, score: &QualityScore) -> Result<()> {
+        let score = score.clone();
+        self.run(move |db| db.save_quality_score(&score)).await
+    }
+
+    /// Get the latest quality score for a prompt
+    pub async fn get_quality_score(&self, prompt_id: &str) -> Result<Option<QualityScore>> {
+        let prompt_id = prompt_id.to_string();
+        self.run(move |db| db.get_quality_score(&prompt_id)).await
+    }
+
+    /// Save efficiency metrics
+    pub async fn save_efficiency_metrics(&self, metrics: &EfficiencyMetrics) -> Result<()> {
+        let metrics = metrics.clone();
+        self.run(move |db| db.save_efficiency_metrics(&metrics))
+            .await
+    }
+
+    /// Get the latest efficiency metrics for a prompt
+    pub async fn get_efficiency_metrics(
+        &self,
+        prompt_id: &str,
+    ) -> Result<Option<EfficiencyMetrics>> {
+        let prompt_id = prompt_id.to_string();
+        self.run(move |db| db.get_efficiency_metrics(&prompt_id))
+            .await
+    }
+
+    /// Get the latest quality score of each prompt in `prompt_ids`
+    pub async fn get_latest_quality_scores(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, QualityScore>> {
+        let ids = owned(prompt_ids);
+        self.run(move |db| db.get_latest_quality_scores(&borrowed(&ids)))
+            .await
+    }
+
+    /// Get the latest efficiency metrics of each prompt in `prompt_ids`
+    pub async fn get_latest_efficiency_metrics(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, EfficiencyMetrics>> {
+        let ids = owned(prompt_ids);
+        self.run(move |db| db.get_latest_efficiency_metrics(&borrowed(&ids)))
+            .await
+    }
+
+    /// Summarize the prompts matching a filter
+    pub async fn summary(&self, filter: &PromptFilter) -> Result<AnalysisSummary> {
+        let filter = filter.clone();
+        self.run(move |db| {
+            let prompts = db.list_prompts(&filter)?;
+            let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+            let scores: Vec<_> = db.get_latest_quality_scores(&ids)?.into_values().collect();
+            let metrics: Vec<_> = db
+                .get_latest_efficiency_metrics(&ids)?
+                .into_values()
+                .collect();
+            Ok(generate_summary(&prompts, &scores, &metrics))
+        })
+        .await
+    }
+
+    /// Daily prompt counts and average scores for the last `days` days
+    pub async fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>> {
+        self.run(move |db| db.get_daily_trends(days)).await
+    }
+
+    /// Per-project prompt counts for the last `days` days
+    pub async fn get_project_trends(&self, days: i32) -> Result<Vec<ProjectTrend>> {
+        self.run(move |db| db.get_project_trends(days)).await
+    }
+
+    /// Prompt counts by category
+    pub async fn get_category_distribution(&self) -> Result<Vec<(String, usize)>> {
+        self.run(Database::get_category_distribution).await
+    }
+
+    // Export
+
+    /// Export all prompts as JSON
+    pub async fn export_to_json(&self) -> Result<String> {
+        self.run(Database::export_to_json).await
+    }
+
+    /// Import prompts from JSON, returning how many were imported
+    pub async fn import_from_json(&self, json: &str) -> Result<usize> {
+        let json = json.to_string();
+        self.run(move |db| db.import_from_json(&json)).await
+    }
+}
+
+/// Run blocking work on tokio's blocking pool
+///
+/// A panic inside `f` is resumed in the awaiting task.
+async fn spawn<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(PromptTrackingError::DatabaseError(format!(
+            "Database task was cancelled: {e}"
+        ))),
+    }
+}
+
+fn owned(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| (*id).to_string()).collect()
+}
+
+fn borrowed(ids: &[String]) -> Vec<&str> {
+    ids.iter().map(String::as_str).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_async_crud_and_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("prompts.db");
+        let db = AsyncDatabase::new(path.to_str().unwrap()).await.unwrap();
+
+        let mut prompt = Prompt::new("Explain async Rust".to_string());
+        prompt.tags = vec!["rust".to_string()];
+        db.create_prompt(&prompt).await.unwrap();
+
+        let fetched = db.get_prompt(&prompt.id).await.unwrap().unwrap();
+        assert_eq!(fetched.content, prompt.content);
+        assert_eq!(fetched.tags, vec!["rust"]);
+
+        // Concurrent reads share the reader pool
+        let filter = PromptFilter::default();
+        let (count, found, listed) = tokio::join!(
+            db.count_prompts(),
+            db.search_prompts("async"),
+            db.list_prompts(&filter)
+        );
+        assert_eq!(count.unwrap(), 1);
+        assert_eq!(found.unwrap().len(), 1);
+        assert_eq!(listed.unwrap().len(), 1);
+
+        prompt.content = "Explain async Rust with tokio".to_string();
+        db.update_prompt(&prompt).await.unwrap();
+        let updated = db.get_prompt(&prompt.id).await.unwrap().unwrap();
+        assert_eq!(updated.content, prompt.content);
+        db.archive_prompt(&prompt.id).await.unwrap();
+        db.delete_prompt(&prompt.id).await.unwrap();
+        assert!(db.get_prompt(&prompt.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_analysis_and_export() {
+        let db = AsyncDatabase::in_memory().await.unwrap();
+        let prompt = Prompt::new(
+            "Write a Rust function that parses RFC 3339 timestamps. Return a Result.".to_string(),
+        );
+        db.create_prompt(&prompt).await.unwrap();
+
+        let weights = crate::config::Config::default().analysis.quality_weights;
+        let (quality, _) = db
+            .analyze_prompt(&prompt.id, &weights)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(db
+            .analyze_prompt("missing", &weights)
+            .await
+            .unwrap()
+            .is_none());
+        let stored = db.get_quality_score(&prompt.id).await.unwrap().unwrap();
+        assert_eq!(stored.total_score, quality.total_score);
+
+        let summary = db.summary(&PromptFilter::default()).await.unwrap();
+        assert_eq!(summary.total_prompts, 1);
+        assert_eq!(summary.avg_quality_score, quality.total_score);
+
+        let json = db.export_to_json().await.unwrap();
+        let copy = AsyncDatabase::in_memory().await.unwrap();
+        assert_eq!(copy.import_from_json(&json).await.unwrap(), 1);
+        assert_eq!(copy.count_prompts().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_transaction_rolls_back() {
+        let db = AsyncDatabase::in_memory().await.unwrap();
+        let prompt = Prompt::new("Rolled back prompt".to_string());
+        let result = db
+            .transaction(move |tx| {
+                tx.create_prompt(&prompt)?;
+                Err::<(), _>(PromptTrackingError::InvalidFormat)
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(db.count_prompts().await.unwrap(), 0);
+    }
+}
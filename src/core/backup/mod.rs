@@ -0,0 +1,353 @@
+//! Database backups with rotation
+//!
+//! A backup is a consistent snapshot taken with `SQLite`'s online backup API
+//! into a `backup-<UTC timestamp>.db` file. Each one passes
+//! `PRAGMA integrity_check` before it is kept. Pruning keeps the newest
+//! backup of each of the `backup_keep_daily` most recent days that have one
+//! and of each of the `backup_keep_weekly` most recent ISO weeks, plus the
+//! newest backup overall.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::DatabaseConfig;
+use crate::database::Database;
+use crate::{PromptTrackingError, Result};
+
+/// File name prefix of backups
+const BACKUP_PREFIX: &str = "backup-";
+
+/// Timestamp in backup file names; sorts chronologically
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// A backup file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// File name, used to pick a backup on the command line
+    pub name: String,
+    /// Where the backup file is
+    pub path: PathBuf,
+    /// When the backup was taken
+    pub created_at: DateTime<Utc>,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Creates, rotates and restores backups of one database
+#[derive(Debug, Clone)]
+pub struct BackupManager {
+    dir: PathBuf,
+    /// Time between automatic backups; `None` when they are disabled
+    interval: Option<Duration>,
+    keep_daily: usize,
+    keep_weekly: usize,
+}
+
+impl BackupManager {
+    /// Manager for the database described by the `database` config section
+    pub fn from_config(config: &DatabaseConfig) -> Self {
+        let dir = config.backup_dir.as_ref().map_or_else(
+            || {
+                Path::new(&shellexpand::tilde(&config.path).to_string())
+                    .parent()
+                    .unwrap_or_else(|| Path::new("."))
+                    .join("backups")
+            },
+            |dir| PathBuf::from(shellexpand::tilde(dir).to_string()),
+        );
+        Self {
+            dir,
+            interval: config
+                .auto_backup
+                .then(|| Duration::hours(i64::from(config.backup_interval.max(1)))),
+            keep_daily: config.backup_keep_daily,
+            keep_weekly: config.backup_keep_weekly,
+        }
+    }
+
+    /// Directory holding the backups
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Time between automatic backups, or `None` if they are disabled
+    pub const fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Take a verified backup of `db`
+    ///
+    /// The snapshot is written under a temporary name and only renamed into
+    /// place once it passes the integrity check, so a listed backup is
+    /// always a complete one.
+    pub fn create(&self, db: &Database) -> Result<BackupInfo> {
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to create backup directory: {e}"))
+        })?;
+
+        let name = format!(
+            "{}{}.db",
+            BACKUP_PREFIX,
+            Utc::now().format(TIMESTAMP_FORMAT)
+        );
+        let path = self.dir.join(&name);
+        let partial = self.dir.join(format!("{name}.partial"));
+
+        let result = db
+            .backup_to(&partial)
+            .and_then(|()| verify_backup(&partial))
+            .and_then(|()| fs::rename(&partial, &path).map_err(PromptTrackingError::IoError));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        log::info!("Created backup {name}");
+        backup_info(&path)
+            .ok_or_else(|| PromptTrackingError::DatabaseError(format!("Backup {name} disappeared")))
+    }
+
+    /// List backups, newest first
+    pub fn list(&self) -> Result<Vec<BackupInfo>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups: Vec<BackupInfo> = entries
+            .filter_map(std::result::Result::ok)
+            .filter_map(|entry| backup_info(&entry.path()))
+            .collect();
+        backups.sort_by(|a, b| b.name.cmp(&a.name));
+        Ok(backups)
+    }
+
+    /// Find a backup by name, with or without the `.db` extension
+    pub fn find(&self, name: &str) -> Result<BackupInfo> {
+        let name = name.trim_end_matches(".db");
+        self.list()?
+            .into_iter()
+            .find(|backup| backup.name.trim_end_matches(".db") == name)
+            .ok_or_else(|| PromptTrackingError::DatabaseError(format!("Backup not found: {name}")))
+    }
+
+    /// Delete the backups outside the retention rules, returning them
+    pub fn prune(&self) -> Result<Vec<BackupInfo>> {
+        let backups = self.list()?;
+        let keep = retained(&backups, self.keep_daily, self.keep_weekly);
+
+        let mut removed = Vec::new();
+        for backup in backups {
+            if keep.contains(&backup.name) {
+                continue;
+            }
+            fs::remove_file(&backup.path)?;
+            log::info!("Removed backup {}", backup.name);
+            removed.push(backup);
+        }
+        Ok(removed)
+    }
+
+    /// When the next automatic backup is due, or `None` if they are
+    /// disabled
+    pub fn next_due(&self) -> Result<Option<DateTime<Utc>>> {
+        let Some(interval) = self.interval else {
+            return Ok(None);
+        };
+        Ok(Some(self.list()?.first().map_or_else(Utc::now, |latest| {
+            latest.created_at + interval
+        })))
+    }
+
+    /// Replace the contents of `db` with a backup
+    ///
+    /// The backup is verified first, and the current contents are backed
+    /// up so the restore can itself be undone; that backup is returned.
+    pub fn restore(&self, db: &Database, name: &str) -> Result<BackupInfo> {
+        let backup = self.find(name)?;
+        verify_backup(&backup.path)?;
+
+        let current = self.create(db)?;
+        db.restore_from(&backup.path)?;
+        log::info!("Restored backup {}", backup.name);
+        Ok(current)
+    }
+}
+
+/// Check a backup with `PRAGMA integrity_check`
+pub fn verify_backup(path: &Path) -> Result<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to open backup: {e}")))?;
+    let problems: Vec<String> = conn
+        .prepare("PRAGMA integrity_check")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()
+        })
+        .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to verify backup: {e}")))?;
+
+    if problems == ["ok"] {
+        Ok(())
+    } else {
+        Err(PromptTrackingError::DatabaseError(format!(
+            "Backup {} failed the integrity check: {}",
+            path.display(),
+            problems.join("; ")
+        )))
+    }
+}
+
+/// Describe a file if it is named like a backup
+fn backup_info(path: &Path) -> Option<BackupInfo> {
+    let name = path.file_name()?.to_str()?;
+    let timestamp = name.strip_prefix(BACKUP_PREFIX)?.strip_suffix(".db")?;
+    let created_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+        .ok()?
+        .and_utc();
+    Some(BackupInfo {
+        name: name.to_string(),
+        path: path.to_path_buf(),
+        created_at,
+        size: fs::metadata(path).ok()?.len(),
+    })
+}
+
+/// Names of the backups to keep from a newest-first list
+fn retained(backups: &[BackupInfo], keep_daily: usize, keep_weekly: usize) -> HashSet<String> {
+    let mut keep = HashSet::new();
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    for (i, backup) in backups.iter().enumerate() {
+        let day = backup.created_at.date_naive();
+        let week = day.iso_week();
+        let new_day = days.len() < keep_daily && days.insert(day);
+        let new_week = weeks.len() < keep_weekly && weeks.insert((week.year(), week.week()));
+        if i == 0 || new_day || new_week {
+            keep.insert(backup.name.clone());
+        }
+    }
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::Prompt;
+    use tempfile::TempDir;
+
+    fn setup(temp_dir: &TempDir) -> (Database, BackupManager) {
+        let config = DatabaseConfig {
+            path: temp_dir.path().join("prompts.db").display().to_string(),
+            ..Config::default().database
+        };
+        let db = Database::from_config(&config).unwrap();
+        (db, BackupManager::from_config(&config))
+    }
+
+    fn info(name: &str, created_at: &str) -> BackupInfo {
+        BackupInfo {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            created_at: DateTime::parse_from_rfc3339(created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_create_list_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let (db, manager) = setup(&temp_dir);
+        assert_eq!(manager.dir(), temp_dir.path().join("backups"));
+        assert!(manager.list().unwrap().is_empty());
+        assert!(manager.next_due().unwrap().unwrap() <= Utc::now());
+
+        let kept = Prompt::new("Kept prompt".to_string());
+        db.create_prompt(&kept).unwrap();
+        let backup = manager.create(&db).unwrap();
+        assert!(backup.size > 0);
+        verify_backup(&backup.path).unwrap();
+        assert_eq!(manager.list().unwrap(), vec![backup.clone()]);
+        assert!(manager.next_due().unwrap().unwrap() > Utc::now());
+
+        // Changes after the backup are undone by restoring it
+        let later = Prompt::new("Later prompt".to_string());
+        db.create_prompt(&later).unwrap();
+        let undo = manager
+            .restore(&db, backup.name.trim_end_matches(".db"))
+            .unwrap();
+        assert!(db.get_prompt(&kept.id).unwrap().is_some());
+        assert!(db.get_prompt(&later.id).unwrap().is_none());
+        assert_eq!(db.count_prompts().unwrap(), 1);
+
+        // ...and the pre-restore state was kept as a backup of its own
+        manager.restore(&db, &undo.name).unwrap();
+        assert!(db.get_prompt(&later.id).unwrap().is_some());
+        assert!(manager.find("backup-missing").is_err());
+    }
+
+    #[test]
+    fn test_corrupt_backup_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let (db, manager) = setup(&temp_dir);
+        db.create_prompt(&Prompt::new("Prompt".to_string()))
+            .unwrap();
+        let backup = manager.create(&db).unwrap();
+
+        let mut bytes = fs::read(&backup.path).unwrap();
+        let len = bytes.len();
+        bytes[len / 2..].fill(0xff);
+        fs::write(&backup.path, bytes).unwrap();
+
+        assert!(verify_backup(&backup.path).is_err());
+        assert!(manager.restore(&db, &backup.name).is_err());
+        assert_eq!(db.count_prompts().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_retention() {
+        // Newest first: two on one day, then one a day going back
+        let backups = vec![
+            info("b1", "2026-10-18T12:00:00Z"),
+            info("b2", "2026-10-18T06:00:00Z"),
+            info("b3", "2026-10-17T06:00:00Z"),
+            info("b4", "2026-10-16T06:00:00Z"),
+            info("b5", "2026-10-09T06:00:00Z"),
+            info("b6", "2026-10-02T06:00:00Z"),
+            info("b7", "2026-09-25T06:00:00Z"),
+        ];
+
+        let keep = retained(&backups, 2, 3);
+        let mut names: Vec<&str> = keep.iter().map(String::as_str).collect();
+        names.sort_unstable();
+        // Days 10-18 and 10-17; weeks 42, 41 and 40
+        assert_eq!(names, vec!["b1", "b3", "b5", "b6"]);
+
+        // The newest backup always survives
+        assert_eq!(retained(&backups, 0, 0).len(), 1);
+    }
+
+    #[test]
+    fn test_prune_removes_old_backups() {
+        let temp_dir = TempDir::new().unwrap();
+        let (db, mut manager) = setup(&temp_dir);
+        manager.keep_daily = 1;
+        manager.keep_weekly = 0;
+
+        let first = manager.create(&db).unwrap();
+        let second = manager.create(&db).unwrap();
+        fs::write(manager.dir().join("notes.txt"), "not a backup").unwrap();
+
+        let removed = manager.prune().unwrap();
+        assert_eq!(removed, vec![first]);
+        assert_eq!(manager.list().unwrap(), vec![second]);
+        assert!(manager.dir().join("notes.txt").exists());
+    }
+}
@@ -1,8 +1,11 @@
 //! Configuration management
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use crate::events::EventKind;
+use crate::models::{PostCaptureAction, RedactionAction};
 use crate::PromptTrackingError;
 
 /// Main configuration structure
@@ -13,14 +16,80 @@ pub struct Config {
     pub analysis: AnalysisConfig,
     pub reporting: ReportingConfig,
     pub categories: Vec<String>,
+    /// Redaction of secrets in captured prompts
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Directories watched for prompt files
+    #[serde(default)]
+    pub watch: WatchConfig,
+    /// Background daemon running the watcher
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    /// Commands run on prompt lifecycle events
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// HTTP endpoints notified of lifecycle events
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    /// Rules for archiving and deleting old data
+    #[serde(default)]
+    pub retention: RetentionConfig,
 }
 
 /// Database configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub path: String,
+    /// Let the daemon take a backup every `backup_interval` hours
     pub auto_backup: bool,
     pub backup_interval: u32,
+    /// Directory for backups; `backups` next to the database by default
+    #[serde(default)]
+    pub backup_dir: Option<String>,
+    /// Days for which the newest backup of the day is kept
+    #[serde(default = "default_keep_daily")]
+    pub backup_keep_daily: usize,
+    /// Weeks for which the newest backup of the week is kept
+    #[serde(default = "default_keep_weekly")]
+    pub backup_keep_weekly: usize,
+    /// Use write-ahead logging so readers never block the writer
+    #[serde(default = "default_true")]
+    pub wal: bool,
+    /// Milliseconds to wait for a lock held by another connection
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+    /// Maximum number of pooled read-only connections
+    #[serde(default = "default_max_readers")]
+    pub max_readers: usize,
+    /// Encryption of prompt text at rest
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+}
+
+/// Where the encryption key comes from
+///
+/// The key itself never appears in the configuration: it is read from a key
+/// file or derived from a passphrase held in an environment variable. Content
+/// hashes stay in plaintext, so identical prompts remain recognizable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Require an encrypted database; new databases are created encrypted
+    pub enabled: bool,
+    /// File holding a 256-bit key as hex, used instead of a passphrase
+    pub key_file: Option<String>,
+    /// Environment variable holding the passphrase
+    pub passphrase_env: String,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_file: None,
+            passphrase_env: "PROMPT_TRACKING_PASSPHRASE".to_string(),
+        }
+    }
 }
 
 /// Capture configuration
@@ -30,6 +99,53 @@ pub struct CaptureConfig {
     pub auto_capture: bool,
     pub deduplicate: bool,
     pub similarity_threshold: f64,
+    /// Record git repository context for captured prompts
+    #[serde(default = "default_true")]
+    pub git_context: bool,
+    /// Limits applied when reading prompt files
+    #[serde(default)]
+    pub safeguards: CaptureSafeguards,
+}
+
+/// Limits and decoding rules for captured files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureSafeguards {
+    /// Maximum number of bytes read from a file
+    pub max_file_size: u64,
+    /// What to do with files larger than `max_file_size`
+    pub truncation: TruncationPolicy,
+    /// Skip files that look binary
+    pub reject_binary: bool,
+    /// Encoding label (e.g. `utf-8`, `windows-1252`) or `auto` to detect
+    pub encoding: String,
+    /// Replace undecodable bytes instead of skipping the file
+    pub lossy: bool,
+}
+
+/// Handling of files over the size limit
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TruncationPolicy {
+    /// Skip the file
+    #[default]
+    Skip,
+    /// Keep the beginning of the file
+    Head,
+    /// Keep the end of the file (useful for logs)
+    Tail,
+}
+
+impl Default for CaptureSafeguards {
+    fn default() -> Self {
+        Self {
+            max_file_size: 1024 * 1024,
+            truncation: TruncationPolicy::Skip,
+            reject_binary: true,
+            encoding: "auto".to_string(),
+            lossy: true,
+        }
+    }
 }
 
 /// Analysis configuration
@@ -74,6 +190,320 @@ pub struct MonthlyScheduleConfig {
     pub time: String,
 }
 
+/// File watcher configuration
+///
+/// Global settings apply to every root unless the root overrides them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Directories to watch; empty means `capture.watch_directory`
+    pub roots: Vec<WatchRootConfig>,
+    /// Watch subdirectories
+    pub recursive: bool,
+    /// File extensions to capture (without the dot)
+    pub extensions: Vec<String>,
+    /// Glob patterns a path (relative to its root) must match; empty matches all
+    pub include: Vec<String>,
+    /// Glob patterns for paths to ignore
+    pub exclude: Vec<String>,
+    /// Milliseconds to wait after the last change to a file before capturing it
+    pub debounce_ms: u64,
+    /// What to do with a file once it has been captured
+    pub after_capture: PostCaptureAction,
+    /// Folder files are moved to by the `move` action, relative to each root
+    /// unless absolute; it is never watched itself
+    pub processed_dir: String,
+}
+
+/// A single watch root with optional overrides and capture defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchRootConfig {
+    /// Directory to watch; `~` is expanded
+    pub path: String,
+    /// Overrides the global `recursive` setting
+    #[serde(default)]
+    pub recursive: Option<bool>,
+    /// Replaces the global extension list
+    #[serde(default)]
+    pub extensions: Option<Vec<String>>,
+    /// Added to the global include patterns
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Added to the global exclude patterns
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Category applied to prompts captured under this root
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Tags added to prompts captured under this root
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Model recorded for prompts captured under this root
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Overrides the global `after_capture` action
+    #[serde(default)]
+    pub after_capture: Option<PostCaptureAction>,
+    /// Overrides the global `processed_dir`
+    #[serde(default)]
+    pub processed_dir: Option<String>,
+}
+
+/// Background daemon configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Pidfile, locked while a daemon is running
+    pub pid_file: String,
+    /// JSON file with counts, last error and uptime, rewritten as the daemon runs
+    pub status_file: String,
+    /// Log file; rotated to `<log_file>.1`, `.2`, ... when it grows too large
+    pub log_file: String,
+    /// Size in bytes at which the log file is rotated
+    pub log_max_bytes: u64,
+    /// Number of rotated log files to keep
+    pub log_keep: usize,
+    /// Minimum level written to the log (`error`, `warn`, `info`, `debug`, `trace`)
+    pub log_level: String,
+}
+
+/// Local commands run on prompt lifecycle events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Seconds a hook may run before it is killed
+    pub timeout_secs: u64,
+    /// Hooks, each run for every matching event
+    pub commands: Vec<HookConfig>,
+}
+
+/// A command run with the event JSON on stdin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookConfig {
+    /// Name used in log messages
+    pub name: String,
+    /// Shell command line
+    pub command: String,
+    /// Events that trigger the hook; empty means all
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    /// Only run for `analyzed` events whose total score is at least this
+    #[serde(default)]
+    pub min_score: Option<f64>,
+    /// Overrides the global `timeout_secs`
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Outbound HTTP webhooks for prompt lifecycle events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    /// Seconds to wait for an endpoint to respond
+    pub timeout_secs: u64,
+    /// Attempts before a delivery is marked failed
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after each failed attempt
+    pub retry_base_secs: u64,
+    /// Endpoints, each sent every matching event
+    pub endpoints: Vec<WebhookConfig>,
+}
+
+/// An endpoint that receives event JSON by HTTP POST
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Name used in the outbox and log messages
+    pub name: String,
+    /// URL the events are posted to
+    pub url: String,
+    /// Events sent to the endpoint; empty means all
+    #[serde(default)]
+    pub events: Vec<EventKind>,
+    /// Key for the `X-Prompt-Tracking-Signature` header; `$VAR` is expanded
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Only send `analyzed` events whose total score is at least this
+    #[serde(default)]
+    pub min_score: Option<f64>,
+}
+
+/// Rules for archiving, purging and compacting old data
+///
+/// Every rule but emptying the trash is off until set. Ages are measured
+/// from a prompt's last update; archiving counts as one, so archived
+/// prompts are purged `purge_archived_after_days` after they were archived.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Let the daemon apply the rules every `interval_hours`
+    pub auto_apply: bool,
+    /// Hours between automatic runs
+    pub interval_hours: u32,
+    /// Archive active prompts not updated for this many days
+    pub archive_after_days: Option<u32>,
+    /// Delete archived prompts not updated for this many days
+    pub purge_archived_after_days: Option<u32>,
+    /// Newest quality and efficiency rows kept per prompt (at least 1)
+    pub keep_scores: Option<usize>,
+    /// Newest version history entries kept per prompt (at least 1); entries
+    /// identical to the version before them are dropped as well
+    pub keep_versions: Option<usize>,
+    /// Permanently delete prompts this many days after they were moved to
+    /// the trash; 0 keeps them until the trash is emptied
+    pub trash_days: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            auto_apply: true,
+            interval_hours: 24,
+            archive_after_days: None,
+            purge_archived_after_days: None,
+            keep_scores: None,
+            keep_versions: None,
+            trash_days: 30,
+        }
+    }
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            max_attempts: 8,
+            retry_base_secs: 30,
+            endpoints: Vec::new(),
+        }
+    }
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            pid_file: "~/.local/share/prompt-tracking/daemon.pid".to_string(),
+            status_file: "~/.local/share/prompt-tracking/daemon.status.json".to_string(),
+            log_file: "~/.local/share/prompt-tracking/daemon.log".to_string(),
+            log_max_bytes: 10 * 1024 * 1024,
+            log_keep: 5,
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+impl WatchRootConfig {
+    /// Create a root that uses the global watch settings
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            recursive: None,
+            extensions: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            category: None,
+            tags: Vec::new(),
+            model: None,
+            after_capture: None,
+            processed_dir: None,
+        }
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            recursive: true,
+            extensions: vec!["txt".to_string(), "md".to_string(), "prompt".to_string()],
+            include: Vec::new(),
+            exclude: vec![
+                "**/.git/**".to_string(),
+                "**/node_modules/**".to_string(),
+                "**/target/**".to_string(),
+            ],
+            debounce_ms: 500,
+            after_capture: PostCaptureAction::Leave,
+            processed_dir: "processed".to_string(),
+        }
+    }
+}
+
+/// Secret and PII redaction configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Apply redaction to captured prompts
+    pub enabled: bool,
+    /// Include the built-in secret detectors (keys and tokens)
+    pub builtin_rules: bool,
+    /// Include the built-in personal data detectors (emails, IPv4
+    /// addresses), which also match ordinary text such as version numbers
+    pub pii_rules: bool,
+    /// Action for rules without an explicit action
+    pub default_action: RedactionAction,
+    /// Per-rule action overrides, keyed by rule name
+    pub rule_actions: BTreeMap<String, RedactionAction>,
+    /// Rule names to skip
+    pub disabled_rules: Vec<String>,
+    /// Additional user-defined rules
+    pub rules: Vec<RedactionRuleConfig>,
+}
+
+/// User-defined redaction rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionRuleConfig {
+    /// Rule name, reported in findings and usable in `rule_actions`
+    pub name: String,
+    /// Regular expression matching the text to redact
+    pub pattern: String,
+    /// Action for this rule, overriding `rule_actions` and the default
+    #[serde(default)]
+    pub action: Option<RedactionAction>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            builtin_rules: true,
+            pii_rules: false,
+            default_action: RedactionAction::Mask,
+            rule_actions: BTreeMap::new(),
+            disabled_rules: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+const fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+
+const fn default_max_readers() -> usize {
+    4
+}
+
+const fn default_keep_daily() -> usize {
+    7
+}
+
+const fn default_keep_weekly() -> usize {
+    4
+}
+
 impl Config {
     /// Load configuration from file
     pub fn load(path: &PathBuf) -> Result<Self, PromptTrackingError> {
@@ -102,12 +532,21 @@ impl Default for Config {
                 path: "~/.local/share/prompt-tracking/prompts.db".to_string(),
                 auto_backup: true,
                 backup_interval: 24,
+                backup_dir: None,
+                backup_keep_daily: default_keep_daily(),
+                backup_keep_weekly: default_keep_weekly(),
+                wal: true,
+                busy_timeout_ms: default_busy_timeout_ms(),
+                max_readers: default_max_readers(),
+                encryption: EncryptionConfig::default(),
             },
             capture: CaptureConfig {
                 watch_directory: "$HOME/.claude-code-data".to_string(),
                 auto_capture: true,
                 deduplicate: true,
                 similarity_threshold: 0.95,
+                git_context: true,
+                safeguards: CaptureSafeguards::default(),
             },
             analysis: AnalysisConfig {
                 auto_analyze: true,
@@ -140,6 +579,12 @@ impl Default for Config {
                 "testing".to_string(),
                 "debugging".to_string(),
             ],
+            redaction: RedactionConfig::default(),
+            watch: WatchConfig::default(),
+            daemon: DaemonConfig::default(),
+            hooks: HooksConfig::default(),
+            webhooks: WebhooksConfig::default(),
+            retention: RetentionConfig::default(),
         }
     }
 }
@@ -215,6 +660,162 @@ categories:
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.database.path, "/test/path");
         assert!(!config.database.auto_backup);
+        assert!(config.database.wal);
+        assert_eq!(config.database.busy_timeout_ms, 5000);
+        assert_eq!(config.database.max_readers, 4);
+        assert_eq!(config.database.backup_dir, None);
+        assert_eq!(config.database.backup_keep_daily, 7);
+        assert_eq!(config.database.backup_keep_weekly, 4);
+        assert!(!config.database.encryption.enabled);
+        assert_eq!(
+            config.database.encryption.passphrase_env,
+            "PROMPT_TRACKING_PASSPHRASE"
+        );
         assert_eq!(config.database.backup_interval, 12);
+        assert!(config.capture.git_context);
+        assert!(config.redaction.enabled);
+        assert_eq!(config.capture.safeguards.truncation, TruncationPolicy::Skip);
+        assert!(config.retention.auto_apply);
+        assert_eq!(config.retention.interval_hours, 24);
+        assert!(config.retention.archive_after_days.is_none());
+        assert!(config.retention.keep_versions.is_none());
+        assert_eq!(config.retention.trash_days, 30);
+    }
+
+    #[test]
+    fn test_watch_config_deserialization() {
+        let yaml = r#"
+recursive: false
+exclude: ["**/drafts/**"]
+roots:
+  - path: ~/prompts/work
+    recursive: true
+    category: code-generation
+    tags: [work]
+    model: claude-3-opus
+    after_capture: move
+    processed_dir: ~/prompts/archive
+  - path: ~/prompts/notes
+    extensions: [md]
+"#;
+
+        let config: WatchConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(!config.recursive);
+        assert_eq!(config.extensions, vec!["txt", "md", "prompt"]);
+        assert_eq!(config.exclude, vec!["**/drafts/**"]);
+        assert_eq!(config.roots.len(), 2);
+        assert_eq!(config.roots[0].recursive, Some(true));
+        assert_eq!(config.roots[0].tags, vec!["work"]);
+        assert_eq!(config.roots[1].extensions, Some(vec!["md".to_string()]));
+        assert!(config.roots[1].category.is_none());
+        assert_eq!(config.after_capture, PostCaptureAction::Leave);
+        assert_eq!(config.roots[0].after_capture, Some(PostCaptureAction::Move));
+        assert_eq!(
+            config.roots[0].processed_dir.as_deref(),
+            Some("~/prompts/archive")
+        );
+        assert!(config.roots[1].after_capture.is_none());
+    }
+
+    #[test]
+    fn test_hooks_config_deserialization() {
+        let yaml = r#"
+commands:
+  - name: team-notes
+    command: ~/bin/post-to-notes.sh
+    events: [analyzed]
+    min_score: 90
+  - name: audit
+    command: logger -t prompts
+    timeout_secs: 2
+"#;
+
+        let hooks: HooksConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(hooks.timeout_secs, 10);
+        assert_eq!(hooks.commands[0].events, vec![EventKind::Analyzed]);
+        assert_eq!(hooks.commands[0].min_score, Some(90.0));
+        assert!(hooks.commands[1].events.is_empty());
+        assert_eq!(hooks.commands[1].timeout_secs, Some(2));
+    }
+
+    #[test]
+    fn test_webhooks_config_deserialization() {
+        let yaml = r#"
+max_attempts: 3
+endpoints:
+  - name: notes
+    url: https://example.com/hooks/prompts
+    events: [captured, analyzed]
+    secret: $NOTES_WEBHOOK_SECRET
+"#;
+
+        let webhooks: WebhooksConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(webhooks.max_attempts, 3);
+        assert_eq!(webhooks.retry_base_secs, 30);
+        assert_eq!(
+            webhooks.endpoints[0].events,
+            vec![EventKind::Captured, EventKind::Analyzed]
+        );
+        assert_eq!(
+            webhooks.endpoints[0].secret.as_deref(),
+            Some("$NOTES_WEBHOOK_SECRET")
+        );
+        assert!(webhooks.endpoints[0].min_score.is_none());
+    }
+
+    #[test]
+    fn test_retention_config_deserialization() {
+        let yaml = r#"
+archive_after_days: 90
+purge_archived_after_days: 365
+keep_scores: 5
+trash_days: 0
+"#;
+
+        let retention: RetentionConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(retention.auto_apply);
+        assert_eq!(retention.archive_after_days, Some(90));
+        assert_eq!(retention.purge_archived_after_days, Some(365));
+        assert_eq!(retention.keep_scores, Some(5));
+        assert!(retention.keep_versions.is_none());
+        assert_eq!(retention.trash_days, 0);
+    }
+
+    #[test]
+    fn test_capture_safeguards_deserialization() {
+        let yaml = r#"
+max_file_size: 4096
+truncation: tail
+encoding: windows-1252
+"#;
+
+        let safeguards: CaptureSafeguards = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(safeguards.max_file_size, 4096);
+        assert_eq!(safeguards.truncation, TruncationPolicy::Tail);
+        assert_eq!(safeguards.encoding, "windows-1252");
+        assert!(safeguards.reject_binary);
+        assert!(safeguards.lossy);
+    }
+
+    #[test]
+    fn test_redaction_config_deserialization() {
+        let yaml = r#"
+enabled: true
+default_action: hash
+rule_actions:
+  private_key: reject
+disabled_rules: [ipv4]
+rules:
+  - name: internal_host
+    pattern: '[a-z]+\.corp\.example\.com'
+"#;
+
+        let config: RedactionConfig = serde_yaml::from_str(yaml).unwrap();
+        assert!(config.builtin_rules);
+        assert!(!config.pii_rules);
+        assert_eq!(config.default_action, RedactionAction::Hash);
+        assert_eq!(config.rule_actions["private_key"], RedactionAction::Reject);
+        assert_eq!(config.rules[0].name, "internal_host");
+        assert!(config.rules[0].action.is_none());
     }
 }
@@ -0,0 +1,947 @@
+//! Background capture daemon
+//!
+//! Runs the file watcher as a long-lived process. A locked pidfile keeps a
+//! single instance running, SIGTERM and SIGINT stop it gracefully, SIGHUP
+//! reloads the configuration, and progress is reported through a JSON status
+//! file and a size-rotated log file. Webhooks are sent from a background
+//! worker that retries failed deliveries every [`webhooks::RETRY_INTERVAL`],
+//! and when `auto_backup` is on a rotated backup of the database is taken
+//! every `backup_interval` hours. The retention rules, emptying old prompts
+//! from the trash among them, are applied at startup and then every
+//! `retention.interval_hours` when `retention.auto_apply` is on. Between
+//! events the daemon sleeps until the next backup or retention run is due,
+//! and indefinitely when neither is scheduled.
+
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::analysis::{EfficiencyAnalyzer, QualityAnalyzer};
+use crate::backup::BackupManager;
+use crate::config::{Config, DaemonConfig};
+use crate::database::Database;
+use crate::hooks::HookRunner;
+use crate::retention::RetentionPolicy;
+use crate::watcher::{FileWatcher, WatchEvent, WatcherConfig, Waker};
+use crate::webhooks::{self, WebhookDispatcher, WebhookWorker};
+use crate::{PromptTrackingError, Result};
+
+/// Lifecycle state reported in the status file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DaemonState {
+    /// The daemon is watching for prompts
+    Running,
+    /// The daemon has shut down
+    Stopped,
+}
+
+/// Snapshot of a daemon's progress, written to the status file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonStatus {
+    /// Process ID of the daemon
+    pub pid: u32,
+    /// Whether the daemon is running
+    pub state: DaemonState,
+    /// When the daemon started
+    pub started_at: DateTime<Utc>,
+    /// When this status was written
+    pub updated_at: DateTime<Utc>,
+    /// Directories being watched
+    pub roots: Vec<String>,
+    /// Prompts captured from new files or log turns
+    pub captured: u64,
+    /// Tracked files saved as new prompt versions
+    pub revised: u64,
+    /// Errors logged since the daemon started
+    pub errors: u64,
+    /// Successful configuration reloads
+    pub reloads: u64,
+    /// When the last prompt was captured or revised
+    pub last_event_at: Option<DateTime<Utc>>,
+    /// Message of the most recent error
+    pub last_error: Option<String>,
+}
+
+impl DaemonStatus {
+    /// Status for a daemon starting in the current process
+    pub fn new() -> Self {
+        let now = Utc::now();
+        Self {
+            pid: std::process::id(),
+            state: DaemonState::Running,
+            started_at: now,
+            updated_at: now,
+            roots: Vec::new(),
+            captured: 0,
+            revised: 0,
+            errors: 0,
+            reloads: 0,
+            last_event_at: None,
+            last_error: None,
+        }
+    }
+
+    /// Time since start, or total run time once stopped
+    pub fn uptime(&self) -> chrono::Duration {
+        match self.state {
+            DaemonState::Running => Utc::now() - self.started_at,
+            DaemonState::Stopped => self.updated_at - self.started_at,
+        }
+    }
+
+    /// Read a status file
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            PromptTrackingError::SerializationError(format!("Invalid status file: {e}"))
+        })
+    }
+
+    /// Write the status file, replacing it atomically
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| PromptTrackingError::SerializationError(e.to_string()))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = with_suffix(path, ".tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn record_event(&mut self, event: &WatchEvent) {
+        match event {
+            WatchEvent::Captured { .. } => self.captured += 1,
+            WatchEvent::Revised { .. } => self.revised += 1,
+        }
+        self.last_event_at = Some(Utc::now());
+    }
+
+    fn record_error(&mut self, error: impl std::fmt::Display) {
+        self.errors += 1;
+        self.last_error = Some(error.to_string());
+    }
+}
+
+impl Default for DaemonStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Exclusive lock on the daemon pidfile, released and removed on drop
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl PidFile {
+    /// Lock `path` and write the current process id to it
+    ///
+    /// Fails if another daemon holds the lock.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        if FileExt::try_lock_exclusive(&file).is_err() {
+            let pid = read_pid(path).map_or_else(|| "unknown".to_string(), |p| p.to_string());
+            return Err(PromptTrackingError::DaemonError(format!(
+                "Daemon already running (pid {pid})"
+            )));
+        }
+
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        file.flush()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Pid of the daemon holding the lock on `path`, if one is running
+pub fn running_pid(path: &Path) -> Option<u32> {
+    let file = File::open(path).ok()?;
+    if FileExt::try_lock_shared(&file).is_ok() {
+        // Nobody holds the lock: the pidfile is stale
+        let _ = FileExt::unlock(&file);
+        return None;
+    }
+    read_pid(path)
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Logger that writes to a file and rotates it when it grows past a size limit
+///
+/// Rotation renames `daemon.log` to `daemon.log.1`, shifting older files up
+/// and deleting the oldest beyond `keep`.
+#[derive(Debug)]
+pub struct RotatingLog {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    level: log::LevelFilter,
+    file: Mutex<LogFile>,
+}
+
+#[derive(Debug)]
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+impl RotatingLog {
+    /// Open (or create) the log file for appending
+    pub fn new(path: &Path, max_bytes: u64, keep: usize, level: log::LevelFilter) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            keep,
+            level,
+            file: Mutex::new(LogFile { file, size }),
+        })
+    }
+
+    /// Open the log described by the daemon configuration
+    pub fn from_config(config: &DaemonConfig) -> Result<Self> {
+        let level = config.log_level.parse().map_err(|_| {
+            PromptTrackingError::ConfigError(format!("Invalid log level: {}", config.log_level))
+        })?;
+        Self::new(
+            &expand(&config.log_file),
+            config.log_max_bytes,
+            config.log_keep,
+            level,
+        )
+    }
+
+    /// Install as the global logger
+    pub fn install(self) -> Result<()> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))
+            .map_err(|e| PromptTrackingError::ConfigError(format!("Logger already set: {e}")))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    fn rotate(&self, current: &mut LogFile) -> io::Result<()> {
+        if self.keep == 0 {
+            current.file.set_len(0)?;
+        } else {
+            let rotated = (1..self.keep)
+                .rev()
+                .map(|n| (n, with_suffix(&self.path, &format!(".{n}"))))
+                .filter(|(_, from)| from.exists());
+            for (n, from) in rotated {
+                fs::rename(&from, with_suffix(&self.path, &format!(".{}", n + 1)))?;
+            }
+            fs::rename(&self.path, with_suffix(&self.path, ".1"))?;
+            current.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        current.size = 0;
+        Ok(())
+    }
+}
+
+impl log::Log for RotatingLog {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Utc::now().to_rfc3339(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let Ok(mut current) = self.file.lock() else {
+            return;
+        };
+        if current.size > 0 && current.size + line.len() as u64 > self.max_bytes {
+            if let Err(e) = self.rotate(&mut current) {
+                eprintln!("Failed to rotate {}: {}", self.path.display(), e);
+            }
+        }
+        if current.file.write_all(line.as_bytes()).is_ok() {
+            current.size += line.len() as u64;
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut current) = self.file.lock() {
+            let _ = current.file.flush();
+        }
+    }
+}
+
+/// Options for a daemon run
+#[derive(Debug, Clone, Default)]
+pub struct DaemonOptions {
+    /// Configuration file re-read on SIGHUP; without one, SIGHUP is ignored
+    pub config_path: Option<PathBuf>,
+    /// Capture files already in the watch roots before watching
+    pub backfill: bool,
+}
+
+/// State shared with the signal-handling thread
+#[derive(Default)]
+struct Control {
+    stop: AtomicBool,
+    reload: AtomicBool,
+    /// Wakes the watcher of the current configuration
+    waker: Mutex<Option<Waker>>,
+    /// When scheduled work is next due
+    next_due: Mutex<Option<DateTime<Utc>>>,
+    /// Thread waking the daemon when work is due, started the first time
+    /// work is scheduled
+    ticker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Control {
+    fn set_waker(&self, waker: Waker) {
+        if let Ok(mut current) = self.waker.lock() {
+            *current = Some(waker);
+        }
+    }
+
+    fn wake(&self) {
+        if let Ok(current) = self.waker.lock() {
+            if let Some(ref waker) = *current {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Wake the daemon at `due`, or never for `None`
+    fn schedule(self: &Arc<Self>, due: Option<DateTime<Utc>>) {
+        let Ok(mut next_due) = self.next_due.lock() else {
+            return;
+        };
+        if *next_due == due {
+            return;
+        }
+        *next_due = due;
+        drop(next_due);
+
+        let Ok(mut ticker) = self.ticker.lock() else {
+            return;
+        };
+        match *ticker {
+            Some(ref handle) => handle.thread().unpark(),
+            None if due.is_some() => {
+                let control = Arc::clone(self);
+                *ticker = Some(std::thread::spawn(move || control.tick()));
+            }
+            None => {}
+        }
+    }
+
+    /// Sleep until the scheduled time and wake the daemon, until stopped
+    fn tick(&self) {
+        while !self.stop.load(Ordering::SeqCst) {
+            let Ok(mut next_due) = self.next_due.lock() else {
+                return;
+            };
+            match *next_due {
+                Some(due) if due <= Utc::now() => {
+                    // Rescheduled by the daemon once it has done the work
+                    *next_due = None;
+                    drop(next_due);
+                    self.wake();
+                }
+                Some(due) => {
+                    drop(next_due);
+                    let wait = (due - Utc::now()).to_std().unwrap_or_default();
+                    std::thread::park_timeout(wait);
+                }
+                None => {
+                    drop(next_due);
+                    std::thread::park();
+                }
+            }
+        }
+    }
+
+    /// Stop the ticker thread, if one was started
+    fn stop_ticker(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let handle = self.ticker.lock().ok().and_then(|mut ticker| ticker.take());
+        if let Some(handle) = handle {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Run the daemon in the current process until SIGTERM or SIGINT
+///
+/// The pidfile, status file and log paths are taken from `config` at start
+/// and kept for the life of the process; a reload applies everything else.
+pub fn run(config: Config, options: &DaemonOptions) -> Result<()> {
+    let _pid_file = PidFile::acquire(&expand(&config.daemon.pid_file))?;
+
+    let control = Arc::new(Control::default());
+    let mut signals = Signals::new([SIGTERM, SIGINT, SIGHUP])?;
+    let signals_handle = signals.handle();
+    let signal_control = Arc::clone(&control);
+    let signal_thread = std::thread::spawn(move || {
+        for signal in signals.forever() {
+            if signal == SIGHUP {
+                signal_control.reload.store(true, Ordering::SeqCst);
+            } else {
+                signal_control.stop.store(true, Ordering::SeqCst);
+            }
+            signal_control.wake();
+        }
+    });
+
+    let result = Daemon::start(config, options, Arc::clone(&control)).map(Daemon::run);
+
+    signals_handle.close();
+    let _ = signal_thread.join();
+    control.stop_ticker();
+    result
+}
+
+/// Ask the running daemon to stop, returning its pid
+pub fn stop(config: &DaemonConfig) -> Result<u32> {
+    signal_daemon(config, Signal::SIGTERM)
+}
+
+/// Ask the running daemon to reload its configuration, returning its pid
+pub fn reload(config: &DaemonConfig) -> Result<u32> {
+    signal_daemon(config, Signal::SIGHUP)
+}
+
+/// Wait up to `timeout` for the running daemon to exit
+///
+/// Returns `true` once no daemon holds the pidfile lock.
+pub fn wait_for_exit(config: &DaemonConfig, timeout: Duration) -> bool {
+    let pid_file = expand(&config.pid_file);
+    let deadline = Instant::now() + timeout;
+    while running_pid(&pid_file).is_some() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    true
+}
+
+/// Last status written by a daemon using this configuration, if any
+pub fn status(config: &DaemonConfig) -> Result<Option<DaemonStatus>> {
+    let path = expand(&config.status_file);
+    if !path.exists() {
+        return Ok(None);
+    }
+    DaemonStatus::read(&path).map(Some)
+}
+
+/// Pid of the daemon running with this configuration, if any
+pub fn pid(config: &DaemonConfig) -> Option<u32> {
+    running_pid(&expand(&config.pid_file))
+}
+
+fn signal_daemon(config: &DaemonConfig, signal: Signal) -> Result<u32> {
+    let pid = pid(config)
+        .ok_or_else(|| PromptTrackingError::DaemonError("Daemon is not running".to_string()))?;
+    let raw = i32::try_from(pid)
+        .map_err(|_| PromptTrackingError::DaemonError(format!("Invalid pid {pid}")))?;
+
+    kill(Pid::from_raw(raw), signal).map_err(|e| {
+        PromptTrackingError::DaemonError(format!("Failed to signal pid {pid}: {e}"))
+    })?;
+    Ok(pid)
+}
+
+/// The running daemon: configuration, database and watcher
+struct Daemon {
+    config: Config,
+    config_path: Option<PathBuf>,
+    db: Database,
+    watcher: FileWatcher,
+    /// Delivers webhooks in the background; `None` without endpoints
+    webhooks: Option<WebhookWorker>,
+    backups: BackupManager,
+    /// When the next automatic backup is due, cached between checks
+    backup_due: Option<DateTime<Utc>>,
+    retention: RetentionPolicy,
+    /// When the retention rules are next applied
+    retention_due: DateTime<Utc>,
+    status: DaemonStatus,
+    status_path: PathBuf,
+    control: Arc<Control>,
+    quality_analyzer: QualityAnalyzer,
+    efficiency_analyzer: EfficiencyAnalyzer,
+}
+
+impl Daemon {
+    fn start(config: Config, options: &DaemonOptions, control: Arc<Control>) -> Result<Self> {
+        let mut db = Database::from_config(&config.database)?;
+        let webhooks = subscribe_observers(&mut db, &config);
+        let watcher = build_watcher(&config, &control)?;
+
+        let mut daemon = Self {
+            status_path: expand(&config.daemon.status_file),
+            config_path: options.config_path.clone(),
+            db,
+            watcher,
+            webhooks,
+            backups: BackupManager::from_config(&config.database),
+            backup_due: None,
+            retention: RetentionPolicy::from_config(&config.retention),
+            retention_due: Utc::now(),
+            status: DaemonStatus::new(),
+            control,
+            quality_analyzer: QualityAnalyzer::default(),
+            efficiency_analyzer: EfficiencyAnalyzer::default(),
+            config,
+        };
+        daemon.status.roots = daemon.root_names();
+
+        log::info!(
+            "Daemon started (pid {}), watching {}",
+            daemon.status.pid,
+            daemon.status.roots.join(", ")
+        );
+        if options.backfill {
+            daemon.backfill();
+        }
+        daemon.write_status();
+
+        Ok(daemon)
+    }
+
+    fn run(mut self) {
+        while !self.control.stop.load(Ordering::SeqCst) {
+            if self.control.reload.swap(false, Ordering::SeqCst) {
+                self.reload();
+            }
+
+            self.backup_if_due();
+            self.apply_retention_if_due();
+            self.control.schedule(self.next_due());
+            self.write_status();
+
+            match self.watcher.wait_events(&self.db) {
+                Ok(events) => events.iter().for_each(|event| self.handle(event)),
+                Err(e) => {
+                    log::error!("Failed to process events: {e}");
+                    self.status.record_error(e);
+                }
+            }
+        }
+
+        self.watcher.stop();
+        self.status.state = DaemonState::Stopped;
+        self.write_status();
+        log::info!("Daemon stopped");
+        log::logger().flush();
+    }
+
+    /// Capture files written while the daemon was not running
+    fn backfill(&mut self) {
+        match self.watcher.backfill(&self.db) {
+            Ok(report) => {
+                for event in &report.events {
+                    self.handle(event);
+                }
+                log::info!(
+                    "Backfill: {} files scanned, {} captured or updated, {} failed",
+                    report.scanned,
+                    report.events.len(),
+                    report.failed
+                );
+            }
+            Err(e) => {
+                log::error!("Backfill failed: {e}");
+                self.status.record_error(e);
+            }
+        }
+    }
+
+    /// Re-read the configuration file and rebuild the watcher
+    ///
+    /// On any error the current configuration stays in effect.
+    fn reload(&mut self) {
+        let Some(ref path) = self.config_path else {
+            log::warn!("SIGHUP received but no configuration file was given");
+            return;
+        };
+
+        let result = Config::load(path).and_then(|config| {
+            let db = if config.database.path == self.config.database.path {
+                None
+            } else {
+                Some(Database::from_config(&config.database)?)
+            };
+            let watcher = build_watcher(&config, &self.control)?;
+            Ok((config, db, watcher))
+        });
+
+        match result {
+            Ok((config, db, watcher)) => {
+                self.watcher.stop();
+                self.watcher = watcher;
+                if let Some(db) = db {
+                    self.db = db;
+                }
+                self.db.unsubscribe_all();
+                self.webhooks = subscribe_observers(&mut self.db, &config);
+                self.backups = BackupManager::from_config(&config.database);
+                self.backup_due = None;
+                self.retention = RetentionPolicy::from_config(&config.retention);
+                self.retention_due = Utc::now();
+                self.config = config;
+                self.status.roots = self.root_names();
+                self.status.reloads += 1;
+                log::info!("Configuration reloaded from {}", path.display());
+                self.backfill();
+            }
+            Err(e) => {
+                log::error!("Reload failed, keeping current configuration: {e}");
+                self.status.record_error(format!("Reload failed: {e}"));
+                // The new watcher may have replaced the waker before failing
+                self.control.set_waker(self.watcher.waker());
+            }
+        }
+    }
+
+    fn handle(&mut self, event: &WatchEvent) {
+        self.status.record_event(event);
+        match event {
+            WatchEvent::Captured { prompt_id, path } => {
+                log::info!("Captured prompt {} from {}", prompt_id, path.display());
+            }
+            WatchEvent::Revised {
+                prompt_id,
+                path,
+                version,
+            } => {
+                log::info!(
+                    "Updated prompt {} from {}, previous content saved as version {}",
+                    prompt_id,
+                    path.display(),
+                    version
+                );
+            }
+        }
+
+        if self.config.analysis.auto_analyze {
+            if let Err(e) = self.analyze(event.prompt_id()) {
+                log::warn!("Failed to analyze prompt {}: {}", event.prompt_id(), e);
+            }
+        }
+    }
+
+    fn analyze(&self, prompt_id: &str) -> Result<()> {
+        if let Some(prompt) = self.db.get_prompt(prompt_id)? {
+            self.db
+                .save_quality_score(&self.quality_analyzer.analyze(&prompt)?)?;
+            self.db
+                .save_efficiency_metrics(&self.efficiency_analyzer.analyze(&prompt)?)?;
+        }
+        Ok(())
+    }
+
+    /// When a backup or retention run is next due, if either is scheduled
+    fn next_due(&self) -> Option<DateTime<Utc>> {
+        let retention_due = self.retention.interval().map(|_| self.retention_due);
+        match (self.backup_due, retention_due) {
+            (Some(backup), Some(retention)) => Some(backup.min(retention)),
+            (backup, retention) => backup.or(retention),
+        }
+    }
+
+    /// Work out when the next backup is due from the latest one
+    fn refresh_backup_due(&mut self) {
+        match self.backups.next_due() {
+            Ok(due) => self.backup_due = due,
+            Err(e) => {
+                log::error!("Failed to list backups: {e}");
+                self.status.record_error(e);
+                // Check again after a full interval rather than every event
+                self.backup_due = self.backups.interval().map(|interval| Utc::now() + interval);
+            }
+        }
+    }
+
+    /// Take a backup and prune old ones once `backup_interval` has elapsed
+    /// since the latest backup
+    fn backup_if_due(&mut self) {
+        if self.backup_due.is_none() {
+            self.refresh_backup_due();
+        }
+        if !self.backup_due.is_some_and(|due| due <= Utc::now()) {
+            return;
+        }
+
+        let result = self
+            .backups
+            .create(&self.db)
+            .and_then(|_| self.backups.prune());
+        match result {
+            Ok(_) => self.refresh_backup_due(),
+            Err(e) => {
+                log::error!("Automatic backup failed: {e}");
+                self.status.record_error(format!("Backup failed: {e}"));
+                // Wait a full interval before retrying rather than every tick
+                self.backup_due = self.backups.interval().map(|interval| Utc::now() + interval);
+            }
+        }
+    }
+
+    /// Apply the retention rules once `retention.interval_hours` has
+    /// elapsed since the last run
+    fn apply_retention_if_due(&mut self) {
+        let Some(interval) = self.retention.interval() else {
+            return;
+        };
+        if self.retention_due > Utc::now() {
+            return;
+        }
+        // Failures also wait a full interval rather than retrying every tick
+        self.retention_due = Utc::now() + interval;
+
+        match self.retention.run(&self.db, false) {
+            Ok(report) if report.is_empty() => {}
+            Ok(report) => log::info!(
+                "Retention: {} prompts archived, {} purged, {} emptied from the trash, \
+                 {} score rows and {} versions removed",
+                report.archived.len(),
+                report.purged.len(),
+                report.trash_emptied,
+                report.scores_removed,
+                report.versions_removed
+            ),
+            Err(e) => {
+                log::error!("Applying retention rules failed: {e}");
+                self.status.record_error(format!("Retention failed: {e}"));
+            }
+        }
+    }
+
+    fn write_status(&mut self) {
+        self.status.updated_at = Utc::now();
+        if let Err(e) = self.status.write(&self.status_path) {
+            log::warn!("Failed to write {}: {}", self.status_path.display(), e);
+        }
+    }
+
+    fn root_names(&self) -> Vec<String> {
+        self.watcher
+            .roots()
+            .map(|root| root.path.display().to_string())
+            .collect()
+    }
+}
+
+/// Run the configured hooks and webhooks for events raised through `db`
+///
+/// Returns the worker delivering the webhooks, which stops when dropped. A
+/// webhook configuration that cannot be used is logged and skipped.
+fn subscribe_observers(db: &mut Database, config: &Config) -> Option<WebhookWorker> {
+    let hooks = HookRunner::new(&config.hooks);
+    if !hooks.is_empty() {
+        db.subscribe(Arc::new(hooks));
+    }
+
+    if config.webhooks.endpoints.is_empty() {
+        return None;
+    }
+    let dispatcher = Database::from_config(&config.database)
+        .and_then(|outbox| WebhookDispatcher::new(&config.webhooks, outbox));
+    match dispatcher {
+        Ok(dispatcher) => {
+            let dispatcher = Arc::new(dispatcher);
+            db.subscribe(dispatcher.clone());
+            Some(dispatcher.spawn_worker(webhooks::RETRY_INTERVAL))
+        }
+        Err(e) => {
+            log::error!("Webhooks disabled: {e}");
+            None
+        }
+    }
+}
+
+/// Create and start a watcher for `config`, registering its waker
+fn build_watcher(config: &Config, control: &Control) -> Result<FileWatcher> {
+    let mut watcher = FileWatcher::new(WatcherConfig::from_config(config)?)?;
+    control.set_waker(watcher.waker());
+    watcher.start()?;
+    Ok(watcher)
+}
+
+/// Expand `~` and environment variables in a configured path
+fn expand(path: &str) -> PathBuf {
+    PathBuf::from(
+        shellexpand::full(path).map_or_else(|_| path.to_string(), std::borrow::Cow::into_owned),
+    )
+}
+
+/// `path` with `suffix` appended to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pid_file_single_instance() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("run/daemon.pid");
+
+        let lock = PidFile::acquire(&path).unwrap();
+        assert_eq!(running_pid(&path), Some(std::process::id()));
+
+        let err = PidFile::acquire(&path).unwrap_err();
+        assert!(err.to_string().contains("already running"));
+
+        drop(lock);
+        assert!(!path.exists());
+        assert_eq!(running_pid(&path), None);
+    }
+
+    #[test]
+    fn test_stale_pid_file_is_not_running() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("daemon.pid");
+        fs::write(&path, "999999\n").unwrap();
+
+        assert_eq!(running_pid(&path), None);
+        assert!(PidFile::acquire(&path).is_ok());
+    }
+
+    #[test]
+    fn test_ticker_only_runs_for_scheduled_work() {
+        let control = Arc::new(Control::default());
+        control.schedule(None);
+        assert!(control.ticker.lock().unwrap().is_none());
+
+        control.schedule(Some(Utc::now()));
+        assert!(control.ticker.lock().unwrap().is_some());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // The ticker clears the schedule once it has woken the daemon
+        while control.next_due.lock().unwrap().is_some() {
+            assert!(Instant::now() < deadline, "ticker did not fire");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        control.stop_ticker();
+        assert!(control.ticker.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("status.json");
+
+        let mut status = DaemonStatus::new();
+        status.record_event(&WatchEvent::Captured {
+            prompt_id: "p1".to_string(),
+            path: PathBuf::from("/w/a.md"),
+        });
+        status.record_error("disk full");
+        status.write(&path).unwrap();
+
+        let read = DaemonStatus::read(&path).unwrap();
+        assert_eq!(read, status);
+        assert_eq!(read.captured, 1);
+        assert_eq!(read.last_error.as_deref(), Some("disk full"));
+        assert!(read.last_event_at.is_some());
+    }
+
+    #[test]
+    fn test_rotating_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("daemon.log");
+        let logger = RotatingLog::new(&path, 200, 2, log::LevelFilter::Info).unwrap();
+
+        let record = |message: &str| {
+            log::Log::log(
+                &logger,
+                &log::Record::builder()
+                    .args(format_args!("{}", message))
+                    .level(log::Level::Info)
+                    .target("test")
+                    .build(),
+            );
+        };
+        for i in 0..20 {
+            record(&format!("captured prompt number {}", i));
+        }
+        log::Log::log(
+            &logger,
+            &log::Record::builder()
+                .args(format_args!("not written"))
+                .level(log::Level::Debug)
+                .build(),
+        );
+
+        assert!(fs::metadata(&path).unwrap().len() <= 200);
+        assert!(with_suffix(&path, ".1").exists());
+        assert!(with_suffix(&path, ".2").exists());
+        assert!(!with_suffix(&path, ".3").exists());
+
+        let latest = fs::read_to_string(&path).unwrap();
+        assert!(latest.contains("captured prompt number 19"));
+        assert!(!latest.contains("not written"));
+    }
+}
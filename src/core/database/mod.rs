@@ -2,20 +2,316 @@
 //!
 //! Provides SQLite database operations for storing and retrieving prompts.
 
-use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use parking_lot::{
+    MappedMutexGuard, Mutex, MutexGuard, ReentrantMutex, ReentrantMutexGuard, RwLock,
+};
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Result as SqliteResult};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
-
-use crate::migration::MigrationManager;
-use crate::models::{EfficiencyMetrics, Prompt, PromptMetadata, PromptStatus, QualityScore};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{Config, DatabaseConfig, EncryptionConfig};
+use crate::encryption::{self, Cipher, KeyMaterial, KeyParams};
+use crate::events::{Event, EventBus, EventObserver};
+use crate::backup::BackupManager;
+use crate::migration::{MigrationManager, MigrationStep, CURRENT_VERSION, MIGRATIONS};
+use crate::models::{
+    EfficiencyMetrics, GitContext, PostCaptureAction, Prompt, PromptMetadata, PromptStatus,
+    PromptTemplate, QualityScore, RedactionFinding, TemplateUsage,
+};
 use crate::{PromptTrackingError, Result};
 
+/// Columns selected for [`CaptureFailure`] rows
+const CAPTURE_FAILURE_COLUMNS: &str =
+    "id, path, error_kind, message, first_seen, last_seen, attempts, next_retry_at, dismissed";
+
+/// Columns selected for [`FileAction`] rows
+const FILE_ACTION_COLUMNS: &str =
+    "id, prompt_id, action, source, destination, unseal(content), performed_at, undone_at";
+
+/// Columns selected for [`WebhookDelivery`] rows
+const WEBHOOK_DELIVERY_COLUMNS: &str = "id, endpoint, event, unseal(payload), status, attempts, \
+     next_attempt_at, last_error, created_at, delivered_at";
+
+/// Tables that can hold rows left behind by deleted prompts or tags, with
+/// the condition selecting those rows
+///
+/// Tags count as orphaned once no remaining prompt uses them, so removing
+/// orphaned `prompt_tags` rows first leaves no tags behind.
+const ORPHAN_CONDITIONS: &[(&str, &str)] = &[
+    (
+        "prompt_tags",
+        "prompt_id NOT IN (SELECT id FROM prompts) OR tag_id NOT IN (SELECT id FROM tags)",
+    ),
+    (
+        "tags",
+        "id NOT IN (SELECT tag_id FROM prompt_tags WHERE prompt_id IN (SELECT id FROM prompts))",
+    ),
+    ("quality_scores", "prompt_id NOT IN (SELECT id FROM prompts)"),
+    ("efficiency_metrics", "prompt_id NOT IN (SELECT id FROM prompts)"),
+];
+
+/// Columns holding prompt text, sealed when the database is encrypted
+const SEALED_COLUMNS: &[(&str, &str)] = &[
+    ("prompts", "content"),
+    ("prompts", "context"),
+    ("version_history", "content"),
+    ("templates", "content"),
+    ("file_actions", "content"),
+    ("webhook_outbox", "payload"),
+];
+
+/// Key of an encrypted database, shared with every connection's `seal` and
+/// `unseal` SQL functions; `None` while the database is not encrypted
+type SharedCipher = Arc<RwLock<Option<Cipher>>>;
+
+/// Most prompt IDs bound in one `IN (...)` list, below `SQLite`'s
+/// historical limit of 999 parameters per statement; larger sets are
+/// loaded in batches of this size
+const ID_BATCH_SIZE: usize = 500;
+
 /// Database manager for prompt storage
+///
+/// Writes go through a single connection that one thread holds at a time,
+/// while queries draw from a pool of read-only connections. With WAL
+/// journaling a query runs alongside a write instead of waiting for it.
+/// `Database` is `Send + Sync`, so it can be shared across threads in an
+/// [`Arc`].
 pub struct Database {
-    conn: Connection,
+    writer: ReentrantMutex<Connection>,
+    readers: ReaderPool,
+    cipher: SharedCipher,
+    events: EventBus,
+    /// Number of open nested transactions; 0 outside a transaction
+    tx_depth: AtomicU32,
+    /// Events raised inside the open transaction, delivered once it commits
+    pending_events: Mutex<Vec<Event>>,
+}
+
+/// Read-only connections shared by queries
+struct ReaderPool {
+    /// Database file; `None` for in-memory databases, which have no readers
+    path: Option<String>,
+    /// One slot per reader, opened on first use and locked while checked out
+    slots: Vec<Mutex<Option<Connection>>>,
+    busy_timeout: Duration,
+    cipher: SharedCipher,
+}
+
+impl ReaderPool {
+    fn new(path: Option<String>, max: usize, busy_timeout: Duration) -> Self {
+        Self {
+            path,
+            slots: (0..max).map(|_| Mutex::new(None)).collect(),
+            busy_timeout,
+            cipher: SharedCipher::default(),
+        }
+    }
+
+    /// Lock a free slot, opening its connection if needed, unless all are busy
+    fn checkout(&self) -> Option<MappedMutexGuard<'_, Connection>> {
+        let path = self.path.as_deref()?;
+        self.slots.iter().find_map(|slot| {
+            let mut slot = slot.try_lock()?;
+            if slot.is_none() {
+                *slot = Some(self.open(path)?);
+            }
+            MutexGuard::try_map(slot, Option::as_mut).ok()
+        })
+    }
+
+    /// Open a read-only connection to `path`
+    fn open(&self, path: &str) -> Option<Connection> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            | OpenFlags::SQLITE_OPEN_URI;
+        let opened = Connection::open_with_flags(path, flags).and_then(|conn| {
+            conn.busy_timeout(self.busy_timeout)?;
+            register_functions(&conn, &self.cipher)?;
+            Ok(conn)
+        });
+        match opened {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                log::warn!("Failed to open read connection: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Add the `seal` and `unseal` SQL functions to a connection
+///
+/// `seal(x)` encrypts text or bytes with the database key and passes other
+/// values through; without a key it returns `x` unchanged. `unseal(x)`
+/// reverses it and passes plaintext through, failing only on a sealed
+/// value when no key is loaded.
+fn register_functions(conn: &Connection, cipher: &SharedCipher) -> SqliteResult<()> {
+    // The lock is never held across a call that can panic
+    let sealing = AssertUnwindSafe(Arc::clone(cipher));
+    conn.create_scalar_function("seal", 1, FunctionFlags::SQLITE_UTF8, move |ctx| {
+        let guard = sealing.read();
+        let Some(cipher) = guard.as_ref() else {
+            return Ok(Value::from(ctx.get_raw(0)));
+        };
+        let sealed = match ctx.get_raw(0) {
+            ValueRef::Text(text) => {
+                let text = std::str::from_utf8(text).map_err(user_error)?;
+                Value::Text(cipher.seal_text(text).map_err(user_error)?)
+            }
+            ValueRef::Blob(bytes) => Value::Blob(cipher.seal_bytes(bytes).map_err(user_error)?),
+            other => Value::from(other),
+        };
+        drop(guard);
+        Ok(sealed)
+    })?;
+
+    let unsealing = AssertUnwindSafe(Arc::clone(cipher));
+    conn.create_scalar_function(
+        "unseal",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            let value = ctx.get_raw(0);
+            let sealed = match value {
+                ValueRef::Text(text) => encryption::is_sealed_bytes(text),
+                ValueRef::Blob(bytes) => encryption::is_sealed_bytes(bytes),
+                _ => false,
+            };
+            if !sealed {
+                return Ok(Value::from(value));
+            }
+
+            let guard = unsealing.read();
+            let cipher = guard.as_ref().ok_or_else(|| {
+                user_error(PromptTrackingError::EncryptionError(
+                    "Value is encrypted but no key is loaded".to_string(),
+                ))
+            })?;
+            let opened = match value {
+                ValueRef::Text(text) => {
+                    let text = std::str::from_utf8(text).map_err(user_error)?;
+                    Value::Text(cipher.open_text(text).map_err(user_error)?)
+                }
+                _ => Value::Blob(cipher.open_bytes(value.as_blob()?).map_err(user_error)?),
+            };
+            drop(guard);
+            Ok(opened)
+        },
+    )
+}
+
+/// Wrap an error raised inside a SQL function
+fn user_error(e: impl std::error::Error + Send + Sync + 'static) -> rusqlite::Error {
+    rusqlite::Error::UserFunctionError(Box::new(e))
+}
+
+/// Connection handed out by [`Database::reader`]
+enum ReadConn<'a> {
+    /// A pooled read-only connection, free for others once dropped
+    Pooled(MappedMutexGuard<'a, Connection>),
+    /// The writer, when no reader is available or this thread holds it
+    Writer(ReentrantMutexGuard<'a, Connection>),
+}
+
+impl Deref for ReadConn<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            Self::Pooled(conn) => conn,
+            Self::Writer(guard) => guard,
+        }
+    }
 }
 
 use chrono::{DateTime, Utc};
 
+/// A transaction or savepoint opened by [`Database::transaction`]
+///
+/// Rolled back when dropped without being committed, so an early return or
+/// a panic inside the closure leaves the database unchanged.
+struct TransactionScope<'a> {
+    db: &'a Database,
+    /// Keeps other threads off the writer until the scope ends
+    conn: ReentrantMutexGuard<'a, Connection>,
+    /// Nesting depth this scope was opened at; 0 is the outermost
+    depth: u32,
+    /// Events already held back by enclosing scopes
+    pending: usize,
+    finished: bool,
+}
+
+impl<'a> TransactionScope<'a> {
+    fn begin(db: &'a Database) -> Result<Self> {
+        let conn = db.conn();
+        let depth = db.tx_depth.load(Ordering::SeqCst);
+        let sql = if depth == 0 {
+            "BEGIN IMMEDIATE".to_string()
+        } else {
+            format!("SAVEPOINT tx_{depth}")
+        };
+        conn.execute_batch(&sql).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to begin transaction: {e}"))
+        })?;
+
+        db.tx_depth.store(depth + 1, Ordering::SeqCst);
+        Ok(Self {
+            db,
+            conn,
+            depth,
+            pending: db.pending_events.lock().len(),
+            finished: false,
+        })
+    }
+
+    /// Commit the scope, returning the held-back events once the outermost
+    /// transaction is done
+    fn commit(mut self) -> Result<Vec<Event>> {
+        let sql = if self.depth == 0 {
+            "COMMIT".to_string()
+        } else {
+            format!("RELEASE tx_{}", self.depth)
+        };
+        self.conn.execute_batch(&sql).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to commit transaction: {e}"))
+        })?;
+
+        self.finished = true;
+        self.db.tx_depth.store(self.depth, Ordering::SeqCst);
+        if self.depth == 0 {
+            Ok(std::mem::take(&mut *self.db.pending_events.lock()))
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
+
+impl Drop for TransactionScope<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let sql = if self.depth == 0 {
+            "ROLLBACK".to_string()
+        } else {
+            format!("ROLLBACK TO tx_{0}; RELEASE tx_{0}", self.depth)
+        };
+        if let Err(e) = self.conn.execute_batch(&sql) {
+            log::error!("Failed to roll back transaction: {e}");
+        }
+        self.db.tx_depth.store(self.depth, Ordering::SeqCst);
+        self.db.pending_events.lock().truncate(self.pending);
+    }
+}
+
 /// Filter options for querying prompts
 #[derive(Debug, Default, Clone)]
 pub struct PromptFilter {
@@ -29,6 +325,14 @@ pub struct PromptFilter {
     pub date_to: Option<DateTime<Utc>>,
     pub min_quality_score: Option<f64>,
     pub max_quality_score: Option<f64>,
+    /// Attribute `(key, value)` pairs that must all match
+    pub attributes: Vec<(String, String)>,
+    /// Git project name
+    pub project: Option<String>,
+    /// Git branch name
+    pub branch: Option<String>,
+    /// Name of the template the prompt was rendered from
+    pub template: Option<String>,
 }
 
 /// Version history entry
@@ -51,10 +355,221 @@ pub struct TrendDataPoint {
     pub avg_efficiency: f64,
 }
 
+/// Logged redaction event
+#[derive(Debug, Clone)]
+pub struct RedactionEvent {
+    /// Row id of the event
+    pub id: i64,
+    /// Prompt the redaction was applied to
+    pub prompt_id: String,
+    /// Name of the rule that matched
+    pub rule: String,
+    /// Action taken (`mask`, `hash` or `reject`)
+    pub action: String,
+    /// Number of matches replaced
+    pub match_count: usize,
+    /// Where the redaction happened (`capture`, `update`, `scan`)
+    pub source: String,
+    /// When the redaction happened
+    pub created_at: DateTime<Utc>,
+}
+
+/// File tracked by the watcher and the prompt it was captured as
+#[derive(Debug, Clone)]
+pub struct WatchedFile {
+    /// Path of the file
+    pub path: String,
+    /// Prompt captured from the file
+    pub prompt_id: String,
+    /// Hash of the prompt content last captured from the file
+    pub content_hash: String,
+    /// When the file was last captured
+    pub updated_at: DateTime<Utc>,
+}
+
+/// State of a watched file when it was last processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchCheckpoint {
+    /// Path of the file
+    pub path: String,
+    /// File size in bytes
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime_ns: i64,
+    /// SHA-256 of the file bytes
+    pub file_hash: String,
+}
+
+/// A file the watcher could not capture, kept for review and retry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureFailure {
+    /// Row id, used to retry or dismiss the failure
+    pub id: i64,
+    /// Path of the file
+    pub path: String,
+    /// Error kind, see [`PromptTrackingError::kind`]
+    pub error_kind: String,
+    /// Error message of the latest attempt
+    pub message: String,
+    /// When the file first failed
+    pub first_seen: DateTime<Utc>,
+    /// When the file last failed
+    pub last_seen: DateTime<Utc>,
+    /// Number of failed attempts
+    pub attempts: u32,
+    /// When the watcher retries automatically; `None` for permanent errors
+    pub next_retry_at: Option<DateTime<Utc>>,
+    /// Dismissed failures are hidden and not retried until the file fails again
+    pub dismissed: bool,
+}
+
+/// A file the watcher moved, renamed or deleted after capturing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileAction {
+    /// Row id, used to undo the action
+    pub id: i64,
+    /// Prompt captured from the file
+    pub prompt_id: String,
+    /// What was done to the file
+    pub action: PostCaptureAction,
+    /// Path of the file when it was captured
+    pub source: String,
+    /// New path for moved and renamed files
+    pub destination: Option<String>,
+    /// Bytes of a deleted file, kept so the deletion can be undone
+    pub content: Option<Vec<u8>>,
+    /// When the action was performed
+    pub performed_at: DateTime<Utc>,
+    /// When the action was undone, if it was
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+/// State of a webhook delivery in the outbox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    /// Accepted by the endpoint with a 2xx response
+    Delivered,
+    /// Gave up after the maximum number of attempts
+    Failed,
+}
+
+/// An event queued for delivery to a webhook endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookDelivery {
+    /// Outbox row ID
+    pub id: i64,
+    /// Name of the configured endpoint
+    pub endpoint: String,
+    /// Event kind, e.g. `captured`
+    pub event: String,
+    /// JSON body sent to the endpoint
+    pub payload: String,
+    /// Where the delivery stands
+    pub status: DeliveryStatus,
+    /// Attempts made so far
+    pub attempts: u32,
+    /// When the next attempt is due; `None` once delivered or failed
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// Error from the most recent failed attempt
+    pub last_error: Option<String>,
+    /// When the event was queued
+    pub created_at: DateTime<Utc>,
+    /// When the endpoint accepted the delivery
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Delivered => write!(f, "delivered"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl std::str::FromStr for DeliveryStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(Self::Pending),
+            "delivered" => Ok(Self::Delivered),
+            "failed" => Ok(Self::Failed),
+            _ => Err(format!("Invalid delivery status: {s}")),
+        }
+    }
+}
+
+/// Read position in an append-only log file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogOffset {
+    /// Path of the log file
+    pub path: String,
+    /// Bytes consumed so far, always at the end of a complete line
+    pub offset: u64,
+    /// Hash of the first line, used to notice when the file is replaced
+    pub head_hash: Option<String>,
+}
+
+impl LogOffset {
+    /// Offset for a log that has not been read yet
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            offset: 0,
+            head_hash: None,
+        }
+    }
+}
+
+/// Per-project trend statistics
+#[derive(Debug, Clone)]
+pub struct ProjectTrend {
+    /// Project name, `(none)` for prompts captured outside a repository
+    pub project: String,
+    /// Number of prompts
+    pub count: usize,
+    /// Average quality score
+    pub avg_quality: f64,
+    /// Average efficiency score
+    pub avg_efficiency: f64,
+}
+
 impl Database {
-    /// Create a new database connection
+    /// Create a new database connection with the default settings
     pub fn new(path: &str) -> Result<Self> {
-        let path = shellexpand::tilde(path).to_string();
+        Self::from_config(&DatabaseConfig {
+            path: path.to_string(),
+            ..Config::default().database
+        })
+    }
+
+    /// Open the database described by the `database` config section
+    ///
+    /// Pending migrations are applied, after backing up a database that
+    /// already holds a schema. A schema pinned by an explicit downgrade is
+    /// an error rather than being migrated back up.
+    pub fn from_config(config: &DatabaseConfig) -> Result<Self> {
+        let db = Self::open(config)?;
+        db.migrate_on_open(config)?;
+        db.unlock(&config.encryption)?;
+        Ok(db)
+    }
+
+    /// Open the database without migrating its schema or loading its key
+    ///
+    /// Meant for schema administration: queries other than those of the
+    /// migration methods may fail until the schema is current, and sealed
+    /// columns are not readable.
+    pub fn open_unmigrated(config: &DatabaseConfig) -> Result<Self> {
+        Self::open(config)
+    }
+
+    fn open(config: &DatabaseConfig) -> Result<Self> {
+        let path = shellexpand::tilde(&config.path).to_string();
 
         // Create parent directories if they don't exist
         if let Some(parent) = Path::new(&path).parent() {
@@ -66,10 +581,15 @@ impl Database {
         let conn = Connection::open(&path).map_err(|e| {
             PromptTrackingError::DatabaseError(format!("Failed to open database: {}", e))
         })?;
+        let busy_timeout = Duration::from_millis(config.busy_timeout_ms);
+        Self::configure(&conn, config.wal, busy_timeout).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to configure database: {e}"))
+        })?;
 
-        let db = Self { conn };
-        db.initialize_schema()?;
-        Ok(db)
+        Self::with_writer(
+            conn,
+            ReaderPool::new(Some(path), config.max_readers, busy_timeout),
+        )
     }
 
     /// Create an in-memory database for testing
@@ -78,76 +598,261 @@ impl Database {
             PromptTrackingError::DatabaseError(format!("Failed to create in-memory database: {}", e))
         })?;
 
-        let db = Self { conn };
+        let db = Self::with_writer(
+            conn,
+            ReaderPool::new(None, 0, Duration::ZERO),
+        )?;
         db.initialize_schema()?;
         Ok(db)
     }
 
+    fn with_writer(conn: Connection, readers: ReaderPool) -> Result<Self> {
+        register_functions(&conn, &readers.cipher).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to configure database: {e}"))
+        })?;
+        Ok(Self {
+            writer: ReentrantMutex::new(conn),
+            cipher: readers.cipher.clone(),
+            readers,
+            events: EventBus::default(),
+            tx_depth: AtomicU32::new(0),
+            pending_events: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Apply the busy timeout and, if enabled, switch to WAL journaling
+    fn configure(conn: &Connection, wal: bool, busy_timeout: Duration) -> SqliteResult<()> {
+        conn.busy_timeout(busy_timeout)?;
+        if wal {
+            let mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+            if !mode.eq_ignore_ascii_case("wal") {
+                log::warn!("WAL journaling unavailable, using {mode} mode");
+            }
+            // WAL stays consistent at NORMAL; only the last commits can be
+            // lost on power failure
+            conn.execute_batch("PRAGMA synchronous = NORMAL")?;
+        }
+        Ok(())
+    }
+
+    /// The write connection, held by one thread at a time
+    ///
+    /// Re-entrant, so code already holding it (such as an open transaction)
+    /// can lock it again.
+    fn conn(&self) -> ReentrantMutexGuard<'_, Connection> {
+        self.writer.lock()
+    }
+
+    /// A connection for queries
+    ///
+    /// Uses a pooled read-only connection, so reads don't wait for a write in
+    /// progress. Falls back to the writer when this thread already holds it,
+    /// so a transaction sees its own changes, or when no reader is free.
+    fn reader(&self) -> ReadConn<'_> {
+        if !self.writer.is_owned_by_current_thread() {
+            if let Some(conn) = self.readers.checkout() {
+                return ReadConn::Pooled(conn);
+            }
+        }
+        ReadConn::Writer(self.writer.lock())
+    }
+
+    /// Register an observer for prompt lifecycle events
+    pub fn subscribe(&mut self, observer: Arc<dyn EventObserver>) {
+        self.events.subscribe(observer);
+    }
+
+    /// Remove all registered observers
+    pub fn unsubscribe_all(&mut self) {
+        self.events.clear();
+    }
+
+    /// Deliver an event to the registered observers
+    ///
+    /// The database raises its own events as prompts change; this is for
+    /// events it cannot see, such as a generated report. Inside a
+    /// transaction the event is held back until the transaction commits and
+    /// dropped if it rolls back.
+    pub fn emit(&self, event: &Event) {
+        if self.events.is_empty() {
+            return;
+        }
+        if self.in_transaction() {
+            self.pending_events.lock().push(event.clone());
+        } else {
+            self.events.emit(event);
+        }
+    }
+
+    /// Run `f` in a transaction, committing if it returns `Ok`
+    ///
+    /// Any error from `f` rolls back every change it made. Calls nest: an
+    /// inner transaction becomes a savepoint, so it can fail and roll back
+    /// on its own while the outer one carries on. Events raised inside are
+    /// delivered after the outermost transaction commits.
+    ///
+    /// ```rust,no_run
+    /// # use prompt_tracking::{database::Database, models::Prompt};
+    /// # fn main() -> prompt_tracking::Result<()> {
+    /// let db = Database::new("~/.prompt-tracking/prompts.db")?;
+    /// let prompt = Prompt::new("Summarize this changelog".to_string());
+    /// db.transaction(|tx| {
+    ///     tx.create_prompt(&prompt)?;
+    ///     tx.set_attribute(&prompt.id, "reviewed", "yes")
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    // `commit` consumes the scope, so it is never held past its last use
+    #[allow(clippy::significant_drop_tightening)]
+    pub fn transaction<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let scope = TransactionScope::begin(self)?;
+        let value = f(self)?;
+        let events = scope.commit()?;
+
+        for event in &events {
+            self.events.emit(event);
+        }
+        Ok(value)
+    }
+
+    /// Whether this thread has a transaction open
+    pub fn in_transaction(&self) -> bool {
+        self.writer.is_owned_by_current_thread() && self.tx_depth.load(Ordering::SeqCst) > 0
+    }
+
     /// Initialize database schema using migrations
     fn initialize_schema(&self) -> Result<()> {
-        let manager = MigrationManager::new(&self.conn);
-        manager.run_migrations()
+        MigrationManager::new(&self.conn()).run_migrations()
+    }
+
+    /// Apply pending migrations, backing up an existing schema first
+    ///
+    /// A schema pinned by [`Database::migrate_to`] is an error.
+    fn migrate_on_open(&self, config: &DatabaseConfig) -> Result<()> {
+        let version = self.schema_version()?;
+        if version > 0 && version < CURRENT_VERSION && self.pinned_version()?.is_none() {
+            let backup = BackupManager::from_config(config).create(self)?;
+            log::info!(
+                "Backed up schema v{} to {} before migrating",
+                version,
+                backup.path.display()
+            );
+        }
+        self.initialize_schema()
+    }
+
+    /// Current schema version, 0 for an empty database
+    pub fn schema_version(&self) -> Result<i32> {
+        MigrationManager::new(&self.conn()).current_version()
+    }
+
+    /// Version the schema was explicitly migrated down to, if it is pinned
+    pub fn pinned_version(&self) -> Result<Option<i32>> {
+        MigrationManager::new(&self.conn()).pinned_version()
+    }
+
+    /// Migration steps that would bring the schema to `target`
+    pub fn migration_plan(&self, target: i32) -> Result<Vec<MigrationStep>> {
+        MigrationManager::new(&self.conn()).plan(target)
+    }
+
+    /// Bring the schema to `target`, applying or reverting migrations
+    ///
+    /// Either every step is applied or the schema is left unchanged. A
+    /// `target` older than [`CURRENT_VERSION`] pins the schema, so
+    /// [`Database::from_config`] refuses to open it until it is migrated to
+    /// the latest version again.
+    pub fn migrate_to(&self, target: i32) -> Result<Vec<MigrationStep>> {
+        MigrationManager::new(&self.conn()).migrate_to(target)
     }
 
     /// Check if database needs migration
     pub fn needs_migration(&self) -> Result<bool> {
-        let manager = MigrationManager::new(&self.conn);
-        manager.needs_migration()
+        MigrationManager::new(&self.conn()).needs_migration()
     }
 
     /// Get migration history
     pub fn get_migration_history(&self) -> Result<Vec<(i32, String)>> {
-        let manager = MigrationManager::new(&self.conn);
-        manager.get_migration_history()
+        MigrationManager::new(&self.conn()).get_migration_history()
     }
 
     /// Create a new prompt
+    ///
+    /// A prompt with the same id in the trash, as when a deleted prompt is
+    /// imported again, is replaced.
     pub fn create_prompt(&self, prompt: &Prompt) -> Result<()> {
-        self.conn
-            .execute(
-                r#"
-            INSERT INTO prompts (
-                id, content, content_hash, category, status, created_at, updated_at,
-                model, input_tokens, output_tokens, execution_time_ms, estimated_cost, context
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
-            "#,
-                params![
-                    prompt.id,
-                    prompt.content,
-                    prompt.content_hash,
-                    prompt.category,
-                    prompt.status.to_string(),
-                    prompt.created_at.to_rfc3339(),
-                    prompt.updated_at.to_rfc3339(),
-                    prompt.metadata.model,
-                    prompt.metadata.input_tokens,
-                    prompt.metadata.output_tokens,
-                    prompt.metadata.execution_time_ms.map(|v| v as i64),
-                    prompt.metadata.estimated_cost,
-                    prompt.metadata.context,
-                ],
-            )
-            .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to create prompt: {}", e))
-            })?;
+        self.transaction(|tx| {
+            if tx.in_trash(&prompt.id)? {
+                tx.delete_prompt_permanently(&prompt.id)?;
+            }
 
-        // Insert tags
-        for tag in &prompt.tags {
-            self.add_tag_to_prompt(&prompt.id, tag)?;
-        }
+            tx.conn()
+                .execute(
+                    r"
+                INSERT INTO prompts (
+                    id, content, content_hash, category, status, created_at, updated_at,
+                    model, input_tokens, output_tokens, execution_time_ms, estimated_cost, context
+                ) VALUES (?1, seal(?2), ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, seal(?13))
+                ",
+                    params![
+                        prompt.id,
+                        prompt.content,
+                        prompt.content_hash,
+                        prompt.category,
+                        prompt.status.to_string(),
+                        prompt.created_at.to_rfc3339(),
+                        prompt.updated_at.to_rfc3339(),
+                        prompt.metadata.model,
+                        prompt.metadata.input_tokens,
+                        prompt.metadata.output_tokens,
+                        prompt.metadata.execution_time_ms,
+                        prompt.metadata.estimated_cost,
+                        prompt.metadata.context,
+                    ],
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to create prompt: {e}"))
+                })?;
 
-        Ok(())
+            // Insert tags
+            for tag in &prompt.tags {
+                tx.add_tag_to_prompt(&prompt.id, tag)?;
+            }
+
+            // Insert attributes
+            for (key, value) in &prompt.metadata.attributes {
+                tx.set_attribute(&prompt.id, key, value)?;
+            }
+
+            if let Some(ref git) = prompt.metadata.git {
+                tx.save_git_context(&prompt.id, git)?;
+            }
+
+            if let Some(ref usage) = prompt.metadata.template {
+                tx.save_template_usage(&prompt.id, usage)?;
+            }
+
+            tx.log_redaction_events(&prompt.id, &prompt.redactions, "capture")?;
+
+            if !tx.events.is_empty() {
+                tx.emit(&Event::Captured {
+                    prompt: prompt.clone(),
+                });
+            }
+            Ok(())
+        })
     }
 
     /// Get a prompt by ID
     pub fn get_prompt(&self, id: &str) -> Result<Option<Prompt>> {
-        let mut stmt = self
-            .conn
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
                 r#"
-            SELECT id, content, content_hash, category, status, created_at, updated_at,
-                   model, input_tokens, output_tokens, execution_time_ms, estimated_cost, context
-            FROM prompts WHERE id = ?1
+            SELECT id, unseal(content), content_hash, category, status, created_at, updated_at,
+                   model, input_tokens, output_tokens, execution_time_ms, estimated_cost, unseal(context)
+            FROM prompts WHERE id = ?1 AND deleted_at IS NULL
             "#,
             )
             .map_err(|e| {
@@ -163,9 +868,12 @@ impl Database {
                 PromptTrackingError::DatabaseError(format!("Failed to get prompt: {}", e))
             })?;
 
-        // Get tags if prompt exists
+        drop(stmt);
+        drop(conn);
+
+        // Load related data if prompt exists
         if let Some(mut p) = prompt {
-            p.tags = self.get_tags_for_prompt(&p.id)?;
+            self.load_prompt_relations(&mut p)?;
             Ok(Some(p))
         } else {
             Ok(None)
@@ -174,77 +882,149 @@ impl Database {
 
     /// Update an existing prompt
     pub fn update_prompt(&self, prompt: &Prompt) -> Result<()> {
-        self.conn
+        self.transaction(|tx| {
+            tx.conn()
+                .execute(
+                    r"
+                UPDATE prompts SET
+                    content = seal(?2), content_hash = ?3, category = ?4, status = ?5, updated_at = ?6,
+                    model = ?7, input_tokens = ?8, output_tokens = ?9,
+                    execution_time_ms = ?10, estimated_cost = ?11, context = seal(?12)
+                WHERE id = ?1 AND deleted_at IS NULL
+                ",
+                    params![
+                        prompt.id,
+                        prompt.content,
+                        prompt.content_hash,
+                        prompt.category,
+                        prompt.status.to_string(),
+                        prompt.updated_at.to_rfc3339(),
+                        prompt.metadata.model,
+                        prompt.metadata.input_tokens,
+                        prompt.metadata.output_tokens,
+                        prompt.metadata.execution_time_ms,
+                        prompt.metadata.estimated_cost,
+                        prompt.metadata.context,
+                    ],
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to update prompt: {e}"))
+                })?;
+
+            // Update tags
+            tx.remove_all_tags_from_prompt(&prompt.id)?;
+            for tag in &prompt.tags {
+                tx.add_tag_to_prompt(&prompt.id, tag)?;
+            }
+
+            // Update attributes
+            tx.remove_all_attributes_from_prompt(&prompt.id)?;
+            for (key, value) in &prompt.metadata.attributes {
+                tx.set_attribute(&prompt.id, key, value)?;
+            }
+
+            // Update git context
+            tx.remove_git_context(&prompt.id)?;
+            if let Some(ref git) = prompt.metadata.git {
+                tx.save_git_context(&prompt.id, git)?;
+            }
+
+            // Update template lineage
+            tx.remove_template_usage(&prompt.id)?;
+            if let Some(ref usage) = prompt.metadata.template {
+                tx.save_template_usage(&prompt.id, usage)?;
+            }
+
+            tx.log_redaction_events(&prompt.id, &prompt.redactions, "update")?;
+
+            if !tx.events.is_empty() {
+                tx.emit(&Event::Updated {
+                    prompt: prompt.clone(),
+                });
+            }
+            Ok(())
+        })
+    }
+
+    /// Move a prompt to the trash
+    ///
+    /// The prompt disappears from lookups and listings but keeps its tags,
+    /// scores and history until the trash is emptied, so
+    /// [`Database::restore_prompt`] can bring it back unchanged.
+    pub fn delete_prompt(&self, id: &str) -> Result<()> {
+        let rows = self
+            .conn()
             .execute(
-                r#"
-            UPDATE prompts SET
-                content = ?2, content_hash = ?3, category = ?4, status = ?5, updated_at = ?6,
-                model = ?7, input_tokens = ?8, output_tokens = ?9,
-                execution_time_ms = ?10, estimated_cost = ?11, context = ?12
-            WHERE id = ?1
-            "#,
-                params![
-                    prompt.id,
-                    prompt.content,
-                    prompt.content_hash,
-                    prompt.category,
-                    prompt.status.to_string(),
-                    prompt.updated_at.to_rfc3339(),
-                    prompt.metadata.model,
-                    prompt.metadata.input_tokens,
-                    prompt.metadata.output_tokens,
-                    prompt.metadata.execution_time_ms.map(|v| v as i64),
-                    prompt.metadata.estimated_cost,
-                    prompt.metadata.context,
-                ],
+                "UPDATE prompts SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+                params![id, Utc::now().to_rfc3339()],
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to update prompt: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to delete prompt: {e}"))
             })?;
 
-        // Update tags
-        self.remove_all_tags_from_prompt(&prompt.id)?;
-        for tag in &prompt.tags {
-            self.add_tag_to_prompt(&prompt.id, tag)?;
+        if rows > 0 {
+            self.emit(&Event::Deleted {
+                prompt_id: id.to_string(),
+            });
         }
-
         Ok(())
     }
 
-    /// Delete a prompt by ID
-    pub fn delete_prompt(&self, id: &str) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM prompts WHERE id = ?1", params![id])
-            .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to delete prompt: {}", e))
-            })?;
+    /// Delete a prompt and everything recorded about it, whether or not it
+    /// is in the trash
+    pub fn delete_prompt_permanently(&self, id: &str) -> Result<()> {
+        self.transaction(|tx| {
+            let live: bool = tx
+                .conn()
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM prompts WHERE id = ?1 AND deleted_at IS NULL)",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to delete prompt: {e}"))
+                })?;
 
-        Ok(())
+            tx.conn()
+                .execute("DELETE FROM prompts WHERE id = ?1", params![id])
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to delete prompt: {e}"))
+                })?;
+
+            tx.remove_all_attributes_from_prompt(id)?;
+            tx.remove_git_context(id)?;
+            tx.remove_template_usage(id)?;
+
+            tx.conn()
+                .execute("DELETE FROM watched_files WHERE prompt_id = ?1", params![id])
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!(
+                        "Failed to remove watched file: {e}"
+                    ))
+                })?;
+
+            if live {
+                tx.emit(&Event::Deleted {
+                    prompt_id: id.to_string(),
+                });
+            }
+            Ok(())
+        })
     }
 
     /// List prompts with optional filtering
     pub fn list_prompts(&self, filter: &PromptFilter) -> Result<Vec<Prompt>> {
         let mut query = String::from(
             r#"
-            SELECT DISTINCT p.id, p.content, p.content_hash, p.category, p.status, p.created_at, p.updated_at,
-                   p.model, p.input_tokens, p.output_tokens, p.execution_time_ms, p.estimated_cost, p.context
+            SELECT p.id, unseal(p.content), p.content_hash, p.category, p.status, p.created_at, p.updated_at,
+                   p.model, p.input_tokens, p.output_tokens, p.execution_time_ms, p.estimated_cost, unseal(p.context)
             FROM prompts p
             "#,
         );
 
-        let mut conditions = Vec::new();
+        let mut conditions = vec!["p.deleted_at IS NULL".to_string()];
         let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
-        // Join with tags if filtering by tags
-        if !filter.tags.is_empty() {
-            query.push_str(
-                r#"
-                JOIN prompt_tags pt ON p.id = pt.prompt_id
-                JOIN tags t ON pt.tag_id = t.id
-                "#,
-            );
-        }
-
         // Category filter
         if let Some(ref category) = filter.category {
             conditions.push(format!("p.category = ?{}", params_vec.len() + 1));
@@ -259,7 +1039,11 @@ impl Database {
                 .enumerate()
                 .map(|(i, _)| format!("?{}", params_vec.len() + i + 1))
                 .collect();
-            conditions.push(format!("t.name IN ({})", placeholders.join(", ")));
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM prompt_tags pt JOIN tags t ON pt.tag_id = t.id \
+                 WHERE pt.prompt_id = p.id AND t.name IN ({}))",
+                placeholders.join(", ")
+            ));
             for tag in &filter.tags {
                 params_vec.push(Box::new(tag.clone()));
             }
@@ -267,7 +1051,7 @@ impl Database {
 
         // Search query
         if let Some(ref search) = filter.search_query {
-            conditions.push(format!("p.content LIKE ?{}", params_vec.len() + 1));
+            conditions.push(format!("unseal(p.content) LIKE ?{}", params_vec.len() + 1));
             params_vec.push(Box::new(format!("%{}%", search)));
         }
 
@@ -287,6 +1071,42 @@ impl Database {
             params_vec.push(Box::new(status.to_string()));
         }
 
+        // Git project/branch filters
+        if let Some(ref project) = filter.project {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM prompt_git_context g WHERE g.prompt_id = p.id AND g.project = ?{})",
+                params_vec.len() + 1
+            ));
+            params_vec.push(Box::new(project.clone()));
+        }
+        if let Some(ref branch) = filter.branch {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM prompt_git_context g WHERE g.prompt_id = p.id AND g.branch = ?{})",
+                params_vec.len() + 1
+            ));
+            params_vec.push(Box::new(branch.clone()));
+        }
+
+        // Template filter
+        if let Some(ref template) = filter.template {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM prompt_templates pt2 WHERE pt2.prompt_id = p.id AND pt2.template_name = ?{})",
+                params_vec.len() + 1
+            ));
+            params_vec.push(Box::new(template.clone()));
+        }
+
+        // Attribute filters
+        for (key, value) in &filter.attributes {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM prompt_attributes pa WHERE pa.prompt_id = p.id AND pa.key = ?{} AND pa.value = ?{})",
+                params_vec.len() + 1,
+                params_vec.len() + 2
+            ));
+            params_vec.push(Box::new(key.clone()));
+            params_vec.push(Box::new(value.clone()));
+        }
+
         // Add WHERE clause if there are conditions
         if !conditions.is_empty() {
             query.push_str(" WHERE ");
@@ -312,13 +1132,15 @@ impl Database {
             (None, None) => {}
         }
 
-        let mut stmt = self.conn.prepare(&query).map_err(|e| {
+        let conn = self.reader();
+
+        let mut stmt = conn.prepare(&query).map_err(|e| {
             PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
         })?;
 
         let params_slice: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
 
-        let prompts: Vec<Prompt> = stmt
+        let mut prompts: Vec<Prompt> = stmt
             .query_map(params_slice.as_slice(), |row| {
                 self.row_to_prompt(row)
             })
@@ -329,15 +1151,12 @@ impl Database {
             .map_err(|e| {
                 PromptTrackingError::DatabaseError(format!("Failed to collect prompts: {}", e))
             })?;
+        drop(stmt);
+        drop(conn);
 
-        // Get tags for each prompt
-        let mut result = Vec::new();
-        for mut prompt in prompts {
-            prompt.tags = self.get_tags_for_prompt(&prompt.id)?;
-            result.push(prompt);
-        }
-
-        Ok(result)
+        // Load related data for all listed prompts at once
+        self.load_relations_batch(&mut prompts)?;
+        Ok(prompts)
     }
 
     /// Search prompts by content
@@ -351,13 +1170,13 @@ impl Database {
 
     /// Check if a prompt with the same hash exists
     pub fn find_by_hash(&self, hash: &str) -> Result<Option<Prompt>> {
-        let mut stmt = self
-            .conn
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
                 r#"
-            SELECT id, content, content_hash, category, status, created_at, updated_at,
-                   model, input_tokens, output_tokens, execution_time_ms, estimated_cost, context
-            FROM prompts WHERE content_hash = ?1
+            SELECT id, unseal(content), content_hash, category, status, created_at, updated_at,
+                   model, input_tokens, output_tokens, execution_time_ms, estimated_cost, unseal(context)
+            FROM prompts WHERE content_hash = ?1 AND deleted_at IS NULL
             "#,
             )
             .map_err(|e| {
@@ -370,9 +1189,11 @@ impl Database {
             .map_err(|e| {
                 PromptTrackingError::DatabaseError(format!("Failed to find by hash: {}", e))
             })?;
+        drop(stmt);
+        drop(conn);
 
         if let Some(mut p) = prompt {
-            p.tags = self.get_tags_for_prompt(&p.id)?;
+            self.load_prompt_relations(&mut p)?;
             Ok(Some(p))
         } else {
             Ok(None)
@@ -382,8 +1203,12 @@ impl Database {
     /// Get total number of prompts
     pub fn count_prompts(&self) -> Result<usize> {
         let count: i64 = self
-            .conn
-            .query_row("SELECT COUNT(*) FROM prompts", [], |row| row.get(0))
+            .reader()
+            .query_row(
+                "SELECT COUNT(*) FROM prompts WHERE deleted_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
             .map_err(|e| {
                 PromptTrackingError::DatabaseError(format!("Failed to count prompts: {}", e))
             })?;
@@ -394,9 +1219,10 @@ impl Database {
     /// Archive a prompt
     pub fn archive_prompt(&self, id: &str) -> Result<()> {
         let rows = self
-            .conn
+            .conn()
             .execute(
-                "UPDATE prompts SET status = 'archived', updated_at = ?2 WHERE id = ?1",
+                "UPDATE prompts SET status = 'archived', updated_at = ?2 \
+                 WHERE id = ?1 AND deleted_at IS NULL",
                 params![id, Utc::now().to_rfc3339()],
             )
             .map_err(|e| {
@@ -407,15 +1233,19 @@ impl Database {
             return Err(PromptTrackingError::DatabaseError(format!("Prompt not found: {}", id)));
         }
 
+        self.emit(&Event::Archived {
+            prompt_id: id.to_string(),
+        });
         Ok(())
     }
 
     /// Unarchive a prompt (set status back to active)
     pub fn unarchive_prompt(&self, id: &str) -> Result<()> {
         let rows = self
-            .conn
+            .conn()
             .execute(
-                "UPDATE prompts SET status = 'active', updated_at = ?2 WHERE id = ?1",
+                "UPDATE prompts SET status = 'active', updated_at = ?2 \
+                 WHERE id = ?1 AND deleted_at IS NULL",
                 params![id, Utc::now().to_rfc3339()],
             )
             .map_err(|e| {
@@ -426,12 +1256,17 @@ impl Database {
             return Err(PromptTrackingError::DatabaseError(format!("Prompt not found: {}", id)));
         }
 
+        if !self.events.is_empty() {
+            if let Some(prompt) = self.get_prompt(id)? {
+                self.emit(&Event::Updated { prompt });
+            }
+        }
         Ok(())
     }
 
     /// Save quality score for a prompt
     pub fn save_quality_score(&self, score: &QualityScore) -> Result<()> {
-        self.conn
+        self.conn()
             .execute(
                 r#"
             INSERT INTO quality_scores (
@@ -452,13 +1287,17 @@ impl Database {
                 PromptTrackingError::DatabaseError(format!("Failed to save quality score: {}", e))
             })?;
 
+        self.emit(&Event::Analyzed {
+            prompt_id: score.prompt_id.clone(),
+            score: score.clone(),
+        });
         Ok(())
     }
 
     /// Get latest quality score for a prompt
     pub fn get_quality_score(&self, prompt_id: &str) -> Result<Option<QualityScore>> {
-        let mut stmt = self
-            .conn
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
                 r#"
             SELECT prompt_id, total_score, clarity, completeness, specificity, guidance, analyzed_at
@@ -470,28 +1309,32 @@ impl Database {
                 PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
             })?;
 
-        stmt.query_row(params![prompt_id], |row| {
-            Ok(QualityScore {
-                prompt_id: row.get(0)?,
-                total_score: row.get(1)?,
-                clarity: row.get(2)?,
-                completeness: row.get(3)?,
-                specificity: row.get(4)?,
-                guidance: row.get(5)?,
-                analyzed_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now()),
+        let score = stmt
+            .query_row(params![prompt_id], |row| {
+                Ok(QualityScore {
+                    prompt_id: row.get(0)?,
+                    total_score: row.get(1)?,
+                    clarity: row.get(2)?,
+                    completeness: row.get(3)?,
+                    specificity: row.get(4)?,
+                    guidance: row.get(5)?,
+                    analyzed_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
+                        .map_or_else(|_| chrono::Utc::now(), |dt| dt.with_timezone(&chrono::Utc)),
+                })
             })
-        })
-        .optional()
-        .map_err(|e| {
-            PromptTrackingError::DatabaseError(format!("Failed to get quality score: {}", e))
-        })
-    }
-
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get quality score: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(score)
+    }
+
     /// Save efficiency metrics for a prompt
     pub fn save_efficiency_metrics(&self, metrics: &EfficiencyMetrics) -> Result<()> {
-        self.conn
+        self.conn()
             .execute(
                 r#"
             INSERT INTO efficiency_metrics (
@@ -519,8 +1362,8 @@ impl Database {
 
     /// Get latest efficiency metrics for a prompt
     pub fn get_efficiency_metrics(&self, prompt_id: &str) -> Result<Option<EfficiencyMetrics>> {
-        let mut stmt = self
-            .conn
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
                 r#"
             SELECT prompt_id, efficiency_score, token_efficiency, time_efficiency, cost_efficiency, calculated_at
@@ -532,32 +1375,108 @@ impl Database {
                 PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
             })?;
 
-        stmt.query_row(params![prompt_id], |row| {
-            Ok(EfficiencyMetrics {
-                prompt_id: row.get(0)?,
-                efficiency_score: row.get(1)?,
-                token_efficiency: row.get(2)?,
-                time_efficiency: row.get(3)?,
-                cost_efficiency: row.get(4)?,
-                calculated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now()),
+        let metrics = stmt
+            .query_row(params![prompt_id], |row| {
+                Ok(EfficiencyMetrics {
+                    prompt_id: row.get(0)?,
+                    efficiency_score: row.get(1)?,
+                    token_efficiency: row.get(2)?,
+                    time_efficiency: row.get(3)?,
+                    cost_efficiency: row.get(4)?,
+                    calculated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                        .map_or_else(|_| chrono::Utc::now(), |dt| dt.with_timezone(&chrono::Utc)),
+                })
             })
-        })
-        .optional()
-        .map_err(|e| {
-            PromptTrackingError::DatabaseError(format!("Failed to get efficiency metrics: {}", e))
-        })
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get efficiency metrics: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(metrics)
+    }
+
+    /// Get the latest quality score of each listed prompt that has one
+    ///
+    /// Loads scores for many prompts in a few queries instead of one per
+    /// prompt; the result is keyed by prompt ID.
+    pub fn get_latest_quality_scores(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, QualityScore>> {
+        // Rows come oldest first, so the latest score for a prompt wins
+        let rows = self.query_by_ids(
+            r"
+            SELECT prompt_id, total_score, clarity, completeness, specificity, guidance, analyzed_at
+            FROM quality_scores WHERE prompt_id IN (:ids)
+            ORDER BY analyzed_at, id
+            ",
+            prompt_ids,
+            |row| {
+                Ok(QualityScore {
+                    prompt_id: row.get(0)?,
+                    total_score: row.get(1)?,
+                    clarity: row.get(2)?,
+                    completeness: row.get(3)?,
+                    specificity: row.get(4)?,
+                    guidance: row.get(5)?,
+                    analyzed_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
+                        .map_or_else(|_| chrono::Utc::now(), |dt| dt.with_timezone(&chrono::Utc)),
+                })
+            },
+        )?;
+
+        Ok(rows
+            .into_iter()
+            .map(|score| (score.prompt_id.clone(), score))
+            .collect())
+    }
+
+    /// Get the latest efficiency metrics of each listed prompt that has them
+    ///
+    /// Loads metrics for many prompts in a few queries instead of one per
+    /// prompt; the result is keyed by prompt ID.
+    pub fn get_latest_efficiency_metrics(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, EfficiencyMetrics>> {
+        let rows = self.query_by_ids(
+            r"
+            SELECT prompt_id, efficiency_score, token_efficiency, time_efficiency, cost_efficiency, calculated_at
+            FROM efficiency_metrics WHERE prompt_id IN (:ids)
+            ORDER BY calculated_at, id
+            ",
+            prompt_ids,
+            |row| {
+                Ok(EfficiencyMetrics {
+                    prompt_id: row.get(0)?,
+                    efficiency_score: row.get(1)?,
+                    token_efficiency: row.get(2)?,
+                    time_efficiency: row.get(3)?,
+                    cost_efficiency: row.get(4)?,
+                    calculated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                        .map_or_else(|_| chrono::Utc::now(), |dt| dt.with_timezone(&chrono::Utc)),
+                })
+            },
+        )?;
+
+        Ok(rows
+            .into_iter()
+            .map(|metrics| (metrics.prompt_id.clone(), metrics))
+            .collect())
     }
 
     /// Get all quality scores for statistics
     pub fn get_all_quality_scores(&self) -> Result<Vec<QualityScore>> {
-        let mut stmt = self
-            .conn
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
                 r#"
             SELECT prompt_id, total_score, clarity, completeness, specificity, guidance, analyzed_at
-            FROM quality_scores ORDER BY analyzed_at DESC
+            FROM quality_scores
+            WHERE prompt_id NOT IN (SELECT id FROM prompts WHERE deleted_at IS NOT NULL)
+            ORDER BY analyzed_at DESC
             "#,
             )
             .map_err(|e| {
@@ -585,326 +1504,2511 @@ impl Database {
             .map_err(|e| {
                 PromptTrackingError::DatabaseError(format!("Failed to collect scores: {}", e))
             })?;
+        drop(stmt);
+        drop(conn);
 
         Ok(scores)
     }
 
-    // Helper methods
+    // Attribute Methods
 
-    fn row_to_prompt(&self, row: &rusqlite::Row) -> SqliteResult<Prompt> {
-        let status_str: String = row.get::<_, Option<String>>(4)?.unwrap_or_else(|| "active".to_string());
-        Ok(Prompt {
-            id: row.get(0)?,
-            content: row.get(1)?,
-            content_hash: row.get(2)?,
-            category: row.get(3)?,
-            status: status_str.parse().unwrap_or(PromptStatus::Active),
-            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|_| chrono::Utc::now()),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|_| chrono::Utc::now()),
-            tags: Vec::new(), // Will be populated separately
-            metadata: PromptMetadata {
-                model: row.get(7)?,
-                input_tokens: row.get(8)?,
-                output_tokens: row.get(9)?,
-                execution_time_ms: row.get::<_, Option<i64>>(10)?.map(|v| v as u64),
-                estimated_cost: row.get(11)?,
-                context: row.get(12)?,
-            },
-        })
+    /// Set a custom attribute on a prompt, replacing any existing value for the key
+    pub fn set_attribute(&self, prompt_id: &str, key: &str, value: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "INSERT OR REPLACE INTO prompt_attributes (prompt_id, key, value) VALUES (?1, ?2, ?3)",
+                params![prompt_id, key, value],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to set attribute: {e}"))
+            })?;
+
+        Ok(())
     }
 
-    fn add_tag_to_prompt(&self, prompt_id: &str, tag: &str) -> Result<()> {
-        // Insert tag if it doesn't exist
-        self.conn
+    /// Remove a custom attribute from a prompt
+    ///
+    /// Returns `true` if the attribute existed.
+    pub fn remove_attribute(&self, prompt_id: &str, key: &str) -> Result<bool> {
+        let rows = self
+            .conn()
             .execute(
-                "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
-                params![tag],
+                "DELETE FROM prompt_attributes WHERE prompt_id = ?1 AND key = ?2",
+                params![prompt_id, key],
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to insert tag: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to remove attribute: {e}"))
             })?;
 
-        // Get tag ID
-        let tag_id: i64 = self
-            .conn
-            .query_row("SELECT id FROM tags WHERE name = ?1", params![tag], |row| {
-                row.get(0)
-            })
+        Ok(rows > 0)
+    }
+
+    /// Get all custom attributes of a prompt
+    pub fn get_attributes(&self, prompt_id: &str) -> Result<BTreeMap<String, String>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare("SELECT key, value FROM prompt_attributes WHERE prompt_id = ?1")
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get tag ID: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
             })?;
 
-        // Link tag to prompt
-        self.conn
-            .execute(
-                "INSERT OR IGNORE INTO prompt_tags (prompt_id, tag_id) VALUES (?1, ?2)",
-                params![prompt_id, tag_id],
-            )
+        let attributes = stmt
+            .query_map(params![prompt_id], |row| Ok((row.get(0)?, row.get(1)?)))
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to link tag to prompt: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to get attributes: {e}"))
+            })?
+            .collect::<SqliteResult<BTreeMap<String, String>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect attributes: {e}"))
             })?;
+        drop(stmt);
+        drop(conn);
 
-        Ok(())
+        Ok(attributes)
     }
 
-    fn get_tags_for_prompt(&self, prompt_id: &str) -> Result<Vec<String>> {
-        let mut stmt = self
-            .conn
+    // Redaction Methods
+
+    /// Record redaction findings for a prompt
+    pub fn log_redaction_events(
+        &self,
+        prompt_id: &str,
+        findings: &[RedactionFinding],
+        source: &str,
+    ) -> Result<()> {
+        self.transaction(|tx| {
+            let now = Utc::now().to_rfc3339();
+            findings
+                .iter()
+                .try_for_each(|finding| {
+                    tx.conn()
+                        .execute(
+                            r"
+                            INSERT INTO redaction_events (prompt_id, rule, action, match_count, source, created_at)
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                            ",
+                            params![
+                                prompt_id,
+                                finding.rule,
+                                finding.action.to_string(),
+                                finding.count,
+                                source,
+                                now,
+                            ],
+                        )
+                        .map(drop)
+                })
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!(
+                        "Failed to log redaction event: {e}"
+                    ))
+                })
+        })
+    }
+
+    /// Get logged redaction events, optionally for a single prompt
+    pub fn get_redaction_events(&self, prompt_id: Option<&str>) -> Result<Vec<RedactionEvent>> {
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
-                r#"
-            SELECT t.name FROM tags t
-            JOIN prompt_tags pt ON t.id = pt.tag_id
-            WHERE pt.prompt_id = ?1
-            "#,
+                r"
+                SELECT id, prompt_id, rule, action, match_count, source, created_at
+                FROM redaction_events
+                WHERE ?1 IS NULL OR prompt_id = ?1
+                ORDER BY id DESC
+                ",
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
             })?;
 
-        let tags = stmt
-            .query_map(params![prompt_id], |row| row.get(0))
+        let events = stmt
+            .query_map(params![prompt_id], |row| {
+                Ok(RedactionEvent {
+                    id: row.get(0)?,
+                    prompt_id: row.get(1)?,
+                    rule: row.get(2)?,
+                    action: row.get(3)?,
+                    match_count: row.get(4)?,
+                    source: row.get(5)?,
+                    created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
+                        .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc)),
+                })
+            })
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get tags: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to get redaction events: {e}"))
             })?
-            .collect::<SqliteResult<Vec<String>>>()
+            .collect::<SqliteResult<Vec<RedactionEvent>>>()
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to collect tags: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to collect events: {e}"))
             })?;
+        drop(stmt);
+        drop(conn);
 
-        Ok(tags)
+        Ok(events)
     }
 
-    fn remove_all_tags_from_prompt(&self, prompt_id: &str) -> Result<()> {
-        self.conn
+    // Template Methods
+
+    /// Create a new template
+    ///
+    /// Fails if a template with the same name already exists.
+    pub fn create_template(&self, template: &PromptTemplate) -> Result<()> {
+        self.conn()
             .execute(
-                "DELETE FROM prompt_tags WHERE prompt_id = ?1",
-                params![prompt_id],
+                r"
+                INSERT INTO templates (id, name, content, description, created_at, updated_at)
+                VALUES (?1, ?2, seal(?3), ?4, ?5, ?6)
+                ",
+                params![
+                    template.id,
+                    template.name,
+                    template.content,
+                    template.description,
+                    template.created_at.to_rfc3339(),
+                    template.updated_at.to_rfc3339(),
+                ],
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to remove tags: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to create template: {e}"))
             })?;
 
         Ok(())
     }
 
-    // Version History Methods
-
-    /// Save current prompt state to version history
-    pub fn save_version(&self, prompt: &Prompt) -> Result<()> {
-        // Get current version number
-        let version: i32 = self
-            .conn
-            .query_row(
-                "SELECT COALESCE(MAX(version), 0) + 1 FROM version_history WHERE prompt_id = ?1",
-                params![prompt.id],
-                |row| row.get(0),
-            )
-            .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get version: {}", e))
-            })?;
-
-        self.conn
+    /// Update an existing template's name, content and description
+    pub fn update_template(&self, template: &PromptTemplate) -> Result<()> {
+        self.conn()
             .execute(
-                r#"
-                INSERT INTO version_history (prompt_id, content, content_hash, version, created_at)
-                VALUES (?1, ?2, ?3, ?4, ?5)
-                "#,
+                r"
+                UPDATE templates SET name = ?2, content = seal(?3), description = ?4, updated_at = ?5
+                WHERE id = ?1
+                ",
                 params![
-                    prompt.id,
-                    prompt.content,
-                    prompt.content_hash,
-                    version,
+                    template.id,
+                    template.name,
+                    template.content,
+                    template.description,
                     Utc::now().to_rfc3339(),
                 ],
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to save version: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to update template: {e}"))
             })?;
 
         Ok(())
     }
 
-    /// Get version history for a prompt
-    pub fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>> {
-        let mut stmt = self
-            .conn
+    /// Get a template by name or ID
+    pub fn get_template(&self, name_or_id: &str) -> Result<Option<PromptTemplate>> {
+        self.reader()
+            .query_row(
+                r"
+                SELECT id, name, unseal(content), description, created_at, updated_at
+                FROM templates WHERE name = ?1 OR id = ?1
+                ",
+                params![name_or_id],
+                Self::row_to_template,
+            )
+            .optional()
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to get template: {e}")))
+    }
+
+    /// List all templates ordered by name
+    pub fn list_templates(&self) -> Result<Vec<PromptTemplate>> {
+        let conn = self.reader();
+        let mut stmt = conn
             .prepare(
-                r#"
-                SELECT id, prompt_id, content, content_hash, version, created_at
-                FROM version_history
-                WHERE prompt_id = ?1
-                ORDER BY version DESC
-                "#,
+                r"
+                SELECT id, name, unseal(content), description, created_at, updated_at
+                FROM templates ORDER BY name
+                ",
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
             })?;
 
-        let history = stmt
-            .query_map(params![prompt_id], |row| {
-                Ok(VersionHistory {
-                    id: row.get(0)?,
-                    prompt_id: row.get(1)?,
-                    content: row.get(2)?,
-                    content_hash: row.get(3)?,
-                    version: row.get(4)?,
-                    created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                })
-            })
+        let templates = stmt
+            .query_map([], Self::row_to_template)
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get history: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to list templates: {e}"))
             })?
-            .collect::<SqliteResult<Vec<VersionHistory>>>()
+            .collect::<SqliteResult<Vec<PromptTemplate>>>()
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to collect history: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to collect templates: {e}"))
             })?;
+        drop(stmt);
+        drop(conn);
 
-        Ok(history)
+        Ok(templates)
     }
 
-    /// Restore prompt to specific version
-    pub fn restore_version(&self, prompt_id: &str, version: i32) -> Result<Prompt> {
-        let history: VersionHistory = self
-            .conn
-            .query_row(
-                r#"
-                SELECT id, prompt_id, content, content_hash, version, created_at
-                FROM version_history
-                WHERE prompt_id = ?1 AND version = ?2
-                "#,
-                params![prompt_id, version],
-                |row| {
-                    Ok(VersionHistory {
-                        id: row.get(0)?,
-                        prompt_id: row.get(1)?,
-                        content: row.get(2)?,
-                        content_hash: row.get(3)?,
-                        version: row.get(4)?,
-                        created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
-                            .map(|dt| dt.with_timezone(&Utc))
-                            .unwrap_or_else(|_| Utc::now()),
-                    })
-                },
+    /// Delete a template by name or ID
+    ///
+    /// Prompts rendered from it keep their lineage record, including the
+    /// template name. Returns `false` if no template matched.
+    pub fn delete_template(&self, name_or_id: &str) -> Result<bool> {
+        let deleted = self
+            .conn()
+            .execute(
+                "DELETE FROM templates WHERE name = ?1 OR id = ?1",
+                params![name_or_id],
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Version not found: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to delete template: {e}"))
             })?;
 
-        // Get current prompt and update it
-        let mut prompt = self
-            .get_prompt(prompt_id)?
-            .ok_or_else(|| PromptTrackingError::DatabaseError("Prompt not found".to_string()))?;
-
-        // Save current state before restore
-        self.save_version(&prompt)?;
-
-        // Restore content
-        prompt.content = history.content;
-        prompt.content_hash = history.content_hash;
-        prompt.updated_at = Utc::now();
-
-        self.update_prompt(&prompt)?;
-
-        Ok(prompt)
+        Ok(deleted > 0)
     }
 
-    // Trend Analysis Methods
-
-    /// Get daily trend data
-    pub fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>> {
-        let mut stmt = self
-            .conn
-            .prepare(
-                r#"
-                SELECT
-                    date(p.created_at) as date,
-                    COUNT(*) as count,
-                    COALESCE(AVG(q.total_score), 0) as avg_quality,
-                    COALESCE(AVG(e.efficiency_score), 0) as avg_efficiency
-                FROM prompts p
-                LEFT JOIN quality_scores q ON p.id = q.prompt_id
-                LEFT JOIN efficiency_metrics e ON p.id = e.prompt_id
-                WHERE p.created_at >= date('now', ? || ' days')
-                GROUP BY date(p.created_at)
-                ORDER BY date(p.created_at)
-                "#,
+    /// Count prompts rendered from a template
+    pub fn count_template_prompts(&self, template_id: &str) -> Result<usize> {
+        self.reader()
+            .query_row(
+                "SELECT COUNT(*) FROM prompt_templates pt \
+                 JOIN prompts p ON p.id = pt.prompt_id \
+                 WHERE pt.template_id = ?1 AND p.deleted_at IS NULL",
+                params![template_id],
+                |row| row.get(0),
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
-            })?;
-
-        let trends = stmt
-            .query_map(params![format!("-{}", days)], |row| {
-                Ok(TrendDataPoint {
-                    date: row.get(0)?,
-                    count: row.get::<_, i64>(1)? as usize,
-                    avg_quality: row.get(2)?,
-                    avg_efficiency: row.get(3)?,
-                })
+                PromptTrackingError::DatabaseError(format!("Failed to count prompts: {e}"))
             })
+    }
+
+    /// Get the template lineage recorded for a prompt
+    pub fn get_template_usage(&self, prompt_id: &str) -> Result<Option<TemplateUsage>> {
+        let row = self
+            .reader()
+            .query_row(
+                r"
+                SELECT template_id, template_name, variables
+                FROM prompt_templates WHERE prompt_id = ?1
+                ",
+                params![prompt_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get trends: {}", e))
-            })?
-            .collect::<SqliteResult<Vec<TrendDataPoint>>>()
-            .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to collect trends: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to get template usage: {e}"))
             })?;
 
-        Ok(trends)
+        row.map(|(template_id, template_name, variables)| {
+            let variables = serde_json::from_str(&variables).map_err(|e| {
+                PromptTrackingError::SerializationError(format!("Invalid template variables: {e}"))
+            })?;
+            Ok(TemplateUsage {
+                template_id,
+                template_name,
+                variables,
+            })
+        })
+        .transpose()
     }
 
-    /// Get category distribution
-    pub fn get_category_distribution(&self) -> Result<Vec<(String, usize)>> {
-        let mut stmt = self
-            .conn
-            .prepare(
-                r#"
-                SELECT COALESCE(category, 'uncategorized') as cat, COUNT(*) as count
-                FROM prompts
-                GROUP BY category
-                ORDER BY count DESC
-                "#,
+    fn save_template_usage(&self, prompt_id: &str, usage: &TemplateUsage) -> Result<()> {
+        let variables = serde_json::to_string(&usage.variables).map_err(|e| {
+            PromptTrackingError::SerializationError(format!("Invalid template variables: {e}"))
+        })?;
+
+        self.conn()
+            .execute(
+                r"
+                INSERT OR REPLACE INTO prompt_templates (prompt_id, template_id, template_name, variables)
+                VALUES (?1, ?2, ?3, ?4)
+                ",
+                params![prompt_id, usage.template_id, usage.template_name, variables],
             )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to save template usage: {e}"))
             })?;
 
-        let distribution = stmt
-            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))
-            .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get distribution: {}", e))
-            })?
-            .collect::<SqliteResult<Vec<(String, usize)>>>()
+        Ok(())
+    }
+
+    fn remove_template_usage(&self, prompt_id: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "DELETE FROM prompt_templates WHERE prompt_id = ?1",
+                params![prompt_id],
+            )
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to collect: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to remove template usage: {e}"))
             })?;
 
-        Ok(distribution)
+        Ok(())
     }
 
-    // Export/Import Methods
+    fn row_to_template(row: &rusqlite::Row) -> SqliteResult<PromptTemplate> {
+        Ok(PromptTemplate {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            content: row.get(2)?,
+            description: row.get(3)?,
+            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?)
+                .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc)),
+            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc)),
+        })
+    }
+
+    // Watched File Methods
+
+    /// Get the prompt mapping for a watched file path
+    pub fn get_watched_file(&self, path: &str) -> Result<Option<WatchedFile>> {
+        self.reader()
+            .query_row(
+                "SELECT path, prompt_id, content_hash, updated_at FROM watched_files WHERE path = ?1",
+                params![path],
+                |row| {
+                    Ok(WatchedFile {
+                        path: row.get(0)?,
+                        prompt_id: row.get(1)?,
+                        content_hash: row.get(2)?,
+                        updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(3)?).map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc)),
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get watched file: {e}"))
+            })
+    }
+
+    /// Record which prompt a watched file was captured as
+    pub fn set_watched_file(&self, path: &str, prompt_id: &str, content_hash: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                r"
+                INSERT OR REPLACE INTO watched_files (path, prompt_id, content_hash, updated_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ",
+                params![path, prompt_id, content_hash, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to save watched file: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Forget the prompt mapping and checkpoint of a file that left its watch root
+    pub fn forget_watched_file(&self, path: &str) -> Result<()> {
+        self.transaction(|tx| {
+            ["watched_files", "watch_checkpoints"]
+                .iter()
+                .try_for_each(|table| {
+                    tx.conn()
+                        .execute(&format!("DELETE FROM {table} WHERE path = ?1"), params![path])
+                        .map(drop)
+                })
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!(
+                        "Failed to forget watched file: {e}"
+                    ))
+                })
+        })
+    }
+
+    /// Get the checkpoint recorded for a watched file
+    pub fn get_watch_checkpoint(&self, path: &str) -> Result<Option<WatchCheckpoint>> {
+        self.reader()
+            .query_row(
+                "SELECT path, size, mtime_ns, file_hash FROM watch_checkpoints WHERE path = ?1",
+                params![path],
+                |row| {
+                    Ok(WatchCheckpoint {
+                        path: row.get(0)?,
+                        size: row.get(1)?,
+                        mtime_ns: row.get(2)?,
+                        file_hash: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get checkpoint: {e}"))
+            })
+    }
+
+    /// Record that a watched file has been processed
+    pub fn set_watch_checkpoint(&self, checkpoint: &WatchCheckpoint) -> Result<()> {
+        self.conn()
+            .execute(
+                r"
+                INSERT OR REPLACE INTO watch_checkpoints (path, size, mtime_ns, file_hash, processed_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ",
+                params![
+                    checkpoint.path,
+                    checkpoint.size,
+                    checkpoint.mtime_ns,
+                    checkpoint.file_hash,
+                    Utc::now().to_rfc3339(),
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to save checkpoint: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Get the read offset recorded for a tailed log
+    pub fn get_log_offset(&self, path: &str) -> Result<Option<LogOffset>> {
+        self.reader()
+            .query_row(
+                "SELECT path, byte_offset, head_hash FROM log_offsets WHERE path = ?1",
+                params![path],
+                |row| {
+                    Ok(LogOffset {
+                        path: row.get(0)?,
+                        offset: row.get(1)?,
+                        head_hash: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get log offset: {e}"))
+            })
+    }
+
+    /// Record how far a tailed log has been read
+    pub fn set_log_offset(&self, offset: &LogOffset) -> Result<()> {
+        self.conn()
+            .execute(
+                r"
+                INSERT OR REPLACE INTO log_offsets (path, byte_offset, head_hash, updated_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ",
+                params![
+                    offset.path,
+                    offset.offset,
+                    offset.head_hash,
+                    Utc::now().to_rfc3339(),
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to save log offset: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    // Capture Failure Methods
+
+    /// Record a failed capture, incrementing the attempt count for a known path
+    ///
+    /// A new failure also brings back a previously dismissed entry.
+    pub fn record_capture_failure(
+        &self,
+        path: &str,
+        error_kind: &str,
+        message: &str,
+        next_retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn()
+            .execute(
+                r"
+                INSERT INTO capture_failures (path, error_kind, message, first_seen, last_seen, attempts, next_retry_at, dismissed)
+                VALUES (?1, ?2, ?3, ?4, ?4, 1, ?5, 0)
+                ON CONFLICT(path) DO UPDATE SET
+                    error_kind = excluded.error_kind,
+                    message = excluded.message,
+                    last_seen = excluded.last_seen,
+                    attempts = attempts + 1,
+                    next_retry_at = excluded.next_retry_at,
+                    dismissed = 0
+                ",
+                params![
+                    path,
+                    error_kind,
+                    message,
+                    now,
+                    next_retry_at.map(|t| t.to_rfc3339())
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to record capture failure: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Get a failed capture by ID
+    pub fn get_capture_failure(&self, id: i64) -> Result<Option<CaptureFailure>> {
+        self.query_capture_failure("id = ?1", params![id])
+    }
+
+    /// Get the failed capture recorded for a path
+    pub fn find_capture_failure(&self, path: &str) -> Result<Option<CaptureFailure>> {
+        self.query_capture_failure("path = ?1", params![path])
+    }
+
+    fn query_capture_failure(
+        &self,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Option<CaptureFailure>> {
+        self.reader()
+            .query_row(
+                &format!(
+                    "SELECT {CAPTURE_FAILURE_COLUMNS} FROM capture_failures WHERE {condition}"
+                ),
+                params,
+                Self::row_to_capture_failure,
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get capture failure: {e}"))
+            })
+    }
+
+    /// List failed captures, most recent first
+    pub fn list_capture_failures(&self, include_dismissed: bool) -> Result<Vec<CaptureFailure>> {
+        let sql = format!(
+            "SELECT {} FROM capture_failures {} ORDER BY last_seen DESC",
+            CAPTURE_FAILURE_COLUMNS,
+            if include_dismissed {
+                ""
+            } else {
+                "WHERE dismissed = 0"
+            }
+        );
+        self.collect_capture_failures(&sql, params![])
+    }
+
+    /// Failed captures whose retry time has passed
+    pub fn due_capture_failures(&self, now: DateTime<Utc>) -> Result<Vec<CaptureFailure>> {
+        let sql = format!(
+            r"
+            SELECT {CAPTURE_FAILURE_COLUMNS} FROM capture_failures
+            WHERE dismissed = 0 AND next_retry_at IS NOT NULL AND next_retry_at <= ?1
+            ORDER BY next_retry_at
+            "
+        );
+        self.collect_capture_failures(&sql, params![now.to_rfc3339()])
+    }
+
+    /// Earliest scheduled automatic retry, if any
+    pub fn next_capture_retry(&self) -> Result<Option<DateTime<Utc>>> {
+        let next: Option<String> = self
+            .reader()
+            .query_row(
+                "SELECT MIN(next_retry_at) FROM capture_failures WHERE dismissed = 0",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get next retry: {e}"))
+            })?;
+
+        Ok(next.and_then(|t| {
+            chrono::DateTime::parse_from_rfc3339(&t)
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok()
+        }))
+    }
+
+    /// Hide a failed capture and stop retrying it
+    pub fn dismiss_capture_failure(&self, id: i64) -> Result<bool> {
+        let updated = self
+            .conn()
+            .execute(
+                "UPDATE capture_failures SET dismissed = 1, next_retry_at = NULL WHERE id = ?1",
+                params![id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!(
+                    "Failed to dismiss capture failure: {e}"
+                ))
+            })?;
+
+        Ok(updated > 0)
+    }
+
+    /// Remove the failure recorded for a path once it has been captured
+    pub fn clear_capture_failure(&self, path: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "DELETE FROM capture_failures WHERE path = ?1",
+                params![path],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to clear capture failure: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    fn collect_capture_failures(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<CaptureFailure>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(sql).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+        })?;
+
+        let failures = stmt
+            .query_map(params, Self::row_to_capture_failure)
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to list capture failures: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<CaptureFailure>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!(
+                    "Failed to collect capture failures: {e}"
+                ))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(failures)
+    }
+
+    fn row_to_capture_failure(row: &rusqlite::Row) -> SqliteResult<CaptureFailure> {
+        let parse = |s: String| {
+            chrono::DateTime::parse_from_rfc3339(&s)
+                .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc))
+        };
+        Ok(CaptureFailure {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            error_kind: row.get(2)?,
+            message: row.get(3)?,
+            first_seen: parse(row.get(4)?),
+            last_seen: parse(row.get(5)?),
+            attempts: row.get(6)?,
+            next_retry_at: row.get::<_, Option<String>>(7)?.map(parse),
+            dismissed: row.get(8)?,
+        })
+    }
+
+    // File Action Methods
+
+    /// Log a post-capture action and return its ID
+    pub fn record_file_action(
+        &self,
+        prompt_id: &str,
+        action: PostCaptureAction,
+        source: &str,
+        destination: Option<&str>,
+        content: Option<&[u8]>,
+    ) -> Result<i64> {
+        let conn = self.conn();
+        conn
+            .execute(
+                r"
+                INSERT INTO file_actions (prompt_id, action, source, destination, content, performed_at)
+                VALUES (?1, ?2, ?3, ?4, seal(?5), ?6)
+                ",
+                params![
+                    prompt_id,
+                    action.to_string(),
+                    source,
+                    destination,
+                    content,
+                    Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to record file action: {e}"))
+            })?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Get a logged file action by ID
+    pub fn get_file_action(&self, id: i64) -> Result<Option<FileAction>> {
+        self.reader()
+            .query_row(
+                &format!("SELECT {FILE_ACTION_COLUMNS} FROM file_actions WHERE id = ?1"),
+                params![id],
+                Self::row_to_file_action,
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get file action: {e}"))
+            })
+    }
+
+    /// List logged file actions, most recent first
+    pub fn list_file_actions(&self, limit: usize) -> Result<Vec<FileAction>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {FILE_ACTION_COLUMNS} FROM file_actions ORDER BY id DESC LIMIT ?1"
+            ))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+
+        let actions = stmt
+            .query_map(params![limit], Self::row_to_file_action)
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to list file actions: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<FileAction>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect file actions: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(actions)
+    }
+
+    /// Mark a file action as undone
+    pub fn mark_file_action_undone(&self, id: i64) -> Result<()> {
+        self.conn()
+            .execute(
+                "UPDATE file_actions SET undone_at = ?1 WHERE id = ?2",
+                params![Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to update file action: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    fn row_to_file_action(row: &rusqlite::Row) -> SqliteResult<FileAction> {
+        let parse = |s: String| {
+            chrono::DateTime::parse_from_rfc3339(&s)
+                .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc))
+        };
+        Ok(FileAction {
+            id: row.get(0)?,
+            prompt_id: row.get(1)?,
+            action: row
+                .get::<_, String>(2)?
+                .parse()
+                .unwrap_or(PostCaptureAction::Leave),
+            source: row.get(3)?,
+            destination: row.get(4)?,
+            content: row.get(5)?,
+            performed_at: parse(row.get(6)?),
+            undone_at: row.get::<_, Option<String>>(7)?.map(parse),
+        })
+    }
+
+    // Webhook Outbox Methods
+
+    /// Queue an event payload for delivery to an endpoint, due immediately
+    pub fn enqueue_webhook(&self, endpoint: &str, event: &str, payload: &str) -> Result<i64> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn();
+        conn
+            .execute(
+                r"
+                INSERT INTO webhook_outbox (endpoint, event, payload, status, attempts, next_attempt_at, created_at)
+                VALUES (?1, ?2, seal(?3), 'pending', 0, ?4, ?4)
+                ",
+                params![endpoint, event, payload, now],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to queue webhook: {e}"))
+            })?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Get a webhook delivery by ID
+    pub fn get_webhook_delivery(&self, id: i64) -> Result<Option<WebhookDelivery>> {
+        self.reader()
+            .query_row(
+                &format!("SELECT {WEBHOOK_DELIVERY_COLUMNS} FROM webhook_outbox WHERE id = ?1"),
+                params![id],
+                Self::row_to_webhook_delivery,
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get webhook delivery: {e}"))
+            })
+    }
+
+    /// List webhook deliveries, most recent first, optionally by status
+    pub fn list_webhook_deliveries(
+        &self,
+        status: Option<DeliveryStatus>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let sql = format!(
+            "SELECT {WEBHOOK_DELIVERY_COLUMNS} FROM webhook_outbox WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC LIMIT ?2"
+        );
+        self.collect_webhook_deliveries(
+            &sql,
+            params![status.map(|s| s.to_string()), limit],
+        )
+    }
+
+    /// Pending deliveries whose next attempt is due
+    pub fn due_webhook_deliveries(&self, now: DateTime<Utc>) -> Result<Vec<WebhookDelivery>> {
+        let sql = format!(
+            r"
+            SELECT {WEBHOOK_DELIVERY_COLUMNS} FROM webhook_outbox
+            WHERE status = 'pending' AND next_attempt_at <= ?1
+            ORDER BY id
+            "
+        );
+        self.collect_webhook_deliveries(&sql, params![now.to_rfc3339()])
+    }
+
+    /// Claim a due delivery for one attempt by moving its next attempt to
+    /// `until`
+    ///
+    /// Returns `false` if the delivery is no longer due, because another
+    /// thread or process claimed or finished it first. A claim left by a
+    /// process that died mid-attempt expires at `until`, making the delivery
+    /// due again.
+    pub fn claim_webhook_delivery(
+        &self,
+        id: i64,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<bool> {
+        let updated = self
+            .conn()
+            .execute(
+                r"
+                UPDATE webhook_outbox SET next_attempt_at = ?3
+                WHERE id = ?1 AND status = 'pending' AND next_attempt_at <= ?2
+                ",
+                params![id, now.to_rfc3339(), until.to_rfc3339()],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to claim webhook: {e}"))
+            })?;
+
+        Ok(updated > 0)
+    }
+
+    /// Record a successful delivery
+    pub fn mark_webhook_delivered(&self, id: i64) -> Result<()> {
+        self.conn()
+            .execute(
+                r"
+                UPDATE webhook_outbox
+                SET status = 'delivered', attempts = attempts + 1, next_attempt_at = NULL,
+                    last_error = NULL, delivered_at = ?2
+                WHERE id = ?1
+                ",
+                params![id, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to update webhook: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt, retrying at `next_attempt_at` or giving up if `None`
+    pub fn mark_webhook_attempt_failed(
+        &self,
+        id: i64,
+        error: &str,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let status = if next_attempt_at.is_some() {
+            DeliveryStatus::Pending
+        } else {
+            DeliveryStatus::Failed
+        };
+        self.conn()
+            .execute(
+                r"
+                UPDATE webhook_outbox
+                SET status = ?2, attempts = attempts + 1, next_attempt_at = ?3, last_error = ?4
+                WHERE id = ?1
+                ",
+                params![
+                    id,
+                    status.to_string(),
+                    next_attempt_at.map(|t| t.to_rfc3339()),
+                    error
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to update webhook: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Make a failed delivery pending again, due immediately
+    pub fn requeue_webhook(&self, id: i64) -> Result<bool> {
+        let updated = self
+            .conn()
+            .execute(
+                r"
+                UPDATE webhook_outbox SET status = 'pending', attempts = 0, next_attempt_at = ?2
+                WHERE id = ?1 AND status = 'failed'
+                ",
+                params![id, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to requeue webhook: {e}"))
+            })?;
+
+        Ok(updated > 0)
+    }
+
+    fn collect_webhook_deliveries(
+        &self,
+        sql: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<WebhookDelivery>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(sql).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+        })?;
+
+        let deliveries = stmt
+            .query_map(params, Self::row_to_webhook_delivery)
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to list webhooks: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<WebhookDelivery>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect webhooks: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(deliveries)
+    }
+
+    fn row_to_webhook_delivery(row: &rusqlite::Row) -> SqliteResult<WebhookDelivery> {
+        let parse = |s: String| {
+            chrono::DateTime::parse_from_rfc3339(&s)
+                .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc))
+        };
+        Ok(WebhookDelivery {
+            id: row.get(0)?,
+            endpoint: row.get(1)?,
+            event: row.get(2)?,
+            payload: row.get(3)?,
+            status: row
+                .get::<_, String>(4)?
+                .parse()
+                .unwrap_or(DeliveryStatus::Pending),
+            attempts: row.get(5)?,
+            next_attempt_at: row.get::<_, Option<String>>(6)?.map(parse),
+            last_error: row.get(7)?,
+            created_at: parse(row.get(8)?),
+            delivered_at: row.get::<_, Option<String>>(9)?.map(parse),
+        })
+    }
+
+    /// Get the latest version number recorded for a prompt (0 if none)
+    pub fn latest_version(&self, prompt_id: &str) -> Result<i32> {
+        self.reader()
+            .query_row(
+                "SELECT COALESCE(MAX(version), 0) FROM version_history WHERE prompt_id = ?1",
+                params![prompt_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to get version: {e}")))
+    }
+
+    // Git Context Methods
+
+    /// Get the git context recorded for a prompt
+    pub fn get_git_context(&self, prompt_id: &str) -> Result<Option<GitContext>> {
+        self.reader()
+            .query_row(
+                r"
+                SELECT project, repo_root, remote_url, branch, commit_hash, dirty
+                FROM prompt_git_context WHERE prompt_id = ?1
+                ",
+                params![prompt_id],
+                |row| {
+                    Ok(GitContext {
+                        project: row.get(0)?,
+                        repo_root: row.get(1)?,
+                        remote_url: row.get(2)?,
+                        branch: row.get(3)?,
+                        commit: row.get(4)?,
+                        dirty: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get git context: {e}"))
+            })
+    }
+
+    fn save_git_context(&self, prompt_id: &str, git: &GitContext) -> Result<()> {
+        self.conn()
+            .execute(
+                r"
+                INSERT OR REPLACE INTO prompt_git_context (
+                    prompt_id, project, repo_root, remote_url, branch, commit_hash, dirty
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ",
+                params![
+                    prompt_id,
+                    git.project,
+                    git.repo_root,
+                    git.remote_url,
+                    git.branch,
+                    git.commit,
+                    git.dirty,
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to save git context: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    fn remove_git_context(&self, prompt_id: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "DELETE FROM prompt_git_context WHERE prompt_id = ?1",
+                params![prompt_id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to remove git context: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    // Helper methods
+
+    /// Load tags, attributes, git context and template lineage for many
+    /// prompts with a few set-based queries instead of several per prompt
+    fn load_relations_batch(&self, prompts: &mut [Prompt]) -> Result<()> {
+        if prompts.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+        let tags = self.query_by_ids(
+            r"
+            SELECT pt.prompt_id, t.name FROM prompt_tags pt
+            JOIN tags t ON t.id = pt.tag_id
+            WHERE pt.prompt_id IN (:ids)
+            ORDER BY pt.prompt_id, pt.tag_id
+            ",
+            &ids,
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+        let attributes = self.query_by_ids(
+            "SELECT prompt_id, key, value FROM prompt_attributes WHERE prompt_id IN (:ids)",
+            &ids,
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )?;
+        let git = self.query_by_ids(
+            r"
+            SELECT prompt_id, project, repo_root, remote_url, branch, commit_hash, dirty
+            FROM prompt_git_context WHERE prompt_id IN (:ids)
+            ",
+            &ids,
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    GitContext {
+                        project: row.get(1)?,
+                        repo_root: row.get(2)?,
+                        remote_url: row.get(3)?,
+                        branch: row.get(4)?,
+                        commit: row.get(5)?,
+                        dirty: row.get(6)?,
+                    },
+                ))
+            },
+        )?;
+        let templates = self.query_by_ids(
+            r"
+            SELECT prompt_id, template_id, template_name, variables
+            FROM prompt_templates WHERE prompt_id IN (:ids)
+            ",
+            &ids,
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            },
+        )?;
+
+        let index: HashMap<String, usize> = prompts
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.id.clone(), i))
+            .collect();
+        for (prompt_id, tag) in tags {
+            prompts[index[&prompt_id]].tags.push(tag);
+        }
+        for (prompt_id, key, value) in attributes {
+            prompts[index[&prompt_id]]
+                .metadata
+                .attributes
+                .insert(key, value);
+        }
+        for (prompt_id, context) in git {
+            prompts[index[&prompt_id]].metadata.git = Some(context);
+        }
+        for (prompt_id, template_id, template_name, variables) in templates {
+            let variables = serde_json::from_str(&variables).map_err(|e| {
+                PromptTrackingError::SerializationError(format!("Invalid template variables: {e}"))
+            })?;
+            prompts[index[&prompt_id]].metadata.template = Some(TemplateUsage {
+                template_id,
+                template_name,
+                variables,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Run a query for the rows of the given prompts and collect them
+    ///
+    /// `sql` selects the prompt ID as its first column and contains
+    /// `<prompt_id column> IN (:ids)` in its `WHERE` clause. IDs are bound in
+    /// `IN` lists of up to [`ID_BATCH_SIZE`], one query per batch, so every
+    /// lookup can use the prompt ID index. Rows of one prompt always come
+    /// from the same batch, keeping the order `sql` gives them.
+    fn query_by_ids<T>(
+        &self,
+        sql: &str,
+        ids: &[&str],
+        mut map: impl FnMut(&rusqlite::Row) -> SqliteResult<T>,
+    ) -> Result<Vec<T>> {
+        let conn = self.reader();
+        let mut collected = Vec::new();
+
+        for batch in ids.chunks(ID_BATCH_SIZE) {
+            let sql = sql.replace(":ids", &vec!["?"; batch.len()].join(", "));
+            let mut stmt = conn.prepare_cached(&sql).map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(batch), &mut map)
+                .and_then(Iterator::collect::<SqliteResult<Vec<_>>>)
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to load rows: {e}"))
+                })?;
+            collected.extend(rows);
+        }
+        drop(conn);
+
+        Ok(collected)
+    }
+
+    fn load_prompt_relations(&self, prompt: &mut Prompt) -> Result<()> {
+        prompt.tags = self.get_tags_for_prompt(&prompt.id)?;
+        prompt.metadata.attributes = self.get_attributes(&prompt.id)?;
+        prompt.metadata.git = self.get_git_context(&prompt.id)?;
+        prompt.metadata.template = self.get_template_usage(&prompt.id)?;
+        Ok(())
+    }
+
+    fn row_to_prompt(&self, row: &rusqlite::Row) -> SqliteResult<Prompt> {
+        let status_str: String = row.get::<_, Option<String>>(4)?.unwrap_or_else(|| "active".to_string());
+        Ok(Prompt {
+            id: row.get(0)?,
+            content: row.get(1)?,
+            content_hash: row.get(2)?,
+            category: row.get(3)?,
+            status: status_str.parse().unwrap_or(PromptStatus::Active),
+            created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+                .unwrap_or_else(|_| chrono::Utc::now()),
+            updated_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+                .unwrap_or_else(|_| chrono::Utc::now()),
+            tags: Vec::new(), // Will be populated separately
+            metadata: PromptMetadata {
+                model: row.get(7)?,
+                input_tokens: row.get(8)?,
+                output_tokens: row.get(9)?,
+                execution_time_ms: row.get::<_, Option<i64>>(10)?.map(|v| v as u64),
+                estimated_cost: row.get(11)?,
+                context: row.get(12)?,
+                attributes: BTreeMap::new(), // Will be populated separately
+                git: None,                   // Will be populated separately
+                template: None,              // Will be populated separately
+            },
+            redactions: Vec::new(),
+        })
+    }
+
+    fn add_tag_to_prompt(&self, prompt_id: &str, tag: &str) -> Result<()> {
+        // Insert tag if it doesn't exist
+        self.conn()
+            .execute(
+                "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+                params![tag],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to insert tag: {}", e))
+            })?;
+
+        // Get tag ID
+        let tag_id: i64 = self
+            .conn()
+            .query_row("SELECT id FROM tags WHERE name = ?1", params![tag], |row| {
+                row.get(0)
+            })
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get tag ID: {}", e))
+            })?;
+
+        // Link tag to prompt
+        self.conn()
+            .execute(
+                "INSERT OR IGNORE INTO prompt_tags (prompt_id, tag_id) VALUES (?1, ?2)",
+                params![prompt_id, tag_id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to link tag to prompt: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    fn get_tags_for_prompt(&self, prompt_id: &str) -> Result<Vec<String>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r#"
+            SELECT t.name FROM tags t
+            JOIN prompt_tags pt ON t.id = pt.tag_id
+            WHERE pt.prompt_id = ?1
+            ORDER BY pt.tag_id
+            "#,
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+            })?;
+
+        let tags = stmt
+            .query_map(params![prompt_id], |row| row.get(0))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get tags: {}", e))
+            })?
+            .collect::<SqliteResult<Vec<String>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect tags: {}", e))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(tags)
+    }
+
+    fn remove_all_tags_from_prompt(&self, prompt_id: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "DELETE FROM prompt_tags WHERE prompt_id = ?1",
+                params![prompt_id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to remove tags: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    fn remove_all_attributes_from_prompt(&self, prompt_id: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "DELETE FROM prompt_attributes WHERE prompt_id = ?1",
+                params![prompt_id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to remove attributes: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    // Version History Methods
+
+    /// Save current prompt state to version history
+    pub fn save_version(&self, prompt: &Prompt) -> Result<()> {
+        self.transaction(|tx| {
+            // Get current version number
+            let version: i32 = self
+                .conn()
+                .query_row(
+                    "SELECT COALESCE(MAX(version), 0) + 1 FROM version_history WHERE prompt_id = ?1",
+                    params![prompt.id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to get version: {e}"))
+                })?;
+
+            tx.conn()
+                .execute(
+                    r"
+                    INSERT INTO version_history (prompt_id, content, content_hash, version, created_at)
+                    VALUES (?1, seal(?2), ?3, ?4, ?5)
+                    ",
+                    params![
+                        prompt.id,
+                        prompt.content,
+                        prompt.content_hash,
+                        version,
+                        Utc::now().to_rfc3339(),
+                    ],
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to save version: {e}"))
+                })?;
+
+            Ok(())
+        })
+    }
+
+    /// Get version history for a prompt
+    pub fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r#"
+                SELECT id, prompt_id, unseal(content), content_hash, version, created_at
+                FROM version_history
+                WHERE prompt_id = ?1
+                ORDER BY version DESC
+                "#,
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+            })?;
+
+        let history = stmt
+            .query_map(params![prompt_id], |row| {
+                Ok(VersionHistory {
+                    id: row.get(0)?,
+                    prompt_id: row.get(1)?,
+                    content: row.get(2)?,
+                    content_hash: row.get(3)?,
+                    version: row.get(4)?,
+                    created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get history: {}", e))
+            })?
+            .collect::<SqliteResult<Vec<VersionHistory>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect history: {}", e))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(history)
+    }
+
+    /// Get version history entries of all prompts
+    pub fn get_all_versions(&self) -> Result<Vec<VersionHistory>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r"
+                SELECT id, prompt_id, unseal(content), content_hash, version, created_at
+                FROM version_history
+                ORDER BY prompt_id, version
+                ",
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+
+        let history = stmt
+            .query_map([], |row| {
+                Ok(VersionHistory {
+                    id: row.get(0)?,
+                    prompt_id: row.get(1)?,
+                    content: row.get(2)?,
+                    content_hash: row.get(3)?,
+                    version: row.get(4)?,
+                    created_at: chrono::DateTime::parse_from_rfc3339(&row.get::<_, String>(5)?)
+                        .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc)),
+                })
+            })
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to get history: {e}")))?
+            .collect::<SqliteResult<Vec<VersionHistory>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect history: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(history)
+    }
+
+    /// Overwrite the content of a version history entry
+    ///
+    /// Used to scrub secrets from stored history; not for normal edits.
+    pub fn update_version_content(&self, version_id: i64, content: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "UPDATE version_history SET content = seal(?2), content_hash = ?3 WHERE id = ?1",
+                params![version_id, content, crate::utils::calculate_hash(content)],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to update version: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Restore prompt to specific version
+    pub fn restore_version(&self, prompt_id: &str, version: i32) -> Result<Prompt> {
+        self.transaction(|tx| {
+            let history: VersionHistory = self
+                .conn()
+                .query_row(
+                    r"
+                    SELECT id, prompt_id, unseal(content), content_hash, version, created_at
+                    FROM version_history
+                    WHERE prompt_id = ?1 AND version = ?2
+                    ",
+                    params![prompt_id, version],
+                    |row| {
+                        Ok(VersionHistory {
+                            id: row.get(0)?,
+                            prompt_id: row.get(1)?,
+                            content: row.get(2)?,
+                            content_hash: row.get(3)?,
+                            version: row.get(4)?,
+                            created_at: chrono::DateTime::parse_from_rfc3339(
+                                &row.get::<_, String>(5)?,
+                            )
+                            .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc)),
+                        })
+                    },
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Version not found: {e}"))
+                })?;
+
+            // Get current prompt and update it
+            let mut prompt = self
+                .get_prompt(prompt_id)?
+                .ok_or_else(|| PromptTrackingError::DatabaseError("Prompt not found".to_string()))?;
+
+            // Save current state before restore
+            tx.save_version(&prompt)?;
+
+            // Restore content
+            prompt.content = history.content;
+            prompt.content_hash = history.content_hash;
+            prompt.updated_at = Utc::now();
+
+            tx.update_prompt(&prompt)?;
+
+            Ok(prompt)
+        })
+    }
+
+    // Trend Analysis Methods
+
+    /// Get daily trend data
+    pub fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r#"
+                SELECT
+                    date(p.created_at) as date,
+                    COUNT(*) as count,
+                    COALESCE(AVG(q.total_score), 0) as avg_quality,
+                    COALESCE(AVG(e.efficiency_score), 0) as avg_efficiency
+                FROM prompts p
+                LEFT JOIN quality_scores q ON p.id = q.prompt_id
+                LEFT JOIN efficiency_metrics e ON p.id = e.prompt_id
+                WHERE p.created_at >= date('now', ? || ' days') AND p.deleted_at IS NULL
+                GROUP BY date(p.created_at)
+                ORDER BY date(p.created_at)
+                "#,
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+            })?;
+
+        let trends = stmt
+            .query_map(params![format!("-{}", days)], |row| {
+                Ok(TrendDataPoint {
+                    date: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                    avg_quality: row.get(2)?,
+                    avg_efficiency: row.get(3)?,
+                })
+            })
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get trends: {}", e))
+            })?
+            .collect::<SqliteResult<Vec<TrendDataPoint>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect trends: {}", e))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(trends)
+    }
+
+    /// Get prompt counts and average scores grouped by git project
+    ///
+    /// Prompts captured outside a repository are grouped under `(none)`.
+    pub fn get_project_trends(&self, days: i32) -> Result<Vec<ProjectTrend>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r"
+                SELECT
+                    COALESCE(g.project, '(none)') as project,
+                    COUNT(DISTINCT p.id) as count,
+                    COALESCE(AVG(q.total_score), 0) as avg_quality,
+                    COALESCE(AVG(e.efficiency_score), 0) as avg_efficiency
+                FROM prompts p
+                LEFT JOIN prompt_git_context g ON p.id = g.prompt_id
+                LEFT JOIN quality_scores q ON p.id = q.prompt_id
+                LEFT JOIN efficiency_metrics e ON p.id = e.prompt_id
+                WHERE p.created_at >= date('now', ? || ' days') AND p.deleted_at IS NULL
+                GROUP BY COALESCE(g.project, '(none)')
+                ORDER BY count DESC
+                ",
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+
+        let trends = stmt
+            .query_map(params![format!("-{}", days)], |row| {
+                Ok(ProjectTrend {
+                    project: row.get(0)?,
+                    count: row.get(1)?,
+                    avg_quality: row.get(2)?,
+                    avg_efficiency: row.get(3)?,
+                })
+            })
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get project trends: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<ProjectTrend>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect trends: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(trends)
+    }
+
+    /// Get category distribution
+    pub fn get_category_distribution(&self) -> Result<Vec<(String, usize)>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r#"
+                SELECT COALESCE(category, 'uncategorized') as cat, COUNT(*) as count
+                FROM prompts
+                WHERE deleted_at IS NULL
+                GROUP BY category
+                ORDER BY count DESC
+                "#,
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {}", e))
+            })?;
+
+        let distribution = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get distribution: {}", e))
+            })?
+            .collect::<SqliteResult<Vec<(String, usize)>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to collect: {}", e))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(distribution)
+    }
+
+    // Encryption Methods
+
+    /// Whether prompt text is stored encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.read().is_some()
+    }
+
+    /// Key parameters of an encrypted database
+    pub fn encryption_params(&self) -> Option<KeyParams> {
+        self.cipher.read().as_ref().map(|cipher| cipher.params().clone())
+    }
+
+    /// Load the key of an encrypted database, or encrypt a new database
+    /// when `config` requires encryption
+    fn unlock(&self, config: &EncryptionConfig) -> Result<()> {
+        let stored = {
+            let conn = self.reader();
+            Self::read_encryption(&conn)?
+        };
+
+        match stored {
+            Some((params, check)) => {
+                let material = KeyMaterial::from_config(config)?.ok_or_else(|| {
+                    PromptTrackingError::EncryptionError(format!(
+                        "Database is encrypted; provide its key through {}",
+                        encryption::describe_source(config)
+                    ))
+                })?;
+                let cipher = Cipher::derive(&material, &params)?;
+                cipher.verify(&check)?;
+                *self.cipher.write() = Some(cipher);
+                Ok(())
+            }
+            None if config.enabled => {
+                if self.count_sealable()? > 0 {
+                    return Err(PromptTrackingError::EncryptionError(
+                        "Database is not encrypted yet; run `prompt-tracking encryption encrypt`"
+                            .to_string(),
+                    ));
+                }
+                let material = KeyMaterial::from_config(config)?.ok_or_else(|| {
+                    PromptTrackingError::EncryptionError(format!(
+                        "Encryption is enabled but no key was found in {}",
+                        encryption::describe_source(config)
+                    ))
+                })?;
+                self.encrypt(&material).map(|_| ())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Key parameters and check value stored in a database, if it is
+    /// encrypted
+    fn read_encryption(conn: &Connection) -> Result<Option<(KeyParams, String)>> {
+        let has_table: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'encryption'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to read encryption: {e}"))
+            })?;
+        if !has_table {
+            return Ok(None);
+        }
+
+        let row = conn
+            .query_row(
+                "SELECT kdf, salt, iterations, check_value FROM encryption WHERE id = 1",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, u32>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to read encryption: {e}"))
+            })?;
+
+        row.map(|(kdf, salt, iterations, check)| {
+            Ok((
+                KeyParams {
+                    kdf: kdf.parse()?,
+                    salt,
+                    iterations,
+                },
+                check,
+            ))
+        })
+        .transpose()
+    }
+
+    /// Number of stored values that would be sealed
+    fn count_sealable(&self) -> Result<i64> {
+        let sql = SEALED_COLUMNS
+            .iter()
+            .map(|(table, column)| {
+                format!("(SELECT COUNT(*) FROM {table} WHERE {column} IS NOT NULL)")
+            })
+            .collect::<Vec<_>>()
+            .join(" + ");
+        self.reader()
+            .query_row(&format!("SELECT {sql}"), [], |row| row.get(0))
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to count values: {e}")))
+    }
+
+    /// Encrypt the stored prompt text with a key derived from `material`
+    ///
+    /// Everything written afterwards is sealed too. Returns the number of
+    /// values encrypted. Fails if the database is already encrypted; use
+    /// [`Database::rekey`] to change its key.
+    pub fn encrypt(&self, material: &KeyMaterial) -> Result<usize> {
+        if self.is_encrypted() {
+            return Err(PromptTrackingError::EncryptionError(
+                "Database is already encrypted".to_string(),
+            ));
+        }
+        let cipher = Cipher::derive(material, &KeyParams::generate(material.kdf())?)?;
+        self.reseal(Some(cipher))
+    }
+
+    /// Decrypt the stored prompt text and stop encrypting new writes
+    ///
+    /// Returns the number of values decrypted.
+    pub fn decrypt(&self) -> Result<usize> {
+        if !self.is_encrypted() {
+            return Err(PromptTrackingError::EncryptionError(
+                "Database is not encrypted".to_string(),
+            ));
+        }
+        self.reseal(None)
+    }
+
+    /// Re-encrypt the stored prompt text under a key derived from `material`
+    ///
+    /// A fresh salt is used even if the material is unchanged. Returns the
+    /// number of values re-encrypted.
+    pub fn rekey(&self, material: &KeyMaterial) -> Result<usize> {
+        if !self.is_encrypted() {
+            return Err(PromptTrackingError::EncryptionError(
+                "Database is not encrypted".to_string(),
+            ));
+        }
+        let cipher = Cipher::derive(material, &KeyParams::generate(material.kdf())?)?;
+        self.reseal(Some(cipher))
+    }
+
+    /// Rewrite every sealed column from the current key to `next`, or to
+    /// plaintext for `None`, in one transaction
+    ///
+    /// The file is then vacuumed and the WAL emptied, so no copy of the
+    /// previous contents stays on disk. On failure nothing changes.
+    fn reseal(&self, next: Option<Cipher>) -> Result<usize> {
+        if self.in_transaction() {
+            return Err(PromptTrackingError::EncryptionError(
+                "Cannot change encryption inside a transaction".to_string(),
+            ));
+        }
+        let stored = match next {
+            Some(ref cipher) => Some((cipher.params().clone(), cipher.check_value()?)),
+            None => None,
+        };
+
+        let mut previous = None;
+        let result = self.transaction(|tx| {
+            let count = tx.rewrite_sealed("unseal")?;
+            let encrypting = next.is_some();
+            previous = Some(std::mem::replace(&mut *tx.cipher.write(), next));
+            if encrypting {
+                tx.rewrite_sealed("seal")?;
+            }
+            tx.save_encryption(stored.as_ref())?;
+
+            // The full-text index holds its own copy of the prompt text, so
+            // it is only kept while the text is plaintext
+            tx.maintain_search_index(!encrypting)?;
+            tx.rebuild_search_index()?;
+            Ok(count)
+        });
+
+        match result {
+            Ok(count) => {
+                self.compact()?;
+                Ok(count)
+            }
+            Err(e) => {
+                if let Some(previous) = previous {
+                    *self.cipher.write() = previous;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Pass every sealable column through the `seal` or `unseal` SQL
+    /// function, returning the number of values rewritten
+    fn rewrite_sealed(&self, function: &str) -> Result<usize> {
+        let conn = self.conn();
+        let mut count = 0;
+        for (table, column) in SEALED_COLUMNS {
+            count += conn
+                .execute(
+                    &format!(
+                        "UPDATE {table} SET {column} = {function}({column}) WHERE {column} IS NOT NULL"
+                    ),
+                    [],
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!(
+                        "Failed to rewrite {table}.{column}: {e}"
+                    ))
+                })?;
+        }
+        drop(conn);
+        Ok(count)
+    }
+
+    /// Replace the stored key parameters and check value; `None` clears them
+    fn save_encryption(&self, stored: Option<&(KeyParams, String)>) -> Result<()> {
+        let conn = self.conn();
+        conn.execute("DELETE FROM encryption", []).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to save encryption: {e}"))
+        })?;
+        if let Some((params, check)) = stored {
+            conn.execute(
+                r"
+                INSERT INTO encryption (id, kdf, salt, iterations, check_value, created_at)
+                VALUES (1, ?1, ?2, ?3, ?4, ?5)
+                ",
+                params![
+                    params.kdf.as_str(),
+                    params.salt,
+                    params.iterations,
+                    check,
+                    Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to save encryption: {e}"))
+            })?;
+        }
+        drop(conn);
+        Ok(())
+    }
+
+    /// Rebuild the file without free pages and empty the WAL
+    fn compact(&self) -> Result<()> {
+        let conn = self.conn();
+        conn.execute_batch("VACUUM").map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to vacuum database: {e}"))
+        })?;
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to checkpoint database: {e}"))
+            })
+    }
+
+    // Backup Methods
+
+    /// Path of the database file, `None` for an in-memory database
+    pub fn path(&self) -> Option<&str> {
+        self.readers.path.as_deref()
+    }
+
+    /// Copy the database to `path` with `SQLite`'s online backup API
+    ///
+    /// The copy is taken in one step from a single read transaction, so it
+    /// is a consistent snapshot even while other connections write.
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let conn = self.reader();
+        let mut dest = Connection::open(path).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to create backup: {e}"))
+        })?;
+        let backup = Backup::new(&conn, &mut dest).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to start backup: {e}"))
+        })?;
+        let step = backup.step(-1);
+        drop(backup);
+        drop(conn);
+        match step {
+            Ok(StepResult::Done) => Ok(()),
+            Ok(_) => Err(PromptTrackingError::DatabaseError(
+                "Failed to back up database: database is busy".to_string(),
+            )),
+            Err(e) => Err(PromptTrackingError::DatabaseError(format!(
+                "Failed to back up database: {e}"
+            ))),
+        }
+    }
+
+    /// Replace the database contents with the backup at `path`
+    ///
+    /// Writes from this handle wait until the restore is done; other
+    /// connections see the restored contents on their next read. The
+    /// schema is migrated afterwards if the backup predates it.
+    pub fn restore_from(&self, path: &Path) -> Result<()> {
+        let Some(live) = self.path() else {
+            return Err(PromptTrackingError::DatabaseError(
+                "Cannot restore into an in-memory database".to_string(),
+            ));
+        };
+
+        let _writer = self.conn();
+        let source = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to open backup: {e}"))
+            })?;
+
+        // Sealed values only open with the key the database has loaded
+        let backup_params = Self::read_encryption(&source)?.map(|(params, _)| params);
+        let current_params = self.encryption_params();
+        if backup_params != current_params {
+            return Err(PromptTrackingError::EncryptionError(
+                match (backup_params, current_params) {
+                    (Some(_), Some(_)) => "Backup was encrypted with a different key",
+                    (Some(_), None) => "Backup is encrypted but the database is not",
+                    _ => "Backup is not encrypted but the database is; decrypt it first",
+                }
+                .to_string(),
+            ));
+        }
+        let mut dest = Connection::open(live).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to open database: {e}"))
+        })?;
+        dest.busy_timeout(self.readers.busy_timeout).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to configure database: {e}"))
+        })?;
+
+        let restore = Backup::new(&source, &mut dest).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to start restore: {e}"))
+        })?;
+        match restore.step(-1) {
+            Ok(StepResult::Done) => {}
+            Ok(_) => {
+                return Err(PromptTrackingError::DatabaseError(
+                    "Failed to restore database: database is busy".to_string(),
+                ))
+            }
+            Err(e) => {
+                return Err(PromptTrackingError::DatabaseError(format!(
+                    "Failed to restore database: {e}"
+                )))
+            }
+        }
+        drop(restore);
+        drop(dest);
+
+        self.initialize_schema()
+    }
+
+    // Trash Methods
+
+    /// Bring a prompt back from the trash
+    pub fn restore_prompt(&self, id: &str) -> Result<()> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE prompts SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                params![id],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to restore prompt: {e}"))
+            })?;
+
+        if rows == 0 {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Prompt not in trash: {id}"
+            )));
+        }
+
+        if !self.events.is_empty() {
+            if let Some(prompt) = self.get_prompt(id)? {
+                self.emit(&Event::Updated { prompt });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the prompt with `id` is in the trash
+    fn in_trash(&self, id: &str) -> Result<bool> {
+        self.conn()
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM prompts WHERE id = ?1 AND deleted_at IS NOT NULL)",
+                params![id],
+                |row| row.get(0),
+            )
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to check trash: {e}")))
+    }
+
+    /// Prompts in the trash with the time each was deleted, most recently
+    /// deleted first
+    pub fn list_trash(&self) -> Result<Vec<(Prompt, DateTime<Utc>)>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r"
+            SELECT id, unseal(content), content_hash, category, status, created_at, updated_at,
+                   model, input_tokens, output_tokens, execution_time_ms, estimated_cost, unseal(context),
+                   deleted_at
+            FROM prompts WHERE deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC
+            ",
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let deleted_at = DateTime::parse_from_rfc3339(&row.get::<_, String>(13)?)
+                    .map_or_else(|_| Utc::now(), |dt| dt.with_timezone(&Utc));
+                Ok((self.row_to_prompt(row)?, deleted_at))
+            })
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to list trash: {e}")))?
+            .collect::<SqliteResult<Vec<_>>>()
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to collect: {e}")))?;
+        drop(stmt);
+        drop(conn);
+
+        let (mut prompts, deleted_at): (Vec<Prompt>, Vec<DateTime<Utc>>) = rows.into_iter().unzip();
+        self.load_relations_batch(&mut prompts)?;
+        Ok(prompts.into_iter().zip(deleted_at).collect())
+    }
+
+    /// Number of prompts in the trash deleted before `deleted_before`
+    pub fn count_trash_before(&self, deleted_before: DateTime<Utc>) -> Result<usize> {
+        let count: usize = self
+            .reader()
+            .query_row(
+                "SELECT COUNT(*) FROM prompts WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+                params![deleted_before.to_rfc3339()],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to count trash: {e}"))
+            })?;
+
+        Ok(count)
+    }
+
+    /// Permanently delete the prompts in the trash, or only those deleted
+    /// before `deleted_before`, returning how many were removed
+    pub fn empty_trash(&self, deleted_before: Option<DateTime<Utc>>) -> Result<usize> {
+        let cutoff = deleted_before.map(|cutoff| cutoff.to_rfc3339());
+        self.transaction(|tx| {
+            let ids = tx.trash_ids(cutoff.as_deref())?;
+            for id in &ids {
+                tx.delete_prompt_permanently(id)?;
+            }
+            Ok(ids.len())
+        })
+    }
+
+    /// Ids of the prompts in the trash, or only those deleted before
+    /// `cutoff`
+    fn trash_ids(&self, cutoff: Option<&str>) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id FROM prompts WHERE deleted_at IS NOT NULL \
+                 AND (?1 IS NULL OR deleted_at < ?1)",
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+        let ids = stmt
+            .query_map(params![cutoff], |row| row.get::<_, String>(0))
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to list trash: {e}")))?
+            .collect::<SqliteResult<Vec<_>>>()
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to collect: {e}")))?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(ids)
+    }
+
+    // Retention Methods
+
+    /// Ids of prompts with `status` last updated before `cutoff`, oldest
+    /// first
+    pub fn prompts_updated_before(
+        &self,
+        status: &PromptStatus,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id FROM prompts \
+                 WHERE status = ?1 AND updated_at < ?2 AND deleted_at IS NULL \
+                 ORDER BY updated_at",
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+        let ids = stmt
+            .query_map(params![status.to_string(), cutoff.to_rfc3339()], |row| row.get(0))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to list prompts: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<String>>>()
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to collect: {e}")))?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(ids)
+    }
+
+    /// Delete all but the newest `keep` quality and efficiency rows of each
+    /// prompt, or with `dry_run` only count them
+    pub fn trim_scores(&self, keep: usize, dry_run: bool) -> Result<usize> {
+        let quality = self.delete_selected(
+            "quality_scores",
+            r"
+            SELECT id FROM (
+                SELECT id, ROW_NUMBER() OVER (
+                    PARTITION BY prompt_id ORDER BY analyzed_at DESC, id DESC
+                ) AS newest
+                FROM quality_scores
+            ) WHERE newest > ?1
+            ",
+            keep,
+            dry_run,
+        )?;
+        let efficiency = self.delete_selected(
+            "efficiency_metrics",
+            r"
+            SELECT id FROM (
+                SELECT id, ROW_NUMBER() OVER (
+                    PARTITION BY prompt_id ORDER BY calculated_at DESC, id DESC
+                ) AS newest
+                FROM efficiency_metrics
+            ) WHERE newest > ?1
+            ",
+            keep,
+            dry_run,
+        )?;
+        Ok(quality + efficiency)
+    }
+
+    /// Delete version history entries identical to the version before them
+    /// and all but the newest `keep` of each prompt, or with `dry_run` only
+    /// count them
+    pub fn compact_versions(&self, keep: usize, dry_run: bool) -> Result<usize> {
+        self.delete_selected(
+            "version_history",
+            r"
+            SELECT id FROM (
+                SELECT id, content_hash,
+                    LAG(content_hash) OVER (
+                        PARTITION BY prompt_id ORDER BY version
+                    ) AS previous,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY prompt_id ORDER BY version DESC
+                    ) AS newest
+                FROM version_history
+            ) WHERE newest > ?1 OR content_hash = previous
+            ",
+            keep,
+            dry_run,
+        )
+    }
+
+    /// Delete the rows of `table` whose ids `select` returns for `keep`
+    fn delete_selected(
+        &self,
+        table: &str,
+        select: &str,
+        keep: usize,
+        dry_run: bool,
+    ) -> Result<usize> {
+        let sql = if dry_run {
+            format!("SELECT COUNT(*) FROM ({select})")
+        } else {
+            format!("DELETE FROM {table} WHERE id IN ({select})")
+        };
+        let count = if dry_run {
+            self.reader()
+                .query_row(&sql, params![keep], |row| row.get::<_, usize>(0))
+        } else {
+            self.conn().execute(&sql, params![keep])
+        };
+        count
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to trim {table}: {e}")))
+    }
+
+    // Maintenance Methods
+
+    /// Problems reported by `SQLite`'s `integrity_check`, empty when the
+    /// file is sound
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare("PRAGMA integrity_check").map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to check integrity: {e}"))
+        })?;
+        let messages = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to check integrity: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<_>>>()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to check integrity: {e}"))
+            })?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(messages.into_iter().filter(|message| message != "ok").collect())
+    }
+
+    /// Prompts whose stored `content_hash` doesn't match their content, as
+    /// `(id, expected hash)` pairs
+    pub fn content_hash_mismatches(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare("SELECT id, unseal(content), content_hash FROM prompts ORDER BY created_at")
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to read prompts: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<_>>>()
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to collect: {e}")))?;
+        drop(stmt);
+        drop(conn);
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, content, stored)| {
+                let expected = crate::utils::calculate_hash(&content);
+                (expected != stored).then_some((id, expected))
+            })
+            .collect())
+    }
+
+    /// Overwrite the stored content hash of a prompt
+    pub fn set_content_hash(&self, id: &str, hash: &str) -> Result<()> {
+        self.conn()
+            .execute(
+                "UPDATE prompts SET content_hash = ?2 WHERE id = ?1",
+                params![id, hash],
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to update content hash: {e}"))
+            })?;
+        Ok(())
+    }
+
+    /// Content hashes shared by more than one prompt, with the ids of those
+    /// prompts, oldest first
+    pub fn duplicate_hashes(&self) -> Result<Vec<(String, Vec<String>)>> {
+        let conn = self.reader();
+        let mut stmt = conn
+            .prepare(
+                r"
+                SELECT content_hash, id FROM prompts
+                WHERE deleted_at IS NULL AND content_hash IN (
+                    SELECT content_hash FROM prompts WHERE deleted_at IS NULL
+                    GROUP BY content_hash HAVING COUNT(*) > 1
+                )
+                ORDER BY content_hash, created_at
+                ",
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to prepare query: {e}"))
+            })?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to find duplicates: {e}"))
+            })?
+            .collect::<SqliteResult<Vec<_>>>()
+            .map_err(|e| PromptTrackingError::DatabaseError(format!("Failed to collect: {e}")))?;
+        drop(stmt);
+        drop(conn);
+
+        let mut duplicates: Vec<(String, Vec<String>)> = Vec::new();
+        for (hash, id) in rows {
+            match duplicates.last_mut() {
+                Some((last, ids)) if *last == hash => ids.push(id),
+                _ => duplicates.push((hash, vec![id])),
+            }
+        }
+        Ok(duplicates)
+    }
+
+    /// Number of orphaned rows per table, for tables that have any
+    pub fn count_orphans(&self) -> Result<Vec<(&'static str, usize)>> {
+        let conn = self.reader();
+        let mut counts = Vec::new();
+        for (table, condition) in ORPHAN_CONDITIONS {
+            let count: usize = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM {table} WHERE {condition}"),
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to count orphans: {e}"))
+                })?;
+            if count > 0 {
+                counts.push((*table, count));
+            }
+        }
+        drop(conn);
+        Ok(counts)
+    }
+
+    /// Delete orphaned rows, returning the number removed per table
+    pub fn remove_orphans(&self) -> Result<Vec<(&'static str, usize)>> {
+        self.transaction(|tx| {
+            let removed = ORPHAN_CONDITIONS
+                .iter()
+                .map(|(table, condition)| {
+                    tx.conn()
+                        .execute(&format!("DELETE FROM {table} WHERE {condition}"), [])
+                        .map(|count| (*table, count))
+                })
+                .collect::<SqliteResult<Vec<_>>>()
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to remove orphans: {e}"))
+                })?;
+            Ok(removed.into_iter().filter(|&(_, count)| count > 0).collect())
+        })
+    }
+
+    /// Whether the full-text index matches the prompts table
+    ///
+    /// The index must pass FTS5's own integrity check and hold exactly one
+    /// document per prompt, or none at all while the database is encrypted.
+    pub fn search_index_in_sync(&self) -> Result<bool> {
+        let conn = self.conn();
+        if self.is_encrypted() {
+            let indexed: i64 = conn
+                .query_row("SELECT COUNT(*) FROM prompts_fts_docsize", [], |row| {
+                    row.get(0)
+                })
+                .map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!("Failed to check search index: {e}"))
+                })?;
+            return Ok(indexed == 0);
+        }
+
+        match conn.execute_batch("INSERT INTO prompts_fts(prompts_fts) VALUES ('integrity-check')") {
+            Ok(()) => {}
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::DatabaseCorrupt =>
+            {
+                return Ok(false)
+            }
+            Err(e) => {
+                return Err(PromptTrackingError::DatabaseError(format!(
+                    "Failed to check search index: {e}"
+                )))
+            }
+        }
+
+        let mismatched: i64 = conn
+            .query_row(
+                r"
+                SELECT
+                    (SELECT COUNT(*) FROM prompts_fts_docsize
+                        WHERE id NOT IN (SELECT rowid FROM prompts))
+                    + (SELECT COUNT(*) FROM prompts
+                        WHERE rowid NOT IN (SELECT id FROM prompts_fts_docsize))
+                ",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to check search index: {e}"))
+            })?;
+        drop(conn);
+        Ok(mismatched == 0)
+    }
+
+    /// Rebuild the full-text index from the prompts table
+    ///
+    /// While the database is encrypted the index is emptied instead, since
+    /// it could only hold ciphertext.
+    pub fn rebuild_search_index(&self) -> Result<()> {
+        let command = if self.is_encrypted() {
+            "delete-all"
+        } else {
+            "rebuild"
+        };
+        self.conn()
+            .execute(
+                "INSERT INTO prompts_fts(prompts_fts) VALUES (?1)",
+                params![command],
+            )
+            .map(|_| ())
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to rebuild search index: {e}"))
+            })
+    }
+
+    /// Create or drop the triggers keeping the full-text index in sync
+    ///
+    /// They are dropped while the database is encrypted: indexing sealed
+    /// text would only tokenize ciphertext. Searches match the decrypted
+    /// text with `LIKE` either way, so only full-text ranking is lost.
+    fn maintain_search_index(&self, enabled: bool) -> Result<()> {
+        let sql = if enabled {
+            // Migration v2 created the index and its triggers
+            MIGRATIONS[1].up
+        } else {
+            r"
+            DROP TRIGGER IF EXISTS prompts_fts_update;
+            DROP TRIGGER IF EXISTS prompts_fts_delete;
+            DROP TRIGGER IF EXISTS prompts_fts_insert;
+            "
+        };
+        self.conn().execute_batch(sql).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to update search triggers: {e}"))
+        })
+    }
+
+    // Export/Import Methods
 
     /// Export all data to JSON
     pub fn export_to_json(&self) -> Result<String> {
         let prompts = self.list_prompts(&PromptFilter::default())?;
         let quality_scores = self.get_all_quality_scores()?;
 
-        let mut efficiency_metrics = Vec::new();
-        for prompt in &prompts {
-            if let Ok(Some(metrics)) = self.get_efficiency_metrics(&prompt.id) {
-                efficiency_metrics.push(metrics);
-            }
-        }
+        let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+        let mut latest = self.get_latest_efficiency_metrics(&ids)?;
+        let efficiency_metrics: Vec<EfficiencyMetrics> =
+            prompts.iter().filter_map(|p| latest.remove(&p.id)).collect();
+
+        let templates = self.list_templates()?;
 
         let export_data = serde_json::json!({
             "version": "1.0",
             "exported_at": Utc::now().to_rfc3339(),
+            "templates": templates,
             "prompts": prompts,
             "quality_scores": quality_scores,
             "efficiency_metrics": efficiency_metrics,
@@ -920,58 +4024,77 @@ impl Database {
     /// Import data from JSON
     pub fn import_from_json(&self, json_str: &str) -> Result<usize> {
         let data: serde_json::Value = serde_json::from_str(json_str).map_err(|e| {
-            PromptTrackingError::IoError(std::io::Error::other(
-                format!("JSON parse error: {}", e),
+            PromptTrackingError::IoError(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("JSON parse error: {e}"),
             ))
         })?;
 
+        self.transaction(|tx| tx.import_data(&data))
+    }
+
+    /// Import the records of a parsed export, returning the number of new prompts
+    fn import_data(&self, data: &serde_json::Value) -> Result<usize> {
         let mut imported = 0;
 
-        // Import prompts
-        if let Some(prompts) = data["prompts"].as_array() {
-            for prompt_value in prompts {
-                let prompt: Prompt = serde_json::from_value(prompt_value.clone()).map_err(|e| {
-                    PromptTrackingError::IoError(std::io::Error::other(
-                        format!("Prompt parse error: {}", e),
+        // Import templates, keeping existing ones with the same name
+        for template_value in data["templates"].as_array().into_iter().flatten() {
+            let template: PromptTemplate =
+                serde_json::from_value(template_value.clone()).map_err(|e| {
+                    PromptTrackingError::IoError(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("Template parse error: {e}"),
                     ))
                 })?;
 
-                // Skip if already exists
-                if self.find_by_hash(&prompt.content_hash)?.is_some() {
-                    continue;
-                }
+            if self.get_template(&template.name)?.is_none()
+                && self.get_template(&template.id)?.is_none()
+            {
+                self.create_template(&template)?;
+            }
+        }
+
+        // Import prompts
+        for prompt_value in data["prompts"].as_array().into_iter().flatten() {
+            let prompt: Prompt = serde_json::from_value(prompt_value.clone()).map_err(|e| {
+                PromptTrackingError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("Prompt parse error: {e}"),
+                ))
+            })?;
 
-                self.create_prompt(&prompt)?;
-                imported += 1;
+            // Skip if already exists
+            if self.find_by_hash(&prompt.content_hash)?.is_some() {
+                continue;
             }
+
+            self.create_prompt(&prompt)?;
+            imported += 1;
         }
 
         // Import quality scores
-        if let Some(scores) = data["quality_scores"].as_array() {
-            for score_value in scores {
-                let score: QualityScore =
-                    serde_json::from_value(score_value.clone()).map_err(|e| {
-                        PromptTrackingError::IoError(std::io::Error::other(
-                            format!("Score parse error: {}", e),
-                        ))
-                    })?;
-
-                let _ = self.save_quality_score(&score);
-            }
+        for score_value in data["quality_scores"].as_array().into_iter().flatten() {
+            let score: QualityScore = serde_json::from_value(score_value.clone()).map_err(|e| {
+                PromptTrackingError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("Score parse error: {e}"),
+                ))
+            })?;
+
+            let _ = self.save_quality_score(&score);
         }
 
         // Import efficiency metrics
-        if let Some(metrics) = data["efficiency_metrics"].as_array() {
-            for metric_value in metrics {
-                let metric: EfficiencyMetrics =
-                    serde_json::from_value(metric_value.clone()).map_err(|e| {
-                        PromptTrackingError::IoError(std::io::Error::other(
-                            format!("Metric parse error: {}", e),
-                        ))
-                    })?;
-
-                let _ = self.save_efficiency_metrics(&metric);
-            }
+        for metric_value in data["efficiency_metrics"].as_array().into_iter().flatten() {
+            let metric: EfficiencyMetrics =
+                serde_json::from_value(metric_value.clone()).map_err(|e| {
+                    PromptTrackingError::IoError(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("Metric parse error: {e}"),
+                    ))
+                })?;
+
+            let _ = self.save_efficiency_metrics(&metric);
         }
 
         Ok(imported)
@@ -1020,16 +4143,70 @@ mod tests {
     }
 
     #[test]
-    fn test_delete_prompt() {
+    fn test_delete_prompt() {
+        let db = Database::in_memory().unwrap();
+        let mut prompt = Prompt::new("To delete".to_string());
+        prompt.content_hash = "hash".to_string();
+
+        db.create_prompt(&prompt).unwrap();
+        assert_eq!(db.count_prompts().unwrap(), 1);
+
+        db.delete_prompt(&prompt.id).unwrap();
+        assert_eq!(db.count_prompts().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
         let db = Database::in_memory().unwrap();
-        let mut prompt = Prompt::new("To delete".to_string());
-        prompt.content_hash = "hash".to_string();
-
+        let mut prompt = Prompt::new("Trashed prompt".to_string());
+        prompt.content_hash = "trashed".to_string();
+        prompt.tags = vec!["rust".to_string()];
         db.create_prompt(&prompt).unwrap();
-        assert_eq!(db.count_prompts().unwrap(), 1);
+        db.save_version(&prompt).unwrap();
+        db.save_quality_score(&QualityScore {
+            prompt_id: prompt.id.clone(),
+            total_score: 75.0,
+            clarity: 75.0,
+            completeness: 75.0,
+            specificity: 75.0,
+            guidance: 75.0,
+            analyzed_at: Utc::now(),
+        })
+        .unwrap();
 
         db.delete_prompt(&prompt.id).unwrap();
-        assert_eq!(db.count_prompts().unwrap(), 0);
+        assert!(db.get_prompt(&prompt.id).unwrap().is_none());
+        assert!(db.find_by_hash("trashed").unwrap().is_none());
+        assert!(db.list_prompts(&PromptFilter::default()).unwrap().is_empty());
+        assert!(db.archive_prompt(&prompt.id).is_err());
+        let trash = db.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].0.tags, vec!["rust".to_string()]);
+        assert_eq!(db.count_trash_before(trash[0].1).unwrap(), 0);
+        let later = trash[0].1 + chrono::Duration::seconds(1);
+        assert_eq!(db.count_trash_before(later).unwrap(), 1);
+
+        db.restore_prompt(&prompt.id).unwrap();
+        assert!(db.restore_prompt(&prompt.id).is_err());
+        let restored = db.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(restored.tags, vec!["rust".to_string()]);
+        assert_eq!(db.get_quality_score(&prompt.id).unwrap().unwrap().total_score, 75.0);
+        assert_eq!(db.get_version_history(&prompt.id).unwrap().len(), 1);
+
+        // Trash emptied only of prompts deleted before the cutoff
+        db.delete_prompt(&prompt.id).unwrap();
+        assert_eq!(db.empty_trash(Some(Utc::now() - chrono::Duration::days(1))).unwrap(), 0);
+        assert_eq!(db.empty_trash(None).unwrap(), 1);
+        assert!(db.list_trash().unwrap().is_empty());
+        assert!(db.get_quality_score(&prompt.id).unwrap().is_none());
+        assert!(db.get_version_history(&prompt.id).unwrap().is_empty());
+
+        // Creating a prompt whose id is in the trash replaces the trashed copy
+        db.create_prompt(&prompt).unwrap();
+        db.delete_prompt(&prompt.id).unwrap();
+        db.create_prompt(&prompt).unwrap();
+        assert!(db.list_trash().unwrap().is_empty());
+        assert_eq!(db.count_prompts().unwrap(), 1);
     }
 
     #[test]
@@ -1231,4 +4408,921 @@ mod tests {
         let results = db.list_prompts(&filter).unwrap();
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn test_prompt_attributes() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt = Prompt::new("Attributes".to_string());
+        prompt.content_hash = "attr_hash".to_string();
+        prompt
+            .metadata
+            .attributes
+            .insert("ticket".to_string(), "PROJ-1".to_string());
+        db.create_prompt(&prompt).unwrap();
+
+        db.set_attribute(&prompt.id, "author", "alice").unwrap();
+        db.set_attribute(&prompt.id, "ticket", "PROJ-2").unwrap();
+
+        let retrieved = db.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(retrieved.metadata.attributes.len(), 2);
+        assert_eq!(retrieved.metadata.attributes["ticket"], "PROJ-2");
+
+        assert!(db.remove_attribute(&prompt.id, "author").unwrap());
+        assert!(!db.remove_attribute(&prompt.id, "author").unwrap());
+        assert_eq!(db.get_attributes(&prompt.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_attribute_filter() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt1 = Prompt::new("P1".to_string());
+        prompt1.content_hash = "h1".to_string();
+        prompt1
+            .metadata
+            .attributes
+            .insert("experiment".to_string(), "a".to_string());
+        db.create_prompt(&prompt1).unwrap();
+
+        let mut prompt2 = Prompt::new("P2".to_string());
+        prompt2.content_hash = "h2".to_string();
+        prompt2
+            .metadata
+            .attributes
+            .insert("experiment".to_string(), "b".to_string());
+        db.create_prompt(&prompt2).unwrap();
+
+        let filter = PromptFilter {
+            attributes: vec![("experiment".to_string(), "b".to_string())],
+            ..Default::default()
+        };
+        let results = db.list_prompts(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, prompt2.id);
+    }
+
+    #[test]
+    fn test_export_import_attributes() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt = Prompt::new("Export attributes".to_string());
+        prompt.content_hash = "export_attr_hash".to_string();
+        prompt
+            .metadata
+            .attributes
+            .insert("repo".to_string(), "cc-tracker".to_string());
+        db.create_prompt(&prompt).unwrap();
+
+        let json = db.export_to_json().unwrap();
+        let db2 = Database::in_memory().unwrap();
+        db2.import_from_json(&json).unwrap();
+
+        let imported = db2.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(imported.metadata.attributes["repo"], "cc-tracker");
+    }
+
+    #[test]
+    fn test_git_context_storage_and_filters() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt = Prompt::new("Git prompt".to_string());
+        prompt.content_hash = "git_hash".to_string();
+        prompt.metadata.git = Some(GitContext {
+            project: "cc-tracker".to_string(),
+            repo_root: "/src/cc-tracker".to_string(),
+            remote_url: Some("https://example.com/cc-tracker.git".to_string()),
+            branch: Some("main".to_string()),
+            commit: Some("abc123".to_string()),
+            dirty: true,
+        });
+        db.create_prompt(&prompt).unwrap();
+
+        let mut other = Prompt::new("No repo".to_string());
+        other.content_hash = "no_git_hash".to_string();
+        db.create_prompt(&other).unwrap();
+
+        let retrieved = db.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(retrieved.metadata.git, prompt.metadata.git);
+
+        let filter = PromptFilter {
+            project: Some("cc-tracker".to_string()),
+            branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        let results = db.list_prompts(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, prompt.id);
+
+        let trends = db.get_project_trends(7).unwrap();
+        assert_eq!(trends.len(), 2);
+        assert!(trends.iter().any(|t| t.project == "cc-tracker" && t.count == 1));
+        assert!(trends.iter().any(|t| t.project == "(none)" && t.count == 1));
+    }
+
+    #[test]
+    fn test_redaction_events_logged_on_create() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt = Prompt::new("Content [REDACTED:email]".to_string());
+        prompt.content_hash = "redacted_hash".to_string();
+        prompt.redactions = vec![RedactionFinding {
+            rule: "email".to_string(),
+            action: crate::models::RedactionAction::Mask,
+            count: 1,
+        }];
+        db.create_prompt(&prompt).unwrap();
+
+        let events = db.get_redaction_events(Some(&prompt.id)).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].rule, "email");
+        assert_eq!(events[0].action, "mask");
+        assert_eq!(events[0].source, "capture");
+
+        assert!(db.get_redaction_events(Some("other")).unwrap().is_empty());
+        assert_eq!(db.get_redaction_events(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_update_version_content() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt = Prompt::new("secret v1".to_string());
+        prompt.content_hash = "v1".to_string();
+        db.create_prompt(&prompt).unwrap();
+        db.save_version(&prompt).unwrap();
+
+        let versions = db.get_all_versions().unwrap();
+        assert_eq!(versions.len(), 1);
+
+        db.update_version_content(versions[0].id, "scrubbed").unwrap();
+        let history = db.get_version_history(&prompt.id).unwrap();
+        assert_eq!(history[0].content, "scrubbed");
+        assert_eq!(history[0].content_hash, crate::utils::calculate_hash("scrubbed"));
+    }
+
+    #[test]
+    fn test_templates_and_lineage() {
+        let db = Database::in_memory().unwrap();
+
+        let mut template = PromptTemplate::new(
+            "unit-tests".to_string(),
+            "Write {{language}} tests for {{file}}".to_string(),
+        );
+        template.description = Some("Test skeleton".to_string());
+        db.create_template(&template).unwrap();
+        assert!(db.create_template(&template).is_err());
+
+        let found = db.get_template("unit-tests").unwrap().unwrap();
+        assert_eq!(found.id, template.id);
+        assert_eq!(found.description, Some("Test skeleton".to_string()));
+        assert!(db.get_template(&template.id).unwrap().is_some());
+        assert_eq!(db.list_templates().unwrap().len(), 1);
+
+        let mut prompt = Prompt::new("Write Rust tests for lib.rs".to_string());
+        prompt.content_hash = "rendered_hash".to_string();
+        let mut variables = BTreeMap::new();
+        variables.insert("language".to_string(), "Rust".to_string());
+        variables.insert("file".to_string(), "lib.rs".to_string());
+        prompt.metadata.template = Some(TemplateUsage {
+            template_id: template.id.clone(),
+            template_name: template.name.clone(),
+            variables,
+        });
+        db.create_prompt(&prompt).unwrap();
+
+        let loaded = db.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(loaded.metadata.template, prompt.metadata.template);
+        assert_eq!(db.count_template_prompts(&template.id).unwrap(), 1);
+
+        let filter = PromptFilter {
+            template: Some("unit-tests".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.list_prompts(&filter).unwrap().len(), 1);
+
+        // Lineage survives template deletion
+        assert!(db.delete_template("unit-tests").unwrap());
+        assert!(!db.delete_template("unit-tests").unwrap());
+        let loaded = db.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(
+            loaded.metadata.template.map(|t| t.template_name),
+            Some("unit-tests".to_string())
+        );
+    }
+
+    #[test]
+    fn test_watched_files() {
+        let db = Database::in_memory().unwrap();
+
+        let mut prompt = Prompt::new("Watched content".to_string());
+        prompt.content_hash = "watched_hash".to_string();
+        db.create_prompt(&prompt).unwrap();
+
+        assert!(db.get_watched_file("/w/a.md").unwrap().is_none());
+        db.set_watched_file("/w/a.md", &prompt.id, "h1").unwrap();
+        db.set_watched_file("/w/a.md", &prompt.id, "h2").unwrap();
+
+        let watched = db.get_watched_file("/w/a.md").unwrap().unwrap();
+        assert_eq!(watched.prompt_id, prompt.id);
+        assert_eq!(watched.content_hash, "h2");
+
+        assert_eq!(db.latest_version(&prompt.id).unwrap(), 0);
+        db.save_version(&prompt).unwrap();
+        assert_eq!(db.latest_version(&prompt.id).unwrap(), 1);
+
+        db.delete_prompt(&prompt.id).unwrap();
+        assert!(db.get_watched_file("/w/a.md").unwrap().is_some());
+        db.delete_prompt_permanently(&prompt.id).unwrap();
+        assert!(db.get_watched_file("/w/a.md").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_watch_checkpoints() {
+        let db = Database::in_memory().unwrap();
+        assert!(db.get_watch_checkpoint("/w/a.md").unwrap().is_none());
+
+        let mut checkpoint = WatchCheckpoint {
+            path: "/w/a.md".to_string(),
+            size: 42,
+            mtime_ns: 1_700_000_000_123_456_789,
+            file_hash: "abc".to_string(),
+        };
+        db.set_watch_checkpoint(&checkpoint).unwrap();
+        assert_eq!(db.get_watch_checkpoint("/w/a.md").unwrap(), Some(checkpoint.clone()));
+
+        checkpoint.size = 43;
+        db.set_watch_checkpoint(&checkpoint).unwrap();
+        assert_eq!(db.get_watch_checkpoint("/w/a.md").unwrap().unwrap().size, 43);
+    }
+
+    #[test]
+    fn test_log_offsets() {
+        let db = Database::in_memory().unwrap();
+        assert!(db.get_log_offset("/w/session.jsonl").unwrap().is_none());
+
+        let mut offset = LogOffset::new("/w/session.jsonl");
+        offset.offset = 512;
+        offset.head_hash = Some("abc".to_string());
+        db.set_log_offset(&offset).unwrap();
+        assert_eq!(db.get_log_offset("/w/session.jsonl").unwrap(), Some(offset.clone()));
+
+        offset.offset = 1024;
+        db.set_log_offset(&offset).unwrap();
+        assert_eq!(db.get_log_offset("/w/session.jsonl").unwrap().unwrap().offset, 1024);
+    }
+
+    #[test]
+    fn test_capture_failures() {
+        let db = Database::in_memory().unwrap();
+        let retry_at = Utc::now() - chrono::Duration::seconds(1);
+
+        db.record_capture_failure("/w/a.md", "database", "database is locked", Some(retry_at))
+            .unwrap();
+        db.record_capture_failure("/w/a.md", "database", "database is locked", Some(retry_at))
+            .unwrap();
+        db.record_capture_failure("/w/b.bin", "binary", "Binary file skipped", None)
+            .unwrap();
+
+        let a = db.find_capture_failure("/w/a.md").unwrap().unwrap();
+        assert_eq!(a.attempts, 2);
+        assert_eq!(a.error_kind, "database");
+        assert!(!a.dismissed);
+        assert_eq!(db.get_capture_failure(a.id).unwrap(), Some(a.clone()));
+
+        assert_eq!(db.list_capture_failures(false).unwrap().len(), 2);
+        let due = db.due_capture_failures(Utc::now()).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].path, "/w/a.md");
+        assert!(db.next_capture_retry().unwrap().is_some());
+
+        // Dismissed failures are hidden until the file fails again
+        assert!(db.dismiss_capture_failure(a.id).unwrap());
+        assert_eq!(db.list_capture_failures(false).unwrap().len(), 1);
+        assert_eq!(db.list_capture_failures(true).unwrap().len(), 2);
+        assert!(db.due_capture_failures(Utc::now()).unwrap().is_empty());
+        assert!(db.next_capture_retry().unwrap().is_none());
+
+        db.record_capture_failure("/w/a.md", "io", "timed out", None).unwrap();
+        let a = db.find_capture_failure("/w/a.md").unwrap().unwrap();
+        assert!(!a.dismissed);
+        assert_eq!(a.attempts, 3);
+
+        db.clear_capture_failure("/w/a.md").unwrap();
+        assert!(db.find_capture_failure("/w/a.md").unwrap().is_none());
+        assert!(!db.dismiss_capture_failure(a.id).unwrap());
+    }
+
+    #[test]
+    fn test_webhook_outbox() {
+        let db = Database::in_memory().unwrap();
+        let first = db.enqueue_webhook("notes", "captured", "{}").unwrap();
+        let second = db.enqueue_webhook("notes", "deleted", "{}").unwrap();
+
+        let due = db.due_webhook_deliveries(Utc::now()).unwrap();
+        assert_eq!(due.iter().map(|d| d.id).collect::<Vec<_>>(), vec![first, second]);
+        assert_eq!(due[0].status, DeliveryStatus::Pending);
+
+        // Only the first of two claims on a due delivery wins
+        let now = Utc::now();
+        let lease = now + chrono::Duration::minutes(1);
+        assert!(db.claim_webhook_delivery(first, now, lease).unwrap());
+        assert!(!db.claim_webhook_delivery(first, now, lease).unwrap());
+        assert_eq!(db.due_webhook_deliveries(now).unwrap().len(), 1);
+        assert_eq!(db.due_webhook_deliveries(lease).unwrap().len(), 2);
+
+        db.mark_webhook_delivered(first).unwrap();
+        let retry_at = Utc::now() + chrono::Duration::minutes(5);
+        db.mark_webhook_attempt_failed(second, "HTTP 503", Some(retry_at))
+            .unwrap();
+        assert!(db.due_webhook_deliveries(Utc::now()).unwrap().is_empty());
+
+        let delivered = db.get_webhook_delivery(first).unwrap().unwrap();
+        assert_eq!(delivered.status, DeliveryStatus::Delivered);
+        assert_eq!(delivered.attempts, 1);
+        assert!(delivered.delivered_at.is_some());
+
+        let retrying = db.get_webhook_delivery(second).unwrap().unwrap();
+        assert_eq!(retrying.status, DeliveryStatus::Pending);
+        assert_eq!(retrying.last_error.as_deref(), Some("HTTP 503"));
+
+        // Giving up marks it failed; a requeue makes it due again
+        assert!(!db.requeue_webhook(second).unwrap());
+        db.mark_webhook_attempt_failed(second, "HTTP 503", None).unwrap();
+        assert_eq!(
+            db.list_webhook_deliveries(Some(DeliveryStatus::Failed), 10)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(db.requeue_webhook(second).unwrap());
+        assert_eq!(db.due_webhook_deliveries(Utc::now()).unwrap().len(), 1);
+        assert_eq!(db.list_webhook_deliveries(None, 10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_list_prompts_loads_relations_in_bulk() {
+        let db = Database::in_memory().unwrap();
+        let count = ID_BATCH_SIZE + 20;
+        db.transaction(|tx| {
+            for i in 0..count {
+                let mut prompt = Prompt::new(format!("Batched prompt {}", i));
+                prompt.content_hash = format!("batch_hash_{}", i);
+                prompt.tags = vec![format!("t{}", i % 3), "shared".to_string()];
+                prompt
+                    .metadata
+                    .attributes
+                    .insert("n".to_string(), i.to_string());
+                if i % 2 == 0 {
+                    prompt.metadata.git = Some(GitContext {
+                        project: format!("project-{}", i),
+                        repo_root: "/src".to_string(),
+                        remote_url: None,
+                        branch: Some("main".to_string()),
+                        commit: None,
+                        dirty: false,
+                    });
+                }
+                if i % 5 == 0 {
+                    prompt.metadata.template = Some(TemplateUsage {
+                        template_id: "tpl".to_string(),
+                        template_name: "review".to_string(),
+                        variables: BTreeMap::from([("n".to_string(), i.to_string())]),
+                    });
+                }
+                tx.create_prompt(&prompt)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let listed = db.list_prompts(&PromptFilter::default()).unwrap();
+        assert_eq!(listed.len(), count);
+        for prompt in &listed {
+            let single = db.get_prompt(&prompt.id).unwrap().unwrap();
+            assert_eq!(prompt.tags, single.tags);
+            assert_eq!(prompt.metadata.attributes, single.metadata.attributes);
+            assert_eq!(prompt.metadata.git, single.metadata.git);
+            assert_eq!(prompt.metadata.template, single.metadata.template);
+        }
+    }
+
+    #[test]
+    fn test_latest_scores_and_metrics_in_bulk() {
+        let db = Database::in_memory().unwrap();
+        let scored = Prompt::new("Scored twice".to_string());
+        let unscored = Prompt::new("Never scored".to_string());
+        db.create_prompt(&scored).unwrap();
+        db.create_prompt(&unscored).unwrap();
+
+        for (total, age) in [(60.0, 2), (85.0, 1)] {
+            db.save_quality_score(&QualityScore {
+                prompt_id: scored.id.clone(),
+                total_score: total,
+                clarity: total,
+                completeness: total,
+                specificity: total,
+                guidance: total,
+                analyzed_at: Utc::now() - chrono::Duration::hours(age),
+            })
+            .unwrap();
+            db.save_efficiency_metrics(&EfficiencyMetrics {
+                prompt_id: scored.id.clone(),
+                efficiency_score: total,
+                token_efficiency: total,
+                time_efficiency: total,
+                cost_efficiency: total,
+                calculated_at: Utc::now() - chrono::Duration::hours(age),
+            })
+            .unwrap();
+        }
+
+        let ids = [scored.id.as_str(), unscored.id.as_str()];
+        let scores = db.get_latest_quality_scores(&ids).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(
+            scores[&scored.id].total_score,
+            db.get_quality_score(&scored.id).unwrap().unwrap().total_score
+        );
+        assert_eq!(scores[&scored.id].total_score, 85.0);
+
+        let metrics = db.get_latest_efficiency_metrics(&ids).unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[&scored.id].efficiency_score, 85.0);
+        assert!(db.get_latest_quality_scores(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_prompt_is_atomic() {
+        let db = Database::in_memory().unwrap();
+        db.conn()
+            .execute_batch(
+                "CREATE TRIGGER reject_tags BEFORE INSERT ON prompt_tags
+                 BEGIN SELECT RAISE(ABORT, 'tag rejected'); END;",
+            )
+            .unwrap();
+
+        let mut prompt = Prompt::new("Tagged prompt".to_string());
+        prompt.tags = vec!["rust".to_string()];
+        prompt
+            .metadata
+            .attributes
+            .insert("team".to_string(), "core".to_string());
+        assert!(db.create_prompt(&prompt).is_err());
+
+        // Neither the prompt row nor anything written before the tag remains
+        assert!(db.get_prompt(&prompt.id).unwrap().is_none());
+        assert!(db.get_attributes(&prompt.id).unwrap().is_empty());
+        assert!(!db.in_transaction());
+
+        db.conn().execute_batch("DROP TRIGGER reject_tags").unwrap();
+        db.create_prompt(&prompt).unwrap();
+        assert_eq!(db.get_prompt(&prompt.id).unwrap().unwrap().tags, vec!["rust"]);
+    }
+
+    #[test]
+    fn test_transaction_commit_and_rollback() {
+        let db = Database::in_memory().unwrap();
+        let kept = Prompt::new("Kept prompt".to_string());
+        let dropped = Prompt::new("Dropped prompt".to_string());
+
+        let count = db
+            .transaction(|tx| {
+                tx.create_prompt(&kept)?;
+                tx.set_attribute(&kept.id, "reviewed", "yes")?;
+                tx.count_prompts()
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+
+        let result: Result<()> = db.transaction(|tx| {
+            tx.create_prompt(&dropped)?;
+            tx.delete_prompt(&kept.id)?;
+            Err(PromptTrackingError::InvalidFormat)
+        });
+        assert!(matches!(result, Err(PromptTrackingError::InvalidFormat)));
+        assert!(db.get_prompt(&dropped.id).unwrap().is_none());
+        assert_eq!(
+            db.get_attributes(&kept.id).unwrap().get("reviewed"),
+            Some(&"yes".to_string())
+        );
+
+        // A failed inner transaction rolls back only its own changes
+        db.transaction(|tx| {
+            tx.set_attribute(&kept.id, "outer", "1")?;
+            let inner: Result<()> = tx.transaction(|tx| {
+                tx.set_attribute(&kept.id, "inner", "1")?;
+                Err(PromptTrackingError::InvalidFormat)
+            });
+            assert!(inner.is_err());
+            assert!(tx.in_transaction());
+            Ok(())
+        })
+        .unwrap();
+        let attributes = db.get_attributes(&kept.id).unwrap();
+        assert!(attributes.contains_key("outer"));
+        assert!(!attributes.contains_key("inner"));
+    }
+
+    #[test]
+    fn test_events_wait_for_commit() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl EventObserver for Recorder {
+            fn on_event(&self, event: &Event) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(event.prompt_id().unwrap_or_default().to_string());
+            }
+        }
+
+        let mut db = Database::in_memory().unwrap();
+        let recorder = Arc::new(Recorder::default());
+        db.subscribe(recorder.clone());
+
+        let committed = Prompt::new("Committed prompt".to_string());
+        db.transaction(|tx| {
+            tx.create_prompt(&committed)?;
+            assert!(recorder.0.lock().unwrap().is_empty());
+            let rolled_back: Result<()> = tx.transaction(|tx| {
+                tx.archive_prompt(&committed.id)?;
+                Err(PromptTrackingError::InvalidFormat)
+            });
+            assert!(rolled_back.is_err());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), vec![committed.id.clone()]);
+
+        let _ = db.transaction(|tx| {
+            tx.create_prompt(&Prompt::new("Rolled back".to_string()))?;
+            Err::<(), _>(PromptTrackingError::InvalidFormat)
+        });
+        assert_eq!(recorder.0.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_database_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Database>();
+    }
+
+    #[test]
+    fn test_concurrent_access() {
+        use std::sync::mpsc;
+        use std::thread;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            path: temp_dir.path().join("prompts.db").display().to_string(),
+            ..Config::default().database
+        };
+        let db = Arc::new(Database::from_config(&config).unwrap());
+        let mode: String = db
+            .conn()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        db.create_prompt(&Prompt::new("Existing prompt".to_string()))
+            .unwrap();
+
+        // A reader on another thread doesn't wait for an open transaction
+        // and sees only committed rows
+        let (opened, wait_open) = mpsc::channel();
+        let (read, wait_read) = mpsc::channel();
+        let writer = {
+            let db = Arc::clone(&db);
+            thread::spawn(move || {
+                db.transaction(|tx| {
+                    tx.create_prompt(&Prompt::new("Uncommitted prompt".to_string()))?;
+                    opened.send(()).unwrap();
+                    wait_read.recv().unwrap();
+                    Ok(())
+                })
+            })
+        };
+        wait_open.recv().unwrap();
+        assert_eq!(db.count_prompts().unwrap(), 1);
+        read.send(()).unwrap();
+        writer.join().unwrap().unwrap();
+        assert_eq!(db.count_prompts().unwrap(), 2);
+
+        // Threads sharing one handle and a second handle on the same file,
+        // standing in for the daemon and a CLI command, all get through
+        let other = Arc::new(Database::from_config(&config).unwrap());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let db = if i % 2 == 0 {
+                    Arc::clone(&db)
+                } else {
+                    Arc::clone(&other)
+                };
+                thread::spawn(move || {
+                    for n in 0..25 {
+                        let prompt = Prompt::new(format!("Prompt {} from thread {}", n, i));
+                        db.create_prompt(&prompt)?;
+                        db.list_prompts(&PromptFilter::default())?;
+                    }
+                    Ok::<_, PromptTrackingError>(())
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        assert_eq!(other.count_prompts().unwrap(), 102);
+    }
+
+    #[test]
+    fn test_lifecycle_events() {
+        use crate::events::EventKind;
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<(EventKind, Option<String>)>>);
+
+        impl EventObserver for Recorder {
+            fn on_event(&self, event: &Event) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((event.kind(), event.prompt_id().map(String::from)));
+            }
+        }
+
+        let mut db = Database::in_memory().unwrap();
+        let recorder = Arc::new(Recorder::default());
+        db.subscribe(recorder.clone());
+
+        let mut prompt = Prompt::new("Observed prompt".to_string());
+        db.create_prompt(&prompt).unwrap();
+        prompt.category = Some("testing".to_string());
+        db.update_prompt(&prompt).unwrap();
+        db.save_quality_score(&QualityScore {
+            prompt_id: prompt.id.clone(),
+            total_score: 91.0,
+            clarity: 90.0,
+            completeness: 90.0,
+            specificity: 90.0,
+            guidance: 95.0,
+            analyzed_at: Utc::now(),
+        })
+        .unwrap();
+        db.archive_prompt(&prompt.id).unwrap();
+        db.unarchive_prompt(&prompt.id).unwrap();
+        db.delete_prompt(&prompt.id).unwrap();
+
+        let kinds: Vec<EventKind> = recorder.0.lock().unwrap().iter().map(|e| e.0).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                EventKind::Captured,
+                EventKind::Updated,
+                EventKind::Analyzed,
+                EventKind::Archived,
+                EventKind::Updated,
+                EventKind::Deleted,
+            ]
+        );
+        assert!(recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|e| e.1.as_deref() == Some(prompt.id.as_str())));
+
+        db.unsubscribe_all();
+        db.create_prompt(&Prompt::new("Unobserved".to_string()))
+            .unwrap();
+        assert_eq!(recorder.0.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_file_actions() {
+        let db = Database::in_memory().unwrap();
+
+        let moved = db
+            .record_file_action(
+                "p1",
+                PostCaptureAction::Move,
+                "/w/a.md",
+                Some("/w/processed/a.md"),
+                None,
+            )
+            .unwrap();
+        let deleted = db
+            .record_file_action("p2", PostCaptureAction::Delete, "/w/b.md", None, Some(b"b"))
+            .unwrap();
+
+        let actions = db.list_file_actions(10).unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].id, deleted);
+        assert_eq!(actions[0].content.as_deref(), Some(&b"b"[..]));
+        assert_eq!(actions[1].action, PostCaptureAction::Move);
+        assert_eq!(actions[1].destination.as_deref(), Some("/w/processed/a.md"));
+        assert_eq!(db.list_file_actions(1).unwrap().len(), 1);
+
+        db.mark_file_action_undone(moved).unwrap();
+        let action = db.get_file_action(moved).unwrap().unwrap();
+        assert!(action.undone_at.is_some());
+        assert!(db.get_file_action(99).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_forget_watched_file() {
+        let db = Database::in_memory().unwrap();
+        let prompt = Prompt::new("Moved content".to_string());
+        db.create_prompt(&prompt).unwrap();
+
+        db.set_watched_file("/w/a.md", &prompt.id, "h1").unwrap();
+        db.set_watch_checkpoint(&WatchCheckpoint {
+            path: "/w/a.md".to_string(),
+            size: 4,
+            mtime_ns: 1,
+            file_hash: "f1".to_string(),
+        })
+        .unwrap();
+
+        db.forget_watched_file("/w/a.md").unwrap();
+        assert!(db.get_watched_file("/w/a.md").unwrap().is_none());
+        assert!(db.get_watch_checkpoint("/w/a.md").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrate_on_open_backs_up_first() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            path: temp_dir.path().join("prompts.db").display().to_string(),
+            ..Config::default().database
+        };
+
+        {
+            let db = Database::from_config(&config).unwrap();
+            db.create_prompt(&Prompt::new("Prompt from an older schema".to_string()))
+                .unwrap();
+            db.migrate_to(10).unwrap();
+            // Older releases leave no pin behind
+            db.conn().execute("DELETE FROM schema_pin", []).unwrap();
+        }
+        {
+            let db = Database::open_unmigrated(&config).unwrap();
+            assert_eq!(db.schema_version().unwrap(), 10);
+            assert_eq!(
+                db.migration_plan(CURRENT_VERSION).unwrap().len(),
+                (CURRENT_VERSION - 10) as usize
+            );
+        }
+
+        let db = Database::from_config(&config).unwrap();
+        assert_eq!(db.schema_version().unwrap(), CURRENT_VERSION);
+        assert_eq!(db.count_prompts().unwrap(), 1);
+
+        let backups = BackupManager::from_config(&config).list().unwrap();
+        assert_eq!(backups.len(), 1);
+        let old = Connection::open(&backups[0].path).unwrap();
+        assert_eq!(MigrationManager::new(&old).current_version().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_pinned_schema_is_not_migrated_on_open() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = DatabaseConfig {
+            path: temp_dir.path().join("prompts.db").display().to_string(),
+            ..Config::default().database
+        };
+
+        Database::from_config(&config)
+            .unwrap()
+            .migrate_to(11)
+            .unwrap();
+
+        let err = Database::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("pinned at v11"));
+
+        let db = Database::open_unmigrated(&config).unwrap();
+        assert_eq!(db.schema_version().unwrap(), 11);
+        assert_eq!(db.pinned_version().unwrap(), Some(11));
+        assert!(BackupManager::from_config(&config).list().unwrap().is_empty());
+
+        db.migrate_to(CURRENT_VERSION).unwrap();
+        drop(db);
+        let db = Database::from_config(&config).unwrap();
+        assert_eq!(db.pinned_version().unwrap(), None);
+    }
+
+    #[test]
+    fn test_encryption_at_rest() {
+        use crate::encryption::generate_key_file;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key_config = |name: &str| {
+            let path = temp_dir.path().join(name);
+            generate_key_file(&path).unwrap();
+            EncryptionConfig {
+                key_file: Some(path.display().to_string()),
+                ..EncryptionConfig::default()
+            }
+        };
+        let mut config = DatabaseConfig {
+            path: temp_dir.path().join("prompts.db").display().to_string(),
+            encryption: key_config("db.key"),
+            ..Config::default().database
+        };
+
+        let db = Database::from_config(&config).unwrap();
+        let mut prompt = Prompt::new("Draft the Q3 pricing memo".to_string());
+        prompt.metadata.context = Some("confidential".to_string());
+        db.create_prompt(&prompt).unwrap();
+        db.save_version(&prompt).unwrap();
+        assert!(!db.is_encrypted());
+
+        let raw = |db: &Database| -> String {
+            db.conn()
+                .query_row("SELECT content FROM prompts LIMIT 1", [], |row| row.get(0))
+                .unwrap()
+        };
+        let material = KeyMaterial::from_config(&config.encryption)
+            .unwrap()
+            .unwrap();
+        assert_eq!(db.encrypt(&material).unwrap(), 3);
+        assert!(db.is_encrypted());
+        assert!(encryption::is_sealed_text(&raw(&db)));
+        assert!(db.encrypt(&material).is_err());
+
+        // The full-text index holds no ciphertext and stays empty
+        let indexed = |db: &Database| -> i64 {
+            db.conn()
+                .query_row("SELECT COUNT(*) FROM prompts_fts_docsize", [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(indexed(&db), 0);
+        assert!(db.search_index_in_sync().unwrap());
+
+        // Reads, search and new writes go through the key
+        let loaded = db.get_prompt(&prompt.id).unwrap().unwrap();
+        assert_eq!(loaded.content, prompt.content);
+        assert_eq!(loaded.metadata.context.as_deref(), Some("confidential"));
+        assert_eq!(db.search_prompts("pricing").unwrap().len(), 1);
+        assert_eq!(
+            db.get_version_history(&prompt.id).unwrap()[0].content,
+            prompt.content
+        );
+        db.create_prompt(&Prompt::new("Summarize the board deck".to_string()))
+            .unwrap();
+        let backup = temp_dir.path().join("backup.db");
+        db.backup_to(&backup).unwrap();
+        assert_eq!(indexed(&db), 0);
+        drop(db);
+
+        // Reopening needs the key
+        let mut without_key = config.clone();
+        without_key.encryption = EncryptionConfig {
+            passphrase_env: "PROMPT_TRACKING_TEST_UNSET".to_string(),
+            ..EncryptionConfig::default()
+        };
+        assert!(Database::from_config(&without_key).is_err());
+        let db = Database::from_config(&config).unwrap();
+        assert_eq!(db.count_prompts().unwrap(), 2);
+
+        // After a rekey only the new key opens it, and backups under the
+        // old key are refused
+        let old = db.encryption_params().unwrap();
+        let new_key = key_config("new.key");
+        db.rekey(&KeyMaterial::from_config(&new_key).unwrap().unwrap())
+            .unwrap();
+        assert_ne!(db.encryption_params().unwrap(), old);
+        assert_eq!(db.search_prompts("board deck").unwrap().len(), 1);
+        assert!(db.restore_from(&backup).is_err());
+        drop(db);
+        assert!(matches!(
+            Database::from_config(&config),
+            Err(PromptTrackingError::EncryptionError(_))
+        ));
+
+        config.encryption = new_key;
+        let db = Database::from_config(&config).unwrap();
+        assert_eq!(db.decrypt().unwrap(), 4);
+        assert!(!db.is_encrypted());
+        assert!(!encryption::is_sealed_text(&raw(&db)));
+        assert_eq!(indexed(&db), 2);
+        assert!(db.search_index_in_sync().unwrap());
+        db.create_prompt(&Prompt::new("Indexed again".to_string()))
+            .unwrap();
+        assert_eq!(indexed(&db), 3);
+        drop(db);
+
+        // Requiring encryption refuses a plaintext database with data, but
+        // encrypts a new one
+        config.encryption.enabled = true;
+        assert!(Database::from_config(&config).is_err());
+        config.path = temp_dir.path().join("new.db").display().to_string();
+        let db = Database::from_config(&config).unwrap();
+        assert!(db.is_encrypted());
+    }
 }
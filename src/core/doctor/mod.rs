@@ -0,0 +1,254 @@
+//! Database health checks and repair
+//!
+//! [`examine`] runs `SQLite`'s integrity check, recomputes content hashes,
+//! looks for rows left behind by deleted prompts and tags, for prompts
+//! sharing a content hash and for a full-text index out of step with the
+//! prompts table. With `fix` set it repairs what can be repaired in one
+//! transaction: hashes are recomputed, orphaned rows deleted and the
+//! index rebuilt. Duplicates are only reported, since choosing which
+//! prompt to keep is up to the user, and nothing is touched when the file
+//! itself fails the integrity check.
+
+use crate::database::Database;
+use crate::Result;
+
+/// A health check run by [`examine`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// `SQLite`'s `integrity_check`
+    Integrity,
+    /// Stored content hashes match the prompt content
+    ContentHashes,
+    /// No tag, tag link or score rows without their prompt or tag
+    Orphans,
+    /// No two prompts share a content hash
+    DuplicateHashes,
+    /// The full-text index matches the prompts table
+    SearchIndex,
+}
+
+impl Check {
+    /// Every check, in the order they run
+    pub const ALL: [Self; 5] = [
+        Self::Integrity,
+        Self::ContentHashes,
+        Self::Orphans,
+        Self::DuplicateHashes,
+        Self::SearchIndex,
+    ];
+
+    /// Human-readable name
+    pub const fn label(&self) -> &'static str {
+        match self {
+            Self::Integrity => "Database integrity",
+            Self::ContentHashes => "Content hashes",
+            Self::Orphans => "Orphaned rows",
+            Self::DuplicateHashes => "Duplicate prompts",
+            Self::SearchIndex => "Search index",
+        }
+    }
+}
+
+/// A problem found by a check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// Check that found the problem
+    pub check: Check,
+    /// What is wrong, for display
+    pub description: String,
+    /// Whether the problem was repaired
+    pub fixed: bool,
+}
+
+/// Outcome of [`examine`]
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    /// Problems found, in the order the checks ran
+    pub issues: Vec<Issue>,
+}
+
+impl DoctorReport {
+    /// Issues found by one check
+    pub fn issues_for(&self, check: Check) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(move |issue| issue.check == check)
+    }
+
+    /// Number of issues left unrepaired
+    pub fn unresolved(&self) -> usize {
+        self.issues.iter().filter(|issue| !issue.fixed).count()
+    }
+
+    /// Whether no issue was found
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn push(&mut self, check: Check, description: String, fixed: bool) {
+        self.issues.push(Issue {
+            check,
+            description,
+            fixed,
+        });
+    }
+}
+
+/// Run every check, repairing what can be repaired when `fix` is set
+pub fn examine(db: &Database, fix: bool) -> Result<DoctorReport> {
+    let mut report = DoctorReport::default();
+
+    let problems = db.integrity_check()?;
+    let sound = problems.is_empty();
+    for problem in problems {
+        report.push(Check::Integrity, problem, false);
+    }
+    let fix = fix && sound;
+
+    db.transaction(|tx| {
+        for (id, expected) in tx.content_hash_mismatches()? {
+            if fix {
+                tx.set_content_hash(&id, &expected)?;
+            }
+            report.push(
+                Check::ContentHashes,
+                format!("Prompt {id} has a stale content hash"),
+                fix,
+            );
+        }
+
+        let orphans = if fix {
+            tx.remove_orphans()?
+        } else {
+            tx.count_orphans()?
+        };
+        for (table, count) in orphans {
+            report.push(
+                Check::Orphans,
+                format!("{count} orphaned rows in {table}"),
+                fix,
+            );
+        }
+
+        for (hash, ids) in tx.duplicate_hashes()? {
+            report.push(
+                Check::DuplicateHashes,
+                format!(
+                    "{} prompts share content hash {}: {}",
+                    ids.len(),
+                    &hash[..hash.len().min(12)],
+                    ids.join(", ")
+                ),
+                false,
+            );
+        }
+
+        if !tx.search_index_in_sync()? {
+            if fix {
+                tx.rebuild_search_index()?;
+            }
+            report.push(
+                Check::SearchIndex,
+                "Full-text index is out of sync with the prompts table".to_string(),
+                fix,
+            );
+        }
+
+        Ok(())
+    })?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Prompt;
+    use crate::utils::calculate_hash;
+
+    fn prompt(content: &str) -> Prompt {
+        let mut prompt = Prompt::new(content.to_string());
+        prompt.content_hash = calculate_hash(content);
+        prompt
+    }
+
+    #[test]
+    fn test_healthy_database() {
+        let db = Database::in_memory().unwrap();
+        db.create_prompt(&prompt("Explain lifetimes")).unwrap();
+
+        let report = examine(&db, false).unwrap();
+        assert!(report.is_healthy(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_reports_and_fixes_issues() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("prompts.db");
+        let db = Database::new(path.to_str().unwrap()).unwrap();
+        let mut stale = prompt("Explain lifetimes");
+        stale.content_hash = "stale".to_string();
+        stale.tags = vec!["rust".to_string()];
+        db.create_prompt(&stale).unwrap();
+
+        db.create_prompt(&prompt("Same text")).unwrap();
+        db.create_prompt(&prompt("Same text")).unwrap();
+
+        // Rows written without foreign key enforcement, and an index entry
+        // without a prompt behind it
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                r#"
+                PRAGMA foreign_keys = OFF;
+                INSERT INTO tags (name) VALUES ('parsing');
+                INSERT INTO prompt_tags (prompt_id, tag_id)
+                    SELECT 'deleted-prompt', id FROM tags WHERE name = 'rust';
+                INSERT INTO quality_scores
+                    (prompt_id, total_score, clarity, completeness, specificity, guidance, analyzed_at)
+                    VALUES ('deleted-prompt', 50, 50, 50, 50, 50, '2024-01-01T00:00:00Z');
+                INSERT INTO prompts_fts(rowid, content, category) VALUES (9999, 'ghost', '');
+                "#,
+            )
+            .unwrap();
+
+        let report = examine(&db, false).unwrap();
+        assert_eq!(report.issues_for(Check::Integrity).count(), 0);
+        assert_eq!(report.issues_for(Check::ContentHashes).count(), 1);
+        let orphans: Vec<_> = report
+            .issues_for(Check::Orphans)
+            .map(|issue| issue.description.as_str())
+            .collect();
+        assert_eq!(
+            orphans,
+            vec![
+                "1 orphaned rows in prompt_tags",
+                "1 orphaned rows in tags",
+                "1 orphaned rows in quality_scores"
+            ]
+        );
+        assert_eq!(report.issues_for(Check::DuplicateHashes).count(), 1);
+        assert_eq!(report.issues_for(Check::SearchIndex).count(), 1);
+        assert_eq!(report.unresolved(), report.issues.len());
+
+        let report = examine(&db, true).unwrap();
+        assert_eq!(report.unresolved(), 1);
+        assert!(
+            !report
+                .issues_for(Check::DuplicateHashes)
+                .next()
+                .unwrap()
+                .fixed
+        );
+
+        let report = examine(&db, false).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].check, Check::DuplicateHashes);
+        assert_eq!(
+            db.get_prompt(&stale.id).unwrap().unwrap().content_hash,
+            calculate_hash("Explain lifetimes")
+        );
+        assert_eq!(
+            db.get_prompt(&stale.id).unwrap().unwrap().tags,
+            vec!["rust".to_string()]
+        );
+    }
+}
@@ -0,0 +1,648 @@
+//! Encryption at rest
+//!
+//! Prompt text is sealed with AES-256-GCM before it is stored, so the
+//! database file, its backups and export bundles do not reveal it without
+//! the key. The key never comes from the configuration: it is either read
+//! from a key file holding 32 random bytes as hex, expanded with
+//! HKDF-SHA256, or derived from a passphrase in an environment variable
+//! with PBKDF2-HMAC-SHA256. Every database and bundle has its own random
+//! salt, stored with the ciphertext in [`KeyParams`].
+//!
+//! Sealed text is stored as `enc:v1:` followed by the base64 of the nonce,
+//! ciphertext and tag; sealed bytes carry the same prefix in raw form.
+//! Values without the prefix are plaintext, which lets a database be
+//! encrypted or decrypted in place. Only prompt text is sealed: tags,
+//! categories, scores and content hashes stay readable so filtering and
+//! duplicate detection keep working.
+//!
+//! That has two consequences. A `content_hash` is an unsalted hash of the
+//! plaintext, so anyone holding the file can tell which prompts are
+//! identical, and can confirm whether a prompt matches text they already
+//! know. And the full-text index is not maintained while the database is
+//! encrypted, since it could only index ciphertext: searches still match
+//! the decrypted text, but without full-text ranking.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{hkdf, pbkdf2};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write as _};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+use crate::config::EncryptionConfig;
+use crate::{PromptTrackingError, Result};
+
+/// Marks a sealed value
+pub const SEALED_PREFIX: &str = "enc:v1:";
+
+/// PBKDF2 rounds for new passphrase-derived keys
+pub const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Length of keys and key files in bytes
+pub const KEY_LEN: usize = 32;
+
+/// Plaintext sealed into a database's check value to detect a wrong key
+const CHECK_PLAINTEXT: &[u8] = b"prompt-tracking key check";
+
+/// `format` field of export bundles
+const BUNDLE_FORMAT: &str = "prompt-tracking-bundle";
+
+/// How a key is derived from its material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kdf {
+    /// Key file bytes expanded with HKDF-SHA256
+    #[serde(rename = "hkdf-sha256")]
+    Hkdf,
+    /// Passphrase stretched with PBKDF2-HMAC-SHA256
+    #[serde(rename = "pbkdf2-sha256")]
+    Pbkdf2,
+}
+
+impl Kdf {
+    /// Name stored with the key parameters
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Hkdf => "hkdf-sha256",
+            Self::Pbkdf2 => "pbkdf2-sha256",
+        }
+    }
+
+    /// What the user has to supply for this kind of key
+    const fn source(self) -> &'static str {
+        match self {
+            Self::Hkdf => "a key file",
+            Self::Pbkdf2 => "a passphrase",
+        }
+    }
+}
+
+impl fmt::Display for Kdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Kdf {
+    type Err = PromptTrackingError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hkdf-sha256" => Ok(Self::Hkdf),
+            "pbkdf2-sha256" => Ok(Self::Pbkdf2),
+            _ => Err(PromptTrackingError::EncryptionError(format!(
+                "Unknown key derivation: {s}"
+            ))),
+        }
+    }
+}
+
+/// Secret a key is derived from: key file bytes or a passphrase
+pub struct KeyMaterial {
+    kdf: Kdf,
+    secret: Zeroizing<Vec<u8>>,
+}
+
+impl fmt::Debug for KeyMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyMaterial")
+            .field("kdf", &self.kdf)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyMaterial {
+    /// Material from a passphrase
+    pub fn passphrase(passphrase: &str) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(PromptTrackingError::EncryptionError(
+                "Passphrase is empty".to_string(),
+            ));
+        }
+        Ok(Self {
+            kdf: Kdf::Pbkdf2,
+            secret: Zeroizing::new(passphrase.as_bytes().to_vec()),
+        })
+    }
+
+    /// Material from raw key bytes
+    pub fn key(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != KEY_LEN {
+            return Err(PromptTrackingError::EncryptionError(format!(
+                "Key must be {} bytes, got {}",
+                KEY_LEN,
+                bytes.len()
+            )));
+        }
+        Ok(Self {
+            kdf: Kdf::Hkdf,
+            secret: Zeroizing::new(bytes.to_vec()),
+        })
+    }
+
+    /// Read a key file holding the key as hex
+    ///
+    /// Files readable by other users are rejected on Unix, as SSH does for
+    /// private keys.
+    pub fn from_key_file(path: &Path) -> Result<Self> {
+        let contents = Zeroizing::new(fs::read_to_string(path).map_err(|e| {
+            PromptTrackingError::EncryptionError(format!(
+                "Failed to read key file {}: {}",
+                path.display(),
+                e
+            ))
+        })?);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path)?.permissions().mode();
+            if mode & 0o077 != 0 {
+                return Err(PromptTrackingError::EncryptionError(format!(
+                    "Key file {} is accessible by other users (mode {:o}); run chmod 600",
+                    path.display(),
+                    mode & 0o777
+                )));
+            }
+        }
+
+        let bytes = Zeroizing::new(decode_hex(contents.trim()).ok_or_else(|| {
+            PromptTrackingError::EncryptionError(format!(
+                "Key file {} must contain {} hex characters",
+                path.display(),
+                KEY_LEN * 2
+            ))
+        })?);
+        Self::key(&bytes)
+    }
+
+    /// Passphrase from an environment variable, if it is set
+    pub fn from_env(var: &str) -> Result<Option<Self>> {
+        match std::env::var(var) {
+            Ok(passphrase) => Self::passphrase(&Zeroizing::new(passphrase)).map(Some),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(PromptTrackingError::EncryptionError(format!(
+                "Failed to read {var}: {e}"
+            ))),
+        }
+    }
+
+    /// Material from the configured source: the key file if one is set,
+    /// otherwise the passphrase variable
+    ///
+    /// Returns `None` when neither is available.
+    pub fn from_config(config: &EncryptionConfig) -> Result<Option<Self>> {
+        config.key_file.as_ref().map_or_else(
+            || Self::from_env(&config.passphrase_env),
+            |path| {
+                let path = shellexpand::tilde(path).to_string();
+                Self::from_key_file(Path::new(&path)).map(Some)
+            },
+        )
+    }
+
+    /// How a key is derived from this material
+    pub const fn kdf(&self) -> Kdf {
+        self.kdf
+    }
+}
+
+/// Where the key was expected to come from, for error messages
+pub fn describe_source(config: &EncryptionConfig) -> String {
+    config.key_file.as_ref().map_or_else(
+        || format!("the {} environment variable", config.passphrase_env),
+        |path| format!("key file {path}"),
+    )
+}
+
+/// Write a new random key to `path`, readable only by the owner
+///
+/// Fails if the file already exists, so a key in use is never overwritten.
+pub fn generate_key_file(path: &Path) -> Result<()> {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    SystemRandom::new().fill(&mut key[..]).map_err(|_| {
+        PromptTrackingError::EncryptionError("Failed to generate a random key".to_string())
+    })?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| {
+        PromptTrackingError::EncryptionError(format!(
+            "Failed to create key file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let hex = Zeroizing::new(encode_hex(&key[..]));
+    writeln!(file, "{}", hex.as_str())?;
+    Ok(())
+}
+
+/// Salt and derivation settings stored next to encrypted data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyParams {
+    /// How the key is derived from its material
+    pub kdf: Kdf,
+    /// Base64 salt
+    pub salt: String,
+    /// PBKDF2 rounds; unused for key files
+    pub iterations: u32,
+}
+
+impl KeyParams {
+    /// Fresh parameters with a random salt
+    pub fn generate(kdf: Kdf) -> Result<Self> {
+        let mut salt = [0u8; 16];
+        SystemRandom::new().fill(&mut salt).map_err(|_| {
+            PromptTrackingError::EncryptionError("Failed to generate a salt".to_string())
+        })?;
+        Ok(Self {
+            kdf,
+            salt: BASE64.encode(salt),
+            iterations: match kdf {
+                Kdf::Hkdf => 0,
+                Kdf::Pbkdf2 => PBKDF2_ITERATIONS,
+            },
+        })
+    }
+}
+
+/// An AES-256-GCM key with the parameters it was derived with
+pub struct Cipher {
+    key: LessSafeKey,
+    params: KeyParams,
+    rng: SystemRandom,
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cipher")
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Cipher {
+    /// Derive the key for `params` from `material`
+    pub fn derive(material: &KeyMaterial, params: &KeyParams) -> Result<Self> {
+        if material.kdf != params.kdf {
+            return Err(PromptTrackingError::EncryptionError(format!(
+                "Data was encrypted with {}, but {} was given",
+                params.kdf.source(),
+                material.kdf.source()
+            )));
+        }
+        let salt = BASE64
+            .decode(&params.salt)
+            .map_err(|e| PromptTrackingError::EncryptionError(format!("Invalid salt: {e}")))?;
+
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        match params.kdf {
+            Kdf::Hkdf => {
+                hkdf::Salt::new(hkdf::HKDF_SHA256, &salt)
+                    .extract(&material.secret)
+                    .expand(&[b"prompt-tracking"], hkdf::HKDF_SHA256)
+                    .and_then(|okm| okm.fill(&mut key[..]))
+                    .map_err(|_| {
+                        PromptTrackingError::EncryptionError("Failed to derive key".to_string())
+                    })?;
+            }
+            Kdf::Pbkdf2 => {
+                let iterations = NonZeroU32::new(params.iterations).ok_or_else(|| {
+                    PromptTrackingError::EncryptionError("Invalid PBKDF2 iterations".to_string())
+                })?;
+                pbkdf2::derive(
+                    pbkdf2::PBKDF2_HMAC_SHA256,
+                    iterations,
+                    &salt,
+                    &material.secret,
+                    &mut key[..],
+                );
+            }
+        }
+
+        let key = UnboundKey::new(&AES_256_GCM, &key[..]).map_err(|_| {
+            PromptTrackingError::EncryptionError("Failed to create key".to_string())
+        })?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            params: params.clone(),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Salt and derivation settings the key was made with
+    pub const fn params(&self) -> &KeyParams {
+        &self.params
+    }
+
+    /// Encrypt bytes into nonce, ciphertext and tag
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| {
+            PromptTrackingError::EncryptionError("Failed to generate a nonce".to_string())
+        })?;
+
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .map_err(|_| PromptTrackingError::EncryptionError("Failed to encrypt".to_string()))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&in_out);
+        Ok(sealed)
+    }
+
+    /// Decrypt the output of [`Cipher::seal`]
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let wrong_key = || {
+            PromptTrackingError::EncryptionError(
+                "Failed to decrypt: wrong key or corrupted data".to_string(),
+            )
+        };
+        if sealed.len() < NONCE_LEN {
+            return Err(wrong_key());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| wrong_key())?;
+
+        let mut in_out = ciphertext.to_vec();
+        let len = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| wrong_key())?
+            .len();
+        in_out.truncate(len);
+        Ok(in_out)
+    }
+
+    /// Seal text into its prefixed base64 form
+    pub fn seal_text(&self, text: &str) -> Result<String> {
+        Ok(format!(
+            "{}{}",
+            SEALED_PREFIX,
+            BASE64.encode(self.seal(text.as_bytes())?)
+        ))
+    }
+
+    /// Open sealed text; text without the prefix is returned as is
+    pub fn open_text(&self, text: &str) -> Result<String> {
+        let Some(encoded) = text.strip_prefix(SEALED_PREFIX) else {
+            return Ok(text.to_string());
+        };
+        let sealed = BASE64.decode(encoded).map_err(|e| {
+            PromptTrackingError::EncryptionError(format!("Invalid sealed value: {e}"))
+        })?;
+        String::from_utf8(self.open(&sealed)?).map_err(|e| {
+            PromptTrackingError::EncryptionError(format!("Decrypted text is not UTF-8: {e}"))
+        })
+    }
+
+    /// Seal bytes behind the raw prefix
+    pub fn seal_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut sealed = SEALED_PREFIX.as_bytes().to_vec();
+        sealed.extend(self.seal(bytes)?);
+        Ok(sealed)
+    }
+
+    /// Open sealed bytes; bytes without the prefix are returned as is
+    pub fn open_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        bytes
+            .strip_prefix(SEALED_PREFIX.as_bytes())
+            .map_or_else(|| Ok(bytes.to_vec()), |sealed| self.open(sealed))
+    }
+
+    /// A value stored with the data that only this key opens
+    pub fn check_value(&self) -> Result<String> {
+        Ok(BASE64.encode(self.seal(CHECK_PLAINTEXT)?))
+    }
+
+    /// Fail unless `check` came from [`Cipher::check_value`] with this key
+    pub fn verify(&self, check: &str) -> Result<()> {
+        let opened = BASE64
+            .decode(check)
+            .ok()
+            .and_then(|sealed| self.open(&sealed).ok());
+        if opened.as_deref() == Some(CHECK_PLAINTEXT) {
+            Ok(())
+        } else {
+            Err(PromptTrackingError::EncryptionError(
+                "Wrong encryption key".to_string(),
+            ))
+        }
+    }
+}
+
+/// Whether stored text is sealed
+pub fn is_sealed_text(text: &str) -> bool {
+    text.starts_with(SEALED_PREFIX)
+}
+
+/// Whether stored bytes are sealed
+pub fn is_sealed_bytes(bytes: &[u8]) -> bool {
+    bytes.starts_with(SEALED_PREFIX.as_bytes())
+}
+
+/// An encrypted export: the JSON export sealed under its own salt
+#[derive(Debug, Serialize, Deserialize)]
+struct Bundle {
+    format: String,
+    version: u32,
+    #[serde(flatten)]
+    params: KeyParams,
+    /// Base64 nonce, ciphertext and tag
+    data: String,
+}
+
+/// Seal an export into a bundle that `material` opens
+pub fn seal_bundle(export: &str, material: &KeyMaterial) -> Result<String> {
+    let cipher = Cipher::derive(material, &KeyParams::generate(material.kdf())?)?;
+    let bundle = Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: 1,
+        data: BASE64.encode(cipher.seal(export.as_bytes())?),
+        params: cipher.params,
+    };
+    serde_json::to_string_pretty(&bundle)
+        .map_err(|e| PromptTrackingError::SerializationError(e.to_string()))
+}
+
+/// Whether `data` is an encrypted export bundle rather than a plain export
+pub fn is_bundle(data: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(data)
+        .is_ok_and(|value| value.get("format").and_then(|f| f.as_str()) == Some(BUNDLE_FORMAT))
+}
+
+/// Open a bundle written by [`seal_bundle`], returning the export
+pub fn open_bundle(data: &str, material: &KeyMaterial) -> Result<String> {
+    let bundle: Bundle = serde_json::from_str(data)
+        .map_err(|e| PromptTrackingError::SerializationError(e.to_string()))?;
+    if bundle.format != BUNDLE_FORMAT || bundle.version != 1 {
+        return Err(PromptTrackingError::EncryptionError(format!(
+            "Unsupported bundle: {} version {}",
+            bundle.format, bundle.version
+        )));
+    }
+
+    let cipher = Cipher::derive(material, &bundle.params)?;
+    let sealed = BASE64
+        .decode(&bundle.data)
+        .map_err(|e| PromptTrackingError::EncryptionError(format!("Invalid bundle data: {e}")))?;
+    String::from_utf8(cipher.open(&sealed)?)
+        .map_err(|e| PromptTrackingError::EncryptionError(format!("Bundle is not UTF-8: {e}")))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn key_cipher() -> Cipher {
+        let material = KeyMaterial::key(&[7u8; KEY_LEN]).unwrap();
+        Cipher::derive(&material, &KeyParams::generate(Kdf::Hkdf).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = key_cipher();
+
+        let sealed = cipher.seal_text("Refactor the billing service").unwrap();
+        assert!(is_sealed_text(&sealed));
+        assert!(!sealed.contains("billing"));
+        assert_ne!(
+            sealed,
+            cipher.seal_text("Refactor the billing service").unwrap()
+        );
+        assert_eq!(
+            cipher.open_text(&sealed).unwrap(),
+            "Refactor the billing service"
+        );
+        assert_eq!(cipher.open_text("plain").unwrap(), "plain");
+
+        let bytes = cipher.seal_bytes(b"\x00\x01file").unwrap();
+        assert!(is_sealed_bytes(&bytes));
+        assert_eq!(cipher.open_bytes(&bytes).unwrap(), b"\x00\x01file");
+
+        // Another salt gives another key
+        assert!(key_cipher().open_text(&sealed).is_err());
+        let mut tampered = sealed.into_bytes();
+        let last = tampered.len() - 2;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert!(cipher
+            .open_text(&String::from_utf8(tampered).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_check_value() {
+        let cipher = key_cipher();
+        let check = cipher.check_value().unwrap();
+        cipher.verify(&check).unwrap();
+        assert!(key_cipher().verify(&check).is_err());
+    }
+
+    #[test]
+    fn test_passphrase_derivation() {
+        let params = KeyParams {
+            kdf: Kdf::Pbkdf2,
+            salt: BASE64.encode([1u8; 16]),
+            iterations: 1_000,
+        };
+        let a =
+            Cipher::derive(&KeyMaterial::passphrase("correct horse").unwrap(), &params).unwrap();
+        let b =
+            Cipher::derive(&KeyMaterial::passphrase("correct horse").unwrap(), &params).unwrap();
+        let wrong =
+            Cipher::derive(&KeyMaterial::passphrase("battery staple").unwrap(), &params).unwrap();
+
+        let sealed = a.seal_text("prompt").unwrap();
+        assert_eq!(b.open_text(&sealed).unwrap(), "prompt");
+        assert!(wrong.open_text(&sealed).is_err());
+
+        // A key file cannot open data sealed under a passphrase
+        let key = KeyMaterial::key(&[0u8; KEY_LEN]).unwrap();
+        assert!(Cipher::derive(&key, &params).is_err());
+        assert!(KeyMaterial::passphrase("").is_err());
+    }
+
+    #[test]
+    fn test_key_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("keys").join("db.key");
+
+        generate_key_file(&path).unwrap();
+        let material = KeyMaterial::from_key_file(&path).unwrap();
+        assert_eq!(material.kdf(), Kdf::Hkdf);
+        assert_eq!(fs::read_to_string(&path).unwrap().trim().len(), KEY_LEN * 2);
+        assert!(generate_key_file(&path).is_err());
+
+        let config = EncryptionConfig {
+            key_file: Some(path.display().to_string()),
+            ..EncryptionConfig::default()
+        };
+        assert!(KeyMaterial::from_config(&config).unwrap().is_some());
+
+        let bad = temp_dir.path().join("bad.key");
+        fs::write(&bad, "not hex").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&bad, fs::Permissions::from_mode(0o600)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(KeyMaterial::from_key_file(&path).is_err());
+        }
+        assert!(KeyMaterial::from_key_file(&bad).is_err());
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let material = KeyMaterial::key(&[9u8; KEY_LEN]).unwrap();
+        let export = r#"{"prompts":[{"content":"secret roadmap"}]}"#;
+
+        let bundle = seal_bundle(export, &material).unwrap();
+        assert!(is_bundle(&bundle));
+        assert!(!is_bundle(export));
+        assert!(!bundle.contains("secret roadmap"));
+        assert_eq!(open_bundle(&bundle, &material).unwrap(), export);
+
+        let other = KeyMaterial::key(&[8u8; KEY_LEN]).unwrap();
+        assert!(open_bundle(&bundle, &other).is_err());
+    }
+}
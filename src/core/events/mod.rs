@@ -0,0 +1,254 @@
+//! Prompt lifecycle events
+//!
+//! The database raises an [`Event`] whenever a prompt is captured, updated,
+//! analyzed, archived or deleted; callers raise `report_generated` themselves.
+//! Library users observe events by implementing [`EventObserver`] and
+//! registering it with [`Database::subscribe`](crate::database::Database::subscribe).
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::models::{Prompt, QualityScore};
+
+/// Kind of lifecycle event, used to select which events a hook receives
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// See [`Event::Captured`]
+    Captured,
+    /// See [`Event::Updated`]
+    Updated,
+    /// See [`Event::Analyzed`]
+    Analyzed,
+    /// See [`Event::Archived`]
+    Archived,
+    /// See [`Event::Deleted`]
+    Deleted,
+    /// See [`Event::ReportGenerated`]
+    ReportGenerated,
+}
+
+/// Something that happened to a prompt or report
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A new prompt was stored
+    Captured {
+        /// The prompt as stored
+        prompt: Prompt,
+    },
+    /// A stored prompt was changed
+    Updated {
+        /// The prompt after the change
+        prompt: Prompt,
+    },
+    /// A quality score was saved for a prompt
+    Analyzed {
+        /// ID of the scored prompt
+        prompt_id: String,
+        /// The saved score
+        score: QualityScore,
+    },
+    /// A prompt was archived
+    Archived {
+        /// ID of the archived prompt
+        prompt_id: String,
+    },
+    /// A prompt was deleted
+    Deleted {
+        /// ID of the deleted prompt
+        prompt_id: String,
+    },
+    /// A report was generated, and saved if `path` is set
+    ReportGenerated {
+        /// Kind of report, such as `weekly` or `monthly`
+        report_type: String,
+        /// Output format, such as `markdown` or `json`
+        format: String,
+        /// File the report was written to
+        path: Option<String>,
+    },
+}
+
+/// Receives lifecycle events
+///
+/// Observers are called synchronously on the thread that raised the event,
+/// after the change has been stored. They cannot fail the operation, so
+/// errors should be handled or logged by the observer itself.
+pub trait EventObserver: Send + Sync {
+    /// Handle one event
+    fn on_event(&self, event: &Event);
+}
+
+/// Observers registered for lifecycle events
+#[derive(Clone, Default)]
+pub struct EventBus {
+    observers: Vec<Arc<dyn EventObserver>>,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Captured => write!(f, "captured"),
+            Self::Updated => write!(f, "updated"),
+            Self::Analyzed => write!(f, "analyzed"),
+            Self::Archived => write!(f, "archived"),
+            Self::Deleted => write!(f, "deleted"),
+            Self::ReportGenerated => write!(f, "report_generated"),
+        }
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "captured" => Ok(Self::Captured),
+            "updated" => Ok(Self::Updated),
+            "analyzed" => Ok(Self::Analyzed),
+            "archived" => Ok(Self::Archived),
+            "deleted" => Ok(Self::Deleted),
+            "report_generated" => Ok(Self::ReportGenerated),
+            _ => Err(format!("Invalid event: {s}")),
+        }
+    }
+}
+
+impl Event {
+    /// Kind of this event
+    pub const fn kind(&self) -> EventKind {
+        match self {
+            Self::Captured { .. } => EventKind::Captured,
+            Self::Updated { .. } => EventKind::Updated,
+            Self::Analyzed { .. } => EventKind::Analyzed,
+            Self::Archived { .. } => EventKind::Archived,
+            Self::Deleted { .. } => EventKind::Deleted,
+            Self::ReportGenerated { .. } => EventKind::ReportGenerated,
+        }
+    }
+
+    /// ID of the prompt the event is about, if any
+    pub fn prompt_id(&self) -> Option<&str> {
+        match self {
+            Self::Captured { prompt } | Self::Updated { prompt } => Some(&prompt.id),
+            Self::Analyzed { prompt_id, .. }
+            | Self::Archived { prompt_id }
+            | Self::Deleted { prompt_id } => Some(prompt_id),
+            Self::ReportGenerated { .. } => None,
+        }
+    }
+
+    /// Quality score carried by an `analyzed` event
+    pub const fn score(&self) -> Option<f64> {
+        match self {
+            Self::Analyzed { score, .. } => Some(score.total_score),
+            _ => None,
+        }
+    }
+
+    /// JSON payload sent to hooks: the event fields plus a `timestamp`
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(object) = value.as_object_mut() {
+            object.insert(
+                "timestamp".to_string(),
+                serde_json::Value::String(Utc::now().to_rfc3339()),
+            );
+        }
+        value.to_string()
+    }
+}
+
+impl EventBus {
+    /// Register an observer
+    pub fn subscribe(&mut self, observer: Arc<dyn EventObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Remove all observers
+    pub fn clear(&mut self) {
+        self.observers.clear();
+    }
+
+    /// Whether any observer is registered
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// Deliver an event to every observer, in registration order
+    pub fn emit(&self, event: &Event) {
+        for observer in &self.observers {
+            observer.on_event(event);
+        }
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<EventKind>>);
+
+    impl EventObserver for Recorder {
+        fn on_event(&self, event: &Event) {
+            self.0.lock().unwrap().push(event.kind());
+        }
+    }
+
+    #[test]
+    fn test_event_bus_delivers_to_observers() {
+        let recorder = Arc::new(Recorder::default());
+        let mut bus = EventBus::default();
+        assert!(bus.is_empty());
+        bus.subscribe(recorder.clone());
+
+        bus.emit(&Event::Deleted {
+            prompt_id: "p1".to_string(),
+        });
+        bus.emit(&Event::Archived {
+            prompt_id: "p2".to_string(),
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![EventKind::Deleted, EventKind::Archived]
+        );
+
+        bus.clear();
+        bus.emit(&Event::Deleted {
+            prompt_id: "p3".to_string(),
+        });
+        assert_eq!(recorder.0.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = Event::ReportGenerated {
+            report_type: "weekly".to_string(),
+            format: "markdown".to_string(),
+            path: None,
+        };
+        let json: serde_json::Value = serde_json::from_str(&event.to_json()).unwrap();
+        assert_eq!(json["event"], "report_generated");
+        assert_eq!(json["report_type"], "weekly");
+        assert!(json["timestamp"].is_string());
+        assert!(event.prompt_id().is_none());
+
+        assert_eq!("report-generated".parse(), Ok(EventKind::ReportGenerated));
+        assert_eq!(EventKind::ReportGenerated.to_string(), "report_generated");
+        assert!("exploded".parse::<EventKind>().is_err());
+    }
+}
@@ -7,6 +7,10 @@
 //! - `quality:>80` - quality score above 80
 //! - `efficiency:>=70` - efficiency score >= 70
 //! - `date:>2024-01-01` - created after date
+//! - `attr:ticket=PROJ-1` - custom attribute equals value
+//! - `project:cc-tracker` - captured inside this git project
+//! - `branch:main` - captured on this git branch
+//! - `template:code-review` - rendered from this template
 //! - `limit:10` - limit results
 //! - Text without prefix for content search
 
@@ -14,6 +18,7 @@ use chrono::{DateTime, NaiveDate, Utc};
 
 use crate::database::PromptFilter;
 use crate::models::PromptStatus;
+use crate::utils::parse_key_value;
 use crate::{PromptTrackingError, Result};
 
 /// A parsed filter token
@@ -33,6 +38,14 @@ pub enum FilterToken {
     DateFrom(DateTime<Utc>),
     /// Date filter (created_at)
     DateTo(DateTime<Utc>),
+    /// Custom attribute filter (key, value)
+    Attribute(String, String),
+    /// Git project filter
+    Project(String),
+    /// Git branch filter
+    Branch(String),
+    /// Template name filter
+    Template(String),
     /// Limit results
     Limit(usize),
     /// Offset results
@@ -160,6 +173,23 @@ fn parse_token(part: &str) -> Result<Option<FilterToken>> {
                     }
                 }
             }
+            "attr" | "attribute" => {
+                let (key, attr_value) = parse_key_value(value).ok_or_else(|| {
+                    PromptTrackingError::ConfigError(format!(
+                        "Invalid attribute filter '{value}': expected key=value"
+                    ))
+                })?;
+                return Ok(Some(FilterToken::Attribute(key, attr_value)));
+            }
+            "project" | "proj" => {
+                return Ok(Some(FilterToken::Project(value.to_string())));
+            }
+            "branch" => {
+                return Ok(Some(FilterToken::Branch(value.to_string())));
+            }
+            "template" | "tpl" => {
+                return Ok(Some(FilterToken::Template(value.to_string())));
+            }
             "limit" => {
                 let num = value.parse::<usize>().map_err(|e| {
                     PromptTrackingError::ConfigError(format!("Invalid limit: {}", e))
@@ -276,6 +306,18 @@ fn tokens_to_filter(tokens: Vec<FilterToken>) -> Result<PromptFilter> {
             FilterToken::DateTo(date) => {
                 filter.date_to = Some(date);
             }
+            FilterToken::Attribute(key, value) => {
+                filter.attributes.push((key, value));
+            }
+            FilterToken::Project(project) => {
+                filter.project = Some(project);
+            }
+            FilterToken::Branch(branch) => {
+                filter.branch = Some(branch);
+            }
+            FilterToken::Template(template) => {
+                filter.template = Some(template);
+            }
             FilterToken::Limit(limit) => {
                 filter.limit = Some(limit);
             }
@@ -323,6 +365,22 @@ pub fn format_filter(filter: &PromptFilter) -> String {
         parts.push(format!("date:<={}", date.format("%Y-%m-%d")));
     }
 
+    for (key, value) in &filter.attributes {
+        parts.push(format!("attr:{key}={value}"));
+    }
+
+    if let Some(ref project) = filter.project {
+        parts.push(format!("project:{project}"));
+    }
+
+    if let Some(ref branch) = filter.branch {
+        parts.push(format!("branch:{branch}"));
+    }
+
+    if let Some(ref template) = filter.template {
+        parts.push(format!("template:{template}"));
+    }
+
     if let Some(limit) = filter.limit {
         parts.push(format!("limit:{}", limit));
     }
@@ -408,6 +466,39 @@ mod tests {
         assert_eq!(filter.min_quality_score, Some(70.0));
     }
 
+    #[test]
+    fn test_parse_attribute() {
+        let filter = parse_filter_query("attr:ticket=PROJ-1 attr:author=alice").unwrap();
+        assert_eq!(
+            filter.attributes,
+            vec![
+                ("ticket".to_string(), "PROJ-1".to_string()),
+                ("author".to_string(), "alice".to_string()),
+            ]
+        );
+        assert_eq!(format_filter(&filter), "attr:ticket=PROJ-1 attr:author=alice");
+    }
+
+    #[test]
+    fn test_parse_project_and_branch() {
+        let filter = parse_filter_query("project:cc-tracker branch:feature/x").unwrap();
+        assert_eq!(filter.project, Some("cc-tracker".to_string()));
+        assert_eq!(filter.branch, Some("feature/x".to_string()));
+        assert_eq!(format_filter(&filter), "project:cc-tracker branch:feature/x");
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let filter = parse_filter_query("tpl:code-review tag:rust").unwrap();
+        assert_eq!(filter.template, Some("code-review".to_string()));
+        assert_eq!(format_filter(&filter), "tag:rust template:code-review");
+    }
+
+    #[test]
+    fn test_invalid_attribute() {
+        assert!(parse_filter_query("attr:ticket").is_err());
+    }
+
     #[test]
     fn test_split_quoted() {
         let parts = split_query("tag:rust \"hello world\"");
@@ -0,0 +1,289 @@
+//! Local command hooks for lifecycle events
+//!
+//! Runs the commands configured in the `hooks` section for each matching
+//! event. The event JSON is written to the command's stdin and its kind and
+//! prompt ID are set in `PROMPT_TRACKING_EVENT` and
+//! `PROMPT_TRACKING_PROMPT_ID`. A hook that fails or exceeds its timeout is
+//! logged and never fails the operation that raised the event.
+
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{HookConfig, HooksConfig};
+use crate::events::{Event, EventObserver};
+
+/// Interval between checks for a finished hook
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Longest stderr excerpt included in a failure message
+const MAX_STDERR: usize = 500;
+
+/// Runs configured commands for lifecycle events
+#[derive(Debug, Clone)]
+pub struct HookRunner {
+    hooks: Vec<HookConfig>,
+    timeout: Duration,
+}
+
+/// How a single hook run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookResult {
+    /// Exited with status 0
+    Success,
+    /// Exited with a non-zero status (`None` if killed by a signal)
+    Failed {
+        /// Exit status
+        code: Option<i32>,
+        /// What the hook wrote to stderr
+        stderr: String,
+    },
+    /// Killed after running longer than its timeout
+    TimedOut,
+    /// Could not be started
+    SpawnFailed(String),
+}
+
+impl HookRunner {
+    /// Create a runner for the configured hooks
+    pub fn new(config: &HooksConfig) -> Self {
+        Self {
+            hooks: config.commands.clone(),
+            timeout: Duration::from_secs(config.timeout_secs),
+        }
+    }
+
+    /// Whether no hooks are configured
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Whether a hook should run for an event
+    pub fn matches(hook: &HookConfig, event: &Event) -> bool {
+        if !hook.events.is_empty() && !hook.events.contains(&event.kind()) {
+            return false;
+        }
+        hook.min_score
+            .map_or(true, |min| event.score().is_some_and(|score| score >= min))
+    }
+
+    /// Run one hook for an event and wait for it to finish or time out
+    pub fn run(&self, hook: &HookConfig, event: &Event) -> HookResult {
+        let timeout = hook.timeout_secs.map_or(self.timeout, Duration::from_secs);
+
+        let mut child = match shell(&hook.command)
+            .env("PROMPT_TRACKING_EVENT", event.kind().to_string())
+            .env(
+                "PROMPT_TRACKING_PROMPT_ID",
+                event.prompt_id().unwrap_or_default(),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return HookResult::SpawnFailed(e.to_string()),
+        };
+
+        // Written and read on their own threads so a hook that ignores stdin
+        // or fills its stderr pipe cannot block the timeout
+        let payload = event.to_json();
+        let stdin = child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || {
+                let _ = stdin.write_all(payload.as_bytes());
+            })
+        });
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut output = String::new();
+                let _ = stderr.read_to_string(&mut output);
+                output
+            })
+        });
+
+        // After a timeout the threads are left to finish on their own: a
+        // grandchild may still hold the pipes open
+        let Some(status) = wait_timeout(&mut child, timeout) else {
+            return HookResult::TimedOut;
+        };
+        if let Some(handle) = stdin {
+            let _ = handle.join();
+        }
+        let stderr = stderr
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+
+        if status.success() {
+            HookResult::Success
+        } else {
+            HookResult::Failed {
+                code: status.code(),
+                stderr: excerpt(&stderr),
+            }
+        }
+    }
+}
+
+impl EventObserver for HookRunner {
+    fn on_event(&self, event: &Event) {
+        for hook in self.hooks.iter().filter(|hook| Self::matches(hook, event)) {
+            match self.run(hook, event) {
+                HookResult::Success => {
+                    log::debug!("Hook '{}' ran for {}", hook.name, event.kind());
+                }
+                HookResult::Failed { code, stderr } => log::warn!(
+                    "Hook '{}' failed for {} (exit {}): {}",
+                    hook.name,
+                    event.kind(),
+                    code.map_or_else(|| "signal".to_string(), |c| c.to_string()),
+                    stderr
+                ),
+                HookResult::TimedOut => log::warn!(
+                    "Hook '{}' timed out for {} and was killed",
+                    hook.name,
+                    event.kind()
+                ),
+                HookResult::SpawnFailed(e) => {
+                    log::warn!("Hook '{}' could not be started: {}", hook.name, e);
+                }
+            }
+        }
+    }
+}
+
+/// Command running `line` through the platform shell
+fn shell(line: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(line);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(line);
+        command
+    }
+}
+
+/// Wait for a child to exit, killing it after `timeout`
+///
+/// Returns `None` if the child had to be killed.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+/// Trimmed stderr, cut to [`MAX_STDERR`] characters
+fn excerpt(stderr: &str) -> String {
+    let trimmed = stderr.trim();
+    match trimmed.char_indices().nth(MAX_STDERR) {
+        Some((end, _)) => format!("{}...", &trimmed[..end]),
+        None => trimmed.to_string(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+    use crate::models::QualityScore;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    fn hook(command: &str) -> HookConfig {
+        HookConfig {
+            name: "test".to_string(),
+            command: command.to_string(),
+            events: Vec::new(),
+            min_score: None,
+            timeout_secs: None,
+        }
+    }
+
+    fn analyzed(total_score: f64) -> Event {
+        Event::Analyzed {
+            prompt_id: "p1".to_string(),
+            score: QualityScore {
+                prompt_id: "p1".to_string(),
+                total_score,
+                clarity: 0.0,
+                completeness: 0.0,
+                specificity: 0.0,
+                guidance: 0.0,
+                analyzed_at: Utc::now(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_hook_receives_event_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let out = temp_dir.path().join("event.json");
+        let runner = HookRunner::new(&HooksConfig::default());
+
+        let hook = hook(&format!(
+            "cat > '{}'; echo \"$PROMPT_TRACKING_EVENT $PROMPT_TRACKING_PROMPT_ID\" >> '{}'",
+            out.display(),
+            out.display()
+        ));
+        assert_eq!(runner.run(&hook, &analyzed(95.0)), HookResult::Success);
+
+        let written = std::fs::read_to_string(&out).unwrap();
+        let (json, env) = written.rsplit_once('}').unwrap();
+        assert!(json.contains("\"event\":\"analyzed\""));
+        assert!(env.contains("analyzed p1"));
+    }
+
+    #[test]
+    fn test_hook_failure_and_timeout() {
+        let runner = HookRunner::new(&HooksConfig::default());
+        let event = analyzed(50.0);
+
+        assert_eq!(
+            runner.run(&hook("echo broken >&2; exit 3"), &event),
+            HookResult::Failed {
+                code: Some(3),
+                stderr: "broken".to_string()
+            }
+        );
+
+        let mut slow = hook("sleep 5");
+        slow.timeout_secs = Some(0);
+        let started = Instant::now();
+        assert_eq!(runner.run(&slow, &event), HookResult::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_hook_matching() {
+        let mut high_scores = hook("true");
+        high_scores.events = vec![EventKind::Analyzed];
+        high_scores.min_score = Some(90.0);
+
+        assert!(HookRunner::matches(&high_scores, &analyzed(92.5)));
+        assert!(!HookRunner::matches(&high_scores, &analyzed(89.0)));
+        assert!(!HookRunner::matches(
+            &high_scores,
+            &Event::Deleted {
+                prompt_id: "p1".to_string()
+            }
+        ));
+        assert!(HookRunner::matches(
+            &hook("true"),
+            &Event::Deleted {
+                prompt_id: "p1".to_string()
+            }
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+//! Append-only JSONL session logs
+//!
+//! Reads only the lines appended to a log since the last recorded offset and
+//! extracts user turns from Claude Code session records. A log that shrank
+//! or whose first line changed was truncated or rotated and is read again
+//! from the start.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::database::LogOffset;
+
+/// Most bytes of a log read in one call; a longer backlog is read over
+/// several calls
+pub const MAX_READ_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Lines appended to a log since the previous read
+#[derive(Debug, Clone)]
+pub struct Appended {
+    /// Complete, non-empty lines in file order
+    pub lines: Vec<String>,
+    /// Position to resume from on the next read
+    pub offset: LogOffset,
+    /// Whether the log was truncated or replaced and read from the start
+    pub reset: bool,
+    /// Whether the read stopped at [`MAX_READ_BYTES`] with complete lines
+    /// still to read past `offset`
+    pub more: bool,
+}
+
+/// A user turn taken from a session log record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserTurn {
+    /// Text the user typed
+    pub text: String,
+    /// Session the turn belongs to
+    pub session_id: Option<String>,
+    /// Working directory the session was started in
+    pub cwd: Option<String>,
+    /// When the turn was recorded
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// Read the complete lines appended to `path` after `from`
+///
+/// A trailing line without a newline is still being written; it is left for
+/// the next read and the returned offset stops before it. At most
+/// [`MAX_READ_BYTES`] are read; a single line longer than that is skipped
+/// once it is complete.
+pub fn read_appended(path: &Path, from: &LogOffset) -> io::Result<Appended> {
+    read_at_most(path, from, MAX_READ_BYTES)
+}
+
+fn read_at_most(path: &Path, from: &LogOffset, limit: u64) -> io::Result<Appended> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let head_hash = first_line_hash(&mut file, limit)?;
+
+    let replaced = from.head_hash.is_some() && head_hash != from.head_hash;
+    let reset = from.offset > 0 && (len < from.offset || replaced);
+    let start = if reset { 0 } else { from.offset };
+
+    let mut buf = Vec::new();
+    file.seek(SeekFrom::Start(start))?;
+    (&mut file)
+        .take(limit.min(len.saturating_sub(start)))
+        .read_to_end(&mut buf)?;
+    let full = buf.len() as u64 == limit;
+
+    let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    let mut end = start + complete as u64;
+    if complete == 0 && full {
+        if let Some(after) = skip_line(&mut file)? {
+            log::warn!(
+                "Skipping a line of over {} bytes in {}",
+                limit,
+                path.display()
+            );
+            end = after;
+        }
+    }
+
+    let lines = buf[..complete]
+        .split(|&b| b == b'\n')
+        .map(|line| String::from_utf8_lossy(line).trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    Ok(Appended {
+        lines,
+        offset: LogOffset {
+            path: from.path.clone(),
+            offset: end,
+            head_hash,
+        },
+        reset,
+        more: full && end > start,
+    })
+}
+
+/// Read on to the end of the current line in fixed-size chunks, returning
+/// the offset after it, or `None` if the line is not finished yet
+fn skip_line(file: &mut File) -> io::Result<Option<u64>> {
+    let mut chunk = [0; 8192];
+    loop {
+        let read = file.read(&mut chunk)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if let Some(i) = chunk[..read].iter().position(|&b| b == b'\n') {
+            let unread = (read - i - 1) as u64;
+            return Ok(Some(file.stream_position()? - unread));
+        }
+    }
+}
+
+/// Hash of the first complete line, `None` until one has been written
+///
+/// Only the first `limit` bytes of a longer line are hashed.
+fn first_line_hash(file: &mut File, limit: u64) -> io::Result<Option<String>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut first = Vec::new();
+    BufReader::new((&mut *file).take(limit)).read_until(b'\n', &mut first)?;
+
+    if first.last() != Some(&b'\n') && (first.len() as u64) < limit {
+        return Ok(None);
+    }
+    Ok(Some(format!("{:x}", Sha256::digest(&first))))
+}
+
+/// Extract the prompt typed by the user from one log record
+///
+/// Accepts Claude Code records (`{"type": "user", "message": {...}}`) and
+/// plain chat messages (`{"role": "user", "content": ...}`). Tool results,
+/// slash-command bookkeeping, meta records and sub-agent (sidechain) turns
+/// are not user prompts and yield `None`, as do lines that are not JSON.
+pub fn user_turn(line: &str) -> Option<UserTurn> {
+    let record: Value = serde_json::from_str(line).ok()?;
+
+    if is_true(&record, "isMeta") || is_true(&record, "isSidechain") {
+        return None;
+    }
+
+    let message = record.get("message").unwrap_or(&record);
+    let role = message
+        .get("role")
+        .or_else(|| record.get("type"))
+        .and_then(Value::as_str)?;
+    if role != "user" {
+        return None;
+    }
+
+    let text = match message.get("content")? {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|block| block.get("type").and_then(Value::as_str) == Some("text"))
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => return None,
+    };
+
+    let text = text.trim();
+    if text.is_empty() || text.starts_with("<command-") || text.starts_with("<local-command-") {
+        return None;
+    }
+
+    let field = |name: &str| record.get(name).and_then(Value::as_str).map(String::from);
+
+    Some(UserTurn {
+        text: text.to_string(),
+        session_id: field("sessionId"),
+        cwd: field("cwd"),
+        timestamp: field("timestamp")
+            .and_then(|ts| DateTime::parse_from_rfc3339(&ts).ok())
+            .map(|ts| ts.with_timezone(&Utc)),
+    })
+}
+
+fn is_true(record: &Value, key: &str) -> bool {
+    record.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_read_appended_incrementally() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        append(&path, "{\"n\":1}\n{\"n\":2}\n{\"n\":");
+
+        let first = read_appended(&path, &LogOffset::new("session")).unwrap();
+        assert_eq!(first.lines, vec!["{\"n\":1}", "{\"n\":2}"]);
+        assert_eq!(first.offset.offset, 16);
+        assert!(!first.reset);
+
+        // The partial line is picked up once it is complete
+        append(&path, "3}\n");
+        let second = read_appended(&path, &first.offset).unwrap();
+        assert_eq!(second.lines, vec!["{\"n\":3}"]);
+
+        let third = read_appended(&path, &second.offset).unwrap();
+        assert!(third.lines.is_empty());
+        assert_eq!(third.offset, second.offset);
+    }
+
+    #[test]
+    fn test_read_appended_after_truncation_and_rotation() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        append(&path, "{\"n\":1}\n{\"n\":2}\n");
+        let read = read_appended(&path, &LogOffset::new("session")).unwrap();
+
+        // Truncated: shorter than the recorded offset
+        std::fs::write(&path, "{\"n\":9}\n").unwrap();
+        let truncated = read_appended(&path, &read.offset).unwrap();
+        assert!(truncated.reset);
+        assert_eq!(truncated.lines, vec!["{\"n\":9}"]);
+
+        // Rotated: a new file at least as long, with a different first line
+        std::fs::write(&path, "{\"n\":5}\n{\"n\":6}\n{\"n\":7}\n").unwrap();
+        let rotated = read_appended(&path, &truncated.offset).unwrap();
+        assert!(rotated.reset);
+        assert_eq!(rotated.lines.len(), 3);
+    }
+
+    #[test]
+    fn test_read_appended_in_bounded_chunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        append(&path, "{\"n\":1}\n{\"n\":2}\n{\"n\":3}\n");
+
+        let first = read_at_most(&path, &LogOffset::new("session"), 20).unwrap();
+        assert_eq!(first.lines, vec!["{\"n\":1}", "{\"n\":2}"]);
+        assert!(first.more);
+        let second = read_at_most(&path, &first.offset, 20).unwrap();
+        assert_eq!(second.lines, vec!["{\"n\":3}"]);
+        assert!(!second.more);
+    }
+
+    #[test]
+    fn test_read_appended_skips_oversized_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        append(&path, "{\"n\":1}\n");
+        let start = read_at_most(&path, &LogOffset::new("session"), 16).unwrap();
+
+        // Unfinished, it is neither returned nor skipped
+        append(&path, &"x".repeat(40));
+        let waiting = read_at_most(&path, &start.offset, 16).unwrap();
+        assert!(waiting.lines.is_empty());
+        assert_eq!(waiting.offset, start.offset);
+        assert!(!waiting.more);
+
+        append(&path, "\n{\"n\":2}\n");
+        let skipped = read_at_most(&path, &waiting.offset, 16).unwrap();
+        assert!(skipped.lines.is_empty());
+        assert!(skipped.more);
+        let after = read_at_most(&path, &skipped.offset, 16).unwrap();
+        assert_eq!(after.lines, vec!["{\"n\":2}"]);
+    }
+
+    #[test]
+    fn test_user_turn_from_claude_code_record() {
+        let line = r#"{"type":"user","sessionId":"s-1","cwd":"/work/app","timestamp":"2024-05-01T10:00:00Z","message":{"role":"user","content":"Add retries to the HTTP client"}}"#;
+        let turn = user_turn(line).unwrap();
+
+        assert_eq!(turn.text, "Add retries to the HTTP client");
+        assert_eq!(turn.session_id.as_deref(), Some("s-1"));
+        assert_eq!(turn.cwd.as_deref(), Some("/work/app"));
+        assert_eq!(
+            turn.timestamp.unwrap().to_rfc3339(),
+            "2024-05-01T10:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_user_turn_content_blocks() {
+        let line = r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"Explain this error"},{"type":"image","source":{}}]}}"#;
+        assert_eq!(user_turn(line).unwrap().text, "Explain this error");
+
+        let plain = r#"{"role":"user","content":"Summarize the diff"}"#;
+        assert_eq!(user_turn(plain).unwrap().text, "Summarize the diff");
+    }
+
+    #[test]
+    fn test_user_turn_skips_non_prompts() {
+        let skipped = [
+            r#"{"type":"assistant","message":{"role":"assistant","content":"Sure"}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
+            r#"{"type":"user","isMeta":true,"message":{"role":"user","content":"Caveat: ..."}}"#,
+            r#"{"type":"user","isSidechain":true,"message":{"role":"user","content":"Search the repo"}}"#,
+            r#"{"type":"user","message":{"role":"user","content":"<command-name>/clear</command-name>"}}"#,
+            r#"{"type":"summary","summary":"Refactoring session"}"#,
+            "not json",
+        ];
+        for line in skipped {
+            assert!(user_turn(line).is_none(), "{}", line);
+        }
+    }
+}
@@ -10,6 +10,22 @@
 //! - **Reporting**: Generate reports in Markdown, HTML, JSON, and CSV formats
 //! - **Version History**: Track changes and restore previous versions
 //! - **Caching**: In-memory caching for improved performance
+//! - **Redaction**: Mask, hash or reject secrets and PII before they are stored
+//! - **Git Context**: Record repository, branch and commit with each captured prompt
+//! - **Templates**: Render prompts from `{{variable}}` skeletons and track their lineage
+//! - **Session Logs**: Tail append-only JSONL logs and capture each new user turn
+//! - **Daemon**: Run the watcher as a service with signal handling, status and log rotation
+//! - **Events**: Observe prompt lifecycle events and run local hook commands
+//! - **Webhooks**: Deliver signed events to HTTP endpoints with a retrying outbox
+//! - **Concurrent Access**: WAL journaling and pooled readers let the daemon and CLI share a database
+//! - **Async API**: `AsyncDatabase` runs queries on tokio's blocking pool for async services
+//! - **Storage Backends**: `PromptStore` trait with `SQLite`, in-memory and JSON-file implementations
+//! - **Backups**: Verified online backups with daily/weekly rotation and restore
+//! - **Encryption**: AES-256-GCM encryption of prompt text at rest and in export bundles
+//! - **Migrations**: Reversible, transactional schema migrations with dry runs and pre-migration backups
+//! - **Doctor**: Integrity, hash, orphan, duplicate and search index checks with repair
+//! - **Retention**: Config-driven archiving, purging and trimming of scores and version history
+//! - **Trash**: Soft delete with restore and automatic purging of old deleted prompts
 //!
 //! ## Quick Start
 //!
@@ -65,6 +81,21 @@ pub mod watcher;
 pub mod cache;
 pub mod migration;
 pub mod filter;
+pub mod git;
+pub mod redaction;
+pub mod template;
+pub mod jsonl;
+pub mod events;
+pub mod async_database;
+pub mod store;
+pub mod backup;
+pub mod encryption;
+pub mod doctor;
+pub mod retention;
+pub mod hooks;
+pub mod webhooks;
+#[cfg(unix)]
+pub mod daemon;
 
 use thiserror::Error;
 
@@ -139,6 +170,75 @@ pub enum PromptTrackingError {
         version: i32,
     },
 
+    /// File exceeds the capture size limit
+    ///
+    /// This error occurs when a file is larger than the configured
+    /// maximum and the truncation policy is `skip`.
+    #[error("File too large: {path} is {size} bytes (limit {limit})")]
+    FileTooLarge {
+        /// Path of the file
+        path: String,
+        /// Size of the file in bytes
+        size: u64,
+        /// Configured maximum in bytes
+        limit: u64,
+    },
+
+    /// File content is binary
+    ///
+    /// This error occurs when a captured file contains NUL bytes or
+    /// other signs of non-text content.
+    #[error("Binary file skipped: {0}")]
+    BinaryFile(String),
+
+    /// File content could not be decoded
+    ///
+    /// This error occurs when a file is not valid in the detected or
+    /// configured encoding and lossy decoding is disabled.
+    #[error("Cannot decode {path} as {encoding}")]
+    EncodingError {
+        /// Path of the file
+        path: String,
+        /// Encoding the file was decoded as
+        encoding: String,
+    },
+
+    /// Template error
+    ///
+    /// This error occurs when a template is missing or cannot be rendered,
+    /// for example because a variable has no value.
+    #[error("Template error: {0}")]
+    TemplateError(String),
+
+    /// Redaction rule rejected the prompt
+    ///
+    /// This error occurs when prompt content matches a redaction rule
+    /// configured with the `reject` action.
+    #[error("Prompt rejected: content matches redaction rule '{0}'")]
+    RedactionRejected(String),
+
+    /// Daemon control error
+    ///
+    /// This error occurs when the daemon cannot start because another
+    /// instance holds the pidfile lock, or when no daemon is running to
+    /// receive a stop or reload request.
+    #[error("Daemon error: {0}")]
+    DaemonError(String),
+
+    /// Webhook delivery error
+    ///
+    /// This error occurs when an endpoint is not configured, cannot be
+    /// reached, or answers with a non-success status.
+    #[error("Webhook error: {0}")]
+    WebhookError(String),
+
+    /// Encryption error
+    ///
+    /// This error occurs when an encrypted database or bundle is opened
+    /// without a key or with the wrong one, or a key file is unreadable.
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+
     /// Duplicate detected
     ///
     /// This error occurs when attempting to create a prompt
@@ -147,6 +247,52 @@ pub enum PromptTrackingError {
     DuplicateDetected(String),
 }
 
+impl PromptTrackingError {
+    /// Short, stable name for the kind of error, used when recording failures
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::DatabaseError(_) => "database",
+            Self::FileNotFound(_) => "not_found",
+            Self::InvalidFormat => "invalid_format",
+            Self::ConfigError(_) => "config",
+            Self::AnalysisError(_) => "analysis",
+            Self::IoError(_) => "io",
+            Self::CacheError(_) => "cache",
+            Self::SerializationError(_) => "serialization",
+            Self::VersionNotFound { .. } => "version_not_found",
+            Self::FileTooLarge { .. } => "too_large",
+            Self::BinaryFile(_) => "binary",
+            Self::EncodingError { .. } => "encoding",
+            Self::TemplateError(_) => "template",
+            Self::RedactionRejected(_) => "redaction_rejected",
+            Self::DaemonError(_) => "daemon",
+            Self::WebhookError(_) => "webhook",
+            Self::EncryptionError(_) => "encryption",
+            Self::DuplicateDetected(_) => "duplicate",
+        }
+    }
+
+    /// Whether the same operation may succeed if retried later
+    ///
+    /// True for a locked or busy database and for interrupted or timed-out
+    /// I/O; problems with the content itself are permanent.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::DatabaseError(message) => {
+                let message = message.to_lowercase();
+                message.contains("locked") || message.contains("busy")
+            }
+            Self::IoError(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::TimedOut
+            ),
+            _ => false,
+        }
+    }
+}
+
 /// Type alias for Result with PromptTrackingError.
 ///
 /// This is the standard result type used throughout the library
@@ -1,13 +1,483 @@
 //! Database migration system
 //!
-//! Manages database schema versions and migrations.
+//! Manages database schema versions and migrations. Every migration is
+//! reversible: [`MIGRATIONS`] pairs the SQL applying a version with the SQL
+//! reverting it, so [`MigrationManager::plan`] can report exactly what a
+//! migration would run and [`MigrationManager::migrate_to`] can move the
+//! schema in either direction. A run is applied inside a single savepoint,
+//! so a failing step leaves the schema as it was.
+//!
+//! Migrating to a version older than [`CURRENT_VERSION`] pins the schema
+//! there: [`MigrationManager::run_migrations`], which runs whenever the
+//! database is opened normally, refuses to touch a pinned schema instead of
+//! quietly undoing the downgrade. Migrating to the latest version clears the
+//! pin.
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 
 use crate::{PromptTrackingError, Result};
 
 /// Current schema version
-pub const CURRENT_VERSION: i32 = 2;
+pub const CURRENT_VERSION: i32 = 14;
+
+/// A reversible schema change
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Schema version this migration brings the database to
+    pub version: i32,
+    /// Short description of the change
+    pub description: &'static str,
+    /// SQL applying the change
+    pub up: &'static str,
+    /// SQL reverting the change
+    pub down: &'static str,
+    /// Query counting rows that `down` would make unrecoverable, with the
+    /// reason reported when it is non-zero
+    pub down_guard: Option<(&'static str, &'static str)>,
+}
+
+/// All migrations, in version order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial schema",
+        up: r"
+            CREATE TABLE IF NOT EXISTS prompts (
+                id TEXT PRIMARY KEY,
+                content TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                category TEXT,
+                status TEXT NOT NULL DEFAULT 'active',
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                model TEXT NOT NULL,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                execution_time_ms INTEGER,
+                estimated_cost REAL,
+                context TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS prompt_tags (
+                prompt_id TEXT NOT NULL,
+                tag_id INTEGER NOT NULL,
+                PRIMARY KEY (prompt_id, tag_id),
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE,
+                FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS quality_scores (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                prompt_id TEXT NOT NULL,
+                total_score REAL NOT NULL,
+                clarity REAL NOT NULL,
+                completeness REAL NOT NULL,
+                specificity REAL NOT NULL,
+                guidance REAL NOT NULL,
+                analyzed_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS efficiency_metrics (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                prompt_id TEXT NOT NULL,
+                efficiency_score REAL NOT NULL,
+                token_efficiency REAL NOT NULL,
+                time_efficiency REAL NOT NULL,
+                cost_efficiency REAL NOT NULL,
+                calculated_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS version_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                prompt_id TEXT NOT NULL,
+                content TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_prompts_created_at ON prompts(created_at);
+            CREATE INDEX IF NOT EXISTS idx_prompts_category ON prompts(category);
+            CREATE INDEX IF NOT EXISTS idx_prompts_content_hash ON prompts(content_hash);
+            CREATE INDEX IF NOT EXISTS idx_prompts_status ON prompts(status);
+            CREATE INDEX IF NOT EXISTS idx_quality_scores_prompt_id ON quality_scores(prompt_id);
+            CREATE INDEX IF NOT EXISTS idx_efficiency_metrics_prompt_id ON efficiency_metrics(prompt_id);
+            CREATE INDEX IF NOT EXISTS idx_version_history_prompt_id ON version_history(prompt_id);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS version_history;
+            DROP TABLE IF EXISTS efficiency_metrics;
+            DROP TABLE IF EXISTS quality_scores;
+            DROP TABLE IF EXISTS prompt_tags;
+            DROP TABLE IF EXISTS tags;
+            DROP TABLE IF EXISTS prompts;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 2,
+        description: "Add full-text search support",
+        up: r"
+            -- Create FTS5 virtual table for full-text search
+            CREATE VIRTUAL TABLE IF NOT EXISTS prompts_fts USING fts5(
+                content,
+                category,
+                content='prompts',
+                content_rowid='rowid'
+            );
+
+            -- Create triggers to keep FTS table in sync
+            CREATE TRIGGER IF NOT EXISTS prompts_fts_insert AFTER INSERT ON prompts BEGIN
+                INSERT INTO prompts_fts(rowid, content, category)
+                VALUES (NEW.rowid, NEW.content, COALESCE(NEW.category, ''));
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS prompts_fts_delete AFTER DELETE ON prompts BEGIN
+                INSERT INTO prompts_fts(prompts_fts, rowid, content, category)
+                VALUES ('delete', OLD.rowid, OLD.content, COALESCE(OLD.category, ''));
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS prompts_fts_update AFTER UPDATE ON prompts BEGIN
+                INSERT INTO prompts_fts(prompts_fts, rowid, content, category)
+                VALUES ('delete', OLD.rowid, OLD.content, COALESCE(OLD.category, ''));
+                INSERT INTO prompts_fts(rowid, content, category)
+                VALUES (NEW.rowid, NEW.content, COALESCE(NEW.category, ''));
+            END;
+        ",
+        down: r"
+            DROP TRIGGER IF EXISTS prompts_fts_update;
+            DROP TRIGGER IF EXISTS prompts_fts_delete;
+            DROP TRIGGER IF EXISTS prompts_fts_insert;
+            DROP TABLE IF EXISTS prompts_fts;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 3,
+        description: "Add custom key/value attributes per prompt",
+        up: r"
+            CREATE TABLE IF NOT EXISTS prompt_attributes (
+                prompt_id TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (prompt_id, key),
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_prompt_attributes_key_value ON prompt_attributes(key, value);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS prompt_attributes;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 4,
+        description: "Add git repository context per prompt",
+        up: r"
+            CREATE TABLE IF NOT EXISTS prompt_git_context (
+                prompt_id TEXT PRIMARY KEY,
+                project TEXT NOT NULL,
+                repo_root TEXT NOT NULL,
+                remote_url TEXT,
+                branch TEXT,
+                commit_hash TEXT,
+                dirty INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_prompt_git_context_project ON prompt_git_context(project);
+            CREATE INDEX IF NOT EXISTS idx_prompt_git_context_branch ON prompt_git_context(branch);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS prompt_git_context;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 5,
+        description: "Add redaction event log",
+        up: r"
+            CREATE TABLE IF NOT EXISTS redaction_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                prompt_id TEXT NOT NULL,
+                rule TEXT NOT NULL,
+                action TEXT NOT NULL,
+                match_count INTEGER NOT NULL,
+                source TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_redaction_events_prompt_id ON redaction_events(prompt_id);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS redaction_events;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 6,
+        description: "Add prompt templates and template lineage",
+        up: r"
+            CREATE TABLE IF NOT EXISTS templates (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                content TEXT NOT NULL,
+                description TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS prompt_templates (
+                prompt_id TEXT PRIMARY KEY,
+                template_id TEXT NOT NULL,
+                template_name TEXT NOT NULL,
+                variables TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_prompt_templates_template_id ON prompt_templates(template_id);
+            CREATE INDEX IF NOT EXISTS idx_prompt_templates_template_name ON prompt_templates(template_name);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS prompt_templates;
+            DROP TABLE IF EXISTS templates;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 7,
+        description: "Map watched file paths to prompts",
+        up: r"
+            CREATE TABLE IF NOT EXISTS watched_files (
+                path TEXT PRIMARY KEY,
+                prompt_id TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_watched_files_prompt_id ON watched_files(prompt_id);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS watched_files;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 8,
+        description: "Add watcher checkpoints for backfill",
+        up: r"
+            CREATE TABLE IF NOT EXISTS watch_checkpoints (
+                path TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                mtime_ns INTEGER NOT NULL,
+                file_hash TEXT NOT NULL,
+                processed_at TEXT NOT NULL
+            );
+        ",
+        down: r"
+            DROP TABLE IF EXISTS watch_checkpoints;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 9,
+        description: "Read offsets for tailed JSONL session logs",
+        up: r"
+            CREATE TABLE IF NOT EXISTS log_offsets (
+                path TEXT PRIMARY KEY,
+                byte_offset INTEGER NOT NULL,
+                head_hash TEXT,
+                updated_at TEXT NOT NULL
+            );
+        ",
+        down: r"
+            DROP TABLE IF EXISTS log_offsets;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 10,
+        description: "Dead-letter queue for failed captures",
+        up: r"
+            CREATE TABLE IF NOT EXISTS capture_failures (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                error_kind TEXT NOT NULL,
+                message TEXT NOT NULL,
+                first_seen TEXT NOT NULL,
+                last_seen TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 1,
+                next_retry_at TEXT,
+                dismissed INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_capture_failures_retry
+                ON capture_failures(next_retry_at);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS capture_failures;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 11,
+        description: "Log of files moved, renamed or deleted after capture",
+        up: r"
+            CREATE TABLE IF NOT EXISTS file_actions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                prompt_id TEXT NOT NULL,
+                action TEXT NOT NULL,
+                source TEXT NOT NULL,
+                destination TEXT,
+                content BLOB,
+                performed_at TEXT NOT NULL,
+                undone_at TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_file_actions_prompt
+                ON file_actions(prompt_id);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS file_actions;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 12,
+        description: "Outbox of webhook deliveries",
+        up: r"
+            CREATE TABLE IF NOT EXISTS webhook_outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                endpoint TEXT NOT NULL,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at TEXT,
+                last_error TEXT,
+                created_at TEXT NOT NULL,
+                delivered_at TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_webhook_outbox_due
+                ON webhook_outbox(status, next_attempt_at);
+        ",
+        down: r"
+            DROP TABLE IF EXISTS webhook_outbox;
+        ",
+        down_guard: None,
+    },
+    Migration {
+        version: 13,
+        description: "Key parameters of encrypted databases",
+        up: r"
+            CREATE TABLE IF NOT EXISTS encryption (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                kdf TEXT NOT NULL,
+                salt TEXT NOT NULL,
+                iterations INTEGER NOT NULL,
+                check_value TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+        ",
+        down: r"
+            DROP TABLE IF EXISTS encryption;
+        ",
+        down_guard: Some((
+            "SELECT COUNT(*) FROM encryption",
+            "the database is encrypted; run `encryption decrypt` first",
+        )),
+    },
+    Migration {
+        version: 14,
+        description: "Trash for soft-deleted prompts",
+        up: r"
+            ALTER TABLE prompts ADD COLUMN deleted_at TEXT;
+
+            CREATE INDEX IF NOT EXISTS idx_prompts_deleted_at ON prompts(deleted_at);
+        ",
+        down: r"
+            DROP INDEX IF EXISTS idx_prompts_deleted_at;
+            ALTER TABLE prompts DROP COLUMN deleted_at;
+        ",
+        down_guard: Some((
+            "SELECT COUNT(*) FROM prompts WHERE deleted_at IS NOT NULL",
+            "prompts are in the trash; restore them or run `trash empty` first",
+        )),
+    },
+];
+
+/// Direction of a migration step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Apply a migration
+    Up,
+    /// Revert a migration
+    Down,
+}
+
+impl Direction {
+    /// Lowercase name used in output
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+        }
+    }
+}
+
+/// One step of a migration plan
+#[derive(Debug, Clone, Copy)]
+pub struct MigrationStep {
+    /// Migration applied or reverted by this step
+    pub migration: &'static Migration,
+    /// Whether the migration is applied or reverted
+    pub direction: Direction,
+}
+
+impl MigrationStep {
+    /// SQL run by this step
+    pub const fn sql(&self) -> &'static str {
+        match self.direction {
+            Direction::Up => self.migration.up,
+            Direction::Down => self.migration.down,
+        }
+    }
+
+    /// SQL run by this step with its source indentation removed
+    pub fn script(&self) -> String {
+        let sql = self.sql();
+        let indent = sql
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+
+        sql.lines()
+            .map(|line| line.get(indent..).unwrap_or("").trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    }
+
+    /// Schema version once this step has run
+    pub const fn resulting_version(&self) -> i32 {
+        match self.direction {
+            Direction::Up => self.migration.version,
+            Direction::Down => self.migration.version - 1,
+        }
+    }
+}
 
 /// Migration manager for database schema updates
 pub struct MigrationManager<'a> {
@@ -21,41 +491,140 @@ impl<'a> MigrationManager<'a> {
     }
 
     /// Run all pending migrations
+    ///
+    /// A schema newer than [`CURRENT_VERSION`] is an error rather than
+    /// being downgraded; use [`MigrationManager::migrate_to`] for that. So is
+    /// a schema pinned by an explicit downgrade.
     pub fn run_migrations(&self) -> Result<()> {
+        let current = self.current_version()?;
+        if current > CURRENT_VERSION {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Database schema v{current} is newer than the v{CURRENT_VERSION} supported by this version"
+            )));
+        }
+        if let Some(pinned) = self.pinned_version()? {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Database schema is pinned at v{pinned} by an explicit downgrade; \
+                 run `db migrate` to bring it back to v{CURRENT_VERSION}"
+            )));
+        }
+
+        self.migrate_to(CURRENT_VERSION)?;
+        Ok(())
+    }
+
+    /// Current schema version, 0 for an empty database
+    pub fn current_version(&self) -> Result<i32> {
         self.ensure_schema_version_table()?;
-        let current_version = self.get_current_version()?;
+        self.get_current_version()
+    }
 
-        if current_version < CURRENT_VERSION {
-            eprintln!(
-                "Migrating database from version {} to {}...",
-                current_version, CURRENT_VERSION
-            );
+    /// Version the schema was explicitly migrated down to, if it is pinned
+    pub fn pinned_version(&self) -> Result<Option<i32>> {
+        self.ensure_schema_version_table()?;
+        self.conn
+            .query_row("SELECT version FROM schema_pin WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to get schema pin: {e}"))
+            })
+    }
 
-            for version in (current_version + 1)..=CURRENT_VERSION {
-                self.run_migration(version)?;
-                self.set_version(version)?;
-                eprintln!("Applied migration v{}", version);
-            }
+    /// Steps that would bring the schema to `target`, without running them
+    pub fn plan(&self, target: i32) -> Result<Vec<MigrationStep>> {
+        if !(0..=CURRENT_VERSION).contains(&target) {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Unknown schema version: {target} (expected 0-{CURRENT_VERSION})"
+            )));
+        }
 
-            eprintln!("Database migration complete.");
+        let current = self.current_version()?;
+        if current > CURRENT_VERSION {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Database schema v{current} is newer than the v{CURRENT_VERSION} supported by this version"
+            )));
         }
 
-        Ok(())
+        let steps = if target >= current {
+            ((current + 1)..=target)
+                .map(|version| MigrationStep {
+                    migration: migration(version),
+                    direction: Direction::Up,
+                })
+                .collect()
+        } else {
+            ((target + 1)..=current)
+                .rev()
+                .map(|version| MigrationStep {
+                    migration: migration(version),
+                    direction: Direction::Down,
+                })
+                .collect()
+        };
+
+        Ok(steps)
+    }
+
+    /// Bring the schema to `target`, applying or reverting migrations
+    ///
+    /// All steps run inside one savepoint: if any of them fails the schema
+    /// and its recorded version are left unchanged. A `target` older than
+    /// [`CURRENT_VERSION`] pins the schema there; the latest one unpins it.
+    /// Returns the steps run.
+    pub fn migrate_to(&self, target: i32) -> Result<Vec<MigrationStep>> {
+        let steps = self.plan(target)?;
+        if steps.is_empty() {
+            return Ok(steps);
+        }
+
+        let current = self.get_current_version()?;
+        log::info!("Migrating database schema from v{current} to v{target}");
+
+        self.conn.execute_batch("SAVEPOINT migrate").map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to begin migration: {e}"))
+        })?;
+
+        let applied = steps
+            .iter()
+            .try_for_each(|step| self.run_step(step))
+            .and_then(|()| self.set_pin(target));
+        if let Err(e) = applied {
+            if let Err(rollback) = self
+                .conn
+                .execute_batch("ROLLBACK TO migrate; RELEASE migrate")
+            {
+                log::error!("Failed to roll back migration: {rollback}");
+            }
+            return Err(e);
+        }
+
+        self.conn.execute_batch("RELEASE migrate").map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to commit migration: {e}"))
+        })?;
+
+        log::info!("Database schema is at v{target}");
+        Ok(steps)
     }
 
-    /// Ensure the schema_version table exists
+    /// Ensure the `schema_version` and `schema_pin` tables exist
     fn ensure_schema_version_table(&self) -> Result<()> {
         self.conn
-            .execute(
+            .execute_batch(
                 "CREATE TABLE IF NOT EXISTS schema_version (
                     version INTEGER PRIMARY KEY,
                     applied_at TEXT NOT NULL
-                )",
-                [],
+                );
+                CREATE TABLE IF NOT EXISTS schema_pin (
+                    id INTEGER PRIMARY KEY CHECK (id = 1),
+                    version INTEGER NOT NULL,
+                    pinned_at TEXT NOT NULL
+                );",
             )
             .map_err(|e| {
                 PromptTrackingError::DatabaseError(format!(
-                    "Failed to create schema_version table: {}",
+                    "Failed to create schema version tables: {}",
                     e
                 ))
             })?;
@@ -93,150 +662,92 @@ impl<'a> MigrationManager<'a> {
         Ok(())
     }
 
-    /// Run a specific migration
-    fn run_migration(&self, version: i32) -> Result<()> {
-        match version {
-            1 => self.migration_v1(),
-            2 => self.migration_v2(),
-            _ => Err(PromptTrackingError::DatabaseError(format!(
-                "Unknown migration version: {}",
-                version
-            ))),
-        }
+    /// Pin the schema at `target` if it is older than the latest version,
+    /// otherwise unpin it
+    fn set_pin(&self, target: i32) -> Result<()> {
+        let updated = if target < CURRENT_VERSION {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO schema_pin (id, version, pinned_at)
+                 VALUES (1, ?1, datetime('now'))",
+                params![target],
+            )
+        } else {
+            self.conn.execute("DELETE FROM schema_pin", [])
+        };
+
+        updated.map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to set schema pin: {e}"))
+        })?;
+
+        Ok(())
     }
 
-    /// Migration v1: Initial schema (already applied if database exists)
-    fn migration_v1(&self) -> Result<()> {
-        // This is the initial schema - skip if tables already exist
-        let tables_exist: i32 = self
-            .conn
-            .query_row(
-                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='prompts'",
-                [],
-                |row| row.get(0),
+    /// Forget a reverted schema version
+    fn unset_version(&self, version: i32) -> Result<()> {
+        self.conn
+            .execute(
+                "DELETE FROM schema_version WHERE version >= ?1",
+                params![version],
             )
-            .unwrap_or(0);
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to unset schema version: {e}"))
+            })?;
+
+        Ok(())
+    }
+
+    /// Run one step of a plan and record the resulting version
+    fn run_step(&self, step: &MigrationStep) -> Result<()> {
+        let version = step.migration.version;
 
-        if tables_exist == 0 {
-            self.conn
-                .execute_batch(
-                    r#"
-                    CREATE TABLE IF NOT EXISTS prompts (
-                        id TEXT PRIMARY KEY,
-                        content TEXT NOT NULL,
-                        content_hash TEXT NOT NULL,
-                        category TEXT,
-                        status TEXT NOT NULL DEFAULT 'active',
-                        created_at TEXT NOT NULL,
-                        updated_at TEXT NOT NULL,
-                        model TEXT NOT NULL,
-                        input_tokens INTEGER,
-                        output_tokens INTEGER,
-                        execution_time_ms INTEGER,
-                        estimated_cost REAL,
-                        context TEXT
-                    );
-
-                    CREATE TABLE IF NOT EXISTS tags (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        name TEXT UNIQUE NOT NULL
-                    );
-
-                    CREATE TABLE IF NOT EXISTS prompt_tags (
-                        prompt_id TEXT NOT NULL,
-                        tag_id INTEGER NOT NULL,
-                        PRIMARY KEY (prompt_id, tag_id),
-                        FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE,
-                        FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
-                    );
-
-                    CREATE TABLE IF NOT EXISTS quality_scores (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        prompt_id TEXT NOT NULL,
-                        total_score REAL NOT NULL,
-                        clarity REAL NOT NULL,
-                        completeness REAL NOT NULL,
-                        specificity REAL NOT NULL,
-                        guidance REAL NOT NULL,
-                        analyzed_at TEXT NOT NULL,
-                        FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
-                    );
-
-                    CREATE TABLE IF NOT EXISTS efficiency_metrics (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        prompt_id TEXT NOT NULL,
-                        efficiency_score REAL NOT NULL,
-                        token_efficiency REAL NOT NULL,
-                        time_efficiency REAL NOT NULL,
-                        cost_efficiency REAL NOT NULL,
-                        calculated_at TEXT NOT NULL,
-                        FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
-                    );
-
-                    CREATE TABLE IF NOT EXISTS version_history (
-                        id INTEGER PRIMARY KEY AUTOINCREMENT,
-                        prompt_id TEXT NOT NULL,
-                        content TEXT NOT NULL,
-                        content_hash TEXT NOT NULL,
-                        version INTEGER NOT NULL,
-                        created_at TEXT NOT NULL,
-                        FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
-                    );
-
-                    CREATE INDEX IF NOT EXISTS idx_prompts_created_at ON prompts(created_at);
-                    CREATE INDEX IF NOT EXISTS idx_prompts_category ON prompts(category);
-                    CREATE INDEX IF NOT EXISTS idx_prompts_content_hash ON prompts(content_hash);
-                    CREATE INDEX IF NOT EXISTS idx_prompts_status ON prompts(status);
-                    CREATE INDEX IF NOT EXISTS idx_quality_scores_prompt_id ON quality_scores(prompt_id);
-                    CREATE INDEX IF NOT EXISTS idx_efficiency_metrics_prompt_id ON efficiency_metrics(prompt_id);
-                    CREATE INDEX IF NOT EXISTS idx_version_history_prompt_id ON version_history(prompt_id);
-                "#,
-                )
-                .map_err(|e| {
-                    PromptTrackingError::DatabaseError(format!("Failed to run migration v1: {}", e))
+        match step.direction {
+            Direction::Up => {
+                self.conn.execute_batch(step.migration.up).map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!(
+                        "Failed to run migration v{version}: {e}"
+                    ))
+                })?;
+                self.set_version(version)?;
+            }
+            Direction::Down => {
+                self.check_down_guard(step.migration)?;
+                self.conn.execute_batch(step.migration.down).map_err(|e| {
+                    PromptTrackingError::DatabaseError(format!(
+                        "Failed to revert migration v{version}: {e}"
+                    ))
                 })?;
+                self.unset_version(version)?;
+            }
         }
 
+        log::info!(
+            "Migration v{} {} ({})",
+            version,
+            step.direction.as_str(),
+            step.migration.description
+        );
         Ok(())
     }
 
-    /// Migration v2: Add full-text search support
-    fn migration_v2(&self) -> Result<()> {
-        // Add FTS table for full-text search
-        self.conn
-            .execute_batch(
-                r#"
-                -- Create FTS5 virtual table for full-text search
-                CREATE VIRTUAL TABLE IF NOT EXISTS prompts_fts USING fts5(
-                    content,
-                    category,
-                    content='prompts',
-                    content_rowid='rowid'
-                );
-
-                -- Create triggers to keep FTS table in sync
-                CREATE TRIGGER IF NOT EXISTS prompts_fts_insert AFTER INSERT ON prompts BEGIN
-                    INSERT INTO prompts_fts(rowid, content, category)
-                    VALUES (NEW.rowid, NEW.content, COALESCE(NEW.category, ''));
-                END;
-
-                CREATE TRIGGER IF NOT EXISTS prompts_fts_delete AFTER DELETE ON prompts BEGIN
-                    INSERT INTO prompts_fts(prompts_fts, rowid, content, category)
-                    VALUES ('delete', OLD.rowid, OLD.content, COALESCE(OLD.category, ''));
-                END;
-
-                CREATE TRIGGER IF NOT EXISTS prompts_fts_update AFTER UPDATE ON prompts BEGIN
-                    INSERT INTO prompts_fts(prompts_fts, rowid, content, category)
-                    VALUES ('delete', OLD.rowid, OLD.content, COALESCE(OLD.category, ''));
-                    INSERT INTO prompts_fts(rowid, content, category)
-                    VALUES (NEW.rowid, NEW.content, COALESCE(NEW.category, ''));
-                END;
-                "#,
-            )
+    /// Fail if reverting `migration` would lose data its guard detects
+    fn check_down_guard(&self, migration: &Migration) -> Result<()> {
+        let version = migration.version;
+        let Some((query, reason)) = migration.down_guard else {
+            return Ok(());
+        };
+        let count: i64 = self
+            .conn
+            .query_row(query, [], |row| row.get(0))
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to run migration v2: {}", e))
+                PromptTrackingError::DatabaseError(format!(
+                    "Failed to check migration v{version}: {e}"
+                ))
             })?;
-
+        if count > 0 {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Cannot revert migration v{version}: {reason}"
+            )));
+        }
         Ok(())
     }
 
@@ -254,7 +765,7 @@ impl<'a> MigrationManager<'a> {
         let history = stmt
             .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
             .map_err(|e| {
-                PromptTrackingError::DatabaseError(format!("Failed to get migration history: {}", e))
+                PromptTrackingError::DatabaseError(format!("Failed to get migration history: {e}"))
             })?
             .collect::<std::result::Result<Vec<_>, _>>()
             .map_err(|e| {
@@ -272,6 +783,11 @@ impl<'a> MigrationManager<'a> {
     }
 }
 
+/// Migration bringing the schema to `version`, which must be in range
+fn migration(version: i32) -> &'static Migration {
+    &MIGRATIONS[usize::try_from(version - 1).unwrap_or(usize::MAX)]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -339,9 +855,21 @@ mod tests {
         manager.run_migrations().unwrap();
 
         let history = manager.get_migration_history().unwrap();
-        assert_eq!(history.len(), 2);
+        assert_eq!(history.len(), 14);
         assert_eq!(history[0].0, 1);
         assert_eq!(history[1].0, 2);
+        assert_eq!(history[2].0, 3);
+        assert_eq!(history[3].0, 4);
+        assert_eq!(history[4].0, 5);
+        assert_eq!(history[5].0, 6);
+        assert_eq!(history[6].0, 7);
+        assert_eq!(history[7].0, 8);
+        assert_eq!(history[8].0, 9);
+        assert_eq!(history[9].0, 10);
+        assert_eq!(history[10].0, 11);
+        assert_eq!(history[11].0, 12);
+        assert_eq!(history[12].0, 13);
+        assert_eq!(history[13].0, 14);
     }
 
     #[test]
@@ -357,4 +885,124 @@ mod tests {
         let version = manager.get_current_version().unwrap();
         assert_eq!(version, CURRENT_VERSION);
     }
+    fn table_exists(conn: &Connection, name: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![name],
+            |row| row.get::<_, i32>(0),
+        )
+        .unwrap()
+            > 0
+    }
+
+    #[test]
+    fn test_migrations_are_ordered() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i32 + 1);
+        }
+        assert_eq!(MIGRATIONS.len() as i32, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_plan_does_not_migrate() {
+        let conn = create_test_db();
+        let manager = MigrationManager::new(&conn);
+        manager.migrate_to(11).unwrap();
+
+        let plan = manager.plan(13).unwrap();
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].migration.version, 12);
+        assert_eq!(plan[0].direction, Direction::Up);
+        assert!(plan[0]
+            .script()
+            .starts_with("CREATE TABLE IF NOT EXISTS webhook_outbox"));
+        assert_eq!(manager.current_version().unwrap(), 11);
+        assert!(!table_exists(&conn, "webhook_outbox"));
+
+        assert!(manager.plan(CURRENT_VERSION + 1).is_err());
+    }
+
+    #[test]
+    fn test_down_and_up_migrations() {
+        let conn = create_test_db();
+        let manager = MigrationManager::new(&conn);
+        manager.run_migrations().unwrap();
+
+        let steps = manager.migrate_to(10).unwrap();
+        assert_eq!(steps.len(), 4);
+        assert!(steps.iter().all(|step| step.direction == Direction::Down));
+        assert_eq!(steps[0].migration.version, 14);
+        assert_eq!(manager.current_version().unwrap(), 10);
+        assert_eq!(manager.get_migration_history().unwrap().len(), 10);
+        assert!(!table_exists(&conn, "encryption"));
+        assert!(!table_exists(&conn, "webhook_outbox"));
+        assert!(!table_exists(&conn, "file_actions"));
+        assert!(table_exists(&conn, "capture_failures"));
+
+        manager.migrate_to(0).unwrap();
+        assert!(!table_exists(&conn, "prompts"));
+        assert!(!table_exists(&conn, "prompts_fts"));
+
+        manager.migrate_to(CURRENT_VERSION).unwrap();
+        assert_eq!(manager.current_version().unwrap(), CURRENT_VERSION);
+        assert!(table_exists(&conn, "encryption"));
+    }
+
+    #[test]
+    fn test_downgrade_pins_schema() {
+        let conn = create_test_db();
+        let manager = MigrationManager::new(&conn);
+        manager.run_migrations().unwrap();
+        assert_eq!(manager.pinned_version().unwrap(), None);
+
+        manager.migrate_to(11).unwrap();
+        assert_eq!(manager.pinned_version().unwrap(), Some(11));
+
+        // Opening the database normally must not undo the downgrade
+        let err = manager.run_migrations().unwrap_err();
+        assert!(err.to_string().contains("pinned at v11"));
+        assert_eq!(manager.current_version().unwrap(), 11);
+
+        manager.migrate_to(12).unwrap();
+        assert_eq!(manager.pinned_version().unwrap(), Some(12));
+
+        manager.migrate_to(CURRENT_VERSION).unwrap();
+        assert_eq!(manager.pinned_version().unwrap(), None);
+        manager.run_migrations().unwrap();
+    }
+
+    #[test]
+    fn test_down_guard_refuses() {
+        let conn = create_test_db();
+        let manager = MigrationManager::new(&conn);
+        manager.run_migrations().unwrap();
+        conn.execute(
+            "INSERT INTO encryption (id, kdf, salt, iterations, check_value, created_at)
+             VALUES (1, 'hkdf-sha256', 'c2FsdA==', 0, 'check', datetime('now'))",
+            [],
+        )
+        .unwrap();
+
+        let err = manager.migrate_to(11).unwrap_err();
+        assert!(err.to_string().contains("encrypted"));
+        assert_eq!(manager.current_version().unwrap(), CURRENT_VERSION);
+        assert!(table_exists(&conn, "webhook_outbox"));
+    }
+
+    #[test]
+    fn test_failed_migration_leaves_schema_unchanged() {
+        let conn = create_test_db();
+        let manager = MigrationManager::new(&conn);
+        manager.migrate_to(11).unwrap();
+
+        // A table named like the v12 index makes the second half of v12 fail
+        conn.execute_batch("CREATE TABLE idx_webhook_outbox_due (id INTEGER)")
+            .unwrap();
+
+        assert!(manager.migrate_to(CURRENT_VERSION).is_err());
+        assert_eq!(manager.current_version().unwrap(), 11);
+        assert_eq!(manager.pinned_version().unwrap(), Some(11));
+        assert!(!table_exists(&conn, "webhook_outbox"));
+        assert!(!table_exists(&conn, "encryption"));
+    }
 }
@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 use uuid::Uuid;
@@ -38,6 +39,93 @@ impl FromStr for PromptStatus {
     }
 }
 
+/// Action taken when a redaction rule matches
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionAction {
+    /// Replace the match with `[REDACTED:<rule>]`
+    #[default]
+    Mask,
+    /// Replace the match with a short hash so repeats can be correlated
+    Hash,
+    /// Refuse to capture the prompt
+    Reject,
+}
+
+impl fmt::Display for RedactionAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mask => write!(f, "mask"),
+            Self::Hash => write!(f, "hash"),
+            Self::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+impl FromStr for RedactionAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mask" => Ok(Self::Mask),
+            "hash" => Ok(Self::Hash),
+            "reject" => Ok(Self::Reject),
+            _ => Err(format!("Invalid redaction action: {s}")),
+        }
+    }
+}
+
+/// What the watcher does with a file after capturing it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PostCaptureAction {
+    /// Leave the file where it is
+    #[default]
+    Leave,
+    /// Move the file to the root's processed folder, keeping its relative path
+    Move,
+    /// Rename the file in place to include the prompt ID
+    Rename,
+    /// Delete the file
+    Delete,
+}
+
+impl fmt::Display for PostCaptureAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Leave => write!(f, "leave"),
+            Self::Move => write!(f, "move"),
+            Self::Rename => write!(f, "rename"),
+            Self::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+impl FromStr for PostCaptureAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "leave" => Ok(Self::Leave),
+            "move" => Ok(Self::Move),
+            "rename" => Ok(Self::Rename),
+            "delete" => Ok(Self::Delete),
+            _ => Err(format!("Invalid post-capture action: {s}")),
+        }
+    }
+}
+
+/// Summary of matches for one redaction rule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RedactionFinding {
+    /// Name of the rule that matched
+    pub rule: String,
+    /// Action applied to the matches
+    pub action: RedactionAction,
+    /// Number of matches
+    pub count: usize,
+}
+
 /// Represents a captured prompt with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Prompt {
@@ -50,6 +138,9 @@ pub struct Prompt {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub metadata: PromptMetadata,
+    /// Redactions applied during capture, logged when the prompt is saved
+    #[serde(skip)]
+    pub redactions: Vec<RedactionFinding>,
 }
 
 /// Metadata associated with a prompt
@@ -61,6 +152,60 @@ pub struct PromptMetadata {
     pub execution_time_ms: Option<u64>,
     pub estimated_cost: Option<f64>,
     pub context: Option<String>,
+    /// Custom key/value attributes (ticket id, repo, author, experiment, ...)
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Git repository state at capture time, if captured inside a repository
+    #[serde(default)]
+    pub git: Option<GitContext>,
+    /// Template this prompt was rendered from, if any
+    #[serde(default)]
+    pub template: Option<TemplateUsage>,
+}
+
+/// Reusable prompt skeleton with `{{variable}}` placeholders
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PromptTemplate {
+    /// Unique identifier
+    pub id: String,
+    /// Unique, human-friendly name used on the command line
+    pub name: String,
+    /// Template text with `{{variable}}` placeholders
+    pub content: String,
+    /// What the template is for
+    pub description: Option<String>,
+    /// When the template was created
+    pub created_at: DateTime<Utc>,
+    /// When the template was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Link from a rendered prompt back to its template
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct TemplateUsage {
+    /// ID of the template
+    pub template_id: String,
+    /// Template name at render time, kept if the template is later deleted
+    pub template_name: String,
+    /// Variable values used for rendering
+    pub variables: BTreeMap<String, String>,
+}
+
+/// Git repository context captured with a prompt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct GitContext {
+    /// Project name (repository root directory name)
+    pub project: String,
+    /// Absolute path of the working tree root
+    pub repo_root: String,
+    /// Remote URL (prefers `origin`), credentials stripped
+    pub remote_url: Option<String>,
+    /// Current branch, `None` for a detached HEAD
+    pub branch: Option<String>,
+    /// HEAD commit hash, `None` before the first commit
+    pub commit: Option<String>,
+    /// Whether tracked files had uncommitted changes
+    pub dirty: bool,
 }
 
 /// Quality analysis result
@@ -100,6 +245,22 @@ impl Prompt {
             created_at: now,
             updated_at: now,
             metadata: PromptMetadata::default(),
+            redactions: Vec::new(),
+        }
+    }
+}
+
+impl PromptTemplate {
+    /// Create a new template with generated ID and timestamps
+    pub fn new(name: String, content: String) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            content,
+            description: None,
+            created_at: now,
+            updated_at: now,
         }
     }
 }
@@ -113,6 +274,9 @@ impl Default for PromptMetadata {
             execution_time_ms: None,
             estimated_cost: None,
             context: None,
+            attributes: BTreeMap::new(),
+            git: None,
+            template: None,
         }
     }
 }
@@ -151,6 +315,18 @@ mod tests {
         assert!(metadata.execution_time_ms.is_none());
         assert!(metadata.estimated_cost.is_none());
         assert!(metadata.context.is_none());
+        assert!(metadata.attributes.is_empty());
+        assert!(metadata.git.is_none());
+        assert!(metadata.template.is_none());
+    }
+
+    #[test]
+    fn test_prompt_metadata_deserialize_without_attributes() {
+        let json = r#"{"model":"claude-3-opus","input_tokens":null,"output_tokens":null,"execution_time_ms":null,"estimated_cost":null,"context":null}"#;
+        let metadata: PromptMetadata = serde_json::from_str(json).unwrap();
+
+        assert_eq!(metadata.model, "claude-3-opus");
+        assert!(metadata.attributes.is_empty());
     }
 
     #[test]
@@ -4,6 +4,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
 use std::path::Path;
 use std::str::FromStr;
 
@@ -69,6 +70,9 @@ pub struct ReportData {
     pub top_prompts: Vec<PromptSummary>,
     pub category_stats: Vec<CategoryStat>,
     pub tag_stats: Vec<TagStat>,
+    /// Per-template roll-up of rendered prompts
+    #[serde(default)]
+    pub template_stats: Vec<TemplateStat>,
 }
 
 /// Summary statistics for report
@@ -124,6 +128,19 @@ pub struct TagStat {
     pub count: usize,
 }
 
+/// Template statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateStat {
+    /// Template name
+    pub name: String,
+    /// Number of prompts rendered from the template
+    pub count: usize,
+    /// Average quality score of those prompts
+    pub avg_quality: f64,
+    /// Average efficiency score of those prompts
+    pub avg_efficiency: f64,
+}
+
 /// Report generator
 pub struct ReportGenerator {
     format: ReportFormat,
@@ -480,6 +497,21 @@ impl ReportGenerator {
             output.push('\n');
         }
 
+        // Template Statistics
+        if !data.template_stats.is_empty() {
+            output.push_str("## Templates\n\n");
+            output.push_str("| Template | Prompts | Avg Quality | Avg Efficiency |\n");
+            output.push_str("|----------|---------|-------------|----------------|\n");
+            for stat in &data.template_stats {
+                let _ = writeln!(
+                    output,
+                    "| {} | {} | {:.1} | {:.1} |",
+                    stat.name, stat.count, stat.avg_quality, stat.avg_efficiency
+                );
+            }
+            output.push('\n');
+        }
+
         // Tag Statistics
         if !data.tag_stats.is_empty() {
             output.push_str("## Tags\n\n");
@@ -586,6 +618,22 @@ impl ReportGenerator {
         ));
         output.push_str("</table>\n");
 
+        // Template Statistics
+        if !data.template_stats.is_empty() {
+            output.push_str("<h2>Templates</h2>\n<table>\n");
+            output.push_str(
+                "<tr><th>Template</th><th>Prompts</th><th>Avg Quality</th><th>Avg Efficiency</th></tr>\n",
+            );
+            for stat in &data.template_stats {
+                let _ = writeln!(
+                    output,
+                    "<tr><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.1}</td></tr>",
+                    stat.name, stat.count, stat.avg_quality, stat.avg_efficiency
+                );
+            }
+            output.push_str("</table>\n");
+        }
+
         output.push_str("</body>\n</html>");
 
         Ok(output)
@@ -844,6 +892,42 @@ pub fn build_report_data(
     tag_stats.sort_by(|a, b| b.count.cmp(&a.count));
     tag_stats.truncate(20);
 
+    // Template statistics
+    let mut template_scores: HashMap<String, (usize, Vec<f64>, Vec<f64>)> = HashMap::new();
+    for prompt in prompts {
+        if let Some(ref usage) = prompt.metadata.template {
+            let entry = template_scores
+                .entry(usage.template_name.clone())
+                .or_insert((0, Vec::new(), Vec::new()));
+            entry.0 += 1;
+            if let Some(score) = quality_map.get(&prompt.id) {
+                entry.1.push(score.total_score);
+            }
+            if let Some(metrics) = efficiency_map.get(&prompt.id) {
+                entry.2.push(metrics.efficiency_score);
+            }
+        }
+    }
+    // A template's prompt count is far below f64's exact integer range
+    #[allow(clippy::cast_precision_loss)]
+    let average = |values: &[f64]| {
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
+        }
+    };
+    let mut template_stats: Vec<TemplateStat> = template_scores
+        .into_iter()
+        .map(|(name, (count, quality, efficiency))| TemplateStat {
+            name,
+            count,
+            avg_quality: average(&quality),
+            avg_efficiency: average(&efficiency),
+        })
+        .collect();
+    template_stats.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+
     ReportData {
         title,
         generated_at: now,
@@ -862,6 +946,7 @@ pub fn build_report_data(
         top_prompts,
         category_stats,
         tag_stats,
+        template_stats,
     }
 }
 
@@ -999,4 +1084,30 @@ mod tests {
         assert_eq!(data.summary.avg_quality_score, 0.0);
         assert_eq!(data.summary.avg_efficiency_score, 0.0);
     }
+
+    #[test]
+    fn test_template_stats() {
+        let (mut prompts, quality, efficiency) = create_test_data();
+        prompts[0].metadata.template = Some(crate::models::TemplateUsage {
+            template_id: "t1".to_string(),
+            template_name: "unit-tests".to_string(),
+            variables: Default::default(),
+        });
+        let mut untemplated = Prompt::new("Free-form prompt".to_string());
+        untemplated.content_hash = "other_hash".to_string();
+        prompts.push(untemplated);
+
+        let data = build_report_data(ReportType::Weekly, &prompts, &quality, &efficiency);
+        assert_eq!(data.template_stats.len(), 1);
+        assert_eq!(data.template_stats[0].name, "unit-tests");
+        assert_eq!(data.template_stats[0].count, 1);
+        assert_eq!(data.template_stats[0].avg_quality, 85.0);
+        assert_eq!(data.template_stats[0].avg_efficiency, 75.0);
+
+        let report = ReportGenerator::new(ReportFormat::Markdown)
+            .generate(&data)
+            .unwrap();
+        assert!(report.contains("## Templates"));
+        assert!(report.contains("| unit-tests | 1 | 85.0 | 75.0 |"));
+    }
 }
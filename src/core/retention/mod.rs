@@ -0,0 +1,313 @@
+//! Retention rules for old prompts, scores and version history
+//!
+//! A [`RetentionPolicy`] built from the `retention` config section purges
+//! archived prompts, empties prompts deleted long ago from the trash,
+//! archives prompts that haven't been updated for a while, and trims each
+//! prompt's scores and version history down to the newest entries. A run
+//! happens in one transaction and can be a dry run that only reports what
+//! it would change.
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::RetentionConfig;
+use crate::database::Database;
+use crate::models::PromptStatus;
+use crate::Result;
+
+/// Retention rules; a rule left unset is not applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    archive_after: Option<Duration>,
+    purge_after: Option<Duration>,
+    empty_trash_after: Option<Duration>,
+    keep_scores: Option<usize>,
+    keep_versions: Option<usize>,
+    /// Time between automatic runs; `None` when they are disabled
+    interval: Option<Duration>,
+}
+
+/// What a retention run changed, or would change for a dry run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionReport {
+    /// Ids of the prompts archived
+    pub archived: Vec<String>,
+    /// Ids of the archived prompts deleted
+    pub purged: Vec<String>,
+    /// Prompts permanently deleted from the trash
+    pub trash_emptied: usize,
+    /// Quality and efficiency rows removed
+    pub scores_removed: usize,
+    /// Version history entries removed
+    pub versions_removed: usize,
+}
+
+impl RetentionReport {
+    /// Whether the run changed nothing
+    pub fn is_empty(&self) -> bool {
+        self.archived.is_empty()
+            && self.purged.is_empty()
+            && self.trash_emptied == 0
+            && self.scores_removed == 0
+            && self.versions_removed == 0
+    }
+}
+
+impl RetentionPolicy {
+    /// Policy described by the `retention` config section
+    pub fn from_config(config: &RetentionConfig) -> Self {
+        let days = |days: Option<u32>| days.map(|days| Duration::days(i64::from(days)));
+        let mut policy = Self {
+            archive_after: days(config.archive_after_days),
+            purge_after: days(config.purge_archived_after_days),
+            empty_trash_after: days(Some(config.trash_days).filter(|&days| days > 0)),
+            keep_scores: config.keep_scores.map(|keep| keep.max(1)),
+            keep_versions: config.keep_versions.map(|keep| keep.max(1)),
+            interval: None,
+        };
+        if config.auto_apply && config.interval_hours > 0 && !policy.is_empty() {
+            policy.interval = Some(Duration::hours(i64::from(config.interval_hours)));
+        }
+        policy
+    }
+
+    /// Whether no rule is set
+    pub const fn is_empty(&self) -> bool {
+        self.archive_after.is_none()
+            && self.purge_after.is_none()
+            && self.empty_trash_after.is_none()
+            && self.keep_scores.is_none()
+            && self.keep_versions.is_none()
+    }
+
+    /// Time between automatic runs, `None` when the daemon shouldn't run
+    /// the policy
+    pub const fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Apply the rules, or with `dry_run` report what they would change
+    ///
+    /// Archived prompts are purged before others are archived, so a prompt
+    /// is never archived and deleted in the same run.
+    pub fn run(&self, db: &Database, dry_run: bool) -> Result<RetentionReport> {
+        let now = Utc::now();
+
+        db.transaction(|tx| self.apply(tx, now, dry_run))
+    }
+
+    /// Body of [`run`](Self::run), inside its transaction
+    fn apply(&self, tx: &Database, now: DateTime<Utc>, dry_run: bool) -> Result<RetentionReport> {
+        let mut report = RetentionReport::default();
+
+        if let Some(age) = self.purge_after {
+            report.purged = tx.prompts_updated_before(&PromptStatus::Archived, now - age)?;
+            if !dry_run {
+                report
+                    .purged
+                    .iter()
+                    .try_for_each(|id| tx.delete_prompt_permanently(id))?;
+            }
+        }
+
+        if let Some(age) = self.empty_trash_after {
+            let cutoff = now - age;
+            report.trash_emptied = if dry_run {
+                tx.count_trash_before(cutoff)?
+            } else {
+                tx.empty_trash(Some(cutoff))?
+            };
+        }
+
+        if let Some(age) = self.archive_after {
+            report.archived = tx.prompts_updated_before(&PromptStatus::Active, now - age)?;
+            if !dry_run {
+                report
+                    .archived
+                    .iter()
+                    .try_for_each(|id| tx.archive_prompt(id))?;
+            }
+        }
+
+        if let Some(keep) = self.keep_scores {
+            report.scores_removed = tx.trim_scores(keep, dry_run)?;
+        }
+        if let Some(keep) = self.keep_versions {
+            report.versions_removed = tx.compact_versions(keep, dry_run)?;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EfficiencyMetrics, Prompt, QualityScore};
+
+    fn prompt_updated_days_ago(content: &str, status: PromptStatus, days: i64) -> Prompt {
+        let mut prompt = Prompt::new(content.to_string());
+        prompt.status = status;
+        prompt.updated_at = Utc::now() - Duration::days(days);
+        prompt
+    }
+
+    fn score(prompt_id: &str, total: f64, minutes_ago: i64) -> QualityScore {
+        QualityScore {
+            prompt_id: prompt_id.to_string(),
+            total_score: total,
+            clarity: total,
+            completeness: total,
+            specificity: total,
+            guidance: total,
+            analyzed_at: Utc::now() - Duration::minutes(minutes_ago),
+        }
+    }
+
+    fn metrics(prompt_id: &str, minutes_ago: i64) -> EfficiencyMetrics {
+        EfficiencyMetrics {
+            prompt_id: prompt_id.to_string(),
+            efficiency_score: 50.0,
+            token_efficiency: 50.0,
+            time_efficiency: 50.0,
+            cost_efficiency: 50.0,
+            calculated_at: Utc::now() - Duration::minutes(minutes_ago),
+        }
+    }
+
+    fn policy(yaml: &str) -> RetentionPolicy {
+        RetentionPolicy::from_config(&serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_from_config() {
+        let empty = policy("trash_days: 0");
+        assert!(empty.is_empty());
+        assert!(empty.interval().is_none());
+
+        let rules = policy("keep_scores: 0\ninterval_hours: 6");
+        assert_eq!(rules.keep_scores, Some(1));
+        assert_eq!(rules.empty_trash_after, Some(Duration::days(30)));
+        assert_eq!(rules.interval(), Some(Duration::hours(6)));
+
+        assert!(policy("keep_scores: 3\nauto_apply: false")
+            .interval()
+            .is_none());
+    }
+
+    #[test]
+    fn test_archive_and_purge() {
+        let db = Database::in_memory().unwrap();
+        let fresh = prompt_updated_days_ago("Fresh prompt", PromptStatus::Active, 1);
+        let stale = prompt_updated_days_ago("Stale prompt", PromptStatus::Active, 120);
+        let old = prompt_updated_days_ago("Long archived", PromptStatus::Archived, 400);
+        let recent = prompt_updated_days_ago("Recently archived", PromptStatus::Archived, 10);
+        for prompt in [&fresh, &stale, &old, &recent] {
+            db.create_prompt(prompt).unwrap();
+        }
+
+        let policy = policy("archive_after_days: 90\npurge_archived_after_days: 365");
+        let planned = policy.run(&db, true).unwrap();
+        assert_eq!(planned.archived, vec![stale.id.clone()]);
+        assert_eq!(planned.purged, vec![old.id.clone()]);
+        assert_eq!(db.count_prompts().unwrap(), 4);
+        assert_eq!(
+            db.get_prompt(&stale.id).unwrap().unwrap().status,
+            PromptStatus::Active
+        );
+
+        assert_eq!(policy.run(&db, false).unwrap(), planned);
+        assert!(db.get_prompt(&old.id).unwrap().is_none());
+        assert_eq!(
+            db.get_prompt(&stale.id).unwrap().unwrap().status,
+            PromptStatus::Archived
+        );
+        assert_eq!(
+            db.get_prompt(&recent.id).unwrap().unwrap().status,
+            PromptStatus::Archived
+        );
+
+        // Archiving restarts the clock, so nothing more is due
+        assert!(policy.run(&db, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_empty_trash() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("prompts.db");
+        let db = Database::new(path.to_str().unwrap()).unwrap();
+        let old = Prompt::new("Deleted long ago".to_string());
+        let recent = Prompt::new("Deleted today".to_string());
+        for prompt in [&old, &recent] {
+            db.create_prompt(prompt).unwrap();
+            db.delete_prompt(&prompt.id).unwrap();
+        }
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute(
+                "UPDATE prompts SET deleted_at = ?2 WHERE id = ?1",
+                rusqlite::params![old.id, (Utc::now() - Duration::days(45)).to_rfc3339()],
+            )
+            .unwrap();
+
+        let policy = policy("trash_days: 30");
+        let planned = policy.run(&db, true).unwrap();
+        assert_eq!(planned.trash_emptied, 1);
+        assert_eq!(db.list_trash().unwrap().len(), 2);
+
+        assert_eq!(policy.run(&db, false).unwrap(), planned);
+        let trash = db.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].0.id, recent.id);
+    }
+
+    #[test]
+    fn test_trim_scores_and_versions() {
+        let db = Database::in_memory().unwrap();
+        let mut prompt = Prompt::new("Version one".to_string());
+        db.create_prompt(&prompt).unwrap();
+
+        for minutes_ago in [30, 20, 10] {
+            db.save_quality_score(&score(&prompt.id, minutes_ago as f64, minutes_ago))
+                .unwrap();
+            db.save_efficiency_metrics(&metrics(&prompt.id, minutes_ago))
+                .unwrap();
+        }
+
+        // The second version repeats the first, as after a category change
+        for content in ["Version one", "Version one", "Version two", "Version three"] {
+            prompt.content = content.to_string();
+            prompt.content_hash = crate::utils::calculate_hash(content);
+            db.save_version(&prompt).unwrap();
+        }
+
+        let policy = policy(
+            "keep_scores: 1
+keep_versions: 2",
+        );
+        let planned = policy.run(&db, true).unwrap();
+        assert_eq!(planned.scores_removed, 4);
+        assert_eq!(planned.versions_removed, 2);
+        assert_eq!(db.trim_scores(1, true).unwrap(), 4);
+        assert_eq!(db.get_version_history(&prompt.id).unwrap().len(), 4);
+
+        assert_eq!(policy.run(&db, false).unwrap(), planned);
+        assert_eq!(db.trim_scores(1, true).unwrap(), 0);
+        assert_eq!(
+            db.get_quality_score(&prompt.id)
+                .unwrap()
+                .unwrap()
+                .total_score,
+            10.0
+        );
+
+        let mut versions: Vec<_> = db
+            .get_version_history(&prompt.id)
+            .unwrap()
+            .into_iter()
+            .map(|version| version.content)
+            .collect();
+        versions.sort();
+        assert_eq!(versions, vec!["Version three", "Version two"]);
+        assert!(policy.run(&db, false).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,2431 @@
+//! Pluggable prompt storage
+//!
+//! [`PromptStore`] is the storage surface the CLI and library users work
+//! against: prompts with their tags and attributes, analysis scores, version
+//! history, templates, trends, JSON export and the watcher's capture
+//! bookkeeping. Three backends implement it:
+//!
+//! - [`Database`]: `SQLite`, the default. It alone has a trash for deleted
+//!   prompts, a redaction log and lifecycle events.
+//! - [`MemoryStore`]: everything held in memory, for tests and short-lived
+//!   tools.
+//! - [`FileStore`]: one pretty-printed JSON file per prompt in a directory,
+//!   and one per template under `templates/`, so a prompt collection can
+//!   live in a git repository and be reviewed as ordinary diffs.
+//!
+//! The webhook outbox, encryption, backups, schema migrations, retention and
+//! `doctor` remain SQLite-only and are reached through [`Database`] directly.
+//!
+//! ```rust
+//! # use prompt_tracking::{models::Prompt, store::{MemoryStore, PromptStore}};
+//! # fn main() -> prompt_tracking::Result<()> {
+//! let store: Box<dyn PromptStore> = Box::new(MemoryStore::new());
+//! store.create_prompt(&Prompt::new("Summarize this changelog".to_string()))?;
+//! assert_eq!(store.count_prompts()?, 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::database::{
+    CaptureFailure, Database, FileAction, LogOffset, ProjectTrend, PromptFilter, TrendDataPoint,
+    VersionHistory, WatchCheckpoint, WatchedFile,
+};
+use crate::events::Event;
+use crate::models::{
+    EfficiencyMetrics, PostCaptureAction, Prompt, PromptStatus, PromptTemplate, QualityScore,
+    RedactionFinding,
+};
+use crate::{PromptTrackingError, Result};
+
+/// Storage for prompts, their scores, their version history and what the
+/// watcher knows about the files they came from
+///
+/// Object safe, so callers can hold a `&dyn PromptStore` and stay
+/// independent of the backend.
+pub trait PromptStore: Send + Sync {
+    // Prompts
+
+    /// Insert a new prompt
+    fn create_prompt(&self, prompt: &Prompt) -> Result<()>;
+
+    /// Get a prompt by ID
+    fn get_prompt(&self, id: &str) -> Result<Option<Prompt>>;
+
+    /// Replace a stored prompt, including its tags and attributes
+    fn update_prompt(&self, prompt: &Prompt) -> Result<()>;
+
+    /// Delete a prompt
+    ///
+    /// What this keeps is up to the backend: a store with a trash (see
+    /// [`has_trash`](Self::has_trash)) moves the prompt there, restorable
+    /// until the trash is emptied; [`MemoryStore`] deletes it with its scores
+    /// and history; [`FileStore`] refuses, so a prompt's file is only removed
+    /// through [`delete_prompt_permanently`](Self::delete_prompt_permanently).
+    fn delete_prompt(&self, id: &str) -> Result<()>;
+
+    /// Delete a prompt with its scores and history, bypassing any trash
+    fn delete_prompt_permanently(&self, id: &str) -> Result<()>;
+
+    /// List prompts matching a filter, newest first
+    fn list_prompts(&self, filter: &PromptFilter) -> Result<Vec<Prompt>>;
+
+    /// Search prompts by content
+    fn search_prompts(&self, query: &str) -> Result<Vec<Prompt>> {
+        self.list_prompts(&PromptFilter {
+            search_query: Some(query.to_string()),
+            ..Default::default()
+        })
+    }
+
+    /// Find a prompt with the given content hash
+    fn find_by_hash(&self, hash: &str) -> Result<Option<Prompt>>;
+
+    /// Get total number of prompts
+    fn count_prompts(&self) -> Result<usize>;
+
+    /// Archive a prompt
+    fn archive_prompt(&self, id: &str) -> Result<()>;
+
+    /// Set an archived prompt back to active
+    fn unarchive_prompt(&self, id: &str) -> Result<()>;
+
+    // Trash
+
+    /// Whether [`delete_prompt`](Self::delete_prompt) moves prompts to a
+    /// trash; only [`Database`] has one
+    fn has_trash(&self) -> bool {
+        false
+    }
+
+    /// Bring a prompt back from the trash
+    fn restore_prompt(&self, id: &str) -> Result<()> {
+        Err(PromptTrackingError::DatabaseError(format!(
+            "Prompt not in trash: {id}"
+        )))
+    }
+
+    /// Prompts in the trash with the time each was deleted, most recently
+    /// deleted first
+    fn list_trash(&self) -> Result<Vec<(Prompt, DateTime<Utc>)>> {
+        Ok(Vec::new())
+    }
+
+    /// Permanently delete the prompts in the trash, or only those deleted
+    /// before `deleted_before`, returning how many were removed
+    fn empty_trash(&self, deleted_before: Option<DateTime<Utc>>) -> Result<usize> {
+        let _ = deleted_before;
+        Ok(0)
+    }
+
+    // Tags and attributes
+
+    /// Add a tag to a prompt, returning `false` if it already had it
+    fn add_tag(&self, prompt_id: &str, tag: &str) -> Result<bool> {
+        let mut prompt = require(self.get_prompt(prompt_id)?, prompt_id)?;
+        if prompt.tags.iter().any(|t| t == tag) {
+            return Ok(false);
+        }
+        prompt.tags.push(tag.to_string());
+        self.update_prompt(&prompt)?;
+        Ok(true)
+    }
+
+    /// Remove a tag from a prompt, returning whether it had it
+    fn remove_tag(&self, prompt_id: &str, tag: &str) -> Result<bool> {
+        let mut prompt = require(self.get_prompt(prompt_id)?, prompt_id)?;
+        let before = prompt.tags.len();
+        prompt.tags.retain(|t| t != tag);
+        if prompt.tags.len() == before {
+            return Ok(false);
+        }
+        self.update_prompt(&prompt)?;
+        Ok(true)
+    }
+
+    /// Every tag in use with its number of prompts, most used first
+    fn list_tags(&self) -> Result<Vec<(String, usize)>> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for prompt in self.list_prompts(&PromptFilter::default())? {
+            for tag in prompt.tags {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        Ok(by_count(counts))
+    }
+
+    /// Set a custom attribute on a prompt
+    fn set_attribute(&self, prompt_id: &str, key: &str, value: &str) -> Result<()>;
+
+    /// Remove an attribute, returning whether it was set
+    fn remove_attribute(&self, prompt_id: &str, key: &str) -> Result<bool>;
+
+    /// Get all attributes of a prompt
+    fn get_attributes(&self, prompt_id: &str) -> Result<BTreeMap<String, String>>;
+
+    // Scores
+
+    /// Record a quality score
+    fn save_quality_score(&self, score: &QualityScore) -> Result<()>;
+
+    /// Get the latest quality score of a prompt
+    fn get_quality_score(&self, prompt_id: &str) -> Result<Option<QualityScore>>;
+
+    /// Record efficiency metrics
+    fn save_efficiency_metrics(&self, metrics: &EfficiencyMetrics) -> Result<()>;
+
+    /// Get the latest efficiency metrics of a prompt
+    fn get_efficiency_metrics(&self, prompt_id: &str) -> Result<Option<EfficiencyMetrics>>;
+
+    /// Get every recorded quality score, newest first
+    fn get_all_quality_scores(&self) -> Result<Vec<QualityScore>>;
+
+    /// Get the latest quality score of each prompt in `prompt_ids`
+    fn get_latest_quality_scores(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, QualityScore>>;
+
+    /// Get the latest efficiency metrics of each prompt in `prompt_ids`
+    fn get_latest_efficiency_metrics(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, EfficiencyMetrics>>;
+
+    // Versions
+
+    /// Save the prompt's current content as its next version
+    fn save_version(&self, prompt: &Prompt) -> Result<()>;
+
+    /// Get the latest version number recorded for a prompt (0 if none)
+    fn latest_version(&self, prompt_id: &str) -> Result<i32>;
+
+    /// Get version history for a prompt, newest first
+    fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>>;
+
+    /// Get version history entries of all prompts
+    fn get_all_versions(&self) -> Result<Vec<VersionHistory>>;
+
+    /// Overwrite the content of a version history entry
+    ///
+    /// Used to scrub secrets from stored history; not for normal edits.
+    fn update_version_content(&self, version_id: i64, content: &str) -> Result<()>;
+
+    /// Restore a prompt to an earlier version, saving its current content
+    /// as a new version first
+    fn restore_version(&self, prompt_id: &str, version: i32) -> Result<Prompt> {
+        let history = self
+            .get_version_history(prompt_id)?
+            .into_iter()
+            .find(|entry| entry.version == version)
+            .ok_or_else(|| PromptTrackingError::VersionNotFound {
+                prompt_id: prompt_id.to_string(),
+                version,
+            })?;
+
+        let mut restored = None;
+        self.atomic(&mut |store| {
+            let mut prompt = require(store.get_prompt(prompt_id)?, prompt_id)?;
+            store.save_version(&prompt)?;
+            prompt.content.clone_from(&history.content);
+            prompt.content_hash.clone_from(&history.content_hash);
+            prompt.updated_at = Utc::now();
+            store.update_prompt(&prompt)?;
+            restored = Some(prompt);
+            Ok(())
+        })?;
+        require(restored, prompt_id)
+    }
+
+    // Capture bookkeeping
+
+    /// Get the prompt mapping for a watched file path
+    fn get_watched_file(&self, path: &str) -> Result<Option<WatchedFile>>;
+
+    /// Record which prompt a watched file was captured as
+    fn set_watched_file(&self, path: &str, prompt_id: &str, content_hash: &str) -> Result<()>;
+
+    /// Forget the prompt mapping and checkpoint of a file that left its watch root
+    fn forget_watched_file(&self, path: &str) -> Result<()>;
+
+    /// Get the checkpoint recorded for a watched file
+    fn get_watch_checkpoint(&self, path: &str) -> Result<Option<WatchCheckpoint>>;
+
+    /// Record that a watched file has been processed
+    fn set_watch_checkpoint(&self, checkpoint: &WatchCheckpoint) -> Result<()>;
+
+    /// Get the read offset recorded for a tailed log
+    fn get_log_offset(&self, path: &str) -> Result<Option<LogOffset>>;
+
+    /// Record how far a tailed log has been read
+    fn set_log_offset(&self, offset: &LogOffset) -> Result<()>;
+
+    /// Record a failed capture, incrementing the attempt count for a known path
+    ///
+    /// A new failure also brings back a previously dismissed entry.
+    fn record_capture_failure(
+        &self,
+        path: &str,
+        error_kind: &str,
+        message: &str,
+        next_retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()>;
+
+    /// Get a failed capture by ID
+    fn get_capture_failure(&self, id: i64) -> Result<Option<CaptureFailure>>;
+
+    /// Get the failed capture recorded for a path
+    fn find_capture_failure(&self, path: &str) -> Result<Option<CaptureFailure>>;
+
+    /// List failed captures, most recent first
+    fn list_capture_failures(&self, include_dismissed: bool) -> Result<Vec<CaptureFailure>>;
+
+    /// Failed captures whose retry time has passed
+    fn due_capture_failures(&self, now: DateTime<Utc>) -> Result<Vec<CaptureFailure>>;
+
+    /// Earliest scheduled automatic retry, if any
+    fn next_capture_retry(&self) -> Result<Option<DateTime<Utc>>>;
+
+    /// Hide a failed capture and stop retrying it
+    fn dismiss_capture_failure(&self, id: i64) -> Result<bool>;
+
+    /// Remove the failure recorded for a path once it has been captured
+    fn clear_capture_failure(&self, path: &str) -> Result<()>;
+
+    /// Log a post-capture action and return its ID
+    fn record_file_action(
+        &self,
+        prompt_id: &str,
+        action: PostCaptureAction,
+        source: &str,
+        destination: Option<&str>,
+        content: Option<&[u8]>,
+    ) -> Result<i64>;
+
+    /// Get a logged file action by ID
+    fn get_file_action(&self, id: i64) -> Result<Option<FileAction>>;
+
+    /// List logged file actions, most recent first
+    fn list_file_actions(&self, limit: usize) -> Result<Vec<FileAction>>;
+
+    /// Mark a file action as undone
+    fn mark_file_action_undone(&self, id: i64) -> Result<()>;
+
+    // Trends
+
+    /// Prompt counts and average scores per day over the last `days` days
+    fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>>;
+
+    /// Prompt counts and average scores per git project over the last
+    /// `days` days, most active first
+    fn get_project_trends(&self, days: i32) -> Result<Vec<ProjectTrend>>;
+
+    /// Prompt counts per category, largest first
+    fn get_category_distribution(&self) -> Result<Vec<(String, usize)>>;
+
+    // Export
+
+    /// Export prompts and scores as JSON
+    fn export_to_json(&self) -> Result<String> {
+        let prompts = self.list_prompts(&PromptFilter::default())?;
+        let quality_scores = self.get_all_quality_scores()?;
+        let ids: Vec<&str> = prompts.iter().map(|p| p.id.as_str()).collect();
+        let mut latest = self.get_latest_efficiency_metrics(&ids)?;
+        let efficiency_metrics: Vec<EfficiencyMetrics> = prompts
+            .iter()
+            .filter_map(|p| latest.remove(&p.id))
+            .collect();
+
+        let export_data = serde_json::json!({
+            "version": "1.0",
+            "exported_at": Utc::now().to_rfc3339(),
+            "prompts": prompts,
+            "quality_scores": quality_scores,
+            "efficiency_metrics": efficiency_metrics,
+        });
+        serde_json::to_string_pretty(&export_data).map_err(|e| {
+            PromptTrackingError::IoError(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("JSON export error: {e}"),
+            ))
+        })
+    }
+
+    /// Import prompts and scores exported by any store, skipping prompts
+    /// whose content is already stored; returns the number imported
+    fn import_from_json(&self, json_str: &str) -> Result<usize> {
+        let data: serde_json::Value = serde_json::from_str(json_str).map_err(|e| {
+            PromptTrackingError::IoError(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("JSON parse error: {e}"),
+            ))
+        })?;
+        let prompts: Vec<Prompt> = parse_section(&data, "prompts")?;
+        let quality_scores: Vec<QualityScore> = parse_section(&data, "quality_scores")?;
+        let efficiency_metrics: Vec<EfficiencyMetrics> =
+            parse_section(&data, "efficiency_metrics")?;
+
+        let mut imported = 0;
+        self.atomic(&mut |store| {
+            imported = import_prompts(store, &prompts)?;
+            // Scores of prompts that were skipped or missing are dropped
+            for score in &quality_scores {
+                let _ = store.save_quality_score(score);
+            }
+            for metrics in &efficiency_metrics {
+                let _ = store.save_efficiency_metrics(metrics);
+            }
+            Ok(())
+        })?;
+        Ok(imported)
+    }
+
+    // Templates
+
+    /// Create a new template
+    ///
+    /// Fails if a template with the same name already exists.
+    fn create_template(&self, template: &PromptTemplate) -> Result<()>;
+
+    /// Update an existing template's name, content and description
+    fn update_template(&self, template: &PromptTemplate) -> Result<()>;
+
+    /// Get a template by name or ID
+    fn get_template(&self, name_or_id: &str) -> Result<Option<PromptTemplate>>;
+
+    /// List all templates ordered by name
+    fn list_templates(&self) -> Result<Vec<PromptTemplate>>;
+
+    /// Delete a template by name or ID
+    ///
+    /// Prompts rendered from it keep their lineage record, including the
+    /// template name. Returns `false` if no template matched.
+    fn delete_template(&self, name_or_id: &str) -> Result<bool>;
+
+    /// Count prompts rendered from a template
+    fn count_template_prompts(&self, template_id: &str) -> Result<usize> {
+        Ok(self
+            .list_prompts(&PromptFilter::default())?
+            .iter()
+            .filter_map(|prompt| prompt.metadata.template.as_ref())
+            .filter(|usage| usage.template_id == template_id)
+            .count())
+    }
+
+    // Redaction log
+
+    /// Log the secrets redacted from a prompt
+    ///
+    /// Only [`Database`] keeps a redaction log; the other stores drop the
+    /// findings.
+    fn log_redaction_events(
+        &self,
+        prompt_id: &str,
+        findings: &[RedactionFinding],
+        source: &str,
+    ) -> Result<()> {
+        let _ = (prompt_id, findings, source);
+        Ok(())
+    }
+
+    // Events
+
+    /// Deliver an event the store cannot see itself, such as a generated
+    /// report, to its observers
+    ///
+    /// Only [`Database`] has observers; the other stores drop the event.
+    fn emit(&self, event: &Event) {
+        let _ = event;
+    }
+
+    // Atomicity
+
+    /// Run `f` so that its changes are kept only if it returns `Ok`
+    ///
+    /// `SQLite` runs `f` in a transaction. The in-memory and file stores
+    /// restore a snapshot on error, which undoes changes made concurrently
+    /// by other threads as well.
+    fn atomic(&self, f: &mut dyn FnMut(&dyn PromptStore) -> Result<()>) -> Result<()>;
+}
+
+impl PromptStore for Database {
+    fn create_prompt(&self, prompt: &Prompt) -> Result<()> {
+        Self::create_prompt(self, prompt)
+    }
+
+    fn get_prompt(&self, id: &str) -> Result<Option<Prompt>> {
+        Self::get_prompt(self, id)
+    }
+
+    fn update_prompt(&self, prompt: &Prompt) -> Result<()> {
+        Self::update_prompt(self, prompt)
+    }
+
+    fn delete_prompt(&self, id: &str) -> Result<()> {
+        Self::delete_prompt(self, id)
+    }
+
+    fn delete_prompt_permanently(&self, id: &str) -> Result<()> {
+        Self::delete_prompt_permanently(self, id)
+    }
+
+    fn list_prompts(&self, filter: &PromptFilter) -> Result<Vec<Prompt>> {
+        Self::list_prompts(self, filter)
+    }
+
+    fn find_by_hash(&self, hash: &str) -> Result<Option<Prompt>> {
+        Self::find_by_hash(self, hash)
+    }
+
+    fn count_prompts(&self) -> Result<usize> {
+        Self::count_prompts(self)
+    }
+
+    fn archive_prompt(&self, id: &str) -> Result<()> {
+        Self::archive_prompt(self, id)
+    }
+
+    fn unarchive_prompt(&self, id: &str) -> Result<()> {
+        Self::unarchive_prompt(self, id)
+    }
+
+    fn has_trash(&self) -> bool {
+        true
+    }
+
+    fn restore_prompt(&self, id: &str) -> Result<()> {
+        Self::restore_prompt(self, id)
+    }
+
+    fn list_trash(&self) -> Result<Vec<(Prompt, DateTime<Utc>)>> {
+        Self::list_trash(self)
+    }
+
+    fn empty_trash(&self, deleted_before: Option<DateTime<Utc>>) -> Result<usize> {
+        Self::empty_trash(self, deleted_before)
+    }
+
+    fn set_attribute(&self, prompt_id: &str, key: &str, value: &str) -> Result<()> {
+        Self::set_attribute(self, prompt_id, key, value)
+    }
+
+    fn remove_attribute(&self, prompt_id: &str, key: &str) -> Result<bool> {
+        Self::remove_attribute(self, prompt_id, key)
+    }
+
+    fn get_attributes(&self, prompt_id: &str) -> Result<BTreeMap<String, String>> {
+        Self::get_attributes(self, prompt_id)
+    }
+
+    fn save_quality_score(&self, score: &QualityScore) -> Result<()> {
+        Self::save_quality_score(self, score)
+    }
+
+    fn get_quality_score(&self, prompt_id: &str) -> Result<Option<QualityScore>> {
+        Self::get_quality_score(self, prompt_id)
+    }
+
+    fn save_efficiency_metrics(&self, metrics: &EfficiencyMetrics) -> Result<()> {
+        Self::save_efficiency_metrics(self, metrics)
+    }
+
+    fn get_efficiency_metrics(&self, prompt_id: &str) -> Result<Option<EfficiencyMetrics>> {
+        Self::get_efficiency_metrics(self, prompt_id)
+    }
+
+    fn get_all_quality_scores(&self) -> Result<Vec<QualityScore>> {
+        Self::get_all_quality_scores(self)
+    }
+
+    fn get_latest_quality_scores(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, QualityScore>> {
+        Self::get_latest_quality_scores(self, prompt_ids)
+    }
+
+    fn get_latest_efficiency_metrics(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, EfficiencyMetrics>> {
+        Self::get_latest_efficiency_metrics(self, prompt_ids)
+    }
+
+    fn save_version(&self, prompt: &Prompt) -> Result<()> {
+        Self::save_version(self, prompt)
+    }
+
+    fn latest_version(&self, prompt_id: &str) -> Result<i32> {
+        Self::latest_version(self, prompt_id)
+    }
+
+    fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>> {
+        Self::get_version_history(self, prompt_id)
+    }
+
+    fn get_all_versions(&self) -> Result<Vec<VersionHistory>> {
+        Self::get_all_versions(self)
+    }
+
+    fn update_version_content(&self, version_id: i64, content: &str) -> Result<()> {
+        Self::update_version_content(self, version_id, content)
+    }
+
+    fn restore_version(&self, prompt_id: &str, version: i32) -> Result<Prompt> {
+        Self::restore_version(self, prompt_id, version)
+    }
+
+    fn get_watched_file(&self, path: &str) -> Result<Option<WatchedFile>> {
+        Self::get_watched_file(self, path)
+    }
+
+    fn set_watched_file(&self, path: &str, prompt_id: &str, content_hash: &str) -> Result<()> {
+        Self::set_watched_file(self, path, prompt_id, content_hash)
+    }
+
+    fn forget_watched_file(&self, path: &str) -> Result<()> {
+        Self::forget_watched_file(self, path)
+    }
+
+    fn get_watch_checkpoint(&self, path: &str) -> Result<Option<WatchCheckpoint>> {
+        Self::get_watch_checkpoint(self, path)
+    }
+
+    fn set_watch_checkpoint(&self, checkpoint: &WatchCheckpoint) -> Result<()> {
+        Self::set_watch_checkpoint(self, checkpoint)
+    }
+
+    fn get_log_offset(&self, path: &str) -> Result<Option<LogOffset>> {
+        Self::get_log_offset(self, path)
+    }
+
+    fn set_log_offset(&self, offset: &LogOffset) -> Result<()> {
+        Self::set_log_offset(self, offset)
+    }
+
+    fn record_capture_failure(
+        &self,
+        path: &str,
+        error_kind: &str,
+        message: &str,
+        next_retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        Self::record_capture_failure(self, path, error_kind, message, next_retry_at)
+    }
+
+    fn get_capture_failure(&self, id: i64) -> Result<Option<CaptureFailure>> {
+        Self::get_capture_failure(self, id)
+    }
+
+    fn find_capture_failure(&self, path: &str) -> Result<Option<CaptureFailure>> {
+        Self::find_capture_failure(self, path)
+    }
+
+    fn list_capture_failures(&self, include_dismissed: bool) -> Result<Vec<CaptureFailure>> {
+        Self::list_capture_failures(self, include_dismissed)
+    }
+
+    fn due_capture_failures(&self, now: DateTime<Utc>) -> Result<Vec<CaptureFailure>> {
+        Self::due_capture_failures(self, now)
+    }
+
+    fn next_capture_retry(&self) -> Result<Option<DateTime<Utc>>> {
+        Self::next_capture_retry(self)
+    }
+
+    fn dismiss_capture_failure(&self, id: i64) -> Result<bool> {
+        Self::dismiss_capture_failure(self, id)
+    }
+
+    fn clear_capture_failure(&self, path: &str) -> Result<()> {
+        Self::clear_capture_failure(self, path)
+    }
+
+    fn record_file_action(
+        &self,
+        prompt_id: &str,
+        action: PostCaptureAction,
+        source: &str,
+        destination: Option<&str>,
+        content: Option<&[u8]>,
+    ) -> Result<i64> {
+        Self::record_file_action(self, prompt_id, action, source, destination, content)
+    }
+
+    fn get_file_action(&self, id: i64) -> Result<Option<FileAction>> {
+        Self::get_file_action(self, id)
+    }
+
+    fn list_file_actions(&self, limit: usize) -> Result<Vec<FileAction>> {
+        Self::list_file_actions(self, limit)
+    }
+
+    fn mark_file_action_undone(&self, id: i64) -> Result<()> {
+        Self::mark_file_action_undone(self, id)
+    }
+
+    fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>> {
+        Self::get_daily_trends(self, days)
+    }
+
+    fn get_project_trends(&self, days: i32) -> Result<Vec<ProjectTrend>> {
+        Self::get_project_trends(self, days)
+    }
+
+    fn get_category_distribution(&self) -> Result<Vec<(String, usize)>> {
+        Self::get_category_distribution(self)
+    }
+
+    /// Includes templates, unlike the other stores
+    fn export_to_json(&self) -> Result<String> {
+        Self::export_to_json(self)
+    }
+
+    fn import_from_json(&self, json_str: &str) -> Result<usize> {
+        Self::import_from_json(self, json_str)
+    }
+
+    fn create_template(&self, template: &PromptTemplate) -> Result<()> {
+        Self::create_template(self, template)
+    }
+
+    fn update_template(&self, template: &PromptTemplate) -> Result<()> {
+        Self::update_template(self, template)
+    }
+
+    fn get_template(&self, name_or_id: &str) -> Result<Option<PromptTemplate>> {
+        Self::get_template(self, name_or_id)
+    }
+
+    fn list_templates(&self) -> Result<Vec<PromptTemplate>> {
+        Self::list_templates(self)
+    }
+
+    fn delete_template(&self, name_or_id: &str) -> Result<bool> {
+        Self::delete_template(self, name_or_id)
+    }
+
+    fn count_template_prompts(&self, template_id: &str) -> Result<usize> {
+        Self::count_template_prompts(self, template_id)
+    }
+
+    fn log_redaction_events(
+        &self,
+        prompt_id: &str,
+        findings: &[RedactionFinding],
+        source: &str,
+    ) -> Result<()> {
+        Self::log_redaction_events(self, prompt_id, findings, source)
+    }
+
+    fn emit(&self, event: &Event) {
+        Self::emit(self, event);
+    }
+
+    fn atomic(&self, f: &mut dyn FnMut(&dyn PromptStore) -> Result<()>) -> Result<()> {
+        self.transaction(|tx| f(tx))
+    }
+}
+
+/// Everything stored about one prompt; the unit written by [`FileStore`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    prompt: Prompt,
+    #[serde(default)]
+    quality_scores: Vec<QualityScore>,
+    #[serde(default)]
+    efficiency_metrics: Vec<EfficiencyMetrics>,
+    #[serde(default)]
+    versions: Vec<StoredVersion>,
+}
+
+/// A version history entry inside a [`Record`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredVersion {
+    id: i64,
+    version: i32,
+    content: String,
+    content_hash: String,
+    created_at: DateTime<Utc>,
+}
+
+impl StoredVersion {
+    fn to_history(&self, prompt_id: &str) -> VersionHistory {
+        VersionHistory {
+            id: self.id,
+            prompt_id: prompt_id.to_string(),
+            content: self.content.clone(),
+            content_hash: self.content_hash.clone(),
+            version: self.version,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Contents of a [`MemoryStore`]
+#[derive(Debug, Clone, Default)]
+struct Records {
+    prompts: BTreeMap<String, Record>,
+    /// ID given to the next saved version
+    next_version_id: i64,
+    /// Templates by ID
+    templates: BTreeMap<String, PromptTemplate>,
+    capture: CaptureLog,
+}
+
+/// Watcher bookkeeping of a [`MemoryStore`], keyed by file path
+#[derive(Debug, Clone, Default)]
+struct CaptureLog {
+    watched_files: BTreeMap<String, WatchedFile>,
+    checkpoints: BTreeMap<String, WatchCheckpoint>,
+    log_offsets: BTreeMap<String, LogOffset>,
+    failures: BTreeMap<String, CaptureFailure>,
+    /// Oldest first
+    file_actions: Vec<FileAction>,
+}
+
+impl Records {
+    fn get_mut(&mut self, id: &str) -> Result<&mut Record> {
+        self.prompts.get_mut(id).ok_or_else(|| not_found(id))
+    }
+
+    /// The template with this name or ID
+    fn template(&self, name_or_id: &str) -> Option<&PromptTemplate> {
+        self.templates
+            .get(name_or_id)
+            .or_else(|| self.templates.values().find(|t| t.name == name_or_id))
+    }
+
+    /// Fail if a template other than `id` already uses `name`
+    fn check_template_name(&self, id: &str, name: &str) -> Result<()> {
+        if self
+            .templates
+            .values()
+            .any(|t| t.name == name && t.id != id)
+        {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Template already exists: {name}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Prompts created on or after midnight `days` days ago
+    fn since(&self, days: i32) -> impl Iterator<Item = &Record> {
+        let cutoff = (Utc::now() - Duration::days(i64::from(days)))
+            .date_naive()
+            .and_hms_opt(0, 0, 0)
+            .map(|midnight| midnight.and_utc());
+        self.prompts
+            .values()
+            .filter(move |record| cutoff.map_or(true, |c| record.prompt.created_at >= c))
+    }
+}
+
+/// Prompt store held entirely in memory
+///
+/// Nothing is persisted; a dropped store loses its contents. Does not raise
+/// lifecycle events.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    records: RwLock<Records>,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    const fn from_records(records: Records) -> Self {
+        Self {
+            records: RwLock::new(records),
+        }
+    }
+
+    /// Prompt ID owning a version history entry
+    fn version_owner(&self, version_id: i64) -> Option<String> {
+        self.records
+            .read()
+            .prompts
+            .iter()
+            .find(|(_, record)| record.versions.iter().any(|v| v.id == version_id))
+            .map(|(id, _)| id.clone())
+    }
+
+    /// Run `f` and restore the snapshot taken before it if it fails,
+    /// returning the snapshot and the state `f` left behind
+    fn with_snapshot(
+        &self,
+        store: &dyn PromptStore,
+        f: &mut dyn FnMut(&dyn PromptStore) -> Result<()>,
+    ) -> (Result<()>, Option<(Records, Records)>) {
+        let snapshot = self.records.read().clone();
+        match f(store) {
+            Ok(()) => (Ok(()), None),
+            Err(e) => {
+                let changed = std::mem::replace(&mut *self.records.write(), snapshot.clone());
+                (Err(e), Some((changed, snapshot)))
+            }
+        }
+    }
+}
+
+impl PromptStore for MemoryStore {
+    fn create_prompt(&self, prompt: &Prompt) -> Result<()> {
+        let mut records = self.records.write();
+        if records.prompts.contains_key(&prompt.id) {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Prompt already exists: {}",
+                prompt.id
+            )));
+        }
+        records.prompts.insert(
+            prompt.id.clone(),
+            Record {
+                prompt: stored(prompt),
+                quality_scores: Vec::new(),
+                efficiency_metrics: Vec::new(),
+                versions: Vec::new(),
+            },
+        );
+        drop(records);
+        Ok(())
+    }
+
+    fn get_prompt(&self, id: &str) -> Result<Option<Prompt>> {
+        Ok(self
+            .records
+            .read()
+            .prompts
+            .get(id)
+            .map(|record| record.prompt.clone()))
+    }
+
+    fn update_prompt(&self, prompt: &Prompt) -> Result<()> {
+        self.records.write().get_mut(&prompt.id)?.prompt = stored(prompt);
+        Ok(())
+    }
+
+    fn delete_prompt(&self, id: &str) -> Result<()> {
+        self.delete_prompt_permanently(id)
+    }
+
+    fn delete_prompt_permanently(&self, id: &str) -> Result<()> {
+        let mut records = self.records.write();
+        records.prompts.remove(id);
+        records
+            .capture
+            .watched_files
+            .retain(|_, watched| watched.prompt_id != id);
+        drop(records);
+        Ok(())
+    }
+
+    fn list_prompts(&self, filter: &PromptFilter) -> Result<Vec<Prompt>> {
+        let records = self.records.read();
+        let mut prompts: Vec<&Prompt> = records
+            .prompts
+            .values()
+            .map(|record| &record.prompt)
+            .filter(|prompt| matches(prompt, filter))
+            .collect();
+        prompts.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+
+        let page = prompts
+            .into_iter()
+            .skip(filter.offset.unwrap_or(0))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        drop(records);
+        Ok(page)
+    }
+
+    fn find_by_hash(&self, hash: &str) -> Result<Option<Prompt>> {
+        Ok(self
+            .records
+            .read()
+            .prompts
+            .values()
+            .find(|record| record.prompt.content_hash == hash)
+            .map(|record| record.prompt.clone()))
+    }
+
+    fn count_prompts(&self) -> Result<usize> {
+        Ok(self.records.read().prompts.len())
+    }
+
+    fn archive_prompt(&self, id: &str) -> Result<()> {
+        set_status(&mut self.records.write(), id, PromptStatus::Archived)
+    }
+
+    fn unarchive_prompt(&self, id: &str) -> Result<()> {
+        set_status(&mut self.records.write(), id, PromptStatus::Active)
+    }
+
+    fn set_attribute(&self, prompt_id: &str, key: &str, value: &str) -> Result<()> {
+        self.records
+            .write()
+            .get_mut(prompt_id)?
+            .prompt
+            .metadata
+            .attributes
+            .insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove_attribute(&self, prompt_id: &str, key: &str) -> Result<bool> {
+        Ok(self
+            .records
+            .write()
+            .prompts
+            .get_mut(prompt_id)
+            .is_some_and(|record| record.prompt.metadata.attributes.remove(key).is_some()))
+    }
+
+    fn get_attributes(&self, prompt_id: &str) -> Result<BTreeMap<String, String>> {
+        Ok(self
+            .records
+            .read()
+            .prompts
+            .get(prompt_id)
+            .map(|record| record.prompt.metadata.attributes.clone())
+            .unwrap_or_default())
+    }
+
+    fn save_quality_score(&self, score: &QualityScore) -> Result<()> {
+        self.records
+            .write()
+            .get_mut(&score.prompt_id)?
+            .quality_scores
+            .push(score.clone());
+        Ok(())
+    }
+
+    fn get_quality_score(&self, prompt_id: &str) -> Result<Option<QualityScore>> {
+        let records = self.records.read();
+        Ok(records
+            .prompts
+            .get(prompt_id)
+            .and_then(|record| latest(&record.quality_scores, |score| score.analyzed_at).cloned()))
+    }
+
+    fn save_efficiency_metrics(&self, metrics: &EfficiencyMetrics) -> Result<()> {
+        self.records
+            .write()
+            .get_mut(&metrics.prompt_id)?
+            .efficiency_metrics
+            .push(metrics.clone());
+        Ok(())
+    }
+
+    fn get_efficiency_metrics(&self, prompt_id: &str) -> Result<Option<EfficiencyMetrics>> {
+        let records = self.records.read();
+        Ok(records.prompts.get(prompt_id).and_then(|record| {
+            latest(&record.efficiency_metrics, |metrics| metrics.calculated_at).cloned()
+        }))
+    }
+
+    fn get_all_quality_scores(&self) -> Result<Vec<QualityScore>> {
+        let mut scores: Vec<QualityScore> = self
+            .records
+            .read()
+            .prompts
+            .values()
+            .flat_map(|record| record.quality_scores.iter().cloned())
+            .collect();
+        scores.sort_by_key(|score| std::cmp::Reverse(score.analyzed_at));
+        Ok(scores)
+    }
+
+    fn get_latest_quality_scores(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, QualityScore>> {
+        let records = self.records.read();
+        Ok(prompt_ids
+            .iter()
+            .filter_map(|id| records.prompts.get(*id))
+            .filter_map(|record| latest(&record.quality_scores, |score| score.analyzed_at))
+            .map(|score| (score.prompt_id.clone(), score.clone()))
+            .collect())
+    }
+
+    fn get_latest_efficiency_metrics(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, EfficiencyMetrics>> {
+        let records = self.records.read();
+        Ok(prompt_ids
+            .iter()
+            .filter_map(|id| records.prompts.get(*id))
+            .filter_map(|record| latest(&record.efficiency_metrics, |m| m.calculated_at))
+            .map(|metrics| (metrics.prompt_id.clone(), metrics.clone()))
+            .collect())
+    }
+
+    fn save_version(&self, prompt: &Prompt) -> Result<()> {
+        let mut records = self.records.write();
+        let id = records.next_version_id + 1;
+        let record = records.get_mut(&prompt.id)?;
+        let version = record.versions.iter().map(|v| v.version).max().unwrap_or(0) + 1;
+        record.versions.push(StoredVersion {
+            id,
+            version,
+            content: prompt.content.clone(),
+            content_hash: prompt.content_hash.clone(),
+            created_at: Utc::now(),
+        });
+        records.next_version_id = id;
+        drop(records);
+        Ok(())
+    }
+
+    fn latest_version(&self, prompt_id: &str) -> Result<i32> {
+        Ok(self
+            .records
+            .read()
+            .prompts
+            .get(prompt_id)
+            .and_then(|record| record.versions.iter().map(|v| v.version).max())
+            .unwrap_or(0))
+    }
+
+    fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>> {
+        let records = self.records.read();
+        let mut history: Vec<VersionHistory> = records
+            .prompts
+            .get(prompt_id)
+            .map(|record| {
+                record
+                    .versions
+                    .iter()
+                    .map(|v| v.to_history(prompt_id))
+                    .collect()
+            })
+            .unwrap_or_default();
+        history.sort_by_key(|version| std::cmp::Reverse(version.version));
+        drop(records);
+        Ok(history)
+    }
+
+    fn get_all_versions(&self) -> Result<Vec<VersionHistory>> {
+        let records = self.records.read();
+        let mut history: Vec<VersionHistory> = records
+            .prompts
+            .iter()
+            .flat_map(|(id, record)| record.versions.iter().map(move |v| v.to_history(id)))
+            .collect();
+        history.sort_by(|a, b| {
+            a.prompt_id
+                .cmp(&b.prompt_id)
+                .then(a.version.cmp(&b.version))
+        });
+        drop(records);
+        Ok(history)
+    }
+
+    fn update_version_content(&self, version_id: i64, content: &str) -> Result<()> {
+        let mut records = self.records.write();
+        if let Some(version) = records
+            .prompts
+            .values_mut()
+            .flat_map(|record| record.versions.iter_mut())
+            .find(|v| v.id == version_id)
+        {
+            version.content = content.to_string();
+            version.content_hash = crate::utils::calculate_hash(content);
+        }
+        drop(records);
+        Ok(())
+    }
+
+    fn get_watched_file(&self, path: &str) -> Result<Option<WatchedFile>> {
+        Ok(self.records.read().capture.watched_files.get(path).cloned())
+    }
+
+    fn set_watched_file(&self, path: &str, prompt_id: &str, content_hash: &str) -> Result<()> {
+        self.records.write().capture.watched_files.insert(
+            path.to_string(),
+            WatchedFile {
+                path: path.to_string(),
+                prompt_id: prompt_id.to_string(),
+                content_hash: content_hash.to_string(),
+                updated_at: Utc::now(),
+            },
+        );
+        Ok(())
+    }
+
+    fn forget_watched_file(&self, path: &str) -> Result<()> {
+        let capture = &mut self.records.write().capture;
+        capture.watched_files.remove(path);
+        capture.checkpoints.remove(path);
+        Ok(())
+    }
+
+    fn get_watch_checkpoint(&self, path: &str) -> Result<Option<WatchCheckpoint>> {
+        Ok(self.records.read().capture.checkpoints.get(path).cloned())
+    }
+
+    fn set_watch_checkpoint(&self, checkpoint: &WatchCheckpoint) -> Result<()> {
+        self.records
+            .write()
+            .capture
+            .checkpoints
+            .insert(checkpoint.path.clone(), checkpoint.clone());
+        Ok(())
+    }
+
+    fn get_log_offset(&self, path: &str) -> Result<Option<LogOffset>> {
+        Ok(self.records.read().capture.log_offsets.get(path).cloned())
+    }
+
+    fn set_log_offset(&self, offset: &LogOffset) -> Result<()> {
+        self.records
+            .write()
+            .capture
+            .log_offsets
+            .insert(offset.path.clone(), offset.clone());
+        Ok(())
+    }
+
+    fn record_capture_failure(
+        &self,
+        path: &str,
+        error_kind: &str,
+        message: &str,
+        next_retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let now = Utc::now();
+        let capture = &mut self.records.write().capture;
+        let next_id = capture.failures.values().map(|f| f.id).max().unwrap_or(0) + 1;
+        let failure = capture
+            .failures
+            .entry(path.to_string())
+            .or_insert_with(|| CaptureFailure {
+                id: next_id,
+                path: path.to_string(),
+                error_kind: String::new(),
+                message: String::new(),
+                first_seen: now,
+                last_seen: now,
+                attempts: 0,
+                next_retry_at: None,
+                dismissed: false,
+            });
+        failure.error_kind = error_kind.to_string();
+        failure.message = message.to_string();
+        failure.last_seen = now;
+        failure.attempts += 1;
+        failure.next_retry_at = next_retry_at;
+        failure.dismissed = false;
+        Ok(())
+    }
+
+    fn get_capture_failure(&self, id: i64) -> Result<Option<CaptureFailure>> {
+        Ok(self
+            .records
+            .read()
+            .capture
+            .failures
+            .values()
+            .find(|failure| failure.id == id)
+            .cloned())
+    }
+
+    fn find_capture_failure(&self, path: &str) -> Result<Option<CaptureFailure>> {
+        Ok(self.records.read().capture.failures.get(path).cloned())
+    }
+
+    fn list_capture_failures(&self, include_dismissed: bool) -> Result<Vec<CaptureFailure>> {
+        let mut failures: Vec<CaptureFailure> = self
+            .records
+            .read()
+            .capture
+            .failures
+            .values()
+            .filter(|failure| include_dismissed || !failure.dismissed)
+            .cloned()
+            .collect();
+        failures.sort_by_key(|failure| std::cmp::Reverse(failure.last_seen));
+        Ok(failures)
+    }
+
+    fn due_capture_failures(&self, now: DateTime<Utc>) -> Result<Vec<CaptureFailure>> {
+        let mut failures: Vec<CaptureFailure> = self
+            .records
+            .read()
+            .capture
+            .failures
+            .values()
+            .filter(|failure| {
+                !failure.dismissed && failure.next_retry_at.is_some_and(|at| at <= now)
+            })
+            .cloned()
+            .collect();
+        failures.sort_by_key(|failure| failure.next_retry_at);
+        Ok(failures)
+    }
+
+    fn next_capture_retry(&self) -> Result<Option<DateTime<Utc>>> {
+        Ok(self
+            .records
+            .read()
+            .capture
+            .failures
+            .values()
+            .filter(|failure| !failure.dismissed)
+            .filter_map(|failure| failure.next_retry_at)
+            .min())
+    }
+
+    fn dismiss_capture_failure(&self, id: i64) -> Result<bool> {
+        Ok(self
+            .records
+            .write()
+            .capture
+            .failures
+            .values_mut()
+            .find(|failure| failure.id == id)
+            .map(|failure| {
+                failure.dismissed = true;
+                failure.next_retry_at = None;
+            })
+            .is_some())
+    }
+
+    fn clear_capture_failure(&self, path: &str) -> Result<()> {
+        self.records.write().capture.failures.remove(path);
+        Ok(())
+    }
+
+    fn record_file_action(
+        &self,
+        prompt_id: &str,
+        action: PostCaptureAction,
+        source: &str,
+        destination: Option<&str>,
+        content: Option<&[u8]>,
+    ) -> Result<i64> {
+        let actions = &mut self.records.write().capture.file_actions;
+        let id = actions.last().map_or(0, |last| last.id) + 1;
+        actions.push(FileAction {
+            id,
+            prompt_id: prompt_id.to_string(),
+            action,
+            source: source.to_string(),
+            destination: destination.map(str::to_string),
+            content: content.map(<[u8]>::to_vec),
+            performed_at: Utc::now(),
+            undone_at: None,
+        });
+        Ok(id)
+    }
+
+    fn get_file_action(&self, id: i64) -> Result<Option<FileAction>> {
+        Ok(self
+            .records
+            .read()
+            .capture
+            .file_actions
+            .iter()
+            .find(|action| action.id == id)
+            .cloned())
+    }
+
+    fn list_file_actions(&self, limit: usize) -> Result<Vec<FileAction>> {
+        Ok(self
+            .records
+            .read()
+            .capture
+            .file_actions
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn mark_file_action_undone(&self, id: i64) -> Result<()> {
+        if let Some(action) = self
+            .records
+            .write()
+            .capture
+            .file_actions
+            .iter_mut()
+            .find(|action| action.id == id)
+        {
+            action.undone_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>> {
+        let records = self.records.read();
+        let mut by_date: BTreeMap<String, Vec<&Record>> = BTreeMap::new();
+        for record in records.since(days) {
+            let date = record.prompt.created_at.format("%Y-%m-%d").to_string();
+            by_date.entry(date).or_default().push(record);
+        }
+
+        let trends = by_date
+            .into_iter()
+            .map(|(date, group)| {
+                let (avg_quality, avg_efficiency) = averages(&group);
+                TrendDataPoint {
+                    date,
+                    count: group.len(),
+                    avg_quality,
+                    avg_efficiency,
+                }
+            })
+            .collect();
+        drop(records);
+        Ok(trends)
+    }
+
+    fn get_project_trends(&self, days: i32) -> Result<Vec<ProjectTrend>> {
+        let records = self.records.read();
+        let mut by_project: BTreeMap<String, Vec<&Record>> = BTreeMap::new();
+        for record in records.since(days) {
+            let project = record
+                .prompt
+                .metadata
+                .git
+                .as_ref()
+                .map_or_else(|| "(none)".to_string(), |git| git.project.clone());
+            by_project.entry(project).or_default().push(record);
+        }
+
+        let mut trends: Vec<ProjectTrend> = by_project
+            .into_iter()
+            .map(|(project, group)| {
+                let (avg_quality, avg_efficiency) = averages(&group);
+                ProjectTrend {
+                    project,
+                    count: group.len(),
+                    avg_quality,
+                    avg_efficiency,
+                }
+            })
+            .collect();
+        trends.sort_by_key(|trend| std::cmp::Reverse(trend.count));
+        drop(records);
+        Ok(trends)
+    }
+
+    fn get_category_distribution(&self) -> Result<Vec<(String, usize)>> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for record in self.records.read().prompts.values() {
+            let category = record
+                .prompt
+                .category
+                .clone()
+                .unwrap_or_else(|| "uncategorized".to_string());
+            *counts.entry(category).or_default() += 1;
+        }
+        Ok(by_count(counts))
+    }
+
+    fn create_template(&self, template: &PromptTemplate) -> Result<()> {
+        let mut records = self.records.write();
+        if records.templates.contains_key(&template.id) {
+            return Err(PromptTrackingError::DatabaseError(format!(
+                "Template already exists: {}",
+                template.id
+            )));
+        }
+        records.check_template_name(&template.id, &template.name)?;
+        records
+            .templates
+            .insert(template.id.clone(), template.clone());
+        drop(records);
+        Ok(())
+    }
+
+    fn update_template(&self, template: &PromptTemplate) -> Result<()> {
+        let mut records = self.records.write();
+        records.check_template_name(&template.id, &template.name)?;
+        if let Some(stored) = records.templates.get_mut(&template.id) {
+            stored.name.clone_from(&template.name);
+            stored.content.clone_from(&template.content);
+            stored.description.clone_from(&template.description);
+            stored.updated_at = Utc::now();
+        }
+        drop(records);
+        Ok(())
+    }
+
+    fn get_template(&self, name_or_id: &str) -> Result<Option<PromptTemplate>> {
+        Ok(self.records.read().template(name_or_id).cloned())
+    }
+
+    fn list_templates(&self) -> Result<Vec<PromptTemplate>> {
+        let mut templates: Vec<PromptTemplate> =
+            self.records.read().templates.values().cloned().collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    fn delete_template(&self, name_or_id: &str) -> Result<bool> {
+        let mut records = self.records.write();
+        let id = records.template(name_or_id).map(|t| t.id.clone());
+        let removed = id.is_some_and(|id| records.templates.remove(&id).is_some());
+        drop(records);
+        Ok(removed)
+    }
+
+    fn atomic(&self, f: &mut dyn FnMut(&dyn PromptStore) -> Result<()>) -> Result<()> {
+        self.with_snapshot(self, f).0
+    }
+}
+
+/// Prompt store keeping one JSON file per prompt in a directory
+///
+/// Each `<prompt id>.json` file holds the prompt with its scores and version
+/// history, pretty-printed with a stable field order so changes diff
+/// cleanly under version control. The directory is read once when opened
+/// and every change is written through to the prompt's file, so the store
+/// assumes no other process edits the files while it is open.
+///
+/// Capture bookkeeping describes files on this machine rather than the
+/// prompts, so it is only held in memory. A reopened store has the watcher
+/// read its files again; content that is already stored is matched to its
+/// prompt instead of being captured twice.
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    memory: MemoryStore,
+    /// Held while changing a record and writing its file, so files are
+    /// written in the order the changes were made
+    writing: Mutex<()>,
+}
+
+/// Subdirectory of a [`FileStore`] holding one JSON file per template
+const TEMPLATE_DIR: &str = "templates";
+
+impl FileStore {
+    /// Open a prompt directory, creating it if needed
+    pub fn open(dir: &str) -> Result<Self> {
+        let dir = PathBuf::from(shellexpand::tilde(dir).to_string());
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to create directory: {e}"))
+        })?;
+
+        let mut records = Records::default();
+        for record in load_dir::<Record>(&dir)? {
+            let max_version_id = record.versions.iter().map(|v| v.id).max().unwrap_or(0);
+            records.next_version_id = records.next_version_id.max(max_version_id);
+            records.prompts.insert(record.prompt.id.clone(), record);
+        }
+        let template_dir = dir.join(TEMPLATE_DIR);
+        if template_dir.is_dir() {
+            for template in load_dir::<PromptTemplate>(&template_dir)? {
+                records.templates.insert(template.id.clone(), template);
+            }
+        }
+
+        Ok(Self {
+            dir,
+            memory: MemoryStore::from_records(records),
+            writing: Mutex::new(()),
+        })
+    }
+
+    /// Directory holding the prompt files
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+
+    /// Apply a change to one prompt and write its file, undoing the change
+    /// if the file cannot be written
+    fn write<T>(&self, id: &str, f: impl FnOnce(&MemoryStore) -> Result<T>) -> Result<T> {
+        let _writing = self.writing.lock();
+        let before = self.memory.records.read().prompts.get(id).cloned();
+        let value = f(&self.memory)?;
+        if let Err(e) = self.save(id) {
+            let mut records = self.memory.records.write();
+            match before {
+                Some(record) => records.prompts.insert(id.to_string(), record),
+                None => records.prompts.remove(id),
+            };
+            drop(records);
+            return Err(e);
+        }
+        Ok(value)
+    }
+
+    /// Apply a change to one template and write its file, undoing the change
+    /// if the file cannot be written
+    fn write_template<T>(&self, id: &str, f: impl FnOnce(&MemoryStore) -> Result<T>) -> Result<T> {
+        let _writing = self.writing.lock();
+        let before = self.memory.records.read().templates.get(id).cloned();
+        let value = f(&self.memory)?;
+        if let Err(e) = self.save_template(id) {
+            let mut records = self.memory.records.write();
+            match before {
+                Some(template) => records.templates.insert(id.to_string(), template),
+                None => records.templates.remove(id),
+            };
+            drop(records);
+            return Err(e);
+        }
+        Ok(value)
+    }
+
+    /// Write a prompt's file, or remove it if the prompt is gone
+    fn save(&self, id: &str) -> Result<()> {
+        check_file_id(id)?;
+        let json = self.memory.records.read().prompts.get(id).map(to_json);
+        save_file(&self.dir.join(format!("{id}.json")), json.transpose()?)
+    }
+
+    /// Write a template's file, or remove it if the template is gone
+    fn save_template(&self, id: &str) -> Result<()> {
+        check_file_id(id)?;
+        let json = self.memory.records.read().templates.get(id).map(to_json);
+        let template_dir = self.dir.join(TEMPLATE_DIR);
+        if json.is_some() {
+            std::fs::create_dir_all(&template_dir).map_err(|e| {
+                PromptTrackingError::DatabaseError(format!("Failed to create directory: {e}"))
+            })?;
+        }
+        save_file(&template_dir.join(format!("{id}.json")), json.transpose()?)
+    }
+
+    /// Rewrite the files of every prompt and template a rolled-back
+    /// change touched
+    fn restore_files(&self, changed: &Records, restored: &Records) {
+        let _writing = self.writing.lock();
+        let ids: std::collections::BTreeSet<&String> = changed
+            .prompts
+            .keys()
+            .chain(restored.prompts.keys())
+            .collect();
+        for id in ids {
+            if let Err(e) = self.save(id) {
+                log::error!("Failed to restore prompt file: {e}");
+            }
+        }
+        let template_ids: std::collections::BTreeSet<&String> = changed
+            .templates
+            .keys()
+            .chain(restored.templates.keys())
+            .collect();
+        for id in template_ids {
+            if let Err(e) = self.save_template(id) {
+                log::error!("Failed to restore template file: {e}");
+            }
+        }
+    }
+}
+
+/// Parse every `.json` file directly inside `dir`
+fn load_dir<T: serde::de::DeserializeOwned>(dir: &std::path::Path) -> Result<Vec<T>> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        PromptTrackingError::DatabaseError(format!("Failed to read {}: {}", dir.display(), e))
+    })?;
+    let mut loaded = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(PromptTrackingError::IoError)?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let value = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                PromptTrackingError::DatabaseError(format!(
+                    "Failed to load {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        loaded.push(value);
+    }
+    Ok(loaded)
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value)
+        .map_err(|e| PromptTrackingError::SerializationError(e.to_string()))
+}
+
+/// Write `json` to `path`, or remove the file if there is nothing to write
+fn save_file(path: &std::path::Path, json: Option<String>) -> Result<()> {
+    let Some(json) = json else {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(PromptTrackingError::DatabaseError(format!(
+                    "Failed to remove {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+            _ => Ok(()),
+        };
+    };
+
+    // Write beside the file and rename, so a crash never leaves half a file
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json + "\n")
+        .and_then(|()| std::fs::rename(&temp, path))
+        .map_err(|e| {
+            PromptTrackingError::DatabaseError(format!("Failed to write {}: {}", path.display(), e))
+        })
+}
+
+/// Fail for an ID that cannot safely be used as a file name
+fn check_file_id(id: &str) -> Result<()> {
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        return Err(PromptTrackingError::DatabaseError(format!(
+            "Invalid ID for a file store: {id}"
+        )));
+    }
+    Ok(())
+}
+
+impl PromptStore for FileStore {
+    fn create_prompt(&self, prompt: &Prompt) -> Result<()> {
+        self.write(&prompt.id, |m| m.create_prompt(prompt))
+    }
+
+    fn get_prompt(&self, id: &str) -> Result<Option<Prompt>> {
+        self.memory.get_prompt(id)
+    }
+
+    fn update_prompt(&self, prompt: &Prompt) -> Result<()> {
+        self.write(&prompt.id, |m| m.update_prompt(prompt))
+    }
+
+    fn delete_prompt(&self, id: &str) -> Result<()> {
+        Err(PromptTrackingError::DatabaseError(format!(
+            "A file store has no trash; delete {id} permanently instead"
+        )))
+    }
+
+    fn delete_prompt_permanently(&self, id: &str) -> Result<()> {
+        self.write(id, |m| m.delete_prompt_permanently(id))
+    }
+
+    fn list_prompts(&self, filter: &PromptFilter) -> Result<Vec<Prompt>> {
+        self.memory.list_prompts(filter)
+    }
+
+    fn find_by_hash(&self, hash: &str) -> Result<Option<Prompt>> {
+        self.memory.find_by_hash(hash)
+    }
+
+    fn count_prompts(&self) -> Result<usize> {
+        self.memory.count_prompts()
+    }
+
+    fn archive_prompt(&self, id: &str) -> Result<()> {
+        self.write(id, |m| m.archive_prompt(id))
+    }
+
+    fn unarchive_prompt(&self, id: &str) -> Result<()> {
+        self.write(id, |m| m.unarchive_prompt(id))
+    }
+
+    fn set_attribute(&self, prompt_id: &str, key: &str, value: &str) -> Result<()> {
+        self.write(prompt_id, |m| m.set_attribute(prompt_id, key, value))
+    }
+
+    fn remove_attribute(&self, prompt_id: &str, key: &str) -> Result<bool> {
+        self.write(prompt_id, |m| m.remove_attribute(prompt_id, key))
+    }
+
+    fn get_attributes(&self, prompt_id: &str) -> Result<BTreeMap<String, String>> {
+        self.memory.get_attributes(prompt_id)
+    }
+
+    fn save_quality_score(&self, score: &QualityScore) -> Result<()> {
+        self.write(&score.prompt_id, |m| m.save_quality_score(score))
+    }
+
+    fn get_quality_score(&self, prompt_id: &str) -> Result<Option<QualityScore>> {
+        self.memory.get_quality_score(prompt_id)
+    }
+
+    fn save_efficiency_metrics(&self, metrics: &EfficiencyMetrics) -> Result<()> {
+        self.write(&metrics.prompt_id, |m| m.save_efficiency_metrics(metrics))
+    }
+
+    fn get_efficiency_metrics(&self, prompt_id: &str) -> Result<Option<EfficiencyMetrics>> {
+        self.memory.get_efficiency_metrics(prompt_id)
+    }
+
+    fn get_all_quality_scores(&self) -> Result<Vec<QualityScore>> {
+        self.memory.get_all_quality_scores()
+    }
+
+    fn get_latest_quality_scores(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, QualityScore>> {
+        self.memory.get_latest_quality_scores(prompt_ids)
+    }
+
+    fn get_latest_efficiency_metrics(
+        &self,
+        prompt_ids: &[&str],
+    ) -> Result<HashMap<String, EfficiencyMetrics>> {
+        self.memory.get_latest_efficiency_metrics(prompt_ids)
+    }
+
+    fn save_version(&self, prompt: &Prompt) -> Result<()> {
+        self.write(&prompt.id, |m| m.save_version(prompt))
+    }
+
+    fn latest_version(&self, prompt_id: &str) -> Result<i32> {
+        self.memory.latest_version(prompt_id)
+    }
+
+    fn get_version_history(&self, prompt_id: &str) -> Result<Vec<VersionHistory>> {
+        self.memory.get_version_history(prompt_id)
+    }
+
+    fn get_all_versions(&self) -> Result<Vec<VersionHistory>> {
+        self.memory.get_all_versions()
+    }
+
+    fn update_version_content(&self, version_id: i64, content: &str) -> Result<()> {
+        self.memory
+            .version_owner(version_id)
+            .map_or(Ok(()), |prompt_id| {
+                self.write(&prompt_id, |m| {
+                    m.update_version_content(version_id, content)
+                })
+            })
+    }
+
+    fn get_watched_file(&self, path: &str) -> Result<Option<WatchedFile>> {
+        self.memory.get_watched_file(path)
+    }
+
+    fn set_watched_file(&self, path: &str, prompt_id: &str, content_hash: &str) -> Result<()> {
+        self.memory.set_watched_file(path, prompt_id, content_hash)
+    }
+
+    fn forget_watched_file(&self, path: &str) -> Result<()> {
+        self.memory.forget_watched_file(path)
+    }
+
+    fn get_watch_checkpoint(&self, path: &str) -> Result<Option<WatchCheckpoint>> {
+        self.memory.get_watch_checkpoint(path)
+    }
+
+    fn set_watch_checkpoint(&self, checkpoint: &WatchCheckpoint) -> Result<()> {
+        self.memory.set_watch_checkpoint(checkpoint)
+    }
+
+    fn get_log_offset(&self, path: &str) -> Result<Option<LogOffset>> {
+        self.memory.get_log_offset(path)
+    }
+
+    fn set_log_offset(&self, offset: &LogOffset) -> Result<()> {
+        self.memory.set_log_offset(offset)
+    }
+
+    fn record_capture_failure(
+        &self,
+        path: &str,
+        error_kind: &str,
+        message: &str,
+        next_retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        self.memory
+            .record_capture_failure(path, error_kind, message, next_retry_at)
+    }
+
+    fn get_capture_failure(&self, id: i64) -> Result<Option<CaptureFailure>> {
+        self.memory.get_capture_failure(id)
+    }
+
+    fn find_capture_failure(&self, path: &str) -> Result<Option<CaptureFailure>> {
+        self.memory.find_capture_failure(path)
+    }
+
+    fn list_capture_failures(&self, include_dismissed: bool) -> Result<Vec<CaptureFailure>> {
+        self.memory.list_capture_failures(include_dismissed)
+    }
+
+    fn due_capture_failures(&self, now: DateTime<Utc>) -> Result<Vec<CaptureFailure>> {
+        self.memory.due_capture_failures(now)
+    }
+
+    fn next_capture_retry(&self) -> Result<Option<DateTime<Utc>>> {
+        self.memory.next_capture_retry()
+    }
+
+    fn dismiss_capture_failure(&self, id: i64) -> Result<bool> {
+        self.memory.dismiss_capture_failure(id)
+    }
+
+    fn clear_capture_failure(&self, path: &str) -> Result<()> {
+        self.memory.clear_capture_failure(path)
+    }
+
+    fn record_file_action(
+        &self,
+        prompt_id: &str,
+        action: PostCaptureAction,
+        source: &str,
+        destination: Option<&str>,
+        content: Option<&[u8]>,
+    ) -> Result<i64> {
+        self.memory
+            .record_file_action(prompt_id, action, source, destination, content)
+    }
+
+    fn get_file_action(&self, id: i64) -> Result<Option<FileAction>> {
+        self.memory.get_file_action(id)
+    }
+
+    fn list_file_actions(&self, limit: usize) -> Result<Vec<FileAction>> {
+        self.memory.list_file_actions(limit)
+    }
+
+    fn mark_file_action_undone(&self, id: i64) -> Result<()> {
+        self.memory.mark_file_action_undone(id)
+    }
+
+    fn get_daily_trends(&self, days: i32) -> Result<Vec<TrendDataPoint>> {
+        self.memory.get_daily_trends(days)
+    }
+
+    fn get_project_trends(&self, days: i32) -> Result<Vec<ProjectTrend>> {
+        self.memory.get_project_trends(days)
+    }
+
+    fn get_category_distribution(&self) -> Result<Vec<(String, usize)>> {
+        self.memory.get_category_distribution()
+    }
+
+    fn create_template(&self, template: &PromptTemplate) -> Result<()> {
+        self.write_template(&template.id, |m| m.create_template(template))
+    }
+
+    fn update_template(&self, template: &PromptTemplate) -> Result<()> {
+        self.write_template(&template.id, |m| m.update_template(template))
+    }
+
+    fn get_template(&self, name_or_id: &str) -> Result<Option<PromptTemplate>> {
+        self.memory.get_template(name_or_id)
+    }
+
+    fn list_templates(&self) -> Result<Vec<PromptTemplate>> {
+        self.memory.list_templates()
+    }
+
+    fn delete_template(&self, name_or_id: &str) -> Result<bool> {
+        match self.memory.get_template(name_or_id)? {
+            Some(template) => self.write_template(&template.id, |m| m.delete_template(name_or_id)),
+            None => Ok(false),
+        }
+    }
+
+    fn atomic(&self, f: &mut dyn FnMut(&dyn PromptStore) -> Result<()>) -> Result<()> {
+        let (result, rolled_back) = self.memory.with_snapshot(self, f);
+        if let Some((changed, restored)) = rolled_back {
+            self.restore_files(&changed, &restored);
+        }
+        result
+    }
+}
+
+/// The prompt as the stores keep it; redactions are only logged by `SQLite`
+fn stored(prompt: &Prompt) -> Prompt {
+    Prompt {
+        redactions: Vec::new(),
+        ..prompt.clone()
+    }
+}
+
+fn not_found(id: &str) -> PromptTrackingError {
+    PromptTrackingError::DatabaseError(format!("Prompt not found: {id}"))
+}
+
+fn require<T>(value: Option<T>, id: &str) -> Result<T> {
+    value.ok_or_else(|| not_found(id))
+}
+
+fn set_status(records: &mut Records, id: &str, status: PromptStatus) -> Result<()> {
+    let prompt = &mut records.get_mut(id)?.prompt;
+    prompt.status = status;
+    prompt.updated_at = Utc::now();
+    Ok(())
+}
+
+/// Whether a prompt passes a filter, with the same rules as the SQL query
+fn matches(prompt: &Prompt, filter: &PromptFilter) -> bool {
+    let git = prompt.metadata.git.as_ref();
+    let template = prompt.metadata.template.as_ref();
+
+    filter
+        .category
+        .as_ref()
+        .map_or(true, |c| prompt.category.as_ref() == Some(c))
+        && (filter.tags.is_empty() || prompt.tags.iter().any(|t| filter.tags.contains(t)))
+        && filter.search_query.as_ref().map_or(true, |q| {
+            prompt.content.to_lowercase().contains(&q.to_lowercase())
+        })
+        && filter
+            .date_from
+            .map_or(true, |from| prompt.created_at >= from)
+        && filter.date_to.map_or(true, |to| prompt.created_at <= to)
+        && filter.status.as_ref().map_or(true, |s| &prompt.status == s)
+        && filter
+            .project
+            .as_ref()
+            .map_or(true, |p| git.is_some_and(|g| &g.project == p))
+        && filter
+            .branch
+            .as_ref()
+            .map_or(true, |b| git.is_some_and(|g| g.branch.as_ref() == Some(b)))
+        && filter
+            .template
+            .as_ref()
+            .map_or(true, |t| template.is_some_and(|u| &u.template_name == t))
+        && filter
+            .attributes
+            .iter()
+            .all(|(key, value)| prompt.metadata.attributes.get(key) == Some(value))
+}
+
+/// The most recent entry by `at`; the later-saved one wins a tie
+fn latest<T>(items: &[T], at: impl Fn(&T) -> DateTime<Utc>) -> Option<&T> {
+    items.iter().max_by_key(|item| at(item))
+}
+
+/// Average quality and efficiency over every score in a group, 0 if none
+fn averages(group: &[&Record]) -> (f64, f64) {
+    fn mean(values: impl Iterator<Item = f64>) -> f64 {
+        let (sum, count) = values.fold((0.0, 0u32), |(sum, n), v| (sum + v, n + 1));
+        if count == 0 {
+            0.0
+        } else {
+            sum / f64::from(count)
+        }
+    }
+    (
+        mean(
+            group
+                .iter()
+                .flat_map(|r| r.quality_scores.iter().map(|s| s.total_score)),
+        ),
+        mean(
+            group
+                .iter()
+                .flat_map(|r| r.efficiency_metrics.iter().map(|m| m.efficiency_score)),
+        ),
+    )
+}
+
+/// Counts sorted largest first, then by name
+fn by_count(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts
+}
+
+/// Create the prompts whose content is not stored yet, returning how many
+fn import_prompts(store: &dyn PromptStore, prompts: &[Prompt]) -> Result<usize> {
+    let mut imported = 0;
+    for prompt in prompts {
+        if store.find_by_hash(&prompt.content_hash)?.is_none() {
+            store.create_prompt(prompt)?;
+            imported += 1;
+        }
+    }
+    Ok(imported)
+}
+
+fn parse_section<T: serde::de::DeserializeOwned>(
+    data: &serde_json::Value,
+    key: &str,
+) -> Result<Vec<T>> {
+    data.get(key).map_or_else(
+        || Ok(Vec::new()),
+        |values| {
+            serde_json::from_value(values.clone()).map_err(|e| {
+                PromptTrackingError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("Failed to parse {key}: {e}"),
+                ))
+            })
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GitContext;
+    use tempfile::TempDir;
+
+    fn prompt(content: &str) -> Prompt {
+        let mut prompt = Prompt::new(content.to_string());
+        prompt.content_hash = crate::utils::calculate_hash(content);
+        prompt
+    }
+
+    fn score(prompt_id: &str, total: f64) -> QualityScore {
+        QualityScore {
+            prompt_id: prompt_id.to_string(),
+            total_score: total,
+            clarity: total,
+            completeness: total,
+            specificity: total,
+            guidance: total,
+            analyzed_at: Utc::now(),
+        }
+    }
+
+    /// Behaviour every backend must share
+    fn exercise(store: &dyn PromptStore) {
+        let mut first = prompt("Write a parser for RFC 3339 timestamps");
+        first.category = Some("coding".to_string());
+        first.tags = vec!["rust".to_string()];
+        first.metadata.git = Some(GitContext {
+            project: "tracker".to_string(),
+            branch: Some("main".to_string()),
+            ..Default::default()
+        });
+        let second = prompt("Summarize the release notes");
+        store.create_prompt(&first).unwrap();
+        store.create_prompt(&second).unwrap();
+        assert!(store.create_prompt(&second).is_err());
+        assert_eq!(store.count_prompts().unwrap(), 2);
+
+        // Filters and search
+        let rust = PromptFilter {
+            tags: vec!["rust".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(store.list_prompts(&rust).unwrap()[0].id, first.id);
+        let main = PromptFilter {
+            project: Some("tracker".to_string()),
+            branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.list_prompts(&main).unwrap().len(), 1);
+        assert_eq!(store.search_prompts("RELEASE").unwrap()[0].id, second.id);
+        assert_eq!(
+            store
+                .find_by_hash(&second.content_hash)
+                .unwrap()
+                .unwrap()
+                .id,
+            second.id
+        );
+
+        // Tags and attributes
+        assert!(store.add_tag(&second.id, "docs").unwrap());
+        assert!(!store.add_tag(&second.id, "docs").unwrap());
+        assert!(store.remove_tag(&first.id, "rust").unwrap());
+        assert_eq!(store.list_tags().unwrap(), vec![("docs".to_string(), 1)]);
+        store.set_attribute(&first.id, "ticket", "T-1").unwrap();
+        assert_eq!(store.get_attributes(&first.id).unwrap()["ticket"], "T-1");
+        assert!(store.remove_attribute(&first.id, "ticket").unwrap());
+        assert!(!store.remove_attribute(&first.id, "ticket").unwrap());
+
+        // Trends, before any prompt has more than one score
+        let trends = store.get_daily_trends(7).unwrap();
+        assert_eq!(trends.iter().map(|t| t.count).sum::<usize>(), 2);
+        let projects = store.get_project_trends(7).unwrap();
+        assert!(projects
+            .iter()
+            .any(|p| p.project == "tracker" && p.count == 1));
+        let categories = store.get_category_distribution().unwrap();
+        assert!(categories.contains(&("coding".to_string(), 1)));
+
+        // Scores
+        store.save_quality_score(&score(&first.id, 60.0)).unwrap();
+        store.save_quality_score(&score(&first.id, 80.0)).unwrap();
+        assert_eq!(
+            store
+                .get_quality_score(&first.id)
+                .unwrap()
+                .unwrap()
+                .total_score,
+            80.0
+        );
+        let latest = store
+            .get_latest_quality_scores(&[first.id.as_str(), second.id.as_str()])
+            .unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[&first.id].total_score, 80.0);
+        assert_eq!(store.get_all_quality_scores().unwrap().len(), 2);
+
+        // Versions
+        store.save_version(&first).unwrap();
+        let mut edited = store.get_prompt(&first.id).unwrap().unwrap();
+        edited.content = "Write a parser for ISO 8601 durations".to_string();
+        store.update_prompt(&edited).unwrap();
+        assert_eq!(store.latest_version(&first.id).unwrap(), 1);
+        let restored = store.restore_version(&first.id, 1).unwrap();
+        assert_eq!(restored.content, first.content);
+        assert_eq!(store.get_version_history(&first.id).unwrap()[0].version, 2);
+        assert!(matches!(
+            store.restore_version(&first.id, 9),
+            Err(PromptTrackingError::VersionNotFound { .. })
+                | Err(PromptTrackingError::DatabaseError(_))
+        ));
+
+        // Capture bookkeeping
+        store
+            .set_watched_file("/w/a.md", &first.id, &first.content_hash)
+            .unwrap();
+        assert_eq!(
+            store
+                .get_watched_file("/w/a.md")
+                .unwrap()
+                .unwrap()
+                .prompt_id,
+            first.id
+        );
+        let checkpoint = WatchCheckpoint {
+            path: "/w/a.md".to_string(),
+            size: 3,
+            mtime_ns: 1,
+            file_hash: "abc".to_string(),
+        };
+        store.set_watch_checkpoint(&checkpoint).unwrap();
+        assert_eq!(
+            store.get_watch_checkpoint("/w/a.md").unwrap(),
+            Some(checkpoint)
+        );
+        store.forget_watched_file("/w/a.md").unwrap();
+        assert!(store.get_watched_file("/w/a.md").unwrap().is_none());
+        assert!(store.get_watch_checkpoint("/w/a.md").unwrap().is_none());
+        let mut offset = LogOffset::new("/w/session.jsonl");
+        offset.offset = 42;
+        store.set_log_offset(&offset).unwrap();
+        assert_eq!(
+            store.get_log_offset("/w/session.jsonl").unwrap(),
+            Some(offset)
+        );
+
+        let due = Utc::now() - Duration::seconds(1);
+        store
+            .record_capture_failure("/w/b.md", "io", "locked", Some(due))
+            .unwrap();
+        store
+            .record_capture_failure("/w/b.md", "io", "still locked", Some(due))
+            .unwrap();
+        store
+            .record_capture_failure("/w/c.md", "binary", "binary file", None)
+            .unwrap();
+        let failure = store.find_capture_failure("/w/b.md").unwrap().unwrap();
+        assert_eq!(failure.attempts, 2);
+        assert_eq!(failure.message, "still locked");
+        assert!(store.next_capture_retry().unwrap().is_some());
+        assert_eq!(store.due_capture_failures(Utc::now()).unwrap().len(), 1);
+        assert!(store.dismiss_capture_failure(failure.id).unwrap());
+        assert!(store.due_capture_failures(Utc::now()).unwrap().is_empty());
+        assert!(store.next_capture_retry().unwrap().is_none());
+        assert_eq!(store.list_capture_failures(false).unwrap().len(), 1);
+        assert_eq!(store.list_capture_failures(true).unwrap().len(), 2);
+        store.clear_capture_failure("/w/b.md").unwrap();
+        assert!(store.get_capture_failure(failure.id).unwrap().is_none());
+
+        let moved = store
+            .record_file_action(
+                &first.id,
+                PostCaptureAction::Move,
+                "/w/a.md",
+                Some("/w/done/a.md"),
+                None,
+            )
+            .unwrap();
+        let deleted = store
+            .record_file_action(
+                &first.id,
+                PostCaptureAction::Delete,
+                "/w/d.md",
+                None,
+                Some(b"text"),
+            )
+            .unwrap();
+        let actions = store.list_file_actions(10).unwrap();
+        assert_eq!(
+            actions.iter().map(|a| a.id).collect::<Vec<_>>(),
+            vec![deleted, moved]
+        );
+        assert_eq!(actions[0].content.as_deref(), Some(&b"text"[..]));
+        store.mark_file_action_undone(moved).unwrap();
+        assert!(store
+            .get_file_action(moved)
+            .unwrap()
+            .unwrap()
+            .undone_at
+            .is_some());
+
+        // Templates
+        let template = PromptTemplate::new("review".to_string(), "Review {{file}}".to_string());
+        store.create_template(&template).unwrap();
+        let clash = PromptTemplate::new("review".to_string(), "Other".to_string());
+        assert!(store.create_template(&clash).is_err());
+        let mut edited = store.get_template("review").unwrap().unwrap();
+        edited.content = "Review {{file}} for {{focus}}".to_string();
+        store.update_template(&edited).unwrap();
+        assert_eq!(
+            store.get_template(&template.id).unwrap().unwrap().content,
+            edited.content
+        );
+        let mut rendered = prompt("Review lib.rs for safety");
+        rendered.metadata.template = Some(crate::template::usage(
+            &edited,
+            &[("file", "lib.rs"), ("focus", "safety")]
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ));
+        store.create_prompt(&rendered).unwrap();
+        assert_eq!(store.count_template_prompts(&template.id).unwrap(), 1);
+        assert_eq!(store.list_templates().unwrap().len(), 1);
+        store.delete_prompt_permanently(&rendered.id).unwrap();
+
+        // Atomic changes roll back together
+        let third = prompt("Draft a migration plan");
+        let result = store.atomic(&mut |tx| {
+            tx.create_prompt(&third)?;
+            tx.archive_prompt(&first.id)?;
+            Err(PromptTrackingError::InvalidFormat)
+        });
+        assert!(result.is_err());
+        assert!(store.get_prompt(&third.id).unwrap().is_none());
+        assert_eq!(
+            store.get_prompt(&first.id).unwrap().unwrap().status,
+            PromptStatus::Active
+        );
+
+        // Export into an empty memory store
+        let copy = MemoryStore::new();
+        assert_eq!(
+            copy.import_from_json(&store.export_to_json().unwrap())
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            copy.get_quality_score(&first.id)
+                .unwrap()
+                .unwrap()
+                .total_score,
+            80.0
+        );
+
+        if store.delete_prompt(&second.id).is_err() {
+            assert!(!store.has_trash());
+            store.delete_prompt_permanently(&second.id).unwrap();
+        }
+        assert!(store.get_prompt(&second.id).unwrap().is_none());
+        assert_eq!(store.count_prompts().unwrap(), 1);
+        let trashed = store.list_trash().unwrap();
+        assert_eq!(trashed.len(), usize::from(store.has_trash()));
+        if store.has_trash() {
+            store.restore_prompt(&second.id).unwrap();
+            assert_eq!(store.count_prompts().unwrap(), 2);
+        } else {
+            assert!(store.restore_prompt(&second.id).is_err());
+        }
+        store.delete_prompt_permanently(&second.id).unwrap();
+        assert!(store.list_trash().unwrap().is_empty());
+        assert_eq!(store.count_prompts().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        exercise(&Database::in_memory().unwrap());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        exercise(&MemoryStore::new());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        exercise(&FileStore::open(dir).unwrap());
+
+        // One file per remaining prompt, reloaded as it was left
+        let files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().is_file())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let reopened = FileStore::open(dir).unwrap();
+        let prompt = &reopened.list_prompts(&PromptFilter::default()).unwrap()[0];
+        assert_eq!(reopened.get_version_history(&prompt.id).unwrap().len(), 2);
+        assert_eq!(
+            reopened
+                .get_quality_score(&prompt.id)
+                .unwrap()
+                .unwrap()
+                .total_score,
+            80.0
+        );
+
+        // Version IDs keep counting after a reload
+        let history = reopened.get_all_versions().unwrap();
+        reopened.save_version(prompt).unwrap();
+        let newest = reopened.get_version_history(&prompt.id).unwrap()[0].id;
+        assert!(history.iter().all(|v| v.id < newest));
+
+        // Templates live in their own directory
+        assert_eq!(
+            reopened.get_template("review").unwrap().unwrap().content,
+            "Review {{file}} for {{focus}}"
+        );
+        assert!(reopened.delete_template("review").unwrap());
+        assert!(!reopened.delete_template("review").unwrap());
+        let templates = temp_dir.path().join(TEMPLATE_DIR);
+        assert_eq!(std::fs::read_dir(templates).unwrap().count(), 0);
+
+        let mut bad = Prompt::new("Escapes the directory".to_string());
+        bad.id = "../outside".to_string();
+        assert!(reopened.create_prompt(&bad).is_err());
+        assert!(reopened.get_prompt(&bad.id).unwrap().is_none());
+        assert!(reopened.delete_prompt_permanently(&bad.id).is_err());
+
+        // Plain delete has no trash to move the prompt to
+        assert!(reopened.delete_prompt(&prompt.id).is_err());
+        assert!(reopened.get_prompt(&prompt.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_file_store_failed_write_leaves_memory_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        let store = FileStore::open(dir).unwrap();
+        let prompt = Prompt::new("Kept as written".to_string());
+        store.create_prompt(&prompt).unwrap();
+
+        // A directory where the file would go makes the write fail
+        let blocked = Prompt::new("Never written".to_string());
+        std::fs::create_dir(temp_dir.path().join(format!("{}.json", blocked.id))).unwrap();
+        assert!(store.create_prompt(&blocked).is_err());
+        assert!(store.get_prompt(&blocked.id).unwrap().is_none());
+
+        std::fs::remove_file(temp_dir.path().join(format!("{}.json", prompt.id))).unwrap();
+        std::fs::create_dir(temp_dir.path().join(format!("{}.json", prompt.id))).unwrap();
+        let mut changed = prompt.clone();
+        changed.content = "Not on disk".to_string();
+        assert!(store.update_prompt(&changed).is_err());
+        assert_eq!(
+            store.get_prompt(&prompt.id).unwrap().unwrap().content,
+            "Kept as written"
+        );
+    }
+}
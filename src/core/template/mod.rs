@@ -0,0 +1,175 @@
+//! Prompt templates
+//!
+//! Renders `{{variable}}` placeholders in stored templates and records which
+//! template and values produced each prompt.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::models::{PromptTemplate, TemplateUsage};
+use crate::{PromptTrackingError, Result};
+
+/// Placeholders in `content` with their byte ranges, in order
+///
+/// A placeholder is `{{name}}` with optional whitespace inside the braces,
+/// where `name` starts with a letter or `_` and continues with letters,
+/// digits, `_`, `.` or `-`.
+fn placeholders(content: &str) -> Vec<(Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(offset) = content[from..].find("{{") {
+        let start = from + offset;
+        match placeholder_at(content, start) {
+            Some((end, name)) => {
+                found.push((start..end, name));
+                from = end;
+            }
+            None => from = start + 1,
+        }
+    }
+    found
+}
+
+/// The end and name of a placeholder starting at `start`, if one does
+fn placeholder_at(content: &str, start: usize) -> Option<(usize, &str)> {
+    let rest = &content[start + 2..];
+    let name_start = rest.len() - rest.trim_start().len();
+    let after = &rest[name_start..];
+    let first = after.chars().next()?;
+    if !(first.is_ascii_alphabetic() || first == '_') {
+        return None;
+    }
+    let name_len = after
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')))
+        .unwrap_or(after.len());
+    let name = &after[..name_len];
+    let tail = &after[name_len..];
+    let close = tail.len() - tail.trim_start().len();
+    if !tail[close..].starts_with("}}") {
+        return None;
+    }
+    Some((content.len() - tail.len() + close + 2, name))
+}
+
+/// Variable names used in template content, in order of first appearance
+pub fn variables(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, name) in placeholders(content) {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Render a template with the given variable values
+///
+/// Every placeholder must have a value; values for names that do not appear
+/// in the template are ignored with a warning.
+pub fn render(template: &PromptTemplate, values: &BTreeMap<String, String>) -> Result<String> {
+    let names = variables(&template.content);
+
+    let missing: Vec<&str> = names
+        .iter()
+        .filter(|n| !values.contains_key(n.as_str()))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(PromptTrackingError::TemplateError(format!(
+            "Missing value for {} in template '{}'",
+            missing.join(", "),
+            template.name
+        )));
+    }
+
+    for key in values.keys() {
+        if !names.contains(key) {
+            log::warn!(
+                "Variable '{}' is not used by template '{}'",
+                key,
+                template.name
+            );
+        }
+    }
+
+    let content = &template.content;
+    let mut rendered = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, name) in placeholders(content) {
+        rendered.push_str(&content[last..range.start]);
+        if let Some(value) = values.get(name) {
+            rendered.push_str(value);
+        }
+        last = range.end;
+    }
+    rendered.push_str(&content[last..]);
+    Ok(rendered)
+}
+
+/// Lineage record for a prompt rendered from `template`
+pub fn usage(template: &PromptTemplate, values: &BTreeMap<String, String>) -> TemplateUsage {
+    let names = variables(&template.content);
+    TemplateUsage {
+        template_id: template.id.clone(),
+        template_name: template.name.clone(),
+        variables: values
+            .iter()
+            .filter(|(k, _)| names.contains(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(content: &str) -> PromptTemplate {
+        PromptTemplate::new("review".to_string(), content.to_string())
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_variables_in_order_without_duplicates() {
+        let names = variables("Review {{file}} in {{ language }}; focus on {{file}}");
+        assert_eq!(names, vec!["file", "language"]);
+    }
+
+    #[test]
+    fn test_malformed_placeholders_are_left_alone() {
+        assert_eq!(variables("{{{a}}} {{ 1x }} {{b c}} {{d}"), vec!["a"]);
+        let t = template("{{{a}}} {{}} {{ b-c.d }}");
+        let rendered = render(&t, &values(&[("a", "x"), ("b-c.d", "y")])).unwrap();
+        assert_eq!(rendered, "{x} {{}} y");
+    }
+
+    #[test]
+    fn test_render() {
+        let t = template("Write {{language}} tests for {{ file }}");
+        let rendered = render(&t, &values(&[("language", "Rust"), ("file", "lib.rs")])).unwrap();
+        assert_eq!(rendered, "Write Rust tests for lib.rs");
+    }
+
+    #[test]
+    fn test_render_missing_variable() {
+        let t = template("Write {{language}} tests for {{file}}");
+        let err = render(&t, &values(&[("language", "Rust")])).unwrap_err();
+        assert!(err.to_string().contains("file"));
+    }
+
+    #[test]
+    fn test_usage_keeps_only_template_variables() {
+        let t = template("Explain {{topic}}");
+        let usage = usage(&t, &values(&[("topic", "lifetimes"), ("unused", "x")]));
+
+        assert_eq!(usage.template_id, t.id);
+        assert_eq!(usage.template_name, "review");
+        assert_eq!(usage.variables, values(&[("topic", "lifetimes")]));
+    }
+}
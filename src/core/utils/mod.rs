@@ -24,6 +24,18 @@ pub fn normalize_whitespace(text: &str) -> String {
     text.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
+/// Parse a `key=value` pair, trimming whitespace around both parts
+///
+/// Returns `None` when there is no `=` or the key is empty.
+pub fn parse_key_value(s: &str) -> Option<(String, String)> {
+    let (key, value) = s.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,4 +81,18 @@ mod tests {
         let result = normalize_whitespace("line1\n\nline2\tline3");
         assert_eq!(result, "line1 line2 line3");
     }
+
+    #[test]
+    fn test_parse_key_value() {
+        assert_eq!(
+            parse_key_value("ticket = PROJ-123"),
+            Some(("ticket".to_string(), "PROJ-123".to_string()))
+        );
+        assert_eq!(
+            parse_key_value("url=http://x?a=b"),
+            Some(("url".to_string(), "http://x?a=b".to_string()))
+        );
+        assert_eq!(parse_key_value("novalue"), None);
+        assert_eq!(parse_key_value("=value"), None);
+    }
 }
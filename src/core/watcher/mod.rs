@@ -2,86 +2,353 @@
 //!
 //! Monitors directories for new prompt files and captures them automatically.
 
+use chrono::Utc;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, Receiver};
-use std::time::Duration;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use walkdir::WalkDir;
 
 use crate::capture::CaptureService;
-use crate::database::Database;
+use crate::config::{CaptureSafeguards, Config as AppConfig, WatchConfig, WatchRootConfig};
+use crate::database::{CaptureFailure, FileAction, LogOffset, WatchCheckpoint};
+use crate::jsonl;
+use crate::models::{PostCaptureAction, Prompt};
+use crate::redaction::Redactor;
+use crate::store::PromptStore;
 use crate::{PromptTrackingError, Result};
 
+/// Delay before the first automatic retry of a transient failure
+const RETRY_BASE_SECS: i64 = 5;
+
+/// Longest delay between automatic retries
+const RETRY_MAX_SECS: i64 = 3600;
+
+/// Automatic retries before a failure waits for a manual retry
+const MAX_RETRY_ATTEMPTS: u32 = 8;
+
 /// File watcher for automatic prompt capture
 pub struct FileWatcher {
-    watch_path: PathBuf,
+    roots: Vec<CompiledRoot>,
     watcher: Option<RecommendedWatcher>,
-    receiver: Option<Receiver<notify::Result<Event>>>,
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
     capture_service: CaptureService,
+    /// Quiet period after the last event for a path before it is processed
+    debounce: Duration,
+    /// Paths with unprocessed events and the time of their latest event
+    pending: HashMap<PathBuf, Instant>,
 }
 
-/// Watcher configuration
+/// A directory watched for prompt files, with defaults for what it captures
 #[derive(Debug, Clone)]
-pub struct WatcherConfig {
-    pub watch_path: PathBuf,
+pub struct WatchRoot {
+    /// Directory to watch
+    pub path: PathBuf,
+    /// Whether subdirectories are watched too
     pub recursive: bool,
+    /// File extensions to capture (without the dot)
     pub file_extensions: Vec<String>,
+    /// Glob patterns, relative to `path`, a file must match; empty matches all
+    pub include: Vec<String>,
+    /// Glob patterns, relative to `path`, for files to ignore
+    pub exclude: Vec<String>,
+    /// Category applied to captured prompts, replacing auto-detection
+    pub category: Option<String>,
+    /// Tags added to captured prompts
+    pub tags: Vec<String>,
+    /// Model recorded for captured prompts
+    pub model: Option<String>,
+    /// What to do with a file once it has been captured
+    pub after_capture: PostCaptureAction,
+    /// Folder for the `move` action, relative to `path` unless absolute
+    pub processed_dir: PathBuf,
+}
+
+/// Watcher configuration
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    /// Directories to watch
+    pub roots: Vec<WatchRoot>,
+    /// Quiet period after the last event for a file before it is captured
+    pub debounce: Duration,
+    /// Similarity above which a new file counts as a duplicate
     pub similarity_threshold: f64,
+    /// Record git repository context for captured prompts
+    pub git_context: bool,
+    /// Redaction applied to captured files (`None` disables it)
+    pub redactor: Option<Redactor>,
+    /// Size, binary and encoding rules for captured files
+    pub safeguards: CaptureSafeguards,
+}
+
+/// Result of processing a watched file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// A new prompt was captured from the file
+    Captured {
+        /// ID of the new prompt
+        prompt_id: String,
+        /// File the prompt was captured from
+        path: PathBuf,
+    },
+    /// A tracked file changed and was saved as a new version of its prompt
+    Revised {
+        /// ID of the revised prompt
+        prompt_id: String,
+        /// File that changed
+        path: PathBuf,
+        /// Version number holding the previous content
+        version: i32,
+    },
+}
+
+impl WatchEvent {
+    /// ID of the captured or revised prompt
+    pub fn prompt_id(&self) -> &str {
+        match self {
+            Self::Captured { prompt_id, .. } | Self::Revised { prompt_id, .. } => prompt_id,
+        }
+    }
+}
+
+/// Handle that interrupts a blocking [`FileWatcher::wait_events`] call
+#[derive(Debug, Clone)]
+pub struct Waker(Sender<Message>);
+
+impl Waker {
+    /// Make the current or next `wait_events` call return
+    pub fn wake(&self) {
+        let _ = self.0.send(Message::Wake);
+    }
+}
+
+/// Message delivered to the watcher's event channel
+#[derive(Debug)]
+enum Message {
+    Fs(notify::Result<Event>),
+    Wake,
+}
+
+/// Summary of a startup backfill
+#[derive(Debug, Clone, Default)]
+pub struct BackfillReport {
+    /// Files in the watch roots that passed the root filters
+    pub scanned: usize,
+    /// Files skipped because their content was already captured
+    pub unchanged: usize,
+    /// Files that could not be captured
+    pub failed: usize,
+    /// Prompts captured or revised
+    pub events: Vec<WatchEvent>,
+}
+
+/// What processing a single file did
+enum FileOutcome {
+    Ignored,
+    Failed,
+    Unchanged,
+    /// An untracked file whose content was already stored as this prompt
+    Duplicate(String),
+    Changed(WatchEvent),
+}
+
+/// Watch root with compiled glob patterns
+struct CompiledRoot {
+    root: WatchRoot,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl WatchRoot {
+    /// Create a root with the default watch settings and no capture defaults
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let defaults = WatchConfig::default();
+        Self {
+            path: path.into(),
+            recursive: defaults.recursive,
+            file_extensions: defaults.extensions,
+            include: defaults.include,
+            exclude: defaults.exclude,
+            category: None,
+            tags: Vec::new(),
+            model: None,
+            after_capture: defaults.after_capture,
+            processed_dir: PathBuf::from(defaults.processed_dir),
+        }
+    }
+
+    /// Resolve a configured root against the global watch settings
+    pub fn from_config(root: &WatchRootConfig, watch: &WatchConfig) -> Self {
+        let expand = |path: &String| {
+            shellexpand::full(path).map_or_else(|_| path.clone(), std::borrow::Cow::into_owned)
+        };
+        let processed_dir = root.processed_dir.as_ref().unwrap_or(&watch.processed_dir);
+
+        Self {
+            path: PathBuf::from(expand(&root.path)),
+            recursive: root.recursive.unwrap_or(watch.recursive),
+            file_extensions: root
+                .extensions
+                .clone()
+                .unwrap_or_else(|| watch.extensions.clone()),
+            include: watch.include.iter().chain(&root.include).cloned().collect(),
+            exclude: watch.exclude.iter().chain(&root.exclude).cloned().collect(),
+            category: root.category.clone(),
+            tags: root.tags.clone(),
+            model: root.model.clone(),
+            after_capture: root.after_capture.unwrap_or(watch.after_capture),
+            processed_dir: PathBuf::from(expand(processed_dir)),
+        }
+    }
+
+    /// Apply this root's category, tags and model to a captured prompt
+    pub fn apply_defaults(&self, prompt: &mut Prompt) {
+        if let Some(ref category) = self.category {
+            prompt.category = Some(category.clone());
+        }
+        for tag in &self.tags {
+            if !prompt.tags.contains(tag) {
+                prompt.tags.push(tag.clone());
+            }
+        }
+        if let Some(ref model) = self.model {
+            prompt.metadata.model.clone_from(model);
+        }
+    }
 }
 
 impl Default for WatcherConfig {
     fn default() -> Self {
         Self {
-            watch_path: PathBuf::from("."),
-            recursive: true,
-            file_extensions: vec![
-                "txt".to_string(),
-                "md".to_string(),
-                "prompt".to_string(),
-            ],
+            roots: vec![WatchRoot::new(".")],
+            debounce: Duration::from_millis(WatchConfig::default().debounce_ms),
             similarity_threshold: 0.95,
+            git_context: true,
+            redactor: Some(Redactor::default()),
+            safeguards: CaptureSafeguards::default(),
         }
     }
 }
 
+impl WatcherConfig {
+    /// Build watcher settings from application configuration
+    ///
+    /// Uses `capture.watch_directory` as the only root when no roots are
+    /// configured in the `watch` section.
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        let roots = if config.watch.roots.is_empty() {
+            let root = WatchRootConfig::new(config.capture.watch_directory.clone());
+            vec![WatchRoot::from_config(&root, &config.watch)]
+        } else {
+            config
+                .watch
+                .roots
+                .iter()
+                .map(|root| WatchRoot::from_config(root, &config.watch))
+                .collect()
+        };
+
+        let redactor = if config.redaction.enabled {
+            Some(Redactor::new(&config.redaction)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            roots,
+            debounce: Duration::from_millis(config.watch.debounce_ms),
+            similarity_threshold: config.capture.similarity_threshold,
+            git_context: config.capture.git_context,
+            redactor,
+            safeguards: config.capture.safeguards.clone(),
+        })
+    }
+}
+
 impl FileWatcher {
     /// Create a new file watcher
+    ///
+    /// Fails if an include or exclude pattern is not a valid glob.
     pub fn new(config: WatcherConfig) -> Result<Self> {
-        let capture_service = CaptureService::new(config.similarity_threshold);
+        let capture_service = CaptureService::new(config.similarity_threshold)
+            .with_git_context(config.git_context)
+            .with_redactor(config.redactor)
+            .with_safeguards(config.safeguards);
+
+        let mut roots = config
+            .roots
+            .into_iter()
+            .map(|mut root| {
+                if let Ok(canonical) = root.path.canonicalize() {
+                    root.path = canonical;
+                }
+                root.processed_dir = root.path.join(&root.processed_dir);
+                Ok(CompiledRoot {
+                    include: if root.include.is_empty() {
+                        None
+                    } else {
+                        Some(build_globset(&root.include)?)
+                    },
+                    exclude: build_globset(&root.exclude)?,
+                    root,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Most specific root first, so nested roots win
+        roots.sort_by_key(|r| std::cmp::Reverse(r.root.path.components().count()));
+
+        let (sender, receiver) = channel();
 
         Ok(Self {
-            watch_path: config.watch_path,
+            roots,
             watcher: None,
-            receiver: None,
+            sender,
+            receiver,
             capture_service,
+            debounce: config.debounce,
+            pending: HashMap::new(),
         })
     }
 
     /// Start watching for file changes
     pub fn start(&mut self) -> Result<()> {
-        let (tx, rx) = channel();
+        let tx = self.sender.clone();
 
         let mut watcher = RecommendedWatcher::new(
             move |res| {
-                let _ = tx.send(res);
+                let _ = tx.send(Message::Fs(res));
             },
             Config::default().with_poll_interval(Duration::from_secs(2)),
         )
         .map_err(|e| {
-            PromptTrackingError::IoError(std::io::Error::other(
-                format!("Failed to create watcher: {}", e),
+            PromptTrackingError::IoError(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("Failed to create watcher: {e}"),
             ))
         })?;
 
-        watcher
-            .watch(&self.watch_path, RecursiveMode::Recursive)
-            .map_err(|e| {
-                PromptTrackingError::IoError(std::io::Error::other(
-                    format!("Failed to watch path: {}", e),
+        for compiled in &self.roots {
+            let mode = if compiled.root.recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            watcher.watch(&compiled.root.path, mode).map_err(|e| {
+                PromptTrackingError::IoError(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("Failed to watch path {}: {e}", compiled.root.path.display()),
                 ))
             })?;
+        }
 
         self.watcher = Some(watcher);
-        self.receiver = Some(rx);
 
         Ok(())
     }
@@ -89,77 +356,513 @@ impl FileWatcher {
     /// Stop watching
     pub fn stop(&mut self) {
         self.watcher = None;
-        self.receiver = None;
+        while self.receiver.try_recv().is_ok() {}
+        self.pending.clear();
+    }
+
+    /// Handle for waking a blocked [`FileWatcher::wait_events`] from another thread
+    pub fn waker(&self) -> Waker {
+        Waker(self.sender.clone())
+    }
+
+    /// Block until debounced files are ready or the watcher is woken, then process them
+    ///
+    /// Waits on the event channel rather than polling: with nothing pending it
+    /// sleeps until the next filesystem event, otherwise until the earliest
+    /// pending path leaves its debounce window or a failed capture is due for
+    /// retry. A [`Waker`] makes it return early, possibly with no events.
+    pub fn wait_events(&mut self, db: &dyn PromptStore) -> Result<Vec<WatchEvent>> {
+        loop {
+            let now = Instant::now();
+            let retry_in = db
+                .next_capture_retry()?
+                .map(|at| (at - Utc::now()).to_std().unwrap_or(Duration::ZERO));
+            let ready_in = self
+                .pending
+                .values()
+                .map(|last| (*last + self.debounce).saturating_duration_since(now))
+                .chain(retry_in)
+                .min();
+
+            let message = match ready_in {
+                None => self.receiver.recv().ok(),
+                Some(wait) if wait.is_zero() => break,
+                Some(wait) => match self.receiver.recv_timeout(wait) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => None,
+                },
+            };
+
+            match message {
+                Some(Message::Fs(result)) => self.record(result, Instant::now()),
+                Some(Message::Wake) | None => break,
+            }
+        }
+
+        self.process_events(db)
+    }
+
+    /// Mark the paths of a create or modify event as pending
+    fn record(&mut self, result: notify::Result<Event>, at: Instant) {
+        let Ok(event) = result else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            for path in event.paths {
+                self.pending.insert(path, at);
+            }
+        }
     }
 
     /// Process pending events and capture prompts
-    pub fn process_events(&self, db: &Database) -> Result<Vec<String>> {
-        let mut captured_ids = Vec::new();
-
-        if let Some(ref rx) = self.receiver {
-            // Process all pending events
-            while let Ok(event_result) = rx.try_recv() {
-                if let Ok(event) = event_result {
-                    if let Some(id) = self.handle_event(&event, db)? {
-                        captured_ids.push(id);
-                    }
-                }
+    ///
+    /// Events are debounced per path: a file is only processed once no new
+    /// event has arrived for it within the debounce window, so one editor save
+    /// results in one capture.
+    pub fn process_events(&mut self, db: &dyn PromptStore) -> Result<Vec<WatchEvent>> {
+        let now = Instant::now();
+
+        // Collect all pending events
+        while let Ok(message) = self.receiver.try_recv() {
+            if let Message::Fs(result) = message {
+                self.record(result, now);
             }
         }
 
-        Ok(captured_ids)
+        let ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, last)| now.duration_since(**last) >= self.debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut events = Vec::new();
+        for path in ready {
+            self.pending.remove(&path);
+            events.extend(self.capture_path(&path, db)?);
+        }
+
+        events.extend(self.retry_failures(db)?);
+        Ok(events)
+    }
+
+    /// Retry failed captures whose backoff has expired
+    pub fn retry_failures(&self, db: &dyn PromptStore) -> Result<Vec<WatchEvent>> {
+        let mut events = Vec::new();
+        for failure in db.due_capture_failures(Utc::now())? {
+            events.extend(self.retry(&failure, db)?);
+        }
+        Ok(events)
+    }
+
+    /// Try a failed capture again now
+    ///
+    /// A file that no longer exists or is no longer watched is dropped from
+    /// the failure table; one that fails again has its attempt count and
+    /// next retry updated.
+    pub fn retry(&self, failure: &CaptureFailure, db: &dyn PromptStore) -> Result<Vec<WatchEvent>> {
+        let path = Path::new(&failure.path);
+        if self.root_for(path).is_none() {
+            db.clear_capture_failure(&failure.path)?;
+            return Ok(Vec::new());
+        }
+        self.capture_path(path, db)
     }
 
-    /// Handle a single file event
-    fn handle_event(&self, event: &Event, db: &Database) -> Result<Option<String>> {
-        // Only process create and modify events
-        match event.kind {
-            EventKind::Create(_) | EventKind::Modify(_) => {}
-            _ => return Ok(None),
+    /// Capture a file or, for session logs, its new turns
+    fn capture_path(&self, path: &Path, db: &dyn PromptStore) -> Result<Vec<WatchEvent>> {
+        if is_log(path) {
+            self.process_log(path, db)
+        } else {
+            Ok(self.process_file(path, db)?.into_iter().collect())
         }
+    }
 
-        for path in &event.paths {
-            // Check if it's a file with valid extension
-            if !self.is_valid_file(path) {
-                continue;
+    /// Capture a single file, recording edits to tracked files as new versions
+    ///
+    /// Returns `None` if the file is not watched, cannot be captured, or has
+    /// not changed since it was last captured. Session logs (`.jsonl`) are
+    /// captured turn by turn with [`FileWatcher::process_log`] instead.
+    pub fn process_file(&self, path: &Path, db: &dyn PromptStore) -> Result<Option<WatchEvent>> {
+        match self.process_file_outcome(path, db) {
+            FileOutcome::Changed(event) => Ok(Some(event)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Capture files already present in the watch roots
+    ///
+    /// Files whose size and modification time match their checkpoint are
+    /// skipped without being read, so restarting the watcher only processes
+    /// what changed while it was stopped.
+    pub fn backfill(&self, db: &dyn PromptStore) -> Result<BackfillReport> {
+        let mut report = BackfillReport::default();
+        let mut seen = HashSet::new();
+
+        for compiled in &self.roots {
+            let root = &compiled.root;
+            let mut walker = WalkDir::new(&root.path);
+            if !root.recursive {
+                walker = walker.max_depth(1);
             }
 
-            // Try to capture the prompt
-            match self.capture_service.capture_from_file(path) {
-                Ok(prompt) => {
-                    // Check for duplicates
-                    if db.find_by_hash(&prompt.content_hash)?.is_some() {
-                        continue;
-                    }
+            let files = walker
+                .into_iter()
+                .filter_map(std::result::Result::ok)
+                .filter(|entry| entry.file_type().is_file())
+                .filter(|entry| seen.insert(path_key(entry.path())))
+                .filter(|entry| self.is_valid_file(entry.path()));
+            for entry in files {
+                self.backfill_file(entry.path(), db, &mut report)?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Capture one file found by [`FileWatcher::backfill`]
+    fn backfill_file(
+        &self,
+        path: &Path,
+        db: &dyn PromptStore,
+        report: &mut BackfillReport,
+    ) -> Result<()> {
+        report.scanned += 1;
+        if is_log(path) {
+            let events = self.process_log(path, db)?;
+            if events.is_empty() {
+                report.unchanged += 1;
+            }
+            report.events.extend(events);
+            return Ok(());
+        }
+        if Self::is_checkpointed(path, db)? {
+            report.unchanged += 1;
+            return Ok(());
+        }
+
+        match self.process_file_outcome(path, db) {
+            FileOutcome::Changed(event) => report.events.push(event),
+            FileOutcome::Unchanged | FileOutcome::Duplicate(_) => report.unchanged += 1,
+            FileOutcome::Failed => report.failed += 1,
+            FileOutcome::Ignored => {}
+        }
+        Ok(())
+    }
 
-                    // Save to database
-                    db.create_prompt(&prompt)?;
-                    return Ok(Some(prompt.id));
+    /// Capture the user turns appended to a JSONL session log
+    ///
+    /// Only lines written since the last call are read; the offset is stored
+    /// in the database so a restart resumes where it stopped. Each new user
+    /// turn becomes its own prompt, tagged with the session it came from.
+    pub fn process_log(&self, path: &Path, db: &dyn PromptStore) -> Result<Vec<WatchEvent>> {
+        let Some(root) = self.root_for(path) else {
+            return Ok(Vec::new());
+        };
+
+        let key = path_key(path);
+        let mut events = Vec::new();
+        match self.tail_log(root, path, &key, db, &mut events) {
+            Ok(()) => clear_failure(&key, db),
+            Err(e) => record_failure(path, &key, &e, db),
+        }
+        Ok(events)
+    }
+
+    /// Read new lines from a log and store its user turns, pushing an event per prompt
+    ///
+    /// A long backlog is read and stored in bounded batches.
+    fn tail_log(
+        &self,
+        root: &WatchRoot,
+        path: &Path,
+        key: &str,
+        db: &dyn PromptStore,
+        events: &mut Vec<WatchEvent>,
+    ) -> Result<()> {
+        loop {
+            let from = db
+                .get_log_offset(key)?
+                .unwrap_or_else(|| LogOffset::new(key));
+            let appended = jsonl::read_appended(path, &from)?;
+            if appended.reset {
+                log::info!(
+                    "{} was truncated or replaced, reading from the start",
+                    path.display()
+                );
+            }
+
+            // The new turns and the offset past them are stored together
+            let mut captured = Vec::new();
+            db.atomic(&mut |tx| {
+                captured = self.store_turns(root, path, &appended.lines, tx)?;
+                tx.set_log_offset(&appended.offset)
+            })?;
+            events.append(&mut captured);
+            if !appended.more {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Store the user turns among `lines`, returning an event per new prompt
+    fn store_turns(
+        &self,
+        root: &WatchRoot,
+        path: &Path,
+        lines: &[String],
+        db: &dyn PromptStore,
+    ) -> Result<Vec<WatchEvent>> {
+        let mut captured = Vec::new();
+        for turn in lines.iter().filter_map(|line| jsonl::user_turn(line)) {
+            let mut prompt = match self.capture_service.process_content(&turn.text) {
+                Ok(prompt) => prompt,
+                Err(e) => {
+                    log::warn!("Skipping turn in {}: {}", path.display(), e);
+                    continue;
                 }
-                Err(_) => continue,
+            };
+
+            let project_dir = turn.cwd.as_deref().map_or(path, Path::new);
+            self.capture_service
+                .attach_git_context(&mut prompt, project_dir);
+            root.apply_defaults(&mut prompt);
+            if let Some(session) = turn.session_id {
+                prompt
+                    .metadata
+                    .attributes
+                    .insert("session".to_string(), session);
+            }
+            if let Some(timestamp) = turn.timestamp {
+                prompt.created_at = timestamp;
+                prompt.updated_at = timestamp;
+            }
+
+            // Re-reading a rotated log must not store its turns twice
+            if db.find_by_hash(&prompt.content_hash)?.is_some() {
+                continue;
+            }
+
+            db.create_prompt(&prompt)?;
+            captured.push(WatchEvent::Captured {
+                prompt_id: prompt.id,
+                path: path.to_path_buf(),
+            });
+        }
+
+        Ok(captured)
+    }
+
+    /// Capture a file and checkpoint it once its content has been stored
+    ///
+    /// A file that cannot be read or stored is recorded as a failed capture.
+    fn process_file_outcome(&self, path: &Path, db: &dyn PromptStore) -> FileOutcome {
+        // Find the root that accepts this file
+        let Some(root) = self.root_for(path) else {
+            return FileOutcome::Ignored;
+        };
+        if is_log(path) {
+            return FileOutcome::Ignored;
+        }
+
+        // Try to capture and store the prompt
+        let key = path_key(path);
+        let stored = self
+            .capture_service
+            .capture_from_file(path)
+            .and_then(|mut prompt| {
+                root.apply_defaults(&mut prompt);
+                Self::store_prompt(path, &key, &prompt, db)
+            });
+        let outcome = match stored {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                record_failure(path, &key, &e, db);
+                return FileOutcome::Failed;
+            }
+        };
+        clear_failure(&key, db);
+
+        let checkpointed = fingerprint(path, &key)
+            .map_err(PromptTrackingError::from)
+            .and_then(|checkpoint| db.set_watch_checkpoint(&checkpoint));
+        if let Err(e) = checkpointed {
+            log::warn!("Could not checkpoint {}: {}", path.display(), e);
+        }
+
+        let prompt_id = match &outcome {
+            FileOutcome::Changed(event) => event.prompt_id(),
+            FileOutcome::Duplicate(prompt_id) => prompt_id,
+            _ => return outcome,
+        };
+        if let Err(e) = after_capture(root, path, &key, prompt_id, db) {
+            log::warn!("Could not {} {}: {}", root.after_capture, path.display(), e);
+        }
+
+        outcome
+    }
+
+    /// Save a captured prompt, as a new version if the file is already tracked
+    ///
+    /// The prompt, its previous version and the file mapping are written in
+    /// one transaction.
+    fn store_prompt(
+        path: &Path,
+        key: &str,
+        prompt: &Prompt,
+        db: &dyn PromptStore,
+    ) -> Result<FileOutcome> {
+        let mut outcome = FileOutcome::Ignored;
+        db.atomic(&mut |tx| {
+            outcome = Self::store_in(path, key, prompt.clone(), tx)?;
+            Ok(())
+        })?;
+        Ok(outcome)
+    }
+
+    /// Body of [`store_prompt`](Self::store_prompt), run inside its transaction
+    fn store_in(
+        path: &Path,
+        key: &str,
+        prompt: Prompt,
+        tx: &dyn PromptStore,
+    ) -> Result<FileOutcome> {
+        // A tracked file that changed becomes a new version of its prompt
+        let tracked = match tx.get_watched_file(key)? {
+            Some(watched) => tx.get_prompt(&watched.prompt_id)?,
+            None => None,
+        };
+        if let Some(mut existing) = tracked {
+            if existing.content_hash == prompt.content_hash {
+                return Ok(FileOutcome::Unchanged);
             }
+
+            tx.save_version(&existing)?;
+
+            existing.content = prompt.content;
+            existing.content_hash = prompt.content_hash;
+            existing.metadata.input_tokens = prompt.metadata.input_tokens;
+            existing.metadata.context = prompt.metadata.context;
+            existing.metadata.git = prompt.metadata.git;
+            existing.redactions = prompt.redactions;
+            existing.updated_at = Utc::now();
+            tx.update_prompt(&existing)?;
+            tx.set_watched_file(key, &existing.id, &existing.content_hash)?;
+
+            return Ok(FileOutcome::Changed(WatchEvent::Revised {
+                version: tx.latest_version(&existing.id)?,
+                prompt_id: existing.id,
+                path: path.to_path_buf(),
+            }));
+        }
+
+        // Identical content already stored: track the file against it
+        if let Some(existing) = tx.find_by_hash(&prompt.content_hash)? {
+            tx.set_watched_file(key, &existing.id, &existing.content_hash)?;
+            return Ok(FileOutcome::Duplicate(existing.id));
         }
 
-        Ok(None)
+        // Save to database
+        tx.create_prompt(&prompt)?;
+        tx.set_watched_file(key, &prompt.id, &prompt.content_hash)?;
+
+        Ok(FileOutcome::Changed(WatchEvent::Captured {
+            prompt_id: prompt.id,
+            path: path.to_path_buf(),
+        }))
+    }
+
+    /// Whether a file is unchanged since its checkpoint
+    ///
+    /// A file touched without being edited (same size, new mtime) is hashed
+    /// and, if the bytes match, its checkpoint is refreshed.
+    fn is_checkpointed(path: &Path, db: &dyn PromptStore) -> Result<bool> {
+        let key = path_key(path);
+        let Some(checkpoint) = db.get_watch_checkpoint(&key)? else {
+            return Ok(false);
+        };
+        let Ok((size, mtime_ns)) = file_stat(path) else {
+            return Ok(false);
+        };
+
+        if size != checkpoint.size {
+            return Ok(false);
+        }
+        if mtime_ns == checkpoint.mtime_ns {
+            return Ok(true);
+        }
+
+        match fingerprint(path, &key) {
+            Ok(current) if current.file_hash == checkpoint.file_hash => {
+                db.set_watch_checkpoint(&current)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
     }
 
-    /// Check if file has valid extension
+    /// Check if a file would be captured by any watch root
     fn is_valid_file(&self, path: &Path) -> bool {
+        self.root_for(path).is_some()
+    }
+
+    /// Find the watch root responsible for a file, if the file should be captured
+    ///
+    /// The most specific root containing the file decides, so a nested root
+    /// can narrow or override the settings of its parent.
+    fn root_for(&self, path: &Path) -> Option<&WatchRoot> {
         if !path.is_file() {
-            return false;
+            return None;
+        }
+
+        let canonical = path.canonicalize().ok();
+        let compiled = self.roots.iter().find_map(|compiled| {
+            path.strip_prefix(&compiled.root.path)
+                .ok()
+                .or_else(|| {
+                    canonical
+                        .as_deref()
+                        .and_then(|c| c.strip_prefix(&compiled.root.path).ok())
+                })
+                .map(|relative| (compiled, relative))
+        });
+        let (compiled, relative) = compiled?;
+        let root = &compiled.root;
+
+        let processed = |p: &Path| p.starts_with(&root.processed_dir);
+        if processed(path) || canonical.as_deref().is_some_and(processed) {
+            return None;
         }
 
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy().to_lowercase();
-            return ext_str == "txt" || ext_str == "md" || ext_str == "prompt";
+        if !root.recursive && relative.components().count() > 1 {
+            return None;
+        }
+
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        if !root
+            .file_extensions
+            .iter()
+            .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension))
+        {
+            return None;
+        }
+
+        if compiled.exclude.is_match(relative) {
+            return None;
+        }
+        if let Some(ref include) = compiled.include {
+            if !include.is_match(relative) {
+                return None;
+            }
         }
 
-        false
+        Some(root)
     }
 
-    /// Get watch path
-    pub fn watch_path(&self) -> &Path {
-        &self.watch_path
+    /// Get the configured watch roots
+    pub fn roots(&self) -> impl Iterator<Item = &WatchRoot> {
+        self.roots.iter().map(|compiled| &compiled.root)
     }
 
     /// Check if watcher is running
@@ -168,15 +871,269 @@ impl FileWatcher {
     }
 }
 
+/// Stable database key for a file path
+fn path_key(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Move, rename or delete a captured file as configured for its root
+///
+/// Every action except `leave` is logged so it can be reviewed and undone
+/// with [`undo_file_action`]. Files that leave the root are forgotten, so a
+/// new file later created at the same path is captured as a new prompt.
+fn after_capture(
+    root: &WatchRoot,
+    path: &Path,
+    key: &str,
+    prompt_id: &str,
+    db: &dyn PromptStore,
+) -> Result<()> {
+    let destination = match root.after_capture {
+        PostCaptureAction::Leave => return Ok(()),
+        PostCaptureAction::Move => {
+            let relative = Path::new(key)
+                .strip_prefix(&root.path)
+                .or_else(|_| path.strip_prefix(&root.path))
+                .map_err(|_| {
+                    PromptTrackingError::FileNotFound(format!(
+                        "{} is outside {}",
+                        path.display(),
+                        root.path.display()
+                    ))
+                })?;
+            root.processed_dir.join(relative)
+        }
+        PostCaptureAction::Rename => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            if stem.ends_with(prompt_id) {
+                return Ok(());
+            }
+            let mut name = format!("{stem}-{prompt_id}");
+            if let Some(extension) = path.extension() {
+                name = format!("{}.{}", name, extension.to_string_lossy());
+            }
+            path.with_file_name(name)
+        }
+        PostCaptureAction::Delete => {
+            // Logged before deleting so the content is never lost
+            let content = fs::read(path)?;
+            db.record_file_action(
+                prompt_id,
+                PostCaptureAction::Delete,
+                key,
+                None,
+                Some(&content),
+            )?;
+            fs::remove_file(path)?;
+            return db.forget_watched_file(key);
+        }
+    };
+
+    if destination.exists() {
+        return Err(already_exists(&destination));
+    }
+    move_file(path, &destination)?;
+    db.forget_watched_file(key)?;
+
+    // A renamed file stays in its root and is tracked under its new name
+    let destination_key = path_key(&destination);
+    if root.after_capture == PostCaptureAction::Rename {
+        track_file(&destination, &destination_key, prompt_id, db)?;
+    }
+
+    db.record_file_action(
+        prompt_id,
+        root.after_capture,
+        key,
+        Some(&destination_key),
+        None,
+    )?;
+    log::info!(
+        "{} {} to {}",
+        if root.after_capture == PostCaptureAction::Move {
+            "Moved"
+        } else {
+            "Renamed"
+        },
+        path.display(),
+        destination.display()
+    );
+    Ok(())
+}
+
+/// Put a moved, renamed or deleted file back where it was captured
+///
+/// The restored file is tracked against its prompt again, so a running
+/// watcher treats it as already captured instead of repeating the action.
+pub fn undo_file_action(action: &FileAction, db: &dyn PromptStore) -> Result<()> {
+    if action.undone_at.is_some() {
+        return Err(PromptTrackingError::IoError(io::Error::new(
+            io::ErrorKind::Other,
+            format!("File action {} was already undone", action.id),
+        )));
+    }
+
+    let source = Path::new(&action.source);
+    if source.exists() {
+        return Err(already_exists(source));
+    }
+
+    match action.action {
+        PostCaptureAction::Leave => {}
+        PostCaptureAction::Move | PostCaptureAction::Rename => {
+            let destination = action.destination.as_deref().unwrap_or_default();
+            if !Path::new(destination).is_file() {
+                return Err(PromptTrackingError::FileNotFound(destination.to_string()));
+            }
+            move_file(Path::new(destination), source)?;
+            db.forget_watched_file(destination)?;
+        }
+        PostCaptureAction::Delete => {
+            let content = action.content.as_deref().ok_or_else(|| {
+                PromptTrackingError::IoError(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("No content was kept for {}", action.source),
+                ))
+            })?;
+            if let Some(parent) = source.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(source, content)?;
+        }
+    }
+
+    track_file(source, &path_key(source), &action.prompt_id, db)?;
+    db.mark_file_action_undone(action.id)
+}
+
+/// Map a file to its prompt and checkpoint it as processed
+fn track_file(path: &Path, key: &str, prompt_id: &str, db: &dyn PromptStore) -> Result<()> {
+    if let Some(prompt) = db.get_prompt(prompt_id)? {
+        db.set_watched_file(key, prompt_id, &prompt.content_hash)?;
+    }
+    db.set_watch_checkpoint(&fingerprint(path, key)?)
+}
+
+/// Rename a file, copying it when the destination is on another filesystem
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn already_exists(path: &Path) -> PromptTrackingError {
+    PromptTrackingError::IoError(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    ))
+}
+
+/// Record a failed capture, scheduling a retry with backoff for transient errors
+fn record_failure(path: &Path, key: &str, error: &PromptTrackingError, db: &dyn PromptStore) {
+    log::warn!("Failed to capture {}: {}", path.display(), error);
+
+    let attempts = db
+        .find_capture_failure(key)
+        .ok()
+        .flatten()
+        .map_or(0, |failure| failure.attempts)
+        + 1;
+    let next_retry_at = if error.is_transient() {
+        retry_delay(attempts).map(|delay| Utc::now() + delay)
+    } else {
+        None
+    };
+
+    if let Err(e) = db.record_capture_failure(key, error.kind(), &error.to_string(), next_retry_at)
+    {
+        log::error!(
+            "Could not record failed capture of {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// Forget a previous failure once a path has been captured
+fn clear_failure(key: &str, db: &dyn PromptStore) {
+    if let Err(e) = db.clear_capture_failure(key) {
+        log::warn!("Could not clear failed capture of {key}: {e}");
+    }
+}
+
+/// Delay before automatic retry number `attempts`, `None` once retries are exhausted
+///
+/// Doubles from [`RETRY_BASE_SECS`] up to [`RETRY_MAX_SECS`].
+fn retry_delay(attempts: u32) -> Option<chrono::Duration> {
+    if attempts == 0 || attempts > MAX_RETRY_ATTEMPTS {
+        return None;
+    }
+    let secs = RETRY_BASE_SECS.saturating_mul(1 << (attempts - 1).min(20));
+    Some(chrono::Duration::seconds(secs.min(RETRY_MAX_SECS)))
+}
+
+/// Whether a file is an append-only JSONL session log
+fn is_log(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("jsonl"))
+}
+
+/// Size and modification time (nanoseconds since the Unix epoch) of a file
+fn file_stat(path: &Path) -> std::io::Result<(u64, i64)> {
+    let metadata = fs::metadata(path)?;
+    let mtime_ns = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_nanos()).unwrap_or(i64::MAX));
+    Ok((metadata.len(), mtime_ns))
+}
+
+/// Current checkpoint for a file: size, mtime and a hash of its bytes
+fn fingerprint(path: &Path, key: &str) -> std::io::Result<WatchCheckpoint> {
+    let (size, mtime_ns) = file_stat(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(WatchCheckpoint {
+        path: key.to_string(),
+        size,
+        mtime_ns,
+        file_hash: format!("{:x}", hasher.finalize()),
+    })
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| {
+            PromptTrackingError::ConfigError(format!("Invalid glob '{pattern}': {e}"))
+        })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| PromptTrackingError::ConfigError(format!("Invalid glob set: {e}")))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::Database;
+    use crate::store::MemoryStore;
     use tempfile::TempDir;
 
     #[test]
     fn test_watcher_config_default() {
         let config = WatcherConfig::default();
-        assert!(config.recursive);
+        assert!(config.roots[0].recursive);
         assert_eq!(config.similarity_threshold, 0.95);
     }
 
@@ -184,7 +1141,7 @@ mod tests {
     fn test_file_watcher_creation() {
         let temp_dir = TempDir::new().unwrap();
         let config = WatcherConfig {
-            watch_path: temp_dir.path().to_path_buf(),
+            roots: vec![WatchRoot::new(temp_dir.path())],
             ..Default::default()
         };
 
@@ -196,7 +1153,7 @@ mod tests {
     fn test_is_valid_file() {
         let temp_dir = TempDir::new().unwrap();
         let config = WatcherConfig {
-            watch_path: temp_dir.path().to_path_buf(),
+            roots: vec![WatchRoot::new(temp_dir.path())],
             ..Default::default()
         };
 
@@ -216,7 +1173,7 @@ mod tests {
     fn test_watcher_start_stop() {
         let temp_dir = TempDir::new().unwrap();
         let config = WatcherConfig {
-            watch_path: temp_dir.path().to_path_buf(),
+            roots: vec![WatchRoot::new(temp_dir.path())],
             ..Default::default()
         };
 
@@ -229,4 +1186,477 @@ mod tests {
         watcher.stop();
         assert!(!watcher.is_running());
     }
+
+    fn write(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "test").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_configured_extensions_and_globs() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut root = WatchRoot::new(temp_dir.path());
+        root.file_extensions = vec!["prompt".to_string(), ".yaml".to_string()];
+        root.include = vec!["prompts/**".to_string()];
+        root.exclude.push("**/drafts/**".to_string());
+
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![root],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(watcher.is_valid_file(&write(temp_dir.path(), "prompts/a.prompt")));
+        assert!(watcher.is_valid_file(&write(temp_dir.path(), "prompts/b.YAML")));
+        assert!(!watcher.is_valid_file(&write(temp_dir.path(), "prompts/c.txt")));
+        assert!(!watcher.is_valid_file(&write(temp_dir.path(), "other/d.prompt")));
+        assert!(!watcher.is_valid_file(&write(temp_dir.path(), "prompts/drafts/e.prompt")));
+        assert!(!watcher.is_valid_file(&write(temp_dir.path(), "prompts/.git/f.prompt")));
+    }
+
+    #[test]
+    fn test_non_recursive_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut root = WatchRoot::new(temp_dir.path());
+        root.recursive = false;
+
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![root],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(watcher.is_valid_file(&write(temp_dir.path(), "top.txt")));
+        assert!(!watcher.is_valid_file(&write(temp_dir.path(), "sub/nested.txt")));
+    }
+
+    #[test]
+    fn test_nested_root_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let outer = WatchRoot::new(temp_dir.path());
+        let mut inner = WatchRoot::new(temp_dir.path().join("work"));
+        std::fs::create_dir_all(&inner.path).unwrap();
+        inner.category = Some("code-review".to_string());
+        inner.tags = vec!["work".to_string()];
+        inner.model = Some("claude-3-opus".to_string());
+
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![outer, inner],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let root = watcher
+            .root_for(&write(temp_dir.path(), "work/review.md"))
+            .unwrap();
+        let mut prompt = Prompt::new("Review this".to_string());
+        prompt.tags = vec!["work".to_string()];
+        root.apply_defaults(&mut prompt);
+        assert_eq!(prompt.category, Some("code-review".to_string()));
+        assert_eq!(prompt.tags, vec!["work"]);
+        assert_eq!(prompt.metadata.model, "claude-3-opus");
+
+        let root = watcher
+            .root_for(&write(temp_dir.path(), "personal.md"))
+            .unwrap();
+        assert!(root.category.is_none());
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let mut root = WatchRoot::new(".");
+        root.exclude = vec!["[".to_string()];
+
+        let result = FileWatcher::new(WatcherConfig {
+            roots: vec![root],
+            ..Default::default()
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_watcher_config_from_config() {
+        let mut config = AppConfig::default();
+        config.watch.recursive = false;
+        config.watch.exclude = vec!["**/tmp/**".to_string()];
+        config.watch.roots = vec![WatchRootConfig {
+            path: "/srv/prompts".to_string(),
+            recursive: Some(true),
+            extensions: None,
+            include: Vec::new(),
+            exclude: vec!["*.bak.md".to_string()],
+            category: Some("documentation".to_string()),
+            tags: vec!["docs".to_string()],
+            model: None,
+            after_capture: Some(PostCaptureAction::Move),
+            processed_dir: None,
+        }];
+
+        let watcher_config = WatcherConfig::from_config(&config).unwrap();
+        let root = &watcher_config.roots[0];
+        assert_eq!(root.path, PathBuf::from("/srv/prompts"));
+        assert!(root.recursive);
+        assert_eq!(root.file_extensions, vec!["txt", "md", "prompt"]);
+        assert_eq!(root.exclude, vec!["**/tmp/**", "*.bak.md"]);
+        assert_eq!(root.category, Some("documentation".to_string()));
+        assert_eq!(root.after_capture, PostCaptureAction::Move);
+        assert_eq!(root.processed_dir, PathBuf::from("processed"));
+
+        // Falls back to the capture directory
+        config.watch.roots.clear();
+        let watcher_config = WatcherConfig::from_config(&config).unwrap();
+        assert_eq!(watcher_config.roots.len(), 1);
+        assert!(!watcher_config.roots[0].recursive);
+    }
+
+    #[test]
+    fn test_process_file_records_edits_as_versions() {
+        records_edits_as_versions(&Database::in_memory().unwrap());
+        records_edits_as_versions(&MemoryStore::new());
+    }
+
+    fn records_edits_as_versions(db: &dyn PromptStore) {
+        let temp_dir = TempDir::new().unwrap();
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![WatchRoot::new(temp_dir.path())],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let path = temp_dir.path().join("prompt.md");
+        std::fs::write(&path, "Explain borrowing in Rust").unwrap();
+        let first = watcher.process_file(&path, db).unwrap().unwrap();
+        assert!(matches!(first, WatchEvent::Captured { .. }));
+
+        // Unchanged content is not captured again
+        assert!(watcher.process_file(&path, db).unwrap().is_none());
+
+        std::fs::write(&path, "Explain borrowing and lifetimes in Rust").unwrap();
+        let second = watcher.process_file(&path, db).unwrap().unwrap();
+        assert_eq!(
+            second,
+            WatchEvent::Revised {
+                prompt_id: first.prompt_id().to_string(),
+                path: path.clone(),
+                version: 1,
+            }
+        );
+
+        let prompt = db.get_prompt(first.prompt_id()).unwrap().unwrap();
+        assert_eq!(prompt.content, "Explain borrowing and lifetimes in Rust");
+        let history = db.get_version_history(first.prompt_id()).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "Explain borrowing in Rust");
+        assert_eq!(db.list_prompts(&Default::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_process_events_debounces() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::in_memory().unwrap();
+        let mut watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![WatchRoot::new(temp_dir.path())],
+            debounce: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .unwrap();
+        watcher.start().unwrap();
+
+        let path = temp_dir.path().join("burst.txt");
+        for i in 0..5 {
+            std::fs::write(&path, format!("Write a parser, draft {}", i)).unwrap();
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(watcher.process_events(&db).unwrap().is_empty());
+
+        std::thread::sleep(Duration::from_millis(300));
+        let events = watcher.process_events(&db).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], WatchEvent::Captured { .. }));
+
+        let prompt = db.get_prompt(events[0].prompt_id()).unwrap().unwrap();
+        assert_eq!(prompt.content, "Write a parser, draft 4");
+    }
+
+    #[test]
+    fn test_backfill_resumes_from_checkpoints() {
+        let temp_dir = TempDir::new().unwrap();
+        let prompts = temp_dir.path().join("prompts");
+        std::fs::create_dir_all(prompts.join("nested")).unwrap();
+        let db_path = temp_dir.path().join("prompts.db");
+        let db = Database::new(db_path.to_str().unwrap()).unwrap();
+        let config = || WatcherConfig {
+            roots: vec![WatchRoot::new(&prompts)],
+            ..Default::default()
+        };
+
+        std::fs::write(prompts.join("a.md"), "Summarize this design document").unwrap();
+        std::fs::write(prompts.join("nested/b.txt"), "Write tests for the parser").unwrap();
+        std::fs::write(prompts.join("notes.rs"), "fn main() {}").unwrap();
+
+        let report = FileWatcher::new(config()).unwrap().backfill(&db).unwrap();
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.events.len(), 2);
+        assert!(report
+            .events
+            .iter()
+            .all(|e| matches!(e, WatchEvent::Captured { .. })));
+
+        // A restarted watcher skips files that have not changed
+        let watcher = FileWatcher::new(config()).unwrap();
+        let report = watcher.backfill(&db).unwrap();
+        assert_eq!(report.scanned, 2);
+        assert_eq!(report.unchanged, 2);
+        assert!(report.events.is_empty());
+
+        // Touching a file without editing it only refreshes the checkpoint
+        let touched = std::fs::File::options()
+            .write(true)
+            .open(prompts.join("a.md"))
+            .unwrap();
+        touched
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        drop(touched);
+        let report = watcher.backfill(&db).unwrap();
+        assert_eq!(report.unchanged, 2);
+
+        // Edits made while stopped are picked up as new versions
+        std::fs::write(prompts.join("nested/b.txt"), "Write tests for the lexer").unwrap();
+        let report = watcher.backfill(&db).unwrap();
+        assert_eq!(report.events.len(), 1);
+        assert!(matches!(
+            report.events[0],
+            WatchEvent::Revised { version: 1, .. }
+        ));
+        assert_eq!(db.list_prompts(&Default::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_backfill_respects_non_recursive_root() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("sub")).unwrap();
+        write(temp_dir.path(), "top.md");
+        write(&temp_dir.path().join("sub"), "deep.md");
+
+        let mut root = WatchRoot::new(temp_dir.path());
+        root.recursive = false;
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![root],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let db = Database::in_memory().unwrap();
+        let report = watcher.backfill(&db).unwrap();
+        assert_eq!(report.scanned, 1);
+        assert_eq!(report.events.len(), 1);
+    }
+
+    fn watcher_with_action(dir: &Path, action: PostCaptureAction) -> FileWatcher {
+        let mut root = WatchRoot::new(dir);
+        root.after_capture = action;
+        FileWatcher::new(WatcherConfig {
+            roots: vec![root],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_move_after_capture_and_undo() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::in_memory().unwrap();
+        let watcher = watcher_with_action(temp_dir.path(), PostCaptureAction::Move);
+
+        let path = temp_dir.path().join("team/review.md");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "Review the error handling").unwrap();
+
+        let event = watcher.process_file(&path, &db).unwrap().unwrap();
+        let moved = temp_dir.path().join("processed/team/review.md");
+        assert!(!path.exists());
+        assert!(moved.is_file());
+
+        // The processed folder is never captured
+        assert!(!watcher.is_valid_file(&moved));
+        assert!(watcher.backfill(&db).unwrap().events.is_empty());
+
+        let actions = db.list_file_actions(10).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].action, PostCaptureAction::Move);
+        assert_eq!(actions[0].prompt_id, event.prompt_id());
+
+        undo_file_action(&actions[0], &db).unwrap();
+        assert!(path.is_file());
+        assert!(!moved.exists());
+        assert!(
+            undo_file_action(&db.get_file_action(actions[0].id).unwrap().unwrap(), &db).is_err()
+        );
+
+        // The restored file is tracked, so it is not moved again
+        assert!(watcher.process_file(&path, &db).unwrap().is_none());
+        assert!(path.is_file());
+        assert_eq!(db.list_file_actions(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rename_and_delete_after_capture() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::in_memory().unwrap();
+
+        let renamer = watcher_with_action(temp_dir.path(), PostCaptureAction::Rename);
+        let path = temp_dir.path().join("draft.md");
+        std::fs::write(&path, "Draft a release announcement").unwrap();
+        let event = renamer.process_file(&path, &db).unwrap().unwrap();
+
+        let renamed = temp_dir
+            .path()
+            .join(format!("draft-{}.md", event.prompt_id()));
+        assert!(renamed.is_file());
+        assert!(!path.exists());
+        assert!(renamer.process_file(&renamed, &db).unwrap().is_none());
+        assert_eq!(db.list_file_actions(10).unwrap().len(), 1);
+
+        let deleter = watcher_with_action(temp_dir.path(), PostCaptureAction::Delete);
+        let path = temp_dir.path().join("scratch.txt");
+        std::fs::write(&path, "Summarize the meeting notes").unwrap();
+        deleter.process_file(&path, &db).unwrap().unwrap();
+        assert!(!path.exists());
+
+        // A duplicate of a stored prompt is still cleaned up
+        let copy = temp_dir.path().join("copy.txt");
+        std::fs::write(&copy, "Summarize the meeting notes").unwrap();
+        assert!(deleter.process_file(&copy, &db).unwrap().is_none());
+        assert!(!copy.exists());
+
+        let actions = db.list_file_actions(10).unwrap();
+        assert_eq!(actions.len(), 3);
+        assert_eq!(actions[1].action, PostCaptureAction::Delete);
+        undo_file_action(&actions[1], &db).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Summarize the meeting notes"
+        );
+    }
+
+    #[test]
+    fn test_process_log_captures_new_user_turns() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::in_memory().unwrap();
+        let mut root = WatchRoot::new(temp_dir.path());
+        root.file_extensions = vec!["jsonl".to_string()];
+        root.tags = vec!["session-log".to_string()];
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![root],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let log = temp_dir.path().join("session.jsonl");
+        let turn = |text: &str| {
+            format!(
+                "{{\"type\":\"user\",\"sessionId\":\"s-42\",\"message\":{{\"role\":\"user\",\"content\":\"{}\"}}}}\n",
+                text
+            )
+        };
+        let reply =
+            "{\"type\":\"assistant\",\"message\":{\"role\":\"assistant\",\"content\":\"Done\"}}\n";
+
+        std::fs::write(
+            &log,
+            turn("Write a tokenizer for the config format") + reply,
+        )
+        .unwrap();
+        let events = watcher.process_log(&log, &db).unwrap();
+        assert_eq!(events.len(), 1);
+        let prompt = db.get_prompt(events[0].prompt_id()).unwrap().unwrap();
+        assert_eq!(prompt.content, "Write a tokenizer for the config format");
+        assert_eq!(prompt.metadata.attributes["session"], "s-42");
+        assert!(prompt.tags.contains(&"session-log".to_string()));
+
+        // Only appended turns are captured
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            (turn("Now add error recovery to the tokenizer") + reply).as_bytes(),
+        )
+        .unwrap();
+        drop(file);
+        let events = watcher.process_log(&log, &db).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(watcher.process_log(&log, &db).unwrap().is_empty());
+
+        // A rotated log is read again without duplicating known turns
+        std::fs::write(
+            &log,
+            turn("Now add error recovery to the tokenizer") + &turn("Benchmark the tokenizer"),
+        )
+        .unwrap();
+        let events = watcher.process_log(&log, &db).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(db.list_prompts(&Default::default()).unwrap().len(), 3);
+
+        // Logs are never captured whole
+        assert!(watcher.process_file(&log, &db).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failed_capture_is_recorded_and_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::in_memory().unwrap();
+        let watcher = FileWatcher::new(WatcherConfig {
+            roots: vec![WatchRoot::new(temp_dir.path())],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let path = temp_dir.path().join("broken.md");
+        std::fs::write(&path, b"\x00\x01binary\x00").unwrap();
+        assert!(watcher.process_file(&path, &db).unwrap().is_none());
+        assert!(watcher.process_file(&path, &db).unwrap().is_none());
+
+        let failure = db.find_capture_failure(&path_key(&path)).unwrap().unwrap();
+        assert_eq!(failure.error_kind, "binary");
+        assert_eq!(failure.attempts, 2);
+        assert!(failure.next_retry_at.is_none());
+
+        // Permanent errors are not retried automatically
+        assert!(watcher.retry_failures(&db).unwrap().is_empty());
+
+        std::fs::write(&path, "Explain the failure modes of this parser").unwrap();
+        let events = watcher.retry(&failure, &db).unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(db.find_capture_failure(&failure.path).unwrap().is_none());
+
+        // A failure for a file that no longer exists is dropped on retry
+        db.record_capture_failure("/gone/a.md", "io", "timed out", None)
+            .unwrap();
+        let gone = db.find_capture_failure("/gone/a.md").unwrap().unwrap();
+        assert!(watcher.retry(&gone, &db).unwrap().is_empty());
+        assert!(db.list_capture_failures(true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        assert_eq!(retry_delay(1), Some(chrono::Duration::seconds(5)));
+        assert_eq!(retry_delay(2), Some(chrono::Duration::seconds(10)));
+        assert_eq!(retry_delay(4), Some(chrono::Duration::seconds(40)));
+        assert!(retry_delay(MAX_RETRY_ATTEMPTS).is_some());
+        assert!(retry_delay(MAX_RETRY_ATTEMPTS + 1).is_none());
+
+        let locked = PromptTrackingError::DatabaseError(
+            "Failed to create prompt: database is locked".to_string(),
+        );
+        assert!(locked.is_transient());
+        assert_eq!(locked.kind(), "database");
+        assert!(!PromptTrackingError::BinaryFile("a.md".to_string()).is_transient());
+        assert!(
+            PromptTrackingError::IoError(std::io::Error::from(std::io::ErrorKind::TimedOut))
+                .is_transient()
+        );
+    }
 }
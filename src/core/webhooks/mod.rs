@@ -0,0 +1,697 @@
+//! Outbound webhooks for lifecycle events
+//!
+//! Each event matching a configured endpoint is written to the
+//! `webhook_outbox` table and then `POSTed` as JSON. Raising an event only
+//! queues it: a [`WebhookWorker`] thread sends deliveries in the background,
+//! and short-lived processes send theirs with [`WebhookDispatcher::flush`]
+//! once their work is done, so a slow endpoint never holds up a capture.
+//! Deliveries that fail are retried with exponential backoff until
+//! `max_attempts` is reached, so an endpoint that is briefly down still
+//! receives every event once it returns.
+//!
+//! When an endpoint has a `secret`, requests carry an
+//! `X-Prompt-Tracking-Signature` header of the form `sha256=<hex>`, the
+//! HMAC-SHA256 of `<timestamp>.<body>` keyed with the secret, where
+//! `<timestamp>` is the `X-Prompt-Tracking-Timestamp` header.
+
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use sha2::Sha256;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle, Thread};
+use std::time::Duration;
+
+use crate::config::{WebhookConfig, WebhooksConfig};
+use crate::database::{Database, WebhookDelivery};
+use crate::events::{Event, EventObserver};
+use crate::{PromptTrackingError, Result};
+
+/// How often a [`WebhookWorker`] retries due deliveries
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Longest delay between two attempts
+const MAX_RETRY_DELAY_SECS: u64 = 3600;
+
+/// How long a claim on a delivery outlasts the request timeout
+const CLAIM_MARGIN_SECS: u64 = 30;
+
+/// Longest response body excerpt recorded as a delivery error
+const MAX_ERROR_BODY: usize = 200;
+
+/// Sends lifecycle events to the configured HTTP endpoints
+///
+/// The dispatcher keeps its own database connection so it can be shared
+/// between threads and registered as an observer on another connection.
+pub struct WebhookDispatcher {
+    endpoints: Vec<WebhookConfig>,
+    max_attempts: u32,
+    retry_base_secs: u64,
+    /// How long a delivery stays claimed by the attempt in progress
+    claim: ChronoDuration,
+    agent: ureq::Agent,
+    db: Database,
+    /// Deliveries queued since the last [`WebhookDispatcher::flush`]
+    queued: Mutex<Vec<i64>>,
+    /// Thread of the running [`WebhookWorker`], woken when events are queued
+    worker: Mutex<Option<Thread>>,
+}
+
+/// Background thread delivering queued webhooks
+///
+/// Started with [`WebhookDispatcher::spawn_worker`]. It wakes whenever an
+/// event is queued and at every retry interval, and stops when dropped,
+/// after the delivery pass in progress.
+pub struct WebhookWorker {
+    dispatcher: Arc<WebhookDispatcher>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Outcome of one pass over the outbox
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+    /// Accepted by their endpoints
+    pub delivered: usize,
+    /// Failed this time and scheduled for another attempt
+    pub retrying: usize,
+    /// Failed for the last time
+    pub failed: usize,
+}
+
+impl WebhookDispatcher {
+    /// Create a dispatcher writing its outbox to `db`
+    ///
+    /// Fails if an endpoint's secret refers to an unset environment variable.
+    pub fn new(config: &WebhooksConfig, db: Database) -> Result<Self> {
+        let endpoints = config
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let secret = match &endpoint.secret {
+                    Some(secret) => Some(
+                        shellexpand::env(secret)
+                            .map_err(|e| {
+                                PromptTrackingError::ConfigError(format!(
+                                    "Webhook '{}' secret: {}",
+                                    endpoint.name, e
+                                ))
+                            })?
+                            .to_string(),
+                    ),
+                    None => None,
+                };
+                Ok(WebhookConfig {
+                    secret,
+                    ..endpoint.clone()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            endpoints,
+            max_attempts: config.max_attempts.max(1),
+            retry_base_secs: config.retry_base_secs,
+            claim: ChronoDuration::seconds(
+                i64::try_from(
+                    config
+                        .timeout_secs
+                        .saturating_add(CLAIM_MARGIN_SECS)
+                        .min(MAX_RETRY_DELAY_SECS),
+                )
+                .unwrap_or(i64::MAX),
+            ),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(config.timeout_secs))
+                .build(),
+            db,
+            queued: Mutex::new(Vec::new()),
+            worker: Mutex::new(None),
+        })
+    }
+
+    /// Whether no endpoints are configured
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Whether an endpoint should receive an event
+    pub fn matches(endpoint: &WebhookConfig, event: &Event) -> bool {
+        if !endpoint.events.is_empty() && !endpoint.events.contains(&event.kind()) {
+            return false;
+        }
+        endpoint
+            .min_score
+            .map_or(true, |min| event.score().is_some_and(|score| score >= min))
+    }
+
+    /// Queue an event for every matching endpoint, returning the delivery IDs
+    pub fn enqueue(&self, event: &Event) -> Result<Vec<i64>> {
+        let payload = event.to_json();
+        let kind = event.kind().to_string();
+        let db = &self.db;
+        self.endpoints
+            .iter()
+            .filter(|endpoint| Self::matches(endpoint, event))
+            .map(|endpoint| db.enqueue_webhook(&endpoint.name, &kind, &payload))
+            .collect()
+    }
+
+    /// Attempt every delivery that is due
+    pub fn deliver_due(&self) -> Result<DeliveryReport> {
+        let due = self.db.due_webhook_deliveries(Utc::now())?;
+        self.deliver(due)
+    }
+
+    /// Attempt every delivery that is due, logging rather than returning errors
+    fn deliver_due_logged(&self) {
+        if let Err(e) = self.deliver_due() {
+            log::error!("Failed to deliver webhooks: {e}");
+        }
+    }
+
+    /// Attempt the deliveries queued for events raised since the last flush
+    ///
+    /// For processes without a worker, once the work that raised the events
+    /// is done. Deliveries that fail are left to the retry schedule.
+    pub fn flush(&self) -> Result<DeliveryReport> {
+        let queued = std::mem::take(&mut *self.queued.lock());
+        if queued.is_empty() {
+            return Ok(DeliveryReport::default());
+        }
+        let due = self
+            .db
+            .due_webhook_deliveries(Utc::now())?
+            .into_iter()
+            .filter(|delivery| queued.contains(&delivery.id))
+            .collect();
+        self.deliver(due)
+    }
+
+    /// Deliver from a background thread, retrying due deliveries every
+    /// `retry_interval`
+    pub fn spawn_worker(self: &Arc<Self>, retry_interval: Duration) -> WebhookWorker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = Arc::clone(&stop);
+        let dispatcher = Arc::clone(self);
+        let handle = thread::spawn(move || {
+            while !worker_stop.load(Ordering::SeqCst) {
+                // Everything due is sent, including what was queued for a flush
+                dispatcher.queued.lock().clear();
+                dispatcher.deliver_due_logged();
+                thread::park_timeout(retry_interval);
+            }
+        });
+        *self.worker.lock() = Some(handle.thread().clone());
+
+        WebhookWorker {
+            dispatcher: Arc::clone(self),
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Attempt each delivery, recording the outcome in the outbox
+    ///
+    /// Each delivery is claimed before it is sent, so a worker and a flush,
+    /// in this process or another, never send the same one twice.
+    fn deliver(&self, due: Vec<WebhookDelivery>) -> Result<DeliveryReport> {
+        let mut report = DeliveryReport::default();
+
+        for delivery in due {
+            let now = Utc::now();
+            if !self
+                .db
+                .claim_webhook_delivery(delivery.id, now, now + self.claim)?
+            {
+                continue;
+            }
+            let result = match self.endpoint(&delivery.endpoint) {
+                Some(endpoint) => {
+                    self.post(endpoint, &delivery.event, delivery.id, &delivery.payload)
+                }
+                None => Err(format!(
+                    "Endpoint '{}' is not configured",
+                    delivery.endpoint
+                )),
+            };
+
+            let Err(error) = result else {
+                self.db.mark_webhook_delivered(delivery.id)?;
+                report.delivered += 1;
+                continue;
+            };
+            let next_attempt = self.next_attempt(&delivery, Utc::now());
+            self.db
+                .mark_webhook_attempt_failed(delivery.id, &error, next_attempt)?;
+            if next_attempt.is_some() {
+                log::warn!(
+                    "Webhook '{}' delivery {} failed, will retry: {}",
+                    delivery.endpoint,
+                    delivery.id,
+                    error
+                );
+                report.retrying += 1;
+            } else {
+                log::error!(
+                    "Webhook '{}' delivery {} failed after {} attempts: {}",
+                    delivery.endpoint,
+                    delivery.id,
+                    delivery.attempts + 1,
+                    error
+                );
+                report.failed += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Send a synthetic `test` event to an endpoint, bypassing the outbox
+    ///
+    /// Returns the HTTP status of a successful response.
+    pub fn send_test(&self, name: &str) -> Result<u16> {
+        let endpoint = self.endpoint(name).ok_or_else(|| {
+            PromptTrackingError::WebhookError(format!("Endpoint '{name}' is not configured"))
+        })?;
+        let payload = serde_json::json!({
+            "event": "test",
+            "endpoint": name,
+            "timestamp": Utc::now().to_rfc3339(),
+        })
+        .to_string();
+
+        self.post(endpoint, "test", 0, &payload)
+            .map_err(PromptTrackingError::WebhookError)
+    }
+
+    /// Names of the configured endpoints
+    pub fn endpoint_names(&self) -> Vec<&str> {
+        self.endpoints.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    /// When a delivery that just failed should be tried again, if at all
+    fn next_attempt(
+        &self,
+        delivery: &WebhookDelivery,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let attempts = delivery.attempts + 1;
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let factor = 1u64.checked_shl(attempts - 1).unwrap_or(u64::MAX);
+        let delay = self
+            .retry_base_secs
+            .saturating_mul(factor)
+            .min(MAX_RETRY_DELAY_SECS);
+        Some(now + ChronoDuration::seconds(i64::try_from(delay).unwrap_or(i64::MAX)))
+    }
+
+    fn endpoint(&self, name: &str) -> Option<&WebhookConfig> {
+        self.endpoints.iter().find(|e| e.name == name)
+    }
+
+    /// POST a payload, returning the status of a 2xx response or an error message
+    fn post(
+        &self,
+        endpoint: &WebhookConfig,
+        event: &str,
+        delivery_id: i64,
+        payload: &str,
+    ) -> std::result::Result<u16, String> {
+        let timestamp = Utc::now().timestamp().to_string();
+        let mut request = self
+            .agent
+            .post(&endpoint.url)
+            .set("Content-Type", "application/json")
+            .set(
+                "User-Agent",
+                concat!("prompt-tracking/", env!("CARGO_PKG_VERSION")),
+            )
+            .set("X-Prompt-Tracking-Event", event)
+            .set("X-Prompt-Tracking-Delivery", &delivery_id.to_string())
+            .set("X-Prompt-Tracking-Timestamp", &timestamp);
+        if let Some(secret) = &endpoint.secret {
+            let signature = sign(secret, &timestamp, payload).map_err(|e| e.to_string())?;
+            request = request.set("X-Prompt-Tracking-Signature", &signature);
+        }
+
+        match request.send_string(payload) {
+            Ok(response) if (200..300).contains(&response.status()) => Ok(response.status()),
+            Ok(response) => Err(format!("HTTP {}", response.status())),
+            Err(ureq::Error::Status(code, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                let body: String = body.trim().chars().take(MAX_ERROR_BODY).collect();
+                if body.is_empty() {
+                    Err(format!("HTTP {code}"))
+                } else {
+                    Err(format!("HTTP {code}: {body}"))
+                }
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+impl EventObserver for WebhookDispatcher {
+    fn on_event(&self, event: &Event) {
+        match self.enqueue(event) {
+            Ok(ids) if ids.is_empty() => {}
+            Ok(ids) => match &*self.worker.lock() {
+                Some(worker) => worker.unpark(),
+                None => self.queued.lock().extend(ids),
+            },
+            Err(e) => log::error!("Failed to queue webhooks for {}: {}", event.kind(), e),
+        }
+    }
+}
+
+impl Drop for WebhookWorker {
+    fn drop(&mut self) {
+        *self.dispatcher.worker.lock() = None;
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for WebhookWorker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookWorker")
+            .field("dispatcher", &self.dispatcher)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for WebhookDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookDispatcher")
+            .field("endpoints", &self.endpoint_names())
+            .field("max_attempts", &self.max_attempts)
+            .field("retry_base_secs", &self.retry_base_secs)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Signature header value for a request body
+///
+/// `sha256=` followed by the hex HMAC-SHA256 of `<timestamp>.<body>`.
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| {
+        PromptTrackingError::WebhookError(format!("Failed to create signing key: {e}"))
+    })?;
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    Ok(format!("sha256={:x}", mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DeliveryStatus;
+    use crate::events::EventKind;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use tempfile::TempDir;
+
+    /// A request received by [`StandIn`]
+    struct Received {
+        headers: HashMap<String, String>,
+        body: String,
+    }
+
+    /// Local HTTP server answering with a fixed sequence of statuses
+    struct StandIn {
+        url: String,
+        received: Arc<Mutex<Vec<Received>>>,
+    }
+
+    impl StandIn {
+        fn start(statuses: Vec<u16>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let log = received.clone();
+
+            thread::spawn(move || {
+                for status in statuses {
+                    let Ok((stream, _)) = listener.accept() else {
+                        return;
+                    };
+                    let mut reader = BufReader::new(stream);
+                    let mut headers = HashMap::new();
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    loop {
+                        line.clear();
+                        reader.read_line(&mut line).unwrap();
+                        let Some((name, value)) = line.trim_end().split_once(':') else {
+                            break;
+                        };
+                        headers.insert(name.to_lowercase(), value.trim().to_string());
+                    }
+                    let length = headers
+                        .get("content-length")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or(0);
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+                    log.lock().unwrap().push(Received {
+                        headers,
+                        body: String::from_utf8(body).unwrap(),
+                    });
+
+                    let mut stream = reader.into_inner();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    )
+                    .unwrap();
+                }
+            });
+
+            Self { url, received }
+        }
+    }
+
+    fn dispatcher(temp_dir: &TempDir, url: &str, config: WebhooksConfig) -> WebhookDispatcher {
+        let db = Database::new(temp_dir.path().join("prompts.db").to_str().unwrap()).unwrap();
+        let config = WebhooksConfig {
+            endpoints: vec![WebhookConfig {
+                name: "notes".to_string(),
+                url: url.to_string(),
+                events: vec![EventKind::Deleted],
+                secret: Some("s3cret".to_string()),
+                min_score: None,
+            }],
+            ..config
+        };
+        WebhookDispatcher::new(&config, db).unwrap()
+    }
+
+    fn deleted() -> Event {
+        Event::Deleted {
+            prompt_id: "p1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            sign("s3cret", "1700000000", r#"{"event":"deleted"}"#).unwrap(),
+            "sha256=48aa02ea49facfe10fa08db28f4271ad4f900002977b5af13a451f47191a6b2e"
+        );
+    }
+
+    #[test]
+    fn test_delivery_is_signed() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = StandIn::start(vec![204]);
+        let dispatcher = dispatcher(&temp_dir, &server.url, WebhooksConfig::default());
+
+        dispatcher.on_event(&deleted());
+        // Not subscribed to this kind
+        dispatcher.on_event(&Event::Archived {
+            prompt_id: "p1".to_string(),
+        });
+
+        // Raising an event only queues it
+        assert!(server.received.lock().unwrap().is_empty());
+        let due = dispatcher.db.due_webhook_deliveries(Utc::now()).unwrap();
+        assert_eq!(dispatcher.flush().unwrap().delivered, 1);
+        assert_eq!(dispatcher.flush().unwrap(), DeliveryReport::default());
+        // A pass that read the outbox before the flush sent it skips it
+        assert_eq!(dispatcher.deliver(due).unwrap(), DeliveryReport::default());
+
+        let received = server.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        let request = &received[0];
+        let json: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(json["event"], "deleted");
+        assert_eq!(json["prompt_id"], "p1");
+        assert_eq!(request.headers["x-prompt-tracking-event"], "deleted");
+        assert_eq!(
+            request.headers["x-prompt-tracking-signature"],
+            sign(
+                "s3cret",
+                &request.headers["x-prompt-tracking-timestamp"],
+                &request.body
+            )
+            .unwrap()
+        );
+
+        let db = &dispatcher.db;
+        let deliveries = db.list_webhook_deliveries(None, 10).unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+        assert_eq!(
+            request.headers["x-prompt-tracking-delivery"],
+            deliveries[0].id.to_string()
+        );
+    }
+
+    #[test]
+    fn test_worker_delivers_in_background() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = StandIn::start(vec![200]);
+        let dispatcher = Arc::new(dispatcher(
+            &temp_dir,
+            &server.url,
+            WebhooksConfig::default(),
+        ));
+        let worker = dispatcher.spawn_worker(std::time::Duration::from_secs(60));
+
+        dispatcher.on_event(&deleted());
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while server.received.lock().unwrap().is_empty() {
+            assert!(std::time::Instant::now() < deadline, "no delivery");
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        drop(worker);
+
+        assert!(dispatcher.worker.lock().is_none());
+        let delivery = &dispatcher.db.list_webhook_deliveries(None, 1).unwrap()[0];
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+    }
+
+    #[test]
+    fn test_failed_delivery_is_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = StandIn::start(vec![500, 503, 200]);
+        let config = WebhooksConfig {
+            retry_base_secs: 0,
+            ..WebhooksConfig::default()
+        };
+        let dispatcher = dispatcher(&temp_dir, &server.url, config);
+
+        dispatcher.enqueue(&deleted()).unwrap();
+        let report = dispatcher.deliver_due().unwrap();
+        assert_eq!(report.retrying, 1);
+        assert_eq!(dispatcher.deliver_due().unwrap().retrying, 1);
+        assert_eq!(dispatcher.deliver_due().unwrap().delivered, 1);
+        assert_eq!(dispatcher.deliver_due().unwrap(), DeliveryReport::default());
+
+        let received = server.received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        // Every attempt carries the same delivery ID and body
+        assert_eq!(received[0].body, received[2].body);
+        assert_eq!(
+            received[0].headers["x-prompt-tracking-delivery"],
+            received[2].headers["x-prompt-tracking-delivery"]
+        );
+
+        let delivery = &dispatcher.db.list_webhook_deliveries(None, 1).unwrap()[0];
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+    }
+
+    #[test]
+    fn test_delivery_gives_up_after_max_attempts() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = StandIn::start(vec![500, 500]);
+        let config = WebhooksConfig {
+            max_attempts: 2,
+            retry_base_secs: 0,
+            ..WebhooksConfig::default()
+        };
+        let dispatcher = dispatcher(&temp_dir, &server.url, config);
+
+        dispatcher.enqueue(&deleted()).unwrap();
+        assert_eq!(dispatcher.deliver_due().unwrap().retrying, 1);
+        assert_eq!(dispatcher.deliver_due().unwrap().failed, 1);
+        assert_eq!(dispatcher.deliver_due().unwrap(), DeliveryReport::default());
+
+        let delivery = &dispatcher.db.list_webhook_deliveries(None, 1).unwrap()[0];
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.last_error.as_deref(), Some("HTTP 500"));
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let temp_dir = TempDir::new().unwrap();
+        let dispatcher = dispatcher(&temp_dir, "http://127.0.0.1:9/", WebhooksConfig::default());
+        let now = Utc::now();
+        let mut delivery = WebhookDelivery {
+            id: 1,
+            endpoint: "notes".to_string(),
+            event: "deleted".to_string(),
+            payload: "{}".to_string(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+        };
+
+        let delays: Vec<i64> = (0..8)
+            .map(|attempts| {
+                delivery.attempts = attempts;
+                dispatcher
+                    .next_attempt(&delivery, now)
+                    .map_or(-1, |t| (t - now).num_seconds())
+            })
+            .collect();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480, 960, 1920, -1]);
+
+        let dispatcher = WebhookDispatcher {
+            max_attempts: 100,
+            ..dispatcher
+        };
+        delivery.attempts = 20;
+        assert_eq!(
+            (dispatcher.next_attempt(&delivery, now).unwrap() - now).num_seconds(),
+            MAX_RETRY_DELAY_SECS as i64
+        );
+    }
+
+    #[test]
+    fn test_send_test_reports_status() {
+        let temp_dir = TempDir::new().unwrap();
+        let server = StandIn::start(vec![200, 404]);
+        let dispatcher = dispatcher(&temp_dir, &server.url, WebhooksConfig::default());
+
+        assert_eq!(dispatcher.send_test("notes").unwrap(), 200);
+        let error = dispatcher.send_test("notes").unwrap_err();
+        assert!(error.to_string().contains("HTTP 404"));
+        assert!(dispatcher.send_test("missing").is_err());
+
+        let received = server.received.lock().unwrap();
+        assert_eq!(received[0].headers["x-prompt-tracking-event"], "test");
+        // Tests never touch the outbox
+        assert!(dispatcher
+            .db
+            .list_webhook_deliveries(None, 10)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -369,6 +369,7 @@ fn test_prompt_metadata_clone() {
         execution_time_ms: Some(1000),
         estimated_cost: Some(0.01),
         context: Some("context".to_string()),
+        ..Default::default()
     };
 
     let cloned = metadata.clone();